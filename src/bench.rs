@@ -0,0 +1,128 @@
+use anyhow::Result;
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, OcrConfig};
+use crate::{corpus, external, find_lines, freq, layout, ocr, pdf, read_pdf, read_pages, ui, App, PdfText};
+
+/// Minimum time spent repeating a measurement, so fast ones are not lost in timer noise.
+const MIN_MEASURE: Duration = Duration::from_millis(500);
+
+/// Size of the terminal frames are rendered into.
+const SCREEN: (u16, u16) = (120, 40);
+
+/// The `bench` subcommand: time each stage of opening, searching and showing a document.
+pub fn run(path: &Path, config: Config, with_ocr: bool) -> Result<()> {
+    println!("pdf_reader {} — {}", env!("CARGO_PKG_VERSION"), path.display());
+
+    let (doc, time) = timed(|| pdf::load(path));
+    let doc = doc?;
+    let page_count = doc.get_pages().len();
+    println!("\nParsing");
+    row("lopdf", time, &format!("{} pages", page_count));
+
+    println!("\nExtraction");
+    let (_, time) = timed(|| pdf::extract_pages(&doc, config.format.crop));
+    row("pdf-extract", time, &per_page(time, page_count));
+    let (pagination, format) = (config.pagination, config.format);
+    let (text, time) = timed(|| read_pdf(path, pagination, format));
+    let PdfText { pages, outline, title, scanned, language, .. } = text?;
+    row("viewer pipeline", time, &per_page(time, page_count));
+    let (_, time) = timed(|| layout::grid(&pdf::page_glyphs(&doc, 1, format.crop), SCREEN.0 as usize, config.layout.align_amounts));
+    row("layout view", time, "page 1");
+    let file = path.display().to_string();
+    match timed(|| external::run("pdftotext -layout {file} -", &[("file", &file)], None)) {
+        (Ok(_), time) => row("pdftotext", time, &per_page(time, page_count)),
+        (Err(_), _) => println!("  {:<20} not available", "pdftotext"),
+    }
+
+    let mut app = App::new(path.to_path_buf(), pages, outline, config);
+    app.title = title;
+    app.scanned = scanned;
+    app.language = language;
+    app.page_total = app.pages.len();
+    let lang = app.ocr_lang();
+    if with_ocr {
+        let ocr = OcrConfig {
+            command: app.config.ocr.command.clone(),
+            lang: lang.clone(),
+        };
+        match timed(|| ocr::recognize(&ocr, path, 0)) {
+            (Ok(_), time) => row("ocr", time, &format!("page 1, {}", lang)),
+            (Err(e), _) => println!("  {:<20} failed: {}", "ocr", e),
+        }
+    }
+
+    println!("\nCaches");
+    let settings = corpus::settings(pagination, format);
+    let cached = if corpus::is_cached(path, &settings) { "hit" } else { "miss" };
+    // A cache of its own, so timing a miss does not replace the real one
+    let scratch = env::temp_dir().join(format!("pdf_reader-bench-{}", std::process::id()));
+    let read = |path: &Path| read_pages(path, pagination, format);
+    let (_, first) = timed(|| corpus::cached_pages_in(&scratch, path, &settings, read));
+    let (_, second) = timed(|| corpus::cached_pages_in(&scratch, path, &settings, read));
+    let _ = fs::remove_dir_all(&scratch);
+    row("text cache", first, &format!("filled, then {} on reuse; real cache {}", ms(second), cached));
+    let ocr_cached = (0..app.pages.len()).filter(|&page| ocr::cached(path, page, &lang).is_some()).count();
+    println!("  {:<20} {} of {} pages cached ({})", "ocr cache", ocr_cached, app.pages.len(), lang);
+
+    println!("\nSearch");
+    let bytes: usize = app.pages.iter().map(String::len).sum();
+    let common = freq::top_terms(&app.pages, app.language, 1).into_iter().next().map(|(term, _)| term);
+    for query in common.iter().map(String::as_str).chain(["no-such-term-xyzzy"]) {
+        let (hits, time) = repeated(|| find_lines(&app.pages, &query.to_lowercase(), |_| false).len());
+        let throughput = bytes as f64 / time.as_secs_f64().max(1e-9) / (1024.0 * 1024.0);
+        row(&format!("'{}'", query), time, &format!("{:.0} MB/s, {} matching lines", throughput, hits));
+    }
+
+    println!("\nRendering ({}x{})", SCREEN.0, SCREEN.1);
+    let mut terminal = Terminal::new(TestBackend::new(SCREEN.0, SCREEN.1))?;
+    let (_, time) = repeated(|| terminal.draw(|f| ui(f, &mut app)).map(|_| ()));
+    row("page 1", time, "per frame");
+    if let Some(query) = common {
+        app.search(query);
+        let (_, time) = repeated(|| terminal.draw(|f| ui(f, &mut app)).map(|_| ()));
+        row("with search matches", time, "per frame");
+    }
+    if let Some(longest) = (0..app.pages.len()).max_by_key(|&page| app.pages[page].lines().count()) {
+        app.current_page = longest;
+        app.scroll_offset = 0;
+        let (_, time) = repeated(|| terminal.draw(|f| ui(f, &mut app)).map(|_| ()));
+        row(&format!("longest page ({})", longest + 1), time, "per frame");
+    }
+    Ok(())
+}
+
+fn timed<T>(work: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = work();
+    (result, start.elapsed())
+}
+
+/// Run `work` until `MIN_MEASURE` has passed; its last result and the mean time per run.
+fn repeated<T>(mut work: impl FnMut() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let mut runs = 1;
+    let mut result = work();
+    while start.elapsed() < MIN_MEASURE {
+        result = work();
+        runs += 1;
+    }
+    (result, start.elapsed() / runs)
+}
+
+fn row(name: &str, time: Duration, note: &str) {
+    println!("  {:<20} {:>10}   {}", name, ms(time), note);
+}
+
+fn ms(time: Duration) -> String {
+    format!("{:.2} ms", time.as_secs_f64() * 1000.0)
+}
+
+fn per_page(time: Duration, pages: usize) -> String {
+    format!("{} per page", ms(time / pages.max(1) as u32))
+}
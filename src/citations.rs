@@ -0,0 +1,162 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+static DOI: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b10\.\d{4,9}/[-._;()/:A-Za-z0-9]+").unwrap());
+static ARXIV: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\barxiv:\s*(\d{4}\.\d{4,5}(?:v\d+)?|[a-z-]+(?:\.[A-Z]{2})?/\d{7}(?:v\d+)?)").unwrap()
+});
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bhttps?://[^\s<>\x22]+").unwrap());
+static REFERENCES_HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(?:\d+\.?\s*)?(references|bibliography|works cited)$").unwrap());
+static ENTRY_START: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?:\[\d+\]|\d{1,3}\.\s)").unwrap());
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CitationKind {
+    Doi,
+    Arxiv,
+    Reference,
+}
+
+#[derive(Clone)]
+pub struct Citation {
+    pub kind: CitationKind,
+    /// The DOI, arXiv identifier, or reference entry text
+    pub text: String,
+    /// 0-based page the citation was found on
+    pub page: usize,
+}
+
+impl Citation {
+    /// A resolvable URL for DOIs and arXiv identifiers.
+    pub fn url(&self) -> Option<String> {
+        match self.kind {
+            CitationKind::Doi => Some(format!("https://doi.org/{}", self.text)),
+            CitationKind::Arxiv => Some(format!("https://arxiv.org/abs/{}", self.text)),
+            CitationKind::Reference => {
+                let doi = DOI.find(&self.text)?;
+                Some(format!("https://doi.org/{}", trim_doi(doi.as_str())))
+            }
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let kind = match self.kind {
+            CitationKind::Doi => "DOI",
+            CitationKind::Arxiv => "arXiv",
+            CitationKind::Reference => "Ref",
+        };
+        format!("p.{:<4} {:<6} {}", self.page + 1, kind, self.text)
+    }
+
+    /// A minimal BibTeX entry to be completed by hand or by a reference manager.
+    pub fn bibtex(&self, index: usize) -> String {
+        match self.kind {
+            CitationKind::Doi => format!(
+                "@article{{doi{},\n  doi = {{{}}},\n  url = {{https://doi.org/{}}},\n}}\n",
+                index, self.text, self.text
+            ),
+            CitationKind::Arxiv => format!(
+                "@misc{{arxiv{},\n  eprint = {{{}}},\n  archivePrefix = {{arXiv}},\n  url = {{https://arxiv.org/abs/{}}},\n}}\n",
+                index, self.text, self.text
+            ),
+            CitationKind::Reference => format!(
+                "@misc{{ref{},\n  note = {{{}}},\n}}\n",
+                index,
+                self.text.replace(['{', '}'], "")
+            ),
+        }
+    }
+}
+
+/// Where the web address, DOI or arXiv identifier covering byte `offset` of
+/// `line` leads, if there is one.
+pub fn link_at(line: &str, offset: usize) -> Option<String> {
+    let covers = |m: &regex::Match| m.start() <= offset && offset < m.end();
+    if let Some(url) = URL.find_iter(line).find(covers) {
+        return Some(url.as_str().trim_end_matches(['.', ',', ';', ':', ')']).to_string());
+    }
+    if let Some(doi) = DOI.find_iter(line).find(covers) {
+        return Some(format!("https://doi.org/{}", trim_doi(doi.as_str())));
+    }
+    ARXIV
+        .captures_iter(line)
+        .find(|arxiv| arxiv.get(0).is_some_and(|m| covers(&m)))
+        .map(|arxiv| format!("https://arxiv.org/abs/{}", &arxiv[1]))
+}
+
+/// DOIs commonly run into sentence punctuation; drop it.
+fn trim_doi(doi: &str) -> &str {
+    doi.trim_end_matches(['.', ',', ';', ':', ')'])
+}
+
+/// Find DOIs, arXiv identifiers and reference-list entries in the document,
+/// taking its pages in order.
+pub fn extract(pages: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<Citation> {
+    let mut citations: Vec<Citation> = Vec::new();
+    let mut push = |kind, text: &str, page| {
+        if !citations.iter().any(|c| c.kind == kind && c.text == text) {
+            citations.push(Citation { kind, text: text.to_string(), page });
+        }
+    };
+
+    let mut references = References::default();
+    for (page, text) in pages.into_iter().enumerate() {
+        let text = text.as_ref();
+        for doi in DOI.find_iter(text) {
+            push(CitationKind::Doi, trim_doi(doi.as_str()), page);
+        }
+        for arxiv in ARXIV.captures_iter(text) {
+            push(CitationKind::Arxiv, &arxiv[1], page);
+        }
+        references.add_page(page, text);
+    }
+
+    citations.extend(references.finish());
+    citations
+}
+
+/// Entries of the reference section: everything after a "References" heading
+/// that starts with `[N]` or `N.`, joined with its continuation lines.
+#[derive(Default)]
+struct References {
+    entries: Vec<Citation>,
+    in_references: bool,
+    current: Option<Citation>,
+}
+
+impl References {
+    fn add_page(&mut self, page: usize, text: &str) {
+        for line in text.lines().map(str::trim) {
+            if REFERENCES_HEADING.is_match(line) {
+                self.in_references = true;
+                self.entries.extend(self.current.take());
+                continue;
+            }
+            if !self.in_references || line.is_empty() {
+                continue;
+            }
+            if ENTRY_START.is_match(line) {
+                self.entries.extend(self.current.take());
+                self.current = Some(Citation {
+                    kind: CitationKind::Reference,
+                    text: line.to_string(),
+                    page,
+                });
+            } else if let Some(entry) = self.current.as_mut() {
+                // Rejoin words hyphenated across the line break
+                if entry.text.ends_with('-') {
+                    entry.text.pop();
+                } else {
+                    entry.text.push(' ');
+                }
+                entry.text.push_str(line);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Citation> {
+        self.entries.extend(self.current);
+        self.entries
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipFormat {
+    Markdown,
+    Org,
+}
+
+/// Append a quoted passage with its source, and a `link` back to it, to the
+/// clippings file.
+pub fn append(path: &Path, format: ClipFormat, text: &str, file_name: &str, page: usize, link: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let entry = format_entry(format, text, file_name, page, link, &today());
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(entry.as_bytes()))
+        .with_context(|| format!("Could not write to {}", path.display()))
+}
+
+/// Append a quoted passage to a Markdown notes file, referenced as
+/// `file:page` linked to `link`, with the command that opens the PDF at that
+/// page for when `pdfreader://` links are not set up.
+pub fn append_note(path: &Path, text: &str, pdf: &Path, page: usize, link: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file_name = pdf.file_name().map_or_else(|| pdf.display().to_string(), |name| name.to_string_lossy().into_owned());
+    let quote: String = text.lines().map(|line| format!("> {}\n", line)).collect();
+    let command = format!("pdf_reader --page {} '{}'", page, pdf.display().to_string().replace('\'', "'\\''"));
+    let entry = format!("{}\n— [{}:{}]({}) ({}) `{}`\n\n", quote, file_name, page, link, today(), command);
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(entry.as_bytes()))
+        .with_context(|| format!("Could not write to {}", path.display()))
+}
+
+fn format_entry(format: ClipFormat, text: &str, file_name: &str, page: usize, link: &str, date: &str) -> String {
+    match format {
+        ClipFormat::Markdown => {
+            let quote: String = text.lines().map(|line| format!("> {}\n", line)).collect();
+            format!("{}\n— [*{}*, p. {}]({}) ({})\n\n", quote, file_name, page, link, date)
+        }
+        ClipFormat::Org => format!(
+            "* {}, p. {}\n:PROPERTIES:\n:SOURCE: {}\n:PAGE: {}\n:LINK: [[{}]]\n:CLIPPED: [{}]\n:END:\n#+BEGIN_QUOTE\n{}\n#+END_QUOTE\n\n",
+            file_name, page, file_name, page, link, date, text
+        ),
+    }
+}
+
+/// Today's UTC date as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use std::io::{self, Write};
+
+use crate::{external, sandbox};
+
+/// Copy `text` with the configured clipboard command, or via the OSC 52
+/// terminal escape when no command is set or commands are off in `--sandbox`.
+pub fn copy(command: &str, text: &str) -> Result<()> {
+    if !command.is_empty() && !sandbox::enabled() {
+        external::run(command, &[], Some(text))?;
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,172 @@
+use ratatui::style::{Color, Modifier, Style};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::config::CodeConfig;
+
+/// Signs that a line is source code rather than prose.
+static CODE_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        // Statement ends and braces
+        r"[;{}]$",
+        r"|^[}\])]",
+        // Declarations and statements that prose does not open a line with
+        r"|^(?:fn|def|let|const|var|pub|use|impl|struct|enum|func|package|import|return|elif|#include|#define|#!)\b",
+        r"|^(?:class|public|private|protected|static|void|int|from)\s+\w+.*[({:=;]",
+        r"|^(?:if|for|while|else|switch|case|try|except|catch|with)\b.*[:{)]$",
+        // A call on a line of its own: `foo.bar(x)`
+        r"|^[\w.]+\(.*\);?$",
+        r"|^(?://|/\*|\*/|<!--)",
+        r"|\w\s*(?:==|!=|=>|::|:=|\+=|-=|&&|\|\|)\s*[\w(]",
+    ))
+    .unwrap()
+});
+
+/// A line of running text: a sentence of several words.
+static PROSE_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[A-Z][^;{}=]*(?:\s+\S+){5,}[.?!]$").unwrap());
+
+/// Fewer code-like lines are more often a stray semicolon than a listing.
+const MIN_CODE_LINES: usize = 2;
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// Line ranges of a page that hold source code. A region runs from one
+/// code-like line to the last one that follows it with at most one other
+/// line in between, and needs at least half of its lines to look like code.
+pub fn regions(lines: &[&str]) -> Vec<Range<usize>> {
+    let code: Vec<bool> = lines.iter().map(|line| is_code_line(line.trim())).collect();
+    let mut regions = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if !code[i] {
+            i += 1;
+            continue;
+        }
+        let mut end = i + 1;
+        loop {
+            if end < lines.len() && code[end] {
+                end += 1;
+            } else if end + 1 < lines.len() && code[end + 1] && !PROSE_LINE.is_match(lines[end].trim()) {
+                end += 2;
+            } else {
+                break;
+            }
+        }
+        let code_lines = code[i..end].iter().filter(|&&code| code).count();
+        if code_lines >= MIN_CODE_LINES && code_lines * 2 >= end - i {
+            regions.push(i..end);
+        }
+        i = end;
+    }
+    regions
+}
+
+fn is_code_line(line: &str) -> bool {
+    !line.is_empty() && CODE_LINE.is_match(line)
+}
+
+/// Syntax for a code region: the configured language, or a guess from its lines.
+fn syntax(lines: &[&str], config: &CodeConfig) -> &'static SyntaxReference {
+    let configured = (!config.language.is_empty())
+        .then(|| SYNTAXES.find_syntax_by_token(&config.language))
+        .flatten();
+    configured
+        .or_else(|| SYNTAXES.find_syntax_by_first_line(lines.first().copied().unwrap_or_default()))
+        .or_else(|| guess_language(lines).and_then(|token| SYNTAXES.find_syntax_by_token(token)))
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text())
+}
+
+/// File extension of the language a listing is most likely in, from telltale constructs.
+pub fn guess_language(lines: &[&str]) -> Option<&'static str> {
+    static TELLTALES: LazyLock<Vec<(&str, Regex)>> = LazyLock::new(|| {
+        [
+            ("rs", r"\bfn \w+|\blet mut\b|\bimpl\b|\w!\(|&mut |::new\("),
+            ("py", r"^\s*def \w+\(.*\):|^\s*(?:from \w+ )?import \w+$|\bself\.|\belif\b|:\s*$"),
+            ("c", r"#include|\bprintf\(|\bint main\(|\w->\w|\bmalloc\("),
+            ("java", r"\bpublic (?:static |class )|System\.out|\bnew \w+\(|@Override"),
+            ("go", r"\bfunc \w+|^package \w+|:= |\bfmt\."),
+            ("js", r"\bfunction\b|\bconst \w+ = |=> |console\.log|\bdocument\."),
+            ("sql", r"(?i)^\s*(?:select|insert into|create table|update \w+ set)\b"),
+            ("sh", r"^\s*\$ |\becho \S|^fi$|\bdone$|\$\{?\w+"),
+        ]
+        .into_iter()
+        .map(|(token, pattern)| (token, Regex::new(pattern).unwrap()))
+        .collect()
+    });
+    TELLTALES
+        .iter()
+        .map(|(token, pattern)| (*token, lines.iter().filter(|line| pattern.is_match(line)).count()))
+        .filter(|&(_, hits)| hits > 0)
+        .max_by_key(|&(_, hits)| hits)
+        .map(|(token, _)| token)
+}
+
+fn theme(config: &CodeConfig) -> &'static Theme {
+    THEMES
+        .themes
+        .get(&config.theme)
+        .unwrap_or_else(|| &THEMES.themes["base16-ocean.dark"])
+}
+
+/// Styles for the code on a page, per line, as byte ranges. Text in the
+/// theme's plain foreground keeps the pane's own color.
+pub fn highlight(page: &str, config: &CodeConfig) -> Vec<Vec<(Range<usize>, Style)>> {
+    let lines: Vec<&str> = page.lines().collect();
+    let mut styles = vec![Vec::new(); lines.len()];
+    let theme = theme(config);
+    let plain = theme.settings.foreground;
+    for region in regions(&lines) {
+        let block = &lines[region.clone()];
+        let mut highlighter = HighlightLines::new(syntax(block, config), theme);
+        for (index, line) in region.zip(block) {
+            let line_end = format!("{}\n", line);
+            let Ok(pieces) = highlighter.highlight_line(&line_end, &SYNTAXES) else {
+                break;
+            };
+            let mut start = 0;
+            for (style, piece) in pieces {
+                let end = (start + piece.len()).min(line.len());
+                if start < end && Some(style.foreground) != plain {
+                    let color = style.foreground;
+                    let mut patch = Style::default().fg(Color::Rgb(color.r, color.g, color.b));
+                    if style.font_style.contains(FontStyle::BOLD) {
+                        patch = patch.add_modifier(Modifier::BOLD);
+                    }
+                    if style.font_style.contains(FontStyle::ITALIC) {
+                        patch = patch.add_modifier(Modifier::ITALIC);
+                    }
+                    styles[index].push((start..end, patch));
+                }
+                start = end;
+            }
+        }
+    }
+    styles
+}
+
+/// Code highlighting of the shown page, reused between frames.
+#[derive(Default)]
+pub struct CodeCache {
+    page: Option<usize>,
+    lines: Vec<Vec<(Range<usize>, Style)>>,
+}
+
+impl CodeCache {
+    pub fn get(&mut self, page: usize, text: &str, config: &CodeConfig) -> &[Vec<(Range<usize>, Style)>] {
+        if self.page != Some(page) {
+            self.lines = if config.highlight { highlight(text, config) } else { Vec::new() };
+            self.page = Some(page);
+        }
+        &self.lines
+    }
+
+    /// Forget the highlighting after page text changed.
+    pub fn clear(&mut self) {
+        self.page = None;
+    }
+}
@@ -0,0 +1,235 @@
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+/// Commands entered at the `:` prompt.
+pub enum Command {
+    /// Summarize the current page, an inclusive 1-based page range, or the
+    /// current chapter
+    Summarize(SummaryScope),
+    /// Ask a question about the document in the chat panel
+    Ask(String),
+    /// Show the chat panel
+    Chat,
+    /// List DOIs, arXiv identifiers and references
+    Citations,
+    /// Write the document as an Org file
+    ExportOrg(PathBuf),
+    /// Write the text of the current chapter, by the outline, to a file
+    ExportSection(PathBuf),
+    /// Print the current page, or an inclusive 1-based page range
+    Print(Option<RangeInclusive<usize>>),
+    /// Open the LaTeX source of the current position via SyncTeX
+    EditSource,
+    /// Pipe the current page or selection through a shell command
+    Filter(String),
+    /// Show the log of past status messages
+    Messages,
+    /// List the images on the current page
+    Images,
+    /// Save the image selected in the image list, to a file or directory
+    SaveImage(Option<PathBuf>),
+    /// Show document properties and attachments
+    Info,
+    /// List the files embedded in the PDF
+    Attachments,
+    /// Save an attachment (by name, 1-based number or `all`), optionally into a directory
+    SaveAttachment(String, Option<PathBuf>),
+    /// Toggle underlining of misspelled words
+    Spell,
+    /// List the most frequent terms of the document, or of the current chapter when true
+    Freq(bool),
+    /// List the PDFs of the corpus folder, or of the document's folder
+    Documents,
+    /// Tag the current page
+    Tag(Vec<String>),
+    /// Remove tags from the current page; all of them when empty
+    Untag(Vec<String>),
+    /// List the document's tags
+    Tags,
+    /// List the yank registers
+    Registers,
+    /// List everything yanked or clipped this session
+    Clips,
+    /// Limit `n`/`p` to pages with a tag, or lift the limit
+    FilterTag(Option<String>),
+    /// Show the current page's text as extracted, before any cleanup
+    Raw,
+    /// Toggle keeping leading whitespace for this document
+    Indent,
+    /// Toggle rendering math as Unicode for this document
+    Math,
+    /// Toggle showing lines hidden by `[exclude]` patterns
+    Excluded,
+    /// Toggle the minimap strip beside the content
+    Minimap,
+    /// Toggle the large print pane below the content
+    LargePrint,
+    /// Toggle the view of the page laid out as printed
+    Layout,
+    /// Toggle the view of the page rendered as an image
+    PageImage,
+    /// Extract the document again, keeping the place and search
+    Reload,
+    /// Toggle wrapping long lines, or cutting them at the pane
+    Wrap,
+    /// Toggle the line number gutter
+    LineNumbers,
+    /// Set the display filter, or go to the next one
+    Display(Option<String>),
+}
+
+/// What `:summarize` sends to the LLM.
+pub enum SummaryScope {
+    Page,
+    /// Inclusive 1-based page range
+    Pages(RangeInclusive<usize>),
+    /// The top-level outline entry the current page falls under
+    Chapter,
+}
+
+/// Parse a command line (without the leading `:`).
+pub fn parse(line: &str) -> Result<Command, String> {
+    if let Some(filter) = line.trim_start().strip_prefix('!') {
+        return match filter.trim() {
+            "" => Err("Usage: !<shell command>".to_string()),
+            filter => Ok(Command::Filter(filter.to_string())),
+        };
+    }
+
+    let mut words = line.split_whitespace();
+    let name = words.next().ok_or("Empty command")?;
+    let rest: Vec<&str> = words.collect();
+
+    match name {
+        "summarize" | "sum" => match rest.as_slice() {
+            [] | ["page"] => Ok(Command::Summarize(SummaryScope::Page)),
+            ["chapter"] => Ok(Command::Summarize(SummaryScope::Chapter)),
+            [range] => parse_page_range(range).map(|r| Command::Summarize(SummaryScope::Pages(r))),
+            _ => Err("Usage: summarize [page|chapter|N-M]".to_string()),
+        },
+        "ask" if rest.is_empty() => Err("Usage: ask <question>".to_string()),
+        "ask" => Ok(Command::Ask(rest.join(" "))),
+        "chat" => Ok(Command::Chat),
+        "citations" | "refs" => Ok(Command::Citations),
+        "export-org" => match rest.as_slice() {
+            [path] => Ok(Command::ExportOrg(PathBuf::from(path))),
+            _ => Err("Usage: export-org <file.org>".to_string()),
+        },
+        "export-section" => match rest.as_slice() {
+            [path] => Ok(Command::ExportSection(PathBuf::from(path))),
+            _ => Err("Usage: export-section <file.txt>".to_string()),
+        },
+        "print" => match rest.as_slice() {
+            [] => Ok(Command::Print(None)),
+            [range] => parse_page_range(range).map(|r| Command::Print(Some(r))),
+            _ => Err("Usage: print [N-M]".to_string()),
+        },
+        "edit-source" | "synctex" => Ok(Command::EditSource),
+        "messages" | "mes" => Ok(Command::Messages),
+        "images" | "figures" => Ok(Command::Images),
+        "save-image" => match rest.as_slice() {
+            [] => Ok(Command::SaveImage(None)),
+            [path] => Ok(Command::SaveImage(Some(PathBuf::from(path)))),
+            _ => Err("Usage: save-image [file|dir]".to_string()),
+        },
+        "info" => Ok(Command::Info),
+        "docs" | "documents" => Ok(Command::Documents),
+        "tag" if rest.is_empty() => Err("Usage: tag <name>...".to_string()),
+        "tag" => Ok(Command::Tag(rest.iter().map(|tag| tag.to_string()).collect())),
+        "untag" => Ok(Command::Untag(rest.iter().map(|tag| tag.to_string()).collect())),
+        "tags" => Ok(Command::Tags),
+        "registers" | "reg" => Ok(Command::Registers),
+        "clips" | "yanks" => Ok(Command::Clips),
+        "raw" => Ok(Command::Raw),
+        "indent" => Ok(Command::Indent),
+        "math" => Ok(Command::Math),
+        "excluded" => Ok(Command::Excluded),
+        "minimap" => Ok(Command::Minimap),
+        "large" => Ok(Command::LargePrint),
+        "layout" => Ok(Command::Layout),
+        "page-image" => Ok(Command::PageImage),
+        "reload" => Ok(Command::Reload),
+        "wrap" => Ok(Command::Wrap),
+        "numbers" | "nu" => Ok(Command::LineNumbers),
+        "display" => match rest.as_slice() {
+            [] => Ok(Command::Display(None)),
+            [name] => Ok(Command::Display(Some(name.to_string()))),
+            _ => Err("Usage: display [none|sepia|night|gray]".to_string()),
+        },
+        "filter" => match rest.as_slice() {
+            [] => Ok(Command::FilterTag(None)),
+            [tag] => Ok(Command::FilterTag(Some(tag.to_string()))),
+            _ => Err("Usage: filter [tag]".to_string()),
+        },
+        "spell" => Ok(Command::Spell),
+        "freq" => match rest.as_slice() {
+            [] => Ok(Command::Freq(false)),
+            ["chapter"] => Ok(Command::Freq(true)),
+            _ => Err("Usage: freq [chapter]".to_string()),
+        },
+        "attachments" | "att" => Ok(Command::Attachments),
+        "save-attachment" => match rest.as_slice() {
+            [which] => Ok(Command::SaveAttachment(which.to_string(), None)),
+            [which, dir] => Ok(Command::SaveAttachment(which.to_string(), Some(PathBuf::from(dir)))),
+            _ => Err("Usage: save-attachment <name|N|all> [dir]".to_string()),
+        },
+        _ => Err(format!("Unknown command: {}", name)),
+    }
+}
+
+/// Parse `N` or `N-M` into an inclusive range of 1-based page numbers.
+pub fn parse_page_range(text: &str) -> Result<RangeInclusive<usize>, String> {
+    let invalid = || format!("Invalid page range: {}", text);
+    let (start, end) = match text.split_once('-') {
+        Some((start, end)) => (start, end),
+        None => (text, text),
+    };
+    let start: usize = start.trim().parse().map_err(|_| invalid())?;
+    let end: usize = end.trim().parse().map_err(|_| invalid())?;
+    if start == 0 || end < start {
+        return Err(invalid());
+    }
+    Ok(start..=end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn page_ranges_are_one_based_and_inclusive() {
+        assert_eq!(parse_page_range("7"), Ok(7..=7));
+        assert_eq!(parse_page_range("3 - 5"), Ok(3..=5));
+        for bad in ["0", "0-2", "5-3", "x", "3-", "-3", ""] {
+            assert_eq!(parse_page_range(bad), Err(format!("Invalid page range: {}", bad)));
+        }
+    }
+
+    #[test]
+    fn commands_take_their_arguments() {
+        assert!(matches!(parse("summarize"), Ok(Command::Summarize(SummaryScope::Page))));
+        assert!(matches!(parse("sum chapter"), Ok(Command::Summarize(SummaryScope::Chapter))));
+        assert!(matches!(parse("sum 2-4"), Ok(Command::Summarize(SummaryScope::Pages(r))) if r == (2..=4)));
+        assert!(matches!(parse("ask  what is  it?"), Ok(Command::Ask(q)) if q == "what is it?"));
+        assert!(matches!(parse("print"), Ok(Command::Print(None))));
+        assert!(matches!(parse("freq chapter"), Ok(Command::Freq(true))));
+        assert!(matches!(parse("untag"), Ok(Command::Untag(tags)) if tags.is_empty()));
+        assert!(matches!(parse("save-attachment all out"), Ok(Command::SaveAttachment(which, Some(dir))) if which == "all" && dir == Path::new("out")));
+    }
+
+    #[test]
+    fn shell_filters_keep_the_whole_line() {
+        assert!(matches!(parse(" ! sort | uniq -c "), Ok(Command::Filter(f)) if f == "sort | uniq -c"));
+        assert_eq!(parse("!").err().unwrap(), "Usage: !<shell command>");
+    }
+
+    #[test]
+    fn bad_commands_are_explained() {
+        assert_eq!(parse("").err().unwrap(), "Empty command");
+        assert_eq!(parse("ask").err().unwrap(), "Usage: ask <question>");
+        assert_eq!(parse("print 1 2").err().unwrap(), "Usage: print [N-M]");
+        assert_eq!(parse("sum 0").err().unwrap(), "Invalid page range: 0");
+        assert_eq!(parse("frobnicate").err().unwrap(), "Unknown command: frobnicate");
+    }
+}
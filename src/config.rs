@@ -0,0 +1,773 @@
+use anyhow::{anyhow, Context, Result};
+use regex::RegexSet;
+use serde::Deserialize;
+
+use crate::clip::ClipFormat;
+use crate::display::Filter;
+use crate::format::{Controls, Whitespace};
+use crate::messages::Bell;
+use crate::page_view::Fit;
+use crate::paginate::SplitBy;
+use crate::paths;
+use crate::pdf::Crop;
+use crate::theme::ThemeName;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// User configuration, read from `config.toml` in the config directory.
+/// Every field has a default so a missing or partial file is fine.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub reading: ReadingConfig,
+    pub dictionary: DictionaryConfig,
+    pub translation: TranslationConfig,
+    pub tts: TtsConfig,
+    pub llm: LlmConfig,
+    pub clipboard: ClipboardConfig,
+    pub browser: BrowserConfig,
+    pub clippings: ClippingsConfig,
+    pub notes: NotesConfig,
+    pub print: PrintConfig,
+    pub viewer: ViewerConfig,
+    pub plugins: PluginsConfig,
+    pub hooks: HooksConfig,
+    pub status: StatusConfig,
+    pub layout: LayoutConfig,
+    pub exclude: ExcludeConfig,
+    pub theme: ThemeConfig,
+    pub thumbnails: ThumbnailsConfig,
+    pub page_view: PageViewConfig,
+    pub images: ImagesConfig,
+    pub attachments: AttachmentsConfig,
+    pub ocr: OcrConfig,
+    pub spelling: SpellingConfig,
+    pub sync: SyncConfig,
+    pub memory: MemoryConfig,
+    pub pagination: PaginationConfig,
+    pub format: FormatConfig,
+    pub code: CodeConfig,
+    pub search: SearchConfig,
+    pub limits: LimitsConfig,
+    pub keyboard: KeyboardConfig,
+    /// The file as written, for `for_document` to lay overrides over
+    #[serde(skip)]
+    table: toml::Table,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ReadingConfig {
+    /// Reading speed used for the time-remaining estimate
+    pub wpm: u32,
+}
+
+impl Default for ReadingConfig {
+    fn default() -> Self {
+        Self { wpm: 230 }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DictionaryConfig {
+    /// Shell command printing a definition; `{word}` is replaced by the word
+    /// and `{lang}` by the document's two-letter language code (`en` when
+    /// it could not be detected)
+    pub command: String,
+}
+
+impl Default for DictionaryConfig {
+    fn default() -> Self {
+        Self {
+            // Dictionaries under ~/.stardict/dic/<lang> are preferred for that language
+            command: r#"d=~/.stardict/dic/{lang}; [ -d "$d" ] || d=~/.stardict/dic; sdcv --non-interactive --utf8-output --data-dir "$d" {word}"#.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct TranslationConfig {
+    /// Shell command used when `endpoint` is empty; `{text}`, `{source}`
+    /// and `{target}` are substituted
+    pub command: String,
+    /// LibreTranslate-compatible HTTP endpoint, e.g. `http://localhost:5000/translate`
+    pub endpoint: String,
+    pub api_key: String,
+    /// Source language code; empty means auto-detect
+    pub source: String,
+    pub target: String,
+}
+
+impl Default for TranslationConfig {
+    fn default() -> Self {
+        Self {
+            command: "trans -brief {source}:{target} {text}".to_string(),
+            endpoint: String::new(),
+            api_key: String::new(),
+            source: String::new(),
+            target: "en".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct TtsConfig {
+    /// Shell command speaking `{text}` and exiting when done
+    pub command: String,
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        Self {
+            command: "espeak-ng {text}".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct LlmConfig {
+    /// Base URL of an OpenAI-compatible API, e.g. `https://api.openai.com/v1`
+    pub endpoint: String,
+    pub model: String,
+    /// Falls back to the `OPENAI_API_KEY` environment variable when empty
+    pub api_key: String,
+    /// Prompt for `:summarize`; `{file}`, `{pages}` and `{text}` are substituted
+    pub summarize_prompt: String,
+    /// Prompt for `:ask`; `{file}`, `{context}` and `{question}` are substituted
+    pub ask_prompt: String,
+    /// Number of best-matching pages sent along with each `:ask` question
+    pub context_pages: usize,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            model: "gpt-4o-mini".to_string(),
+            api_key: String::new(),
+            summarize_prompt: "Summarize the following text from {file} (pages {pages}) \
+                in a few concise bullet points.\n\n{text}"
+                .to_string(),
+            ask_prompt: "Answer the question using only these excerpts from {file}. \
+                Cite the pages you used as [p. N]. If the excerpts do not contain the \
+                answer, say so.\n\n{context}\n\nQuestion: {question}"
+                .to_string(),
+            context_pages: 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    /// Command reading the text to copy on stdin, e.g. `wl-copy` or `pbcopy`.
+    /// When empty the terminal's OSC 52 clipboard escape is used.
+    pub command: String,
+    /// Copy with `y` as reflowed paragraphs rather than lines as displayed; `Y` copies the other way
+    pub reflow: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BrowserConfig {
+    /// Command opening `{url}` in a browser
+    pub command: String,
+}
+
+impl Default for BrowserConfig {
+    fn default() -> Self {
+        let command = if cfg!(target_os = "macos") { "open {url}" } else { "xdg-open {url}" };
+        Self {
+            command: command.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ClippingsConfig {
+    /// File that clipped passages are appended to
+    pub file: PathBuf,
+    pub format: ClipFormat,
+}
+
+impl Default for ClippingsConfig {
+    fn default() -> Self {
+        Self {
+            file: paths::data_dir().unwrap_or_default().join("clippings.md"),
+            format: ClipFormat::Markdown,
+        }
+    }
+}
+
+/// A Markdown notes file outside the viewer, e.g. in an Obsidian vault,
+/// that `A` sends passages to with a link back to their page.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct NotesConfig {
+    /// Empty means no notes file
+    pub file: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PrintConfig {
+    /// Command reading the formatted pages on stdin
+    pub command: String,
+    /// Column at which printed text is wrapped
+    pub width: usize,
+}
+
+impl Default for PrintConfig {
+    fn default() -> Self {
+        Self {
+            command: "lpr".to_string(),
+            width: 80,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ViewerConfig {
+    /// GUI viewer command; `{file}` and the 1-based `{page}` are substituted
+    pub command: String,
+}
+
+impl Default for ViewerConfig {
+    fn default() -> Self {
+        Self {
+            command: "zathura --page={page} {file}".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PluginsConfig {
+    /// Directory of `*.rhai` plugin scripts
+    pub dir: PathBuf,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            dir: paths::config_dir().unwrap_or_default().join("plugins"),
+        }
+    }
+}
+
+/// Shell commands run on lifecycle events, with `$PDF_FILE`, `$PDF_PAGE`
+/// and `$PDF_PAGES` set. Empty means no hook.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub on_open: String,
+    pub on_close: String,
+    pub on_page_change: String,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct StatusConfig {
+    /// Seconds a status message stays visible; 0 (or anything not a usable
+    /// number of seconds) keeps it until replaced
+    pub timeout: f64,
+    /// Also ring or flash when an action fails: "none", "audible", "visual" or "both"
+    pub bell: Bell,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            timeout: 5.0,
+            bell: Bell::None,
+        }
+    }
+}
+
+/// Header and footer templates. Empty keeps the built-in text. Fields:
+/// `{title}`, `{file}`, `{page}`, `{pages}`, `{percent}`, `{words}`,
+/// `{total_words}`, `{minutes_left}`, `{search_status}`, `{recording}`, and
+/// `{controls}` for the built-in key hints.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub header: String,
+    pub footer: String,
+    /// Show a gauge of the position in the whole document under the header,
+    /// with ticks at the pages holding search hits, tags and marks
+    pub progress_bar: bool,
+    /// Show the part of the page in view, and ticks at its search hits and
+    /// marks, along the content pane's right edge
+    pub scrollbar: bool,
+    /// Show the page in miniature beside the content, clickable to scroll
+    pub minimap: bool,
+    /// Wrap lines wider than the pane, marking the rows they continue on;
+    /// otherwise they are cut at its edge
+    pub wrap: bool,
+    /// Number the lines of the page in a gutter; wrapped rows share a number
+    pub line_numbers: bool,
+    /// Tint over the whole screen: `none`, `sepia`, `night` or `gray`
+    pub display_filter: Filter,
+    /// Show the line being read in large block letters below the content
+    pub large_print: bool,
+    /// In the layout view, place numbers by their right edge so figures set
+    /// flush right stay in columns
+    pub align_amounts: bool,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            header: String::new(),
+            footer: String::new(),
+            progress_bar: true,
+            scrollbar: true,
+            minimap: false,
+            wrap: true,
+            line_numbers: false,
+            display_filter: Filter::None,
+            large_print: false,
+            align_amounts: true,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ThumbnailsConfig {
+    /// Command rendering 1-based `{page}` of `{file}` to `{output}.png`
+    pub command: String,
+    /// Thumbnails per row in the grid
+    pub columns: usize,
+}
+
+impl Default for ThumbnailsConfig {
+    fn default() -> Self {
+        Self {
+            command: "pdftoppm -png -r 30 -f {page} -l {page} -singlefile {file} {output}".to_string(),
+            columns: 4,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PageViewConfig {
+    /// Command rendering the part of 1-based `{page}` of `{file}` at `{dpi}`
+    /// from pixel `{x}`,`{y}`, `{width}` by `{height}`, to `{output}.png`
+    pub command: String,
+    /// What the page fits in the view at 100%: `width` or `page`
+    pub fit: Fit,
+}
+
+impl Default for PageViewConfig {
+    fn default() -> Self {
+        Self {
+            command: "pdftoppm -png -r {dpi} -x {x} -y {y} -W {width} -H {height} -f {page} -l {page} -singlefile {file} {output}"
+                .to_string(),
+            fit: Fit::Width,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct OcrConfig {
+    /// Command printing the text of 1-based `{page}` of `{file}` in `{lang}`
+    pub command: String,
+    /// Tesseract language models joined with `+`, e.g. `deu+eng`; empty
+    /// means the document's detected language
+    pub lang: String,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            command: "pdftoppm -r 300 -gray -png -singlefile -f {page} -l {page} {file} | tesseract - - -l {lang} 2>/dev/null"
+                .to_string(),
+            lang: String::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SpellingConfig {
+    /// Underline misspelled words from the start instead of after `:spell`
+    pub enabled: bool,
+    /// Command reading text on stdin and listing misspelled words in `{lang}`, one per line
+    pub command: String,
+}
+
+impl Default for SpellingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: "aspell list --lang={lang}".to_string(),
+        }
+    }
+}
+
+/// How to cut text into pages when the PDF's page tree gives no boundaries.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct PaginationConfig {
+    pub split: SplitBy,
+    /// Page size for `lines`, and for the others when the text has too few markers
+    pub lines_per_page: usize,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            split: SplitBy::Lines,
+            lines_per_page: 50,
+        }
+    }
+}
+
+/// How extracted page text is tidied before it is shown.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct FormatConfig {
+    pub whitespace: Whitespace,
+    /// Keep leading whitespace, for code listings, poetry and nested lists
+    pub indent: bool,
+    /// Columns between tab stops when tabs are expanded; 0 keeps tabs
+    pub tab_width: usize,
+    pub controls: Controls,
+    /// Lay out lines whose words line up in columns as aligned tables
+    pub tables: bool,
+    /// Show LaTeX source and math-font letters in the text as Unicode math
+    pub math: bool,
+    /// Margins left out of every page's text, in percent
+    pub crop: Crop,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            whitespace: Whitespace::default(),
+            indent: false,
+            tab_width: 8,
+            controls: Controls::default(),
+            tables: true,
+            math: true,
+            crop: Crop::default(),
+        }
+    }
+}
+
+/// Syntax highlighting of source code found in page text.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CodeConfig {
+    pub highlight: bool,
+    /// A syntect theme, e.g. "base16-ocean.dark", "InspiredGitHub", "Solarized (dark)"
+    pub theme: String,
+    /// Language of every listing, as a file extension or name; empty guesses per listing
+    pub language: String,
+}
+
+impl Default for CodeConfig {
+    fn default() -> Self {
+        Self {
+            highlight: true,
+            theme: "base16-ocean.dark".to_string(),
+            language: String::new(),
+        }
+    }
+}
+
+/// Where the view lands when going to a search result.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// Put the matching line in the middle of the content pane
+    pub center: bool,
+    /// Lines shown above the matching line when not centering
+    pub context_lines: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            center: true,
+            context_lines: 5,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Megabytes of extracted text kept in memory; past it the least recently
+    /// viewed pages move to temporary files. 0 means no limit
+    pub max_text_mb: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct KeyboardConfig {
+    /// Use the kitty keyboard protocol where the terminal supports it, for
+    /// Ctrl+Shift chords and Ctrl+I apart from Tab
+    pub kitty_protocol: bool,
+}
+
+impl Default for KeyboardConfig {
+    fn default() -> Self {
+        Self { kitty_protocol: true }
+    }
+}
+
+/// Bounds on the work one PDF may cause, against files made to hang the
+/// viewer or run it out of memory. 0 means no limit.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Seconds extracting one page may take before the rest of it is left out
+    pub page_seconds: f64,
+    /// Kilobytes of text one page may extract to
+    pub page_kb: usize,
+    /// Objects a PDF may have; files with more are not opened
+    pub max_objects: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            page_seconds: 10.0,
+            page_kb: 1024,
+            max_objects: 1_000_000,
+        }
+    }
+}
+
+/// Lines hidden from the text, like stamps and download notices printed on
+/// every page. They stay in the page text, so line numbers do not change
+/// when they are shown; only drawing and searching pass over them.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ExcludeConfig {
+    /// Regular expressions matched against each line, e.g. "^CONFIDENTIAL"
+    pub lines: Vec<String>,
+    /// `lines` compiled, set by `compile`
+    #[serde(skip)]
+    patterns: RegexSet,
+}
+
+impl ExcludeConfig {
+    /// Compile `lines` after they were read or added to.
+    pub fn compile(&mut self) -> Result<()> {
+        self.patterns = RegexSet::new(&self.lines).context("Invalid exclude pattern")?;
+        Ok(())
+    }
+
+    /// Whether `line` of page text matches a pattern, compared trimmed.
+    pub fn matches(&self, line: &str) -> bool {
+        !self.patterns.is_empty() && self.patterns.is_match(line.trim())
+    }
+
+    /// `text` without the lines that match, for output that is not tied to
+    /// line numbers.
+    pub fn hide(&self, text: &str) -> String {
+        if self.patterns.is_empty() {
+            return text.to_string();
+        }
+        text.lines().filter(|line| !self.matches(line)).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// The `[theme]` section: a theme to start from and colors replacing its
+/// own, by role. Colors are names ("yellow", "darkgray"), 0–255 palette
+/// indexes or `#rrggbb`.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// "default" or "high-contrast"
+    pub name: ThemeName,
+    #[serde(flatten)]
+    pub colors: BTreeMap<String, String>,
+}
+
+/// Reading positions shared between machines through a synced folder.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// Folder holding `pdf_reader-progress.json`, e.g. one shared with Syncthing; empty disables syncing
+    pub dir: PathBuf,
+    /// Name recorded with each position; empty uses the hostname
+    pub device: String,
+    /// Also read and write KOReader's `book.sdr/metadata.pdf.lua` next to `book.pdf`
+    pub koreader: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ImagesConfig {
+    /// Directory images exported from `:images` are saved in
+    pub export_dir: PathBuf,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self {
+            export_dir: PathBuf::from("."),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AttachmentsConfig {
+    /// Directory embedded files are saved in
+    pub dir: PathBuf,
+}
+
+impl Default for AttachmentsConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("."),
+        }
+    }
+}
+
+/// A file in a document's folder overriding settings for the documents there.
+const SIDECAR: &str = ".pdf_reader.toml";
+
+/// What a sidecar may set. It travels with the documents, maybe from someone
+/// else, so nothing that runs commands or writes files, and no numbers that
+/// could stall or stop the viewer.
+const SIDECAR_KEYS: &[(&str, &[&str])] = &[
+    ("reading", &["wpm"]),
+    ("pagination", &["split", "lines_per_page"]),
+    ("format", &["whitespace", "indent", "controls", "tables", "math"]),
+    ("code", &["highlight", "theme", "language"]),
+    ("clipboard", &["reflow"]),
+    ("ocr", &["lang"]),
+    ("layout", &["header", "footer", "progress_bar", "scrollbar", "minimap", "wrap", "line_numbers", "display_filter", "large_print", "align_amounts"]),
+];
+
+impl Config {
+    /// Load the config from `path`, or from the default location when `None`.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match paths::config_file() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read config file {}", path.display()))?;
+        let table: toml::Table = toml::from_str(&raw)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        let mut config: Self = toml::Value::Table(table.clone())
+            .try_into()
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.check().with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.table = table;
+        Ok(config)
+    }
+
+    /// The config for `pdf`: this file's settings with the `[documents."<path>"]`
+    /// sections whose folder or file holds `pdf` laid over them, the most
+    /// specific last, then a `.pdf_reader.toml` beside `pdf` and its own
+    /// `[documents."<name>"]` sections.
+    pub fn for_document(&self, pdf: &Path) -> Result<Self> {
+        let pdf = pdf.canonicalize().unwrap_or_else(|_| pdf.to_path_buf());
+        let mut table = self.table.clone();
+        for layer in matching(&self.table, &pdf, None) {
+            merge(&mut table, &layer);
+        }
+        if let Some(dir) = pdf.parent() {
+            let path = dir.join(SIDECAR);
+            if let Ok(raw) = fs::read_to_string(&path) {
+                let sidecar: toml::Table = toml::from_str(&raw)
+                    .with_context(|| format!("Invalid config file {}", path.display()))?;
+                merge(&mut table, &sidecar_settings(&sidecar));
+                for layer in matching(&sidecar, &pdf, Some(dir)) {
+                    merge(&mut table, &sidecar_settings(&layer));
+                }
+            }
+        }
+        let mut config: Self = toml::Value::Table(table)
+            .try_into()
+            .with_context(|| format!("Invalid settings for {}", pdf.display()))?;
+        config.check().with_context(|| format!("Invalid settings for {}", pdf.display()))?;
+        config.table = self.table.clone();
+        Ok(config)
+    }
+
+    /// Values the types allow but the viewer cannot use.
+    fn check(&mut self) -> Result<()> {
+        self.format.crop.check().map_err(|e| anyhow!("format.crop: {}", e))?;
+        self.exclude.compile().context("exclude.lines")
+    }
+}
+
+/// The `[documents."<path>"]` sections of `table` that apply to `pdf`, least
+/// specific first. Relative paths start from `base`.
+fn matching(table: &toml::Table, pdf: &Path, base: Option<&Path>) -> Vec<toml::Table> {
+    let Some(documents) = table.get("documents").and_then(toml::Value::as_table) else {
+        return Vec::new();
+    };
+    let mut layers: Vec<(usize, toml::Table)> = documents
+        .iter()
+        .filter_map(|(path, settings)| {
+            let path = expand_tilde(Path::new(path));
+            let path = base.map_or_else(|| path.clone(), |base| base.join(&path));
+            let path = path.canonicalize().unwrap_or(path);
+            let settings = settings.as_table()?.clone();
+            pdf.starts_with(&path).then(|| (path.components().count(), settings))
+        })
+        .collect();
+    layers.sort_by_key(|(depth, _)| *depth);
+    layers.into_iter().map(|(_, settings)| settings).collect()
+}
+
+/// Lay `over` onto `base`, section by section.
+fn merge(base: &mut toml::Table, over: &toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge(base, over),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// The settings of a sidecar it may set; others are ignored.
+fn sidecar_settings(sidecar: &toml::Table) -> toml::Table {
+    SIDECAR_KEYS
+        .iter()
+        .filter_map(|(section, keys)| {
+            let settings: toml::Table = sidecar
+                .get(*section)?
+                .as_table()?
+                .iter()
+                .filter(|(key, _)| keys.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            Some((section.to_string(), toml::Value::Table(settings)))
+        })
+        .collect()
+}
+
+/// Expand a leading `~/` to the home directory.
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
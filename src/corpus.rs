@@ -0,0 +1,148 @@
+use anyhow::Result;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::config::{FormatConfig, PaginationConfig};
+use crate::{paths, state};
+
+/// Matches kept per file, so a common word does not bury the other files.
+const MAX_HITS_PER_FILE: usize = 100;
+
+/// A search match in one of several documents.
+#[derive(Clone)]
+pub struct FileHit {
+    pub path: PathBuf,
+    /// 0-based page and line, as in the viewer's search results
+    pub page: usize,
+    pub line: usize,
+    pub text: String,
+}
+
+/// PDF files directly inside `dir`, sorted by name.
+pub fn pdfs_in(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+/// The settings the text of a PDF was read with, kept in its cache file, so
+/// changing the pagination, formatting or crop rereads it.
+pub fn settings(pagination: PaginationConfig, format: FormatConfig) -> String {
+    format!("{:?} {:?}", pagination, format)
+}
+
+/// The pages of a PDF from the text cache, or read with `read` and cached,
+/// so a folder is only parsed once between changes.
+pub fn cached_pages(path: &Path, settings: &str, read: impl Fn(&Path) -> Result<Vec<String>>) -> Result<Vec<String>> {
+    cached_pages_in(&cache_dir(), path, settings, read)
+}
+
+/// `cached_pages` with the cache kept in `dir`.
+pub fn cached_pages_in(dir: &Path, path: &Path, settings: &str, read: impl Fn(&Path) -> Result<Vec<String>>) -> Result<Vec<String>> {
+    let cache = cache_path(dir, path);
+    if is_fresh(&cache, path)
+        && let Ok(text) = fs::read_to_string(&cache)
+        && let Some((cached, pages)) = text.split_once('\n')
+        && cached == settings
+    {
+        return Ok(pages.split('\x0C').map(str::to_string).collect());
+    }
+    let pages = read(path)?;
+    // A cache that cannot be written only costs a reparse next time
+    if !paths::private() && let Some(dir) = cache.parent() && fs::create_dir_all(dir).is_ok() {
+        let _ = fs::write(&cache, format!("{}\n{}", settings, pages.join("\x0C")));
+    }
+    Ok(pages)
+}
+
+/// Whether the text cache holds a PDF's current text, read with `settings`.
+pub fn is_cached(path: &Path, settings: &str) -> bool {
+    let cache = cache_path(&cache_dir(), path);
+    is_fresh(&cache, path)
+        && fs::File::open(cache)
+            .and_then(|file| {
+                let mut cached = String::new();
+                BufReader::new(file).read_line(&mut cached)?;
+                Ok(cached.trim_end_matches('\n') == settings)
+            })
+            .unwrap_or(false)
+}
+
+/// Whether the cache file is newer than the PDF.
+fn is_fresh(cache: &Path, path: &Path) -> bool {
+    fs::metadata(cache)
+        .and_then(|cached| Ok(cached.modified()? >= fs::metadata(path)?.modified()?))
+        .unwrap_or(false)
+}
+
+/// Fill the text cache for every file in `paths`. Returns how many were
+/// indexed and the names of those that could not be read.
+pub fn index(paths: &[PathBuf], settings: &str, read: impl Fn(&Path) -> Result<Vec<String>>) -> (usize, Vec<String>) {
+    let mut failed = Vec::new();
+    for path in paths {
+        if cached_pages(path, settings, &read).is_err() {
+            failed.push(path.display().to_string());
+        }
+    }
+    (paths.len() - failed.len(), failed)
+}
+
+/// Case-insensitive matches of `query` in the pages of one document, taken
+/// in order; stops reading pages once it has enough.
+pub fn search_pages(path: &Path, pages: impl IntoIterator<Item = impl AsRef<str>>, query: &str) -> Vec<FileHit> {
+    let query = query.to_lowercase();
+    let mut hits = Vec::new();
+    for (page, text) in pages.into_iter().enumerate() {
+        for (line, text) in text.as_ref().lines().enumerate() {
+            if hits.len() == MAX_HITS_PER_FILE {
+                return hits;
+            }
+            if text.to_lowercase().contains(&query) {
+                hits.push(FileHit {
+                    path: path.to_path_buf(),
+                    page,
+                    line,
+                    text: text.trim().to_string(),
+                });
+            }
+        }
+    }
+    hits
+}
+
+/// Search every file in `paths`, reading each with `read`. Files that cannot
+/// be read are skipped and returned by name.
+pub fn search(
+    paths: &[PathBuf],
+    query: &str,
+    read: impl Fn(&Path) -> Result<Vec<String>>,
+) -> (Vec<FileHit>, Vec<String>) {
+    let mut hits = Vec::new();
+    let mut failed = Vec::new();
+    for path in paths {
+        match read(path) {
+            Ok(pages) => hits.extend(search_pages(path, &pages, query)),
+            Err(_) => failed.push(path.display().to_string()),
+        }
+    }
+    (hits, failed)
+}
+
+fn cache_dir() -> PathBuf {
+    paths::cache_dir().unwrap_or_else(env::temp_dir).join("text")
+}
+
+/// The first line holds the settings; pages are separated by form feeds, as
+/// in `pdftotext` output.
+fn cache_path(dir: &Path, pdf: &Path) -> PathBuf {
+    dir.join(format!("{}.txt", state::file_key(pdf)))
+}
@@ -0,0 +1,159 @@
+/// Position of the content cursor within the current page.
+/// `line` indexes the page's lines, `col` counts characters within that line.
+/// Cursors order by line, then column.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    pub line: usize,
+    pub col: usize,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+fn line_len(lines: &[&str], line: usize) -> usize {
+    lines.get(line).map_or(0, |l| l.chars().count())
+}
+
+impl Cursor {
+    /// Keep the cursor on an existing line and character.
+    pub fn clamp_to(&mut self, lines: &[&str]) {
+        self.line = self.line.min(lines.len().saturating_sub(1));
+        self.col = self.col.min(line_len(lines, self.line).saturating_sub(1));
+    }
+
+    pub fn left(&mut self) {
+        self.col = self.col.saturating_sub(1);
+    }
+
+    pub fn right(&mut self, lines: &[&str]) {
+        if self.col + 1 < line_len(lines, self.line) {
+            self.col += 1;
+        }
+    }
+
+    pub fn up(&mut self, lines: &[&str]) {
+        self.line = self.line.saturating_sub(1);
+        self.clamp_to(lines);
+    }
+
+    pub fn down(&mut self, lines: &[&str]) {
+        if self.line + 1 < lines.len() {
+            self.line += 1;
+        }
+        self.clamp_to(lines);
+    }
+
+    pub fn line_start(&mut self) {
+        self.col = 0;
+    }
+
+    pub fn line_end(&mut self, lines: &[&str]) {
+        self.col = line_len(lines, self.line).saturating_sub(1);
+    }
+
+    /// Move to the start of the next word, continuing onto following lines.
+    pub fn next_word(&mut self, lines: &[&str]) {
+        let mut line = self.line;
+        let mut chars: Vec<char> = lines.get(line).map_or(Vec::new(), |l| l.chars().collect());
+        let mut col = self.col;
+
+        // Skip the rest of the word we are on
+        while col < chars.len() && is_word_char(chars[col]) {
+            col += 1;
+        }
+        loop {
+            while col < chars.len() && !is_word_char(chars[col]) {
+                col += 1;
+            }
+            if col < chars.len() {
+                self.line = line;
+                self.col = col;
+                return;
+            }
+            if line + 1 >= lines.len() {
+                return;
+            }
+            line += 1;
+            chars = lines[line].chars().collect();
+            col = 0;
+        }
+    }
+
+    /// Move to the start of the previous word, continuing onto preceding lines.
+    pub fn prev_word(&mut self, lines: &[&str]) {
+        let mut line = self.line;
+        let mut chars: Vec<char> = lines.get(line).map_or(Vec::new(), |l| l.chars().collect());
+        let mut col = self.col.min(chars.len());
+
+        loop {
+            while col > 0 && !is_word_char(chars[col - 1]) {
+                col -= 1;
+            }
+            if col > 0 {
+                while col > 0 && is_word_char(chars[col - 1]) {
+                    col -= 1;
+                }
+                self.line = line;
+                self.col = col;
+                return;
+            }
+            if line == 0 {
+                return;
+            }
+            line -= 1;
+            chars = lines[line].chars().collect();
+            col = chars.len();
+        }
+    }
+
+    /// The word the cursor is on, if it is on a word character.
+    pub fn word<'a>(&self, lines: &[&'a str]) -> Option<&'a str> {
+        let line = lines.get(self.line)?;
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        if !chars.get(self.col).is_some_and(|&(_, c)| is_word_char(c)) {
+            return None;
+        }
+
+        let mut start = self.col;
+        while start > 0 && is_word_char(chars[start - 1].1) {
+            start -= 1;
+        }
+        let mut end = self.col;
+        while end < chars.len() && is_word_char(chars[end].1) {
+            end += 1;
+        }
+
+        let start_byte = chars[start].0;
+        let end_byte = chars.get(end).map_or(line.len(), |&(i, _)| i);
+        Some(line[start_byte..end_byte].trim_matches('\''))
+    }
+}
+
+/// Byte offset of the `col`-th character of `line`, or the line length past the end.
+pub fn byte_offset(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+}
+
+/// Text between two cursors, inclusive of both ends, with line breaks kept.
+pub fn text_between(lines: &[&str], a: Cursor, b: Cursor) -> String {
+    let (start, end) = if a <= b { (a, b) } else { (b, a) };
+    (start.line..=end.line)
+        .filter_map(|line_idx| {
+            let line = lines.get(line_idx)?;
+            selection_range(line, line_idx, a, b).map(|range| &line[range])
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Byte range of `line_idx` covered by the selection between `a` and `b`.
+pub fn selection_range(line: &str, line_idx: usize, a: Cursor, b: Cursor) -> Option<std::ops::Range<usize>> {
+    let (start, end) = if a <= b { (a, b) } else { (b, a) };
+    if line_idx < start.line || line_idx > end.line {
+        return None;
+    }
+    let from = if line_idx == start.line { byte_offset(line, start.col) } else { 0 };
+    let to = if line_idx == end.line { byte_offset(line, end.col + 1) } else { line.len() };
+    Some(from..to)
+}
@@ -0,0 +1,347 @@
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// One line of a diff, with the 0-based page it is on in its document
+/// (the new one for unchanged lines).
+#[derive(Clone)]
+pub struct DiffLine {
+    pub change: Change,
+    pub text: String,
+    pub page: usize,
+}
+
+/// Line diff of two documents' pages. Lines are compared with whitespace
+/// collapsed, since extraction spaces the same text differently between versions.
+pub fn diff_pages(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    let lines = |pages: &[String]| -> Vec<(usize, String, String)> {
+        pages
+            .iter()
+            .enumerate()
+            .flat_map(|(page, text)| text.lines().map(move |line| (page, line)))
+            .map(|(page, line)| (page, line.to_string(), line.split_whitespace().collect::<Vec<_>>().join(" ")))
+            .collect()
+    };
+    let (old, new) = (lines(old), lines(new));
+    let old_keys: Vec<&str> = old.iter().map(|(_, _, key)| key.as_str()).collect();
+    let new_keys: Vec<&str> = new.iter().map(|(_, _, key)| key.as_str()).collect();
+
+    let mut ops = Vec::new();
+    conquer(&old_keys, 0..old_keys.len(), &new_keys, 0..new_keys.len(), &mut ops);
+    let mut lines: Vec<DiffLine> = ops
+        .into_iter()
+        .map(|op| {
+            let ((page, text, _), change) = match op {
+                Op::Equal(j) => (&new[j], Change::Same),
+                Op::Delete(i) => (&old[i], Change::Removed),
+                Op::Insert(j) => (&new[j], Change::Added),
+            };
+            DiffLine { change, text: text.clone(), page: *page }
+        })
+        .collect();
+    // Within each run of changes, show what was removed before what replaced it
+    for run in lines.split_mut(|line| line.change == Change::Same) {
+        run.sort_by_key(|line| line.change == Change::Added);
+    }
+    lines
+}
+
+/// An edit step, with the index of the line in the old or new document.
+enum Op {
+    /// Unchanged; index into the new document
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Myers' linear-space diff: trim the common ends, split the rest at a
+/// middle snake and recurse on both halves.
+fn conquer(old: &[&str], old_range: Range<usize>, new: &[&str], new_range: Range<usize>, ops: &mut Vec<Op>) {
+    let (mut old_start, mut old_end) = (old_range.start, old_range.end);
+    let (mut new_start, mut new_end) = (new_range.start, new_range.end);
+    while old_start < old_end && new_start < new_end && old[old_start] == new[new_start] {
+        ops.push(Op::Equal(new_start));
+        old_start += 1;
+        new_start += 1;
+    }
+    let mut suffix = 0;
+    while old_start < old_end && new_start < new_end && old[old_end - 1] == new[new_end - 1] {
+        old_end -= 1;
+        new_end -= 1;
+        suffix += 1;
+    }
+
+    if old_start == old_end {
+        ops.extend((new_start..new_end).map(Op::Insert));
+    } else if new_start == new_end {
+        ops.extend((old_start..old_end).map(Op::Delete));
+    } else if let Some((x, y)) = middle_snake(&old[old_start..old_end], &new[new_start..new_end]) {
+        conquer(old, old_start..old_start + x, new, new_start..new_start + y, ops);
+        conquer(old, old_start + x..old_end, new, new_start + y..new_end, ops);
+    } else {
+        ops.extend((old_start..old_end).map(Op::Delete));
+        ops.extend((new_start..new_end).map(Op::Insert));
+    }
+
+    ops.extend((0..suffix).map(|i| Op::Equal(new_end + i)));
+}
+
+/// Where the forward and backward searches for the shortest edit script meet.
+fn middle_snake(old: &[&str], new: &[&str]) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    let max_d = (n + m + 1) / 2 + 1;
+    let offset = max_d + 1;
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = vec![0isize; 2 * offset as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+
+    for d in 0..max_d {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            if odd && (k - delta).abs() < d && forward[at(k)] + backward[at(-(k - delta))] >= n {
+                return Some((x0 as usize, y0 as usize));
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            if !odd && (k - delta).abs() <= d && backward[at(k)] + forward[at(-(k - delta))] >= n {
+                return Some(((n - x) as usize, (m - y) as usize));
+            }
+        }
+    }
+    None
+}
+
+/// One screen row: a single line in the unified view, or an old/new pair side by side.
+enum Row {
+    Unified(DiffLine),
+    Split(Option<DiffLine>, Option<DiffLine>),
+}
+
+/// Full-screen view of a document diff, unified or side by side.
+pub struct DiffView {
+    old_name: String,
+    new_name: String,
+    lines: Vec<DiffLine>,
+    side_by_side: bool,
+    rows: Vec<Row>,
+    /// Row index where each run of changes starts
+    hunks: Vec<usize>,
+    scroll: usize,
+    viewport_height: usize,
+}
+
+impl DiffView {
+    pub fn new(old_name: String, new_name: String, lines: Vec<DiffLine>, side_by_side: bool) -> Self {
+        let mut view = Self {
+            old_name,
+            new_name,
+            lines,
+            side_by_side,
+            rows: Vec::new(),
+            hunks: Vec::new(),
+            scroll: 0,
+            viewport_height: 0,
+        };
+        view.layout();
+        view
+    }
+
+    /// Build the rows for the current mode and find where hunks start.
+    fn layout(&mut self) {
+        self.rows.clear();
+        if self.side_by_side {
+            let mut i = 0;
+            while i < self.lines.len() {
+                if self.lines[i].change == Change::Same {
+                    self.rows.push(Row::Split(Some(self.lines[i].clone()), Some(self.lines[i].clone())));
+                    i += 1;
+                    continue;
+                }
+                // Pair a run of removals with the additions that replace it
+                let run_end = self.lines[i..]
+                    .iter()
+                    .position(|line| line.change == Change::Same)
+                    .map_or(self.lines.len(), |n| i + n);
+                let run = &self.lines[i..run_end];
+                let removed: Vec<&DiffLine> = run.iter().filter(|l| l.change == Change::Removed).collect();
+                let added: Vec<&DiffLine> = run.iter().filter(|l| l.change == Change::Added).collect();
+                for row in 0..removed.len().max(added.len()) {
+                    self.rows.push(Row::Split(removed.get(row).cloned().cloned(), added.get(row).cloned().cloned()));
+                }
+                i = run_end;
+            }
+        } else {
+            self.rows.extend(self.lines.iter().cloned().map(Row::Unified));
+        }
+
+        let changed = |row: &Row| match row {
+            Row::Unified(line) => line.change != Change::Same,
+            Row::Split(left, right) => {
+                left.as_ref().is_some_and(|l| l.change != Change::Same)
+                    || right.as_ref().is_some_and(|l| l.change != Change::Same)
+            }
+        };
+        self.hunks = (0..self.rows.len())
+            .filter(|&i| changed(&self.rows[i]) && (i == 0 || !changed(&self.rows[i - 1])))
+            .collect();
+    }
+
+    /// Index of the hunk at or above the top of the screen.
+    fn current_hunk(&self) -> Option<usize> {
+        self.hunks.iter().rposition(|&start| start <= self.scroll)
+    }
+
+    fn jump_to_hunk(&mut self, hunk: usize) {
+        if let Some(&start) = self.hunks.get(hunk) {
+            self.scroll = start;
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.rows.len().saturating_sub(self.viewport_height)
+    }
+
+    /// Handle a key. Returns false when the view should close.
+    pub fn key(&mut self, code: KeyCode) -> bool {
+        let page = self.viewport_height.max(1);
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll = (self.scroll + 1).min(self.max_scroll()),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll = (self.scroll + page).min(self.max_scroll()),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(page),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.scroll = self.max_scroll(),
+            KeyCode::Char('n') | KeyCode::Char(']') => {
+                if let Some(next) = self.hunks.iter().position(|&start| start > self.scroll) {
+                    self.jump_to_hunk(next);
+                }
+            }
+            KeyCode::Char('p') | KeyCode::Char('[') => {
+                if let Some(previous) = self.hunks.iter().rposition(|&start| start < self.scroll) {
+                    self.jump_to_hunk(previous);
+                }
+            }
+            KeyCode::Char('s') => {
+                let hunk = self.current_hunk();
+                self.side_by_side = !self.side_by_side;
+                self.layout();
+                self.scroll = 0;
+                if let Some(hunk) = hunk {
+                    self.jump_to_hunk(hunk);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn render(&mut self, f: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(1), Constraint::Length(3)])
+            .split(f.size());
+        self.viewport_height = chunks[1].height.saturating_sub(2) as usize;
+
+        let added = self.lines.iter().filter(|l| l.change == Change::Added).count();
+        let removed = self.lines.iter().filter(|l| l.change == Change::Removed).count();
+        let hunk = match (self.current_hunk(), self.hunks.len()) {
+            (_, 0) => "no changes".to_string(),
+            (Some(i), n) => format!("hunk {}/{}", i + 1, n),
+            (None, n) => format!("{} hunks", n),
+        };
+        let header = format!("{} → {} | +{} -{} | {}", self.old_name, self.new_name, added, removed, hunk);
+        f.render_widget(
+            Paragraph::new(header)
+                .block(Block::default().borders(Borders::ALL))
+                .style(Style::default().fg(Color::Cyan)),
+            chunks[0],
+        );
+
+        let visible = self.rows.iter().skip(self.scroll).take(self.viewport_height);
+        if self.side_by_side {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(chunks[1]);
+            let (mut left, mut right) = (Vec::new(), Vec::new());
+            for row in visible {
+                if let Row::Split(old, new) = row {
+                    left.push(styled(old.as_ref()));
+                    right.push(styled(new.as_ref()));
+                }
+            }
+            f.render_widget(Paragraph::new(left).block(Block::default().borders(Borders::ALL).title(self.old_name.as_str())), columns[0]);
+            f.render_widget(Paragraph::new(right).block(Block::default().borders(Borders::ALL).title(self.new_name.as_str())), columns[1]);
+        } else {
+            let lines: Vec<Line> = visible
+                .filter_map(|row| match row {
+                    Row::Unified(line) => Some(styled(Some(line))),
+                    Row::Split(..) => None,
+                })
+                .collect();
+            f.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Diff")), chunks[1]);
+        }
+
+        let controls = "j/k (scroll) | n/p (next/prev change) | Space/PgUp (page) | g/G (top/end) | s (side by side) | q (quit)";
+        f.render_widget(
+            Paragraph::new(controls)
+                .block(Block::default().borders(Borders::ALL))
+                .style(Style::default().fg(Color::Yellow)),
+            chunks[2],
+        );
+    }
+}
+
+/// A diff line with its page number and a `+`/`-` marker in the change's colour.
+fn styled(line: Option<&DiffLine>) -> Line<'static> {
+    let Some(line) = line else {
+        return Line::from("");
+    };
+    let (marker, style) = match line.change {
+        Change::Same => (' ', Style::default()),
+        Change::Removed => ('-', Style::default().fg(Color::Red)),
+        Change::Added => ('+', Style::default().fg(Color::Green)),
+    };
+    Line::from(vec![
+        Span::styled(format!("{:>4} ", line.page + 1), Style::default().fg(Color::DarkGray)),
+        Span::styled(format!("{} {}", marker, line.text), style),
+    ])
+}
@@ -0,0 +1,148 @@
+use ratatui::buffer::Buffer;
+use ratatui::style::Color;
+use serde::Deserialize;
+
+/// A tint laid over everything drawn, for reading late at night.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    /// Colors as drawn
+    #[default]
+    None,
+    /// Browns on a dark paper tone
+    Sepia,
+    /// Dimmed, warmer colors with less blue and less contrast
+    Night,
+    /// Shades of gray
+    Gray,
+}
+
+impl Filter {
+    const ALL: [Filter; 4] = [Filter::None, Filter::Sepia, Filter::Night, Filter::Gray];
+
+    /// The filter after this one, for a key that cycles through them.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&filter| filter == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Filter::None => "none",
+            Filter::Sepia => "sepia",
+            Filter::Night => "night",
+            Filter::Gray => "gray",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "grey" | "grayscale" | "greyscale" => Some(Filter::Gray),
+            "off" => Some(Filter::None),
+            _ => Self::ALL.into_iter().find(|filter| filter.name() == name),
+        }
+    }
+
+    /// What the terminal's own text and background colors become; `None`
+    /// leaves them to the terminal.
+    fn defaults(self) -> (Option<Color>, Option<Color>) {
+        match self {
+            Filter::None | Filter::Gray => (None, None),
+            Filter::Sepia => (Some(Color::Rgb(222, 200, 164)), Some(Color::Rgb(40, 31, 22))),
+            Filter::Night => (Some(Color::Rgb(150, 138, 118)), None),
+        }
+    }
+
+    fn map(self, (r, g, b): (u8, u8, u8)) -> Color {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+        let (r, g, b) = match self {
+            Filter::None => (r, g, b),
+            Filter::Sepia => (
+                0.393 * r + 0.769 * g + 0.189 * b,
+                0.349 * r + 0.686 * g + 0.168 * b,
+                0.272 * r + 0.534 * g + 0.131 * b,
+            ),
+            Filter::Night => (0.65 * r + 20.0, 0.55 * g + 18.0, 0.35 * b + 12.0),
+            Filter::Gray => {
+                let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+                (luma, luma, luma)
+            }
+        };
+        let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+        Color::Rgb(channel(r), channel(g), channel(b))
+    }
+}
+
+/// Recolor every cell of a drawn frame through `filter`.
+pub fn apply(buffer: &mut Buffer, filter: Filter) {
+    if filter == Filter::None {
+        return;
+    }
+    let (default_fg, default_bg) = filter.defaults();
+    for cell in &mut buffer.content {
+        cell.fg = match rgb(cell.fg) {
+            Some(color) => filter.map(color),
+            None => default_fg.unwrap_or(cell.fg),
+        };
+        cell.bg = match rgb(cell.bg) {
+            Some(color) => filter.map(color),
+            None => default_bg.unwrap_or(cell.bg),
+        };
+    }
+}
+
+/// The usual xterm values of a color, or `None` for the terminal's default.
+pub fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    let index = match color {
+        Color::Reset => return None,
+        Color::Rgb(r, g, b) => return Some((r, g, b)),
+        Color::Indexed(index) => index,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+    };
+    Some(match index {
+        0..=15 => ANSI[index as usize],
+        // The 6×6×6 cube, then the gray ramp
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + 40 * n };
+            let n = index - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + 10 * (index - 232);
+            (gray, gray, gray)
+        }
+    })
+}
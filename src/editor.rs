@@ -0,0 +1,153 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// The text of a one-line prompt and where typing goes in it, with the
+/// readline keys for moving and deleting.
+#[derive(Clone, Default)]
+pub struct LineEditor {
+    text: String,
+    /// Byte offset in `text`, always on a character boundary
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The text left of the cursor, to measure where it is on screen.
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Apply a movement or deletion key; returns whether `key` was one.
+    /// Other characters are left to the caller, which decides what a prompt accepts.
+    pub fn edit(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Left => self.cursor = self.prev_boundary(),
+            KeyCode::Right => self.cursor = self.next_boundary(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.text.len(),
+            KeyCode::Backspace => {
+                let start = self.prev_boundary();
+                self.delete_back_to(start);
+            }
+            KeyCode::Delete => {
+                let end = self.next_boundary();
+                self.text.replace_range(self.cursor..end, "");
+            }
+            KeyCode::Char('w') if ctrl => self.delete_back_to(self.word_start()),
+            KeyCode::Char('u') if ctrl => self.delete_back_to(0),
+            KeyCode::Char('k') if ctrl => self.text.truncate(self.cursor),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prev_boundary(&self) -> usize {
+        self.before_cursor().chars().next_back().map_or(self.cursor, |c| self.cursor - c.len_utf8())
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..].chars().next().map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    /// Where the word before the cursor starts, past any spaces after it, as
+    /// readline's Ctrl-W counts words.
+    fn word_start(&self) -> usize {
+        let before = self.before_cursor().trim_end();
+        before.rfind(char::is_whitespace).map_or(0, |space| space + before[space..].chars().next().map_or(1, char::len_utf8))
+    }
+
+    fn delete_back_to(&mut self, start: usize) {
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> LineEditor {
+        let mut editor = LineEditor::default();
+        text.chars().for_each(|c| editor.insert(c));
+        editor
+    }
+
+    fn press(editor: &mut LineEditor, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        editor.edit(KeyEvent::new(code, modifiers))
+    }
+
+    fn ctrl(editor: &mut LineEditor, c: char) -> bool {
+        press(editor, KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn moves_step_over_whole_characters() {
+        let mut editor = typed("añb");
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        assert_eq!(editor.before_cursor(), "a");
+        press(&mut editor, KeyCode::Delete, KeyModifiers::NONE);
+        assert_eq!(editor.text(), "ab");
+        ctrl(&mut editor, 'e');
+        press(&mut editor, KeyCode::Right, KeyModifiers::NONE);
+        assert_eq!(editor.before_cursor(), "ab");
+        ctrl(&mut editor, 'a');
+        press(&mut editor, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!((editor.text(), editor.before_cursor()), ("ab", ""));
+    }
+
+    #[test]
+    fn ctrl_w_deletes_the_word_and_the_spaces_after_it() {
+        let mut editor = typed("grep  -i  été  ");
+        ctrl(&mut editor, 'w');
+        assert_eq!(editor.text(), "grep  -i  ");
+        ctrl(&mut editor, 'w');
+        assert_eq!(editor.text(), "grep  ");
+        ctrl(&mut editor, 'w');
+        ctrl(&mut editor, 'w');
+        assert!(editor.is_empty());
+    }
+
+    #[test]
+    fn ctrl_u_and_ctrl_k_kill_either_side_of_the_cursor() {
+        let mut editor = typed("left right");
+        for _ in 0..5 {
+            press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        }
+        ctrl(&mut editor, 'k');
+        assert_eq!(editor.text(), "left ");
+        let mut editor = typed("left right");
+        for _ in 0..5 {
+            press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        }
+        ctrl(&mut editor, 'u');
+        assert_eq!((editor.text(), editor.before_cursor()), ("right", ""));
+    }
+
+    #[test]
+    fn other_keys_are_left_to_the_caller() {
+        let mut editor = typed("x");
+        assert!(!press(&mut editor, KeyCode::Char('w'), KeyModifiers::NONE));
+        assert!(!press(&mut editor, KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(editor.text(), "x");
+    }
+}
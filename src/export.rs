@@ -0,0 +1,382 @@
+use anyhow::Result;
+use std::borrow::Cow;
+use std::path::Path;
+
+use crate::language::Language;
+use crate::link;
+use crate::pdf::{Annotation, OutlineEntry};
+use crate::search;
+use crate::structure::{self, Block};
+
+/// Everything an export can draw on.
+pub struct ExportSource<'a> {
+    pub path: &'a Path,
+    pub file_name: &'a str,
+    pub pages: &'a [String],
+    pub outline: &'a [OutlineEntry],
+    pub annotations: &'a [Annotation],
+    /// Detected language, for dehyphenation rules
+    pub language: Option<Language>,
+}
+
+/// Index of the outline entry each page falls under: the last entry starting
+/// on or before it. Pages before the first entry map to `None`.
+fn page_sections(outline: &[OutlineEntry], page_count: usize) -> Vec<Option<usize>> {
+    (0..page_count)
+        .map(|page| outline.iter().rposition(|entry| entry.page <= page))
+        .collect()
+}
+
+/// Org-mode document: outline entries as headings, each page as a subtree
+/// with `PAGE` and `LINK` properties, and PDF annotations as subtrees of
+/// their page. Page text comes from `read`, one page at a time, so pages
+/// moved out of memory need not all come back at once.
+pub fn org<'a>(source: &ExportSource, read: impl Fn(usize) -> Result<Cow<'a, str>>) -> Result<String> {
+    let mut out = format!("#+TITLE: {}\n\n", source.file_name);
+    let sections = page_sections(source.outline, source.pages.len());
+
+    let write_pages = |out: &mut String, section: Option<usize>, depth: usize| -> Result<()> {
+        for page in (0..source.pages.len()).filter(|&page| sections[page] == section) {
+            let text = read(page)?;
+            let stars = "*".repeat(depth);
+            let link = link::uri(source.path, page + 1, None);
+            out.push_str(&format!("{} Page {}\n:PROPERTIES:\n:PAGE: {}\n:LINK: [[{}]]\n:END:\n", stars, page + 1, page + 1, link));
+            for line in text.lines() {
+                // Keep page text from being read as headings or keywords
+                if line.starts_with('*') || line.starts_with("#+") {
+                    out.push(' ');
+                }
+                out.push_str(line);
+                out.push('\n');
+            }
+            for annotation in source.annotations.iter().filter(|a| a.page == page) {
+                let title = annotation.contents.lines().next().unwrap_or("").trim();
+                out.push_str(&format!("{}* {}: {}\n", stars, annotation.kind, title));
+                if annotation.contents.lines().count() > 1 {
+                    out.push_str(&annotation.contents);
+                    out.push('\n');
+                }
+            }
+            out.push('\n');
+        }
+        Ok(())
+    };
+
+    write_pages(&mut out, None, 1)?;
+    for (i, entry) in source.outline.iter().enumerate() {
+        let depth = entry.level + 1;
+        out.push_str(&format!(
+            "{} {}\n:PROPERTIES:\n:PAGE: {}\n:END:\n",
+            "*".repeat(depth),
+            entry.title,
+            entry.page + 1
+        ));
+        write_pages(&mut out, Some(i), depth + 1)?;
+    }
+    Ok(out)
+}
+
+/// Plain text with a header line before each page.
+pub fn text(source: &ExportSource) -> String {
+    let mut out = String::new();
+    for (page, text) in source.pages.iter().enumerate() {
+        out.push_str(&format!("--- Page {} of {} ---\n", page + 1, source.pages.len()));
+        out.push_str(text);
+        out.push_str("\n\n");
+    }
+    out
+}
+
+/// `text` with each line numbered from 1 on its page, as the viewer and
+/// links count them. Lines `hidden` leaves out keep their numbers taken.
+pub fn numbered_text(source: &ExportSource, hidden: impl Fn(&str) -> bool) -> String {
+    let widest = source.pages.iter().map(|page| page.lines().count()).max().unwrap_or(0);
+    let width = widest.to_string().len();
+    let mut out = String::new();
+    for (page, text) in source.pages.iter().enumerate() {
+        out.push_str(&format!("--- Page {} of {} ---\n", page + 1, source.pages.len()));
+        for (i, line) in text.lines().enumerate().filter(|(_, line)| !hidden(line)) {
+            out.push_str(&format!("{:>width$} │ {}\n", i + 1, line));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Markdown from the detected headings, paragraphs and lists.
+pub fn markdown(source: &ExportSource) -> String {
+    let mut out = String::new();
+    let mut in_list = false;
+    for (_, block) in structure::document_blocks(source.pages, source.outline, source.language) {
+        let is_item = matches!(block, Block::ListItem(..));
+        if in_list && !is_item {
+            out.push('\n');
+        }
+        match block {
+            Block::Heading(level, text) => {
+                out.push_str(&format!("{} {}\n\n", "#".repeat(level.min(6)), text));
+            }
+            Block::Paragraph(text) => {
+                out.push_str(&escape_markdown(&text));
+                out.push_str("\n\n");
+            }
+            Block::ListItem(marker, text) => {
+                // Keep ordinal markers, normalize the many bullet glyphs to `-`,
+                // and keep other labels such as `[3]` or `b)` as part of the text
+                let text = escape_markdown(&text);
+                if marker.starts_with(|c: char| c.is_ascii_digit()) {
+                    let number = marker.trim_matches(|c: char| !c.is_ascii_digit());
+                    out.push_str(&format!("{}. {}\n", number, text));
+                } else if marker.chars().count() == 1 {
+                    out.push_str(&format!("- {}\n", text));
+                } else {
+                    out.push_str(&format!("- {} {}\n", escape_markdown(&marker), text));
+                }
+            }
+            Block::Code(language, text) => {
+                out.push_str(&format!("```{}\n{}\n```\n\n", language, text));
+            }
+        }
+        in_list = is_item;
+    }
+    out.trim_end().to_string() + "\n"
+}
+
+/// Escape characters that would otherwise start Markdown structure.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    if text.starts_with(['#', '>', '+', '-', '*', '=', '|']) {
+        out.push('\\');
+    }
+    for c in text.chars() {
+        if matches!(c, '*' | '_' | '`' | '[' | ']' | '<') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape `text`, wrapping case-insensitive matches of `highlight` in `<mark>`.
+fn highlight_html(text: &str, highlight: Option<&str>) -> String {
+    let query = highlight.unwrap_or_default().to_lowercase();
+    let mut out = String::new();
+    let mut last = 0;
+    for range in search::matches(text, &query) {
+        out.push_str(&escape_html(&text[last..range.start]));
+        out.push_str("<mark>");
+        out.push_str(&escape_html(&text[range.clone()]));
+        out.push_str("</mark>");
+        last = range.end;
+    }
+    out.push_str(&escape_html(&text[last..]));
+    out
+}
+
+/// `text` with the matches of `term` in black on yellow, for terminals and `less -R`.
+pub fn highlight_ansi(text: &str, term: &str) -> String {
+    let query = term.to_lowercase();
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let mut last = 0;
+        for range in search::matches(line, &query) {
+            out.push_str(&line[last..range.start]);
+            out.push_str("\x1b[30;43m");
+            out.push_str(&line[range.clone()]);
+            out.push_str("\x1b[0m");
+            last = range.end;
+        }
+        out.push_str(&line[last..]);
+    }
+    out
+}
+
+/// Nested `<ul>` navigation from the outline, or a flat page list without one.
+fn html_nav(source: &ExportSource) -> String {
+    let mut out = String::from("<nav>\n<h2>Contents</h2>\n");
+    if source.outline.is_empty() {
+        out.push_str("<ul>\n");
+        for page in 1..=source.pages.len() {
+            out.push_str(&format!("<li><a href=\"#page-{0}\">Page {0}</a></li>\n", page));
+        }
+        out.push_str("</ul>\n</nav>\n");
+        return out;
+    }
+
+    let mut depth = 0;
+    for entry in source.outline {
+        let level = entry.level + 1;
+        if level > depth {
+            for _ in depth..level {
+                out.push_str("<ul>\n");
+            }
+        } else {
+            out.push_str("</li>\n");
+            for _ in level..depth {
+                out.push_str("</ul>\n</li>\n");
+            }
+        }
+        depth = level;
+        out.push_str(&format!(
+            "<li><a href=\"#page-{}\">{}</a>",
+            entry.page + 1,
+            escape_html(&entry.title)
+        ));
+    }
+    for _ in 0..depth {
+        out.push_str("</li>\n</ul>\n");
+    }
+    out.push_str("</nav>\n");
+    out
+}
+
+/// Standalone HTML page: outline navigation, then one section per page with
+/// detected structure, optionally with `highlight` marked throughout.
+pub fn html(source: &ExportSource, highlight: Option<&str>) -> String {
+    let title = escape_html(source.file_name);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         body {{ max-width: 46em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; line-height: 1.5; }}\n\
+         section {{ border-top: 1px solid #ccc; margin-top: 2em; }}\n\
+         .page-number {{ color: #888; font-size: 0.9em; }}\n\
+         mark {{ background: #ff0; }}\n\
+         </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, title
+    );
+    out.push_str(&html_nav(source));
+
+    for (index, page) in source.pages.iter().enumerate() {
+        out.push_str(&format!(
+            "<section id=\"page-{0}\">\n<p class=\"page-number\">Page {0}</p>\n",
+            index + 1
+        ));
+        let mut in_list = false;
+        for block in structure::blocks(page, index, source.outline, source.language) {
+            let is_item = matches!(block, Block::ListItem(..));
+            if in_list && !is_item {
+                out.push_str("</ul>\n");
+            } else if !in_list && is_item {
+                out.push_str("<ul>\n");
+            }
+            in_list = is_item;
+            match block {
+                Block::Heading(level, text) => {
+                    let tag = (level + 1).min(6);
+                    out.push_str(&format!("<h{0}>{1}</h{0}>\n", tag, highlight_html(&text, highlight)));
+                }
+                Block::Paragraph(text) => {
+                    out.push_str(&format!("<p>{}</p>\n", highlight_html(&text, highlight)));
+                }
+                Block::ListItem(marker, text) => {
+                    let text = format!("{} {}", marker, text);
+                    out.push_str(&format!("<li>{}</li>\n", highlight_html(&text, highlight)));
+                }
+                Block::Code(language, text) => {
+                    let class = if language.is_empty() { String::new() } else { format!(" class=\"language-{}\"", language) };
+                    out.push_str(&format!("<pre><code{}>{}</code></pre>\n", class, highlight_html(&text, highlight)));
+                }
+            }
+        }
+        if in_list {
+            out.push_str("</ul>\n");
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Word-wrap `line` to at most `width` characters per row. Words longer than
+/// the width are split.
+pub fn wrap(line: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut row_len = 0;
+    for word in line.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if row_len > 0 {
+                rows.push(std::mem::take(&mut row));
+                row_len = 0;
+            }
+            rows.push(word.drain(..width).collect());
+        }
+        if row_len > 0 && row_len + 1 + word.len() > width {
+            rows.push(std::mem::take(&mut row));
+            row_len = 0;
+        }
+        if row_len > 0 {
+            row.push(' ');
+            row_len += 1;
+        }
+        row_len += word.len();
+        row.extend(word);
+    }
+    if row_len > 0 || rows.is_empty() {
+        rows.push(row);
+    }
+    rows
+}
+
+/// Pages wrapped to `width` columns for a line printer, each starting with a
+/// header and separated by form feeds.
+pub fn printable(file_name: &str, pages: &[(usize, &String)], width: usize) -> String {
+    pages
+        .iter()
+        .map(|(index, text)| {
+            let mut out = format!("{} - page {}\n\n", file_name, index + 1);
+            for line in text.lines() {
+                for row in wrap(line, width) {
+                    out.push_str(&row);
+                    out.push('\n');
+                }
+            }
+            out
+        })
+        .collect::<Vec<_>>()
+        .join("\x0C")
+}
+
+/// Plain text of one outline section: from the line holding its `title` on
+/// page `first` to the line holding the next section's title, when there is
+/// one and it is found on its page. A title not found on its page is taken
+/// to start it, so whole pages are kept or left out.
+pub fn section_text(pages: &[String], first: usize, title: &str, next: Option<(usize, &str)>) -> String {
+    let last = next.map_or(pages.len(), |(page, _)| (page + 1).min(pages.len()));
+    let mut out = String::new();
+    for (page, text) in pages.iter().enumerate().take(last).skip(first) {
+        let lines: Vec<&str> = text.lines().collect();
+        let start = if page == first { title_line(&lines, title).unwrap_or(0) } else { 0 };
+        let end = match next {
+            Some((next_page, next_title)) if page == next_page => title_line(&lines, next_title).unwrap_or(0),
+            _ => lines.len(),
+        };
+        if start >= end {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("--- Page {} ---\n", page + 1));
+        for line in &lines[start..end] {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// The line that is an outline title, ignoring case and spacing.
+fn title_line(lines: &[&str], title: &str) -> Option<usize> {
+    let normalize = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let title = normalize(title);
+    lines.iter().position(|line| normalize(line) == title)
+}
@@ -0,0 +1,107 @@
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+
+use crate::sandbox;
+
+/// Build the `sh -c` invocation for a user-configured command template.
+///
+/// Each `{name}` placeholder in `template` is replaced by a quoted positional
+/// parameter, so values are passed to the shell as arguments rather than being
+/// spliced into the command line.
+fn command(template: &str, vars: &[(&str, &str)]) -> Command {
+    let mut script = template.to_string();
+    for (i, (name, _)) in vars.iter().enumerate() {
+        script = script.replace(&format!("{{{name}}}"), &format!("\"${}\"", i + 1));
+    }
+
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(&script)
+        .arg("sh")
+        .args(vars.iter().map(|(_, value)| value));
+    command
+}
+
+/// A command template set up to take over the terminal, e.g. an editor.
+/// Standard streams are inherited.
+pub fn interactive(template: &str, vars: &[(&str, &str)]) -> Result<Command> {
+    sandbox::check("Running external commands")?;
+    Ok(command(template, vars))
+}
+
+/// Start a command template in the background with its output discarded.
+/// The caller owns the child and must wait for it; see `launch` otherwise.
+pub fn spawn(template: &str, vars: &[(&str, &str)]) -> Result<Child> {
+    sandbox::check("Running external commands")?;
+    command(template, vars)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Could not run '{template}'"))
+}
+
+/// Start a command template that is left to finish on its own, e.g. a GUI
+/// viewer. A thread waits for it so it does not linger as a zombie.
+pub fn launch(template: &str, vars: &[(&str, &str)]) -> Result<()> {
+    let mut child = spawn(template, vars)?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Run a command template to completion and return its standard output.
+/// `stdin` is written to the process if given.
+pub fn run(template: &str, vars: &[(&str, &str)], stdin: Option<&str>) -> Result<String> {
+    sandbox::check("Running external commands")?;
+    let mut child = command(template, vars)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not run '{template}'"))?;
+
+    // Written from another thread, as a command that prints while reading would
+    // otherwise block on a full stdout pipe while we block on its stdin
+    let writer = stdin.zip(child.stdin.take()).map(|(input, mut pipe)| {
+        let input = input.to_string();
+        // The command may exit without reading everything; that is not an error
+        thread::spawn(move || pipe.write_all(input.as_bytes()))
+    });
+
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() && stdout.trim().is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("'{}' failed: {}", template, stderr.trim());
+    }
+    Ok(stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_passes_output_larger_than_a_pipe_through() {
+        let input = "line of text to pass through\n".repeat(20_000);
+        assert_eq!(run("cat", &[], Some(&input)).unwrap(), input);
+    }
+
+    #[test]
+    fn values_are_passed_as_arguments() {
+        let output = run("printf '%s' {word}", &[("word", "a b; echo c")], None).unwrap();
+        assert_eq!(output, "a b; echo c");
+    }
+
+    #[test]
+    fn failure_reports_stderr() {
+        let error = run("echo oops >&2; exit 1", &[], None).unwrap_err();
+        assert!(error.to_string().ends_with("failed: oops"));
+    }
+}
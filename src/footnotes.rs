@@ -0,0 +1,190 @@
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use crate::paginate::PAGE_NUMBER;
+
+/// A line opening a footnote: its mark ("3", "³", "*", "†") and text.
+static NOTE_START: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\d{1,3}|[⁰¹²³⁴⁵⁶⁷⁸⁹]{1,3}|[*†‡§¶]{1,3})[.)]?\s*([^\d\s].*)$").unwrap()
+});
+
+/// Most footnotes one page carries; more lines opening with numbers are a list or table.
+const MAX_NOTES: usize = 40;
+
+const SUPERSCRIPTS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+
+/// A footnote at the bottom of a page.
+pub struct Footnote {
+    /// Its mark as printed, e.g. "3" or "†"
+    pub mark: String,
+    /// Line of the page where it starts
+    pub line: usize,
+    /// Its text, with continuation lines joined
+    pub text: String,
+}
+
+/// Where a footnote is referenced in the body of the page.
+pub struct Marker {
+    pub line: usize,
+    /// Bytes of the mark within the line
+    pub range: Range<usize>,
+    /// Index into `Footnotes::notes`
+    pub note: usize,
+}
+
+#[derive(Default)]
+pub struct Footnotes {
+    pub notes: Vec<Footnote>,
+    /// In reading order
+    pub markers: Vec<Marker>,
+}
+
+impl Footnotes {
+    /// Markers on `line`.
+    pub fn on_line(&self, line: usize) -> impl Iterator<Item = &Marker> {
+        self.markers.iter().filter(move |marker| marker.line == line)
+    }
+}
+
+/// Find the footnote block at the bottom of a page and the markers in the
+/// body that point into it.
+///
+/// The block is the last run of lines in the lower half of the page that
+/// open with a mark, numbered consecutively, ending the page (a page-number
+/// footer may follow). Its first note must be referenced above it, so a
+/// numbered list near the bottom is not taken for footnotes.
+pub fn find(page: &str) -> Footnotes {
+    let lines: Vec<&str> = page.lines().collect();
+    let mut end = lines.len();
+    while end > 0 && (lines[end - 1].trim().is_empty() || PAGE_NUMBER.is_match(lines[end - 1].trim())) {
+        end -= 1;
+    }
+    // Line, mark and text of each line that could open a footnote
+    let starts: Vec<(usize, &str, &str)> = (lines.len() / 2..end)
+        .filter_map(|i| {
+            let caps = NOTE_START.captures(lines[i].trim())?;
+            Some((i, caps.get(1)?.as_str(), caps.get(2)?.as_str()))
+        })
+        .collect();
+
+    for first in starts.len().saturating_sub(MAX_NOTES)..starts.len() {
+        let block = &starts[first..];
+        if !consecutive(block) {
+            continue;
+        }
+        let body = &lines[..block[0].0];
+        let mut footnotes = Footnotes::default();
+        for (index, &(start, mark, first_line)) in block.iter().enumerate() {
+            let stop = block.get(index + 1).map_or(end, |&(next, _, _)| next);
+            let mut text = first_line.to_string();
+            for line in &lines[start + 1..stop] {
+                crate::structure::join_line(&mut text, line.trim(), None);
+            }
+            footnotes.markers.extend(references(body, mark, index));
+            footnotes.notes.push(Footnote { mark: mark.to_string(), line: start, text });
+        }
+        if footnotes.markers.iter().any(|marker| marker.note == 0) {
+            footnotes.markers.sort_by_key(|marker| (marker.line, marker.range.start));
+            return footnotes;
+        }
+    }
+    Footnotes::default()
+}
+
+/// Numeric marks must count up by one; symbol marks can follow in any order.
+fn consecutive(starts: &[(usize, &str, &str)]) -> bool {
+    let numbers: Vec<Option<u32>> = starts.iter().map(|(_, mark, _)| number(mark)).collect();
+    numbers.windows(2).all(|pair| match pair {
+        [Some(a), Some(b)] => *b == a + 1,
+        [None, None] => true,
+        _ => false,
+    })
+}
+
+fn number(mark: &str) -> Option<u32> {
+    let digits: String = mark
+        .chars()
+        .map(|c| SUPERSCRIPTS.iter().position(|&s| s == c).map_or(c, |d| char::from(b'0' + d as u8)))
+        .collect();
+    digits.parse().ok()
+}
+
+/// Places in `body` where footnote `note`, printed as `mark`, is referenced:
+/// the mark right after a word or punctuation ("claim.3", "claim³", "claim*"),
+/// as superscripts come out of extraction.
+fn references(body: &[&str], mark: &str, note: usize) -> Vec<Marker> {
+    let pattern = match number(mark) {
+        Some(n) => {
+            let superscript: String = n.to_string().bytes().map(|d| SUPERSCRIPTS[(d - b'0') as usize]).collect();
+            format!(r"[\p{{L}}.,;:!?)\]”’]({}|{})(?:[^\d⁰¹²³⁴⁵⁶⁷⁸⁹]|$)", n, superscript)
+        }
+        None => format!(r"[^\s*†‡§¶]({})(?:[^*†‡§¶]|$)", regex::escape(mark)),
+    };
+    let Ok(pattern) = Regex::new(&pattern) else {
+        return Vec::new();
+    };
+    body.iter()
+        .enumerate()
+        .flat_map(|(line, text)| {
+            let pattern = &pattern;
+            pattern.captures_iter(text).filter_map(move |caps| {
+                let range = caps.get(1)?.range();
+                Some(Marker { line, range, note })
+            })
+        })
+        .collect()
+}
+
+/// Footnotes of the shown page, reused between frames.
+#[derive(Default)]
+pub struct FootnoteCache {
+    page: Option<usize>,
+    footnotes: Footnotes,
+}
+
+impl FootnoteCache {
+    pub fn get(&mut self, page: usize, text: &str) -> &Footnotes {
+        if self.page != Some(page) {
+            self.footnotes = find(text);
+            self.page = Some(page);
+        }
+        &self.footnotes
+    }
+
+    /// Forget the footnotes after page text changed.
+    pub fn clear(&mut self) {
+        self.page = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_at_the_bottom_are_found_with_their_markers() {
+        let page = "Body text makes a claim.1 Another one³ here.\nMore body.\nAnd more.\nStill more.\nThe end.\n\
+                    1 The first note, which\ngoes on.\n2 A second note.\n3 Third.\n\n12";
+        let footnotes = find(page);
+        let notes: Vec<(&str, usize, &str)> = footnotes.notes.iter().map(|n| (n.mark.as_str(), n.line, n.text.as_str())).collect();
+        assert_eq!(notes, vec![("1", 5, "The first note, which goes on."), ("2", 7, "A second note."), ("3", 8, "Third.")]);
+        let markers: Vec<(usize, &str)> = footnotes.on_line(0).map(|m| (m.note, &page[m.range.clone()])).collect();
+        assert_eq!(markers, vec![(0, "1"), (2, "³")]);
+    }
+
+    #[test]
+    fn a_numbered_list_is_not_taken_for_notes() {
+        let page = "Steps:\nfirst we prepare\nthen\n1 Mix the flour.\n2 Add water.";
+        assert!(find(page).notes.is_empty());
+    }
+
+    #[test]
+    fn numbers_must_count_up() {
+        let page = "A claim.1 and.3\nbody\nbody\nbody\n1 One.\n3 Three.";
+        let notes: Vec<String> = find(page).notes.into_iter().map(|note| note.mark).collect();
+        assert_eq!(notes, vec!["3"]);
+        assert_eq!(number("¹²"), Some(12));
+        assert_eq!(number("†"), None);
+    }
+}
@@ -0,0 +1,251 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+use crate::config::FormatConfig;
+use crate::math;
+use crate::pdf::COLUMN_GAP;
+use crate::tables;
+
+/// What happens to blank lines and spacing in extracted text.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default, Debug, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Whitespace {
+    /// Trim every line and drop blank ones
+    #[default]
+    Strip,
+    /// Trim every line; keep one blank line where paragraphs were separated
+    Paragraphs,
+    /// Like `paragraphs`, and squeeze runs of spaces inside lines to one
+    Collapse,
+    /// Keep indentation and blank lines exactly as extracted, for code-heavy documents
+    Raw,
+}
+
+/// What happens to control characters and invisible formatting characters.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Controls {
+    /// Controls become spaces, which they usually stood for; zero-width characters go
+    #[default]
+    Strip,
+    /// Show each as a visible mark such as ␀ or ⟨ZWJ⟩
+    Escape,
+}
+
+/// Tidy the extracted text of one page for display.
+pub fn page(content: &str, format: FormatConfig) -> String {
+    let content = sanitize(&expand_tabs(content, format.tab_width), format.controls);
+    let content = if format.math { math::render(&content).into_owned() } else { content };
+    if format.whitespace == Whitespace::Raw {
+        return content.lines().collect::<Vec<_>>().join("\n").replace(COLUMN_GAP, " ");
+    }
+    let source: Vec<&str> = content.lines().collect();
+    let source = if format.tables { tables::align(&source) } else { source.iter().map(|line| line.to_string()).collect() };
+    let mut lines: Vec<String> = Vec::new();
+    for line in &source {
+        if tables::is_row(line) {
+            lines.push(line.clone());
+            continue;
+        }
+        let line = close_gaps(line);
+        let (indent, text) = line.split_at(line.len() - line.trim_start().len());
+        let text = match format.whitespace {
+            Whitespace::Collapse => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            _ => text.trim_end().to_string(),
+        };
+        // A run of blank lines becomes one, and none open the page
+        if text.is_empty() {
+            if format.whitespace != Whitespace::Strip && lines.last().is_some_and(|line| !line.is_empty()) {
+                lines.push(String::new());
+            }
+        } else if format.indent {
+            lines.push(format!("{}{}", indent, text));
+        } else {
+            lines.push(text);
+        }
+    }
+    if lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    if format.indent {
+        dedent(&mut lines);
+    }
+    lines.join("\n")
+}
+
+/// Turn each run of `COLUMN_GAP` outside a table into one space, as a gap
+/// between words.
+fn close_gaps(line: &str) -> Cow<'_, str> {
+    if !line.contains(COLUMN_GAP) {
+        return Cow::Borrowed(line);
+    }
+    let mut closed = String::with_capacity(line.len());
+    for c in line.chars() {
+        if c != COLUMN_GAP {
+            closed.push(c);
+        } else if !closed.ends_with(' ') {
+            closed.push(' ');
+        }
+    }
+    Cow::Owned(closed)
+}
+
+/// Replace tabs with spaces up to the next multiple of `width` columns, since
+/// terminals and ratatui disagree on how wide a tab is. 0 leaves tabs alone.
+fn expand_tabs(text: &str, width: usize) -> Cow<'_, str> {
+    if width == 0 || !text.contains('\t') {
+        return Cow::Borrowed(text);
+    }
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = width - column % width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                expanded.push(c);
+                column = 0;
+            }
+            c => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    Cow::Owned(expanded)
+}
+
+/// Take out characters that would move the terminal's cursor, change its
+/// state, or sit invisibly inside words where search cannot see past them.
+/// Line breaks stay, and so do tabs that were not expanded.
+fn sanitize(text: &str, controls: Controls) -> String {
+    let mut clean = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' || c == '\t' {
+            clean.push(c);
+        } else if c == '\r' && chars.peek() == Some(&'\n') {
+            // CRLF line end
+        } else if let Some(mark) = escape(c) {
+            match controls {
+                Controls::Escape => clean.push_str(&mark),
+                Controls::Strip if c.is_control() => clean.push(' '),
+                Controls::Strip => {}
+            }
+        } else {
+            clean.push(c);
+        }
+    }
+    clean
+}
+
+/// A visible stand-in for a control or invisible formatting character; `None`
+/// for characters that show as themselves.
+pub fn escape(c: char) -> Option<String> {
+    let name = match c {
+        // Control pictures start at U+2400 for NUL
+        c if (c as u32) < 0x20 => return char::from_u32(0x2400 + c as u32).map(String::from),
+        '\x7f' => return Some("␡".to_string()),
+        '\u{ad}' => "SHY",
+        '\u{200b}' => "ZWSP",
+        '\u{200c}' => "ZWNJ",
+        '\u{200d}' => "ZWJ",
+        '\u{200e}' => "LRM",
+        '\u{200f}' => "RLM",
+        '\u{2060}' => "WJ",
+        '\u{feff}' => "BOM",
+        // Bidi embeddings, overrides and isolates, invisible operators, annotation marks
+        '\u{202a}'..='\u{202e}' | '\u{2061}'..='\u{2064}' | '\u{2066}'..='\u{2069}' | '\u{fff9}'..='\u{fffb}' => {
+            return Some(format!("⟨U+{:04X}⟩", c as u32));
+        }
+        c if c.is_control() => return Some(format!("⟨U+{:04X}⟩", c as u32)),
+        _ => return None,
+    };
+    Some(format!("⟨{}⟩", name))
+}
+
+/// Remove the indentation all lines share, such as a page margin extracted as
+/// spaces, so only the structure within the page is left.
+fn dedent(lines: &mut [String]) {
+    let common = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    if common == 0 {
+        return;
+    }
+    for line in lines.iter_mut().filter(|line| !line.is_empty()) {
+        // On a char boundary: a no-break space is two bytes
+        let end = line.char_indices().map(|(i, _)| i).find(|&i| i >= common).unwrap_or(line.len());
+        line.drain(..end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with(whitespace: Whitespace) -> FormatConfig {
+        FormatConfig { whitespace, ..FormatConfig::default() }
+    }
+
+    #[test]
+    fn strip_trims_lines_and_drops_blank_ones() {
+        assert_eq!(page("\n  one  \n\n\n two\n\n", with(Whitespace::Strip)), "one\ntwo");
+    }
+
+    #[test]
+    fn paragraphs_keep_one_blank_line_between() {
+        assert_eq!(page("\n\none\n\n\n\ntwo  words\n\n", with(Whitespace::Paragraphs)), "one\n\ntwo  words");
+    }
+
+    #[test]
+    fn collapse_squeezes_spaces_inside_lines() {
+        assert_eq!(page("one   two\n\n three\t four ", with(Whitespace::Collapse)), "one two\n\nthree four");
+    }
+
+    #[test]
+    fn raw_keeps_the_text_as_extracted() {
+        assert_eq!(page("  one  \n\n\n two\r\n", with(Whitespace::Raw)), "  one  \n\n\n two");
+    }
+
+    #[test]
+    fn indent_keeps_structure_but_not_the_shared_margin() {
+        let format = FormatConfig { indent: true, ..FormatConfig::default() };
+        assert_eq!(page("    def f():\n        return 1\n\n    f()", format), "def f():\n    return 1\nf()");
+        assert_eq!(page("    def f():\n        return 1", FormatConfig::default()), "def f():\nreturn 1");
+    }
+
+    #[test]
+    fn dedent_cuts_on_character_boundaries() {
+        let mut lines = vec!["  \u{a0}x".to_string(), "   y".to_string()];
+        dedent(&mut lines);
+        assert_eq!(lines, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn tabs_expand_to_the_next_stop() {
+        assert_eq!(expand_tabs("a\tb\n\tc\td", 4), "a   b\n    c   d");
+        assert_eq!(expand_tabs("ab\tc", 0), "ab\tc");
+        let format = FormatConfig { indent: true, tab_width: 2, ..FormatConfig::default() };
+        assert_eq!(page("x\n\ty", format), "x\n  y");
+    }
+
+    #[test]
+    fn controls_become_spaces_and_invisibles_go() {
+        assert_eq!(sanitize("a\x07b\u{200b}c\r\nd\x1b[2J", Controls::Strip), "a bc\nd [2J");
+    }
+
+    #[test]
+    fn controls_can_be_shown_as_marks() {
+        assert_eq!(sanitize("a\x00b\u{200d}c\u{202e}\x7f", Controls::Escape), "a␀b⟨ZWJ⟩c⟨U+202E⟩␡");
+        let format = FormatConfig { controls: Controls::Escape, ..FormatConfig::default() };
+        assert_eq!(page("soft\u{ad}hyphen", format), "soft⟨SHY⟩hyphen");
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::language::Language;
+
+/// Common English words that say nothing about what a document is about.
+const STOPWORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "all", "also", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "being", "between", "both", "but", "by", "can", "could", "did", "do",
+    "does", "doing", "down", "during", "each", "et", "al", "few", "for", "from", "further", "had", "has",
+    "have", "having", "he", "her", "here", "hers", "him", "his", "how", "however", "i", "if", "in",
+    "into", "is", "it", "its", "itself", "may", "more", "most", "much", "must", "my", "no", "nor", "not",
+    "now", "of", "off", "on", "once", "one", "only", "or", "other", "our", "out", "over", "own", "same",
+    "she", "should", "so", "some", "such", "than", "that", "the", "their", "them", "then", "there",
+    "these", "they", "this", "those", "through", "thus", "to", "too", "two", "under", "until", "up",
+    "upon", "use", "used", "using", "very", "was", "we", "were", "what", "when", "where", "whether",
+    "which", "while", "who", "whom", "why", "will", "with", "within", "without", "would", "you", "your",
+];
+
+/// Shortest word counted; shorter ones are mostly particles and abbreviations.
+const MIN_WORD_CHARS: usize = 3;
+
+/// The `limit` most frequent words of `pages`, lowercased, without stopwords
+/// of English or `language`. Ties are broken alphabetically.
+pub fn top_terms(pages: impl IntoIterator<Item = impl AsRef<str>>, language: Option<Language>, limit: usize) -> Vec<(String, usize)> {
+    let stopwords: HashSet<&str> = STOPWORDS
+        .iter()
+        .chain(language.map_or(&[][..], Language::stopwords))
+        .copied()
+        .collect();
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for page in pages {
+        for word in page.as_ref().split(|c: char| !c.is_alphabetic() && c != '-') {
+            let word = word.trim_matches('-').to_lowercase();
+            if word.chars().count() >= MIN_WORD_CHARS && !stopwords.contains(word.as_str()) {
+                *counts.entry(word).or_default() += 1;
+            }
+        }
+    }
+
+    let mut terms: Vec<(String, usize)> = counts.into_iter().collect();
+    terms.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+    terms.truncate(limit);
+    terms
+}
@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use crate::sandbox;
+
+/// Runs the shell commands configured under `[hooks]` on lifecycle events.
+#[derive(Default)]
+pub struct ShellHooks {
+    /// Hooks still running, reaped as they exit
+    children: Vec<Child>,
+}
+
+impl ShellHooks {
+    /// Start `command` in the background with the document's state in
+    /// `$PDF_FILE`, `$PDF_PAGE` (1-based) and `$PDF_PAGES`. Empty commands are
+    /// skipped, and so is every hook under `--sandbox`.
+    pub fn run(&mut self, command: &str, file: &Path, page: usize, pages: usize) -> Result<()> {
+        if command.trim().is_empty() || sandbox::enabled() {
+            return Ok(());
+        }
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("PDF_FILE", file)
+            .env("PDF_PAGE", (page + 1).to_string())
+            .env("PDF_PAGES", pages.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Could not run hook '{}'", command))?;
+        self.children.push(child);
+        Ok(())
+    }
+
+    /// Collect hooks that have finished.
+    pub fn reap(&mut self) {
+        self.children.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+    }
+}
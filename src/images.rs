@@ -0,0 +1,174 @@
+use anyhow::{anyhow, bail, Context, Result};
+use lopdf::{Document, Stream};
+use regex::Regex;
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::pdf::PageImage;
+
+static CAPTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i:fig(?:ure|\.)?|image|plate|chart)\s*\d+[.:]?\s").unwrap());
+
+/// Lines of a page that look like figure captions, in reading order.
+pub fn captions(page: &str) -> Vec<String> {
+    page.lines()
+        .map(str::trim)
+        .filter(|line| CAPTION.is_match(line))
+        .map(str::to_string)
+        .collect()
+}
+
+impl PageImage {
+    /// One-line summary for the image list.
+    pub fn describe(&self) -> String {
+        let depth = match self.components {
+            Some(1) => "gray",
+            Some(3) => "RGB",
+            Some(4) => "CMYK",
+            _ => &self.color_space,
+        };
+        format!(
+            "{}  {}×{}  {} {}-bit  {}",
+            self.name,
+            self.width,
+            self.height,
+            depth,
+            self.bits,
+            self.filter.as_deref().unwrap_or("raw")
+        )
+    }
+
+    /// File extension the image exports to.
+    pub fn extension(&self) -> &'static str {
+        match self.filter.as_deref() {
+            Some("DCTDecode") => "jpg",
+            Some("JPXDecode") => "jp2",
+            _ => "png",
+        }
+    }
+}
+
+/// Write an image to `path`. JPEG and JPEG 2000 data is copied as is; other
+/// 8-bit gray or RGB images are converted to PNG.
+pub fn export(doc: &Document, image: &PageImage, path: &Path) -> Result<()> {
+    let stream = doc.get_object(image.id)?.as_stream()?;
+    match image.extension() {
+        "jpg" | "jp2" => fs::write(path, &stream.content)?,
+        _ => {
+            let pixels = decoded(stream).map_err(|e| anyhow!("Could not decode {}: {}", image.name, e))?;
+            write_png(path, image, &pixels)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write an image as PNG, decoding JPEG data first, for display with the graphics protocol.
+pub fn export_png(doc: &Document, image: &PageImage, path: &Path) -> Result<()> {
+    let stream = doc.get_object(image.id)?.as_stream()?;
+    match image.filter.as_deref() {
+        Some("DCTDecode") => {
+            let mut decoder = jpeg_decoder::Decoder::new(stream.content.as_slice());
+            let pixels = decoder.decode()?;
+            let info = decoder.info().context("JPEG without header")?;
+            let components = match info.pixel_format {
+                jpeg_decoder::PixelFormat::L8 => 1,
+                jpeg_decoder::PixelFormat::RGB24 => 3,
+                format => bail!("Unsupported JPEG pixel format {:?}", format),
+            };
+            let decoded = PageImage {
+                width: info.width as u32,
+                height: info.height as u32,
+                bits: 8,
+                components: Some(components),
+                ..image.clone()
+            };
+            write_png(path, &decoded, &pixels)
+        }
+        Some("JPXDecode") => bail!("JPEG 2000 images cannot be previewed; export them instead"),
+        _ => export(doc, image, path),
+    }
+}
+
+/// Stream data with its filters undone. lopdf refuses to decode image
+/// streams, so decode a copy that is not marked as one.
+fn decoded(stream: &Stream) -> lopdf::Result<Vec<u8>> {
+    if stream.dict.get(b"Filter").is_err() {
+        return Ok(stream.content.clone());
+    }
+    let mut plain = stream.clone();
+    plain.dict.remove(b"Subtype");
+    plain.decompressed_content()
+}
+
+fn write_png(path: &Path, image: &PageImage, pixels: &[u8]) -> Result<()> {
+    let color = match image.components {
+        Some(1) => png::ColorType::Grayscale,
+        Some(3) => png::ColorType::Rgb,
+        _ => bail!("Unsupported colour space {} for {}", image.color_space, image.name),
+    };
+    if image.bits != 8 || image.color_space == "Indexed" {
+        bail!("Only 8-bit gray and RGB images can be converted ({} is {}-bit {})", image.name, image.bits, image.color_space);
+    }
+    let expected = image.width as usize * image.height as usize * image.components.unwrap_or(1) as usize;
+    if pixels.len() < expected {
+        bail!("Image data for {} is shorter than its size", image.name);
+    }
+
+    let file = fs::File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels[..expected])?;
+    Ok(())
+}
+
+/// Default export file name, e.g. `paper-p3-Im1.jpg`.
+pub fn export_name(pdf: &Path, page: usize, image: &PageImage) -> PathBuf {
+    let stem = pdf.file_stem().unwrap_or_default().to_string_lossy();
+    PathBuf::from(format!("{}-p{}-{}.{}", stem, page + 1, image.name, image.extension()))
+}
+
+/// Width and height of the PNG at `path`, from its header.
+pub fn png_size(path: &Path) -> Result<(u32, u32)> {
+    let file = fs::File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    let info = png::Decoder::new(file).read_info()?.info().clone();
+    Ok((info.width, info.height))
+}
+
+/// Write the PNG at `from` to `to` turned clockwise by `quarter_turns` right
+/// angles, returning its new width and height.
+pub fn rotate_png(from: &Path, to: &Path, quarter_turns: u16) -> Result<(u32, u32)> {
+    let file = fs::File::open(from).with_context(|| format!("Could not open {}", from.display()))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels)?;
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let depth = frame.color_type.samples();
+    let turns = quarter_turns % 4;
+    let (new_width, new_height) = if turns % 2 == 1 { (height, width) } else { (width, height) };
+    let mut rotated = vec![0; width * height * depth];
+    for y in 0..height {
+        for x in 0..width {
+            let (to_x, to_y) = match turns {
+                1 => (height - 1 - y, x),
+                2 => (width - 1 - x, height - 1 - y),
+                3 => (y, width - 1 - x),
+                _ => (x, y),
+            };
+            let from_pixel = (y * width + x) * depth;
+            let to_pixel = (to_y * new_width + to_x) * depth;
+            rotated[to_pixel..to_pixel + depth].copy_from_slice(&pixels[from_pixel..from_pixel + depth]);
+        }
+    }
+
+    let file = fs::File::create(to).with_context(|| format!("Could not create {}", to.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), new_width as u32, new_height as u32);
+    encoder.set_color(frame.color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&rotated)?;
+    Ok((new_width as u32, new_height as u32))
+}
@@ -0,0 +1,43 @@
+use crossterm::event::{
+    KeyEvent, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{execute, terminal};
+use std::io;
+use std::sync::OnceLock;
+
+/// Whether the kitty keyboard protocol is in use, decided when the terminal
+/// is first set up.
+static ENHANCED: OnceLock<bool> = OnceLock::new();
+
+/// Ctrl+Shift+letter, Ctrl+I apart from Tab, key repeat and release. Without
+/// alternate keys, chords come as the unshifted letter with Shift held.
+const FLAGS: KeyboardEnhancementFlags =
+    KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES.union(KeyboardEnhancementFlags::REPORT_EVENT_TYPES);
+
+/// Switch to the kitty keyboard protocol if `wanted` and the terminal answers
+/// that it supports it; others keep the legacy encoding. Needs raw mode.
+pub fn enable(wanted: bool) -> io::Result<()> {
+    let enhanced = *ENHANCED.get_or_init(|| wanted && terminal::supports_keyboard_enhancement().unwrap_or(false));
+    if enhanced {
+        execute!(io::stdout(), PushKeyboardEnhancementFlags(FLAGS))?;
+    }
+    Ok(())
+}
+
+/// Give the terminal back its legacy encoding, before leaving or handing it
+/// to another program; `enable` switches back.
+pub fn disable() -> io::Result<()> {
+    if enhanced() {
+        execute!(io::stdout(), PopKeyboardEnhancementFlags)?;
+    }
+    Ok(())
+}
+
+pub fn enhanced() -> bool {
+    ENHANCED.get().copied().unwrap_or(false)
+}
+
+/// Whether `key` should act: a press or a held key repeating, not a release.
+pub fn pressed(key: &KeyEvent) -> bool {
+    key.kind != KeyEventKind::Release
+}
@@ -0,0 +1,63 @@
+use ratatui::layout::Rect;
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::clipboard::base64;
+
+/// Whether the terminal understands the Kitty graphics protocol.
+pub fn supported() -> bool {
+    env::var_os("KITTY_WINDOW_ID").is_some()
+        || env::var("TERM").is_ok_and(|term| term.contains("kitty"))
+        || env::var("TERM_PROGRAM").is_ok_and(|program| matches!(program.as_str(), "WezTerm" | "ghostty"))
+}
+
+/// Show the PNG at `path` scaled to `area` under image `id`, leaving the cursor alone.
+/// The file is passed by path, so the terminal must run on the same machine.
+pub fn show(id: u32, path: &Path, area: Rect) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(
+        stdout,
+        "\x1b[{};{}H\x1b_Ga=T,t=f,f=100,i={},c={},r={},C=1,q=2;{}\x1b\\",
+        area.y + 1,
+        area.x + 1,
+        id,
+        area.width,
+        area.height,
+        base64(path.to_string_lossy().as_bytes())
+    )?;
+    stdout.flush()
+}
+
+/// Show the part of the PNG at `path` from pixel `x`,`y`, `width` by
+/// `height`, scaled to `area` under image `id`.
+pub fn show_part(id: u32, path: &Path, area: Rect, (x, y, width, height): (u32, u32, u32, u32)) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(
+        stdout,
+        "\x1b[{};{}H\x1b_Ga=T,t=f,f=100,i={},x={},y={},w={},h={},c={},r={},C=1,q=2;{}\x1b\\",
+        area.y + 1,
+        area.x + 1,
+        id,
+        x,
+        y,
+        width,
+        height,
+        area.width,
+        area.height,
+        base64(path.to_string_lossy().as_bytes())
+    )?;
+    stdout.flush()
+}
+
+pub fn delete(id: u32) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b_Ga=d,d=i,i={},q=2\x1b\\", id)?;
+    stdout.flush()
+}
+
+pub fn delete_all() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b_Ga=d,d=A,q=2\x1b\\")?;
+    stdout.flush()
+}
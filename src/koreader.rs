@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Position KOReader left a PDF at, from its sidecar file.
+pub struct Sidecar {
+    /// 0-based page
+    pub page: usize,
+    /// Page count KOReader saw, when recorded
+    pub pages: Option<usize>,
+    /// When the sidecar was last written, in seconds since the Unix epoch
+    pub updated: u64,
+}
+
+/// KOReader keeps per-book settings in `book.sdr/metadata.pdf.lua` next to `book.pdf`.
+fn sidecar_path(pdf: &Path) -> PathBuf {
+    pdf.with_extension("sdr").join("metadata.pdf.lua")
+}
+
+pub fn read(pdf: &Path) -> Option<Sidecar> {
+    let path = sidecar_path(pdf);
+    let text = fs::read_to_string(&path).ok()?;
+    let updated = fs::metadata(&path).ok()?.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let page = number(&text, "last_page")?;
+    Some(Sidecar {
+        page: page.checked_sub(1)?,
+        pages: number(&text, "doc_pages"),
+        updated,
+    })
+}
+
+/// Record a 0-based page in the sidecar. An existing file keeps everything
+/// else KOReader stored (highlights, bookmarks, settings); only the position
+/// fields are replaced.
+pub fn write(pdf: &Path, page: usize, pages: usize) -> Result<()> {
+    let path = sidecar_path(pdf);
+    let fields = [
+        ("last_page", (page + 1).to_string()),
+        ("doc_pages", pages.to_string()),
+        ("percent_finished", format!("{:.4}", (page + 1) as f64 / pages.max(1) as f64)),
+    ];
+    let text = match fs::read_to_string(&path) {
+        Ok(mut text) => {
+            for (key, value) in &fields {
+                text = set_field(&text, key, value)
+                    .with_context(|| format!("{} is not a KOReader metadata table", path.display()))?;
+            }
+            text
+        }
+        Err(_) => {
+            let mut text = format!("-- {}\nreturn {{\n", path.display());
+            for (key, value) in &fields {
+                text.push_str(&format!("    [\"{}\"] = {},\n", key, value));
+            }
+            text.push_str("}\n");
+            text
+        }
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, text).with_context(|| format!("Could not write {}", path.display()))
+}
+
+/// A top-level `["key"] = 12` field.
+fn number(text: &str, key: &str) -> Option<usize> {
+    let pattern = Regex::new(&format!(r#"(?m)^    \["{}"\] = (\d+)"#, regex::escape(key))).ok()?;
+    pattern.captures(text)?[1].parse().ok()
+}
+
+/// Replace a top-level field, or add it at the start of the returned table.
+fn set_field(text: &str, key: &str, value: &str) -> Option<String> {
+    // Top-level fields are indented by four spaces; nested tables go deeper
+    let pattern = Regex::new(&format!(r#"(?m)^    \["{}"\] = [^\n]*,$"#, regex::escape(key))).ok()?;
+    let field = format!("    [\"{}\"] = {},", key, value);
+    if pattern.is_match(text) {
+        return Some(pattern.replace(text, regex::NoExpand(&field)).into_owned());
+    }
+    let start = text.find("return {\n")? + "return {\n".len();
+    Some(format!("{}{}\n{}", &text[..start], field, &text[start..]))
+}
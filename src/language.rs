@@ -0,0 +1,116 @@
+/// Languages the viewer can recognise from a document's text.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Dutch,
+    Portuguese,
+}
+
+const ALL: [Language; 7] = [
+    Language::English,
+    Language::German,
+    Language::French,
+    Language::Spanish,
+    Language::Italian,
+    Language::Dutch,
+    Language::Portuguese,
+];
+
+/// Words sampled from the start of a document; enough to settle on a language.
+const SAMPLE_WORDS: usize = 5000;
+
+impl Language {
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "German",
+            Language::French => "French",
+            Language::Spanish => "Spanish",
+            Language::Italian => "Italian",
+            Language::Dutch => "Dutch",
+            Language::Portuguese => "Portuguese",
+        }
+    }
+
+    /// ISO 639-1 code, as dictionaries and translators expect.
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+            Language::French => "fr",
+            Language::Spanish => "es",
+            Language::Italian => "it",
+            Language::Dutch => "nl",
+            Language::Portuguese => "pt",
+        }
+    }
+
+    /// Tesseract model name (ISO 639-2).
+    pub fn tesseract(self) -> &'static str {
+        match self {
+            Language::English => "eng",
+            Language::German => "deu",
+            Language::French => "fra",
+            Language::Spanish => "spa",
+            Language::Italian => "ita",
+            Language::Dutch => "nld",
+            Language::Portuguese => "por",
+        }
+    }
+
+    /// Very common words that are rare in the other languages.
+    pub fn stopwords(self) -> &'static [&'static str] {
+        match self {
+            Language::English => &["the", "and", "of", "to", "is", "that", "with", "for", "this", "are", "which", "be"],
+            Language::German => &["der", "die", "und", "das", "ist", "nicht", "mit", "sich", "auf", "ein", "eine", "dem"],
+            Language::French => &["le", "la", "les", "et", "des", "est", "une", "dans", "que", "pour", "pas", "sur"],
+            Language::Spanish => &["el", "los", "las", "del", "que", "y", "una", "por", "para", "con", "es", "se"],
+            Language::Italian => &["il", "che", "di", "della", "per", "una", "sono", "non", "gli", "con", "del", "è"],
+            Language::Dutch => &["de", "het", "een", "van", "en", "dat", "niet", "zijn", "voor", "op", "met", "ook"],
+            Language::Portuguese => &["o", "os", "da", "do", "que", "não", "uma", "para", "com", "em", "são", "dos"],
+        }
+    }
+
+    /// Conjunctions after which a line-end hyphen is a suspended hyphen
+    /// ("pre- and post-war", "Vor- und Nachteile") rather than a word break.
+    pub fn suspended_hyphen_words(self) -> &'static [&'static str] {
+        match self {
+            Language::English => &["and", "or", "to"],
+            Language::German => &["und", "oder", "bis", "bzw.", "sowie"],
+            Language::French => &["et", "ou"],
+            Language::Spanish => &["y", "o"],
+            Language::Italian => &["e", "o"],
+            Language::Dutch => &["en", "of", "tot"],
+            Language::Portuguese => &["e", "ou"],
+        }
+    }
+}
+
+/// The dominant language of `pages`, or `None` when no language clearly shows.
+pub fn detect(pages: impl IntoIterator<Item = impl AsRef<str>>) -> Option<Language> {
+    let mut counts = [0usize; ALL.len()];
+    let mut words = 0;
+    'pages: for page in pages {
+        for word in page.as_ref().split_whitespace() {
+            if words == SAMPLE_WORDS {
+                break 'pages;
+            }
+            let word = word.trim_matches(|c: char| !c.is_alphabetic()).to_lowercase();
+            words += 1;
+            for (count, language) in counts.iter_mut().zip(ALL) {
+                if language.stopwords().contains(&word.as_str()) {
+                    *count += 1;
+                }
+            }
+        }
+    }
+    // Ties go to the language listed first
+    let (hits, language) = counts.into_iter().zip(ALL).rev().max_by_key(|&(hits, _)| hits)?;
+    // Stopwords make up a good share of any running text; a handful in a
+    // table or bibliography proves little
+    (hits >= 5 && hits * 20 >= words).then_some(language)
+}
@@ -0,0 +1,130 @@
+use std::cmp::Ordering;
+
+use crate::pdf::Glyph;
+
+/// Glyphs nearer than this part of their size to the one before belong to
+/// the same word.
+const WORD_GAP: f64 = 0.15;
+
+/// Glyphs whose baselines are nearer than this part of their size share a line.
+const SAME_LINE: f64 = 0.5;
+
+/// Line spacing taken as one row, as a part of the usual font size.
+const LINE_PITCH: f64 = 1.2;
+
+/// Most blank rows left for one gap between lines.
+const MAX_BLANK_ROWS: usize = 3;
+
+/// Glyphs smaller than this, in points, are not real print and are left out.
+const MIN_SIZE: f64 = 1.0;
+
+fn by<T>(key: impl Fn(&T) -> f64) -> impl FnMut(&T, &T) -> Ordering {
+    move |a, b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal)
+}
+
+/// The middle value of `values`, which must not be empty.
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(by(|value: &f64| *value));
+    values[values.len() / 2]
+}
+
+/// A run of glyphs with no space between them.
+struct Word {
+    x: f64,
+    end: f64,
+    size: f64,
+    text: String,
+}
+
+/// Whether `text` is an amount as statements print them, which are set
+/// flush right: digits with separators, maybe signed, in parentheses, with a
+/// currency or percent sign; or a dash standing for zero.
+fn is_amount(text: &str) -> bool {
+    if matches!(text, "-" | "–" | "—") {
+        return true;
+    }
+    let number = text.trim_start_matches(['(', '-', '−', '+', '$', '€', '£', '¥']).trim_end_matches([')', '%', '$', '€', '£', '¥']);
+    number.chars().next().is_some_and(|c| c.is_ascii_digit())
+        && number.chars().all(|c| c.is_ascii_digit() || c == ',' || c == '.' || c == '\'')
+}
+
+/// The words of a line of glyphs sorted by position.
+fn words(line: &[&Glyph]) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    for glyph in line {
+        match words.last_mut() {
+            Some(word) if glyph.x - word.end < word.size * WORD_GAP => {
+                word.end = glyph.end;
+                word.text.push_str(&glyph.text);
+            }
+            _ => words.push(Word { x: glyph.x, end: glyph.end, size: glyph.size, text: glyph.text.clone() }),
+        }
+    }
+    words
+}
+
+/// `glyphs` on a character grid about `columns` wide, each line of print at
+/// about the row and column where it was printed: blank rows stand for the
+/// space between lines and blocks, and spaces for the space before text and
+/// between columns; word spaces stay single. With `align_amounts`, numbers
+/// go where their right edge was printed rather than their left, so columns
+/// of figures set flush right stay lined up on their last digits.
+/// Columns are scaled so the printed text spans the grid, or narrower when
+/// that would crowd the characters. Words stay whole, moving what follows
+/// them on their line to the right where the grid is too coarse.
+pub fn grid(glyphs: &[Glyph], columns: usize, align_amounts: bool) -> Vec<String> {
+    let mut glyphs: Vec<&Glyph> = glyphs.iter().filter(|glyph| glyph.size >= MIN_SIZE && !glyph.text.trim().is_empty()).collect();
+    if glyphs.is_empty() {
+        return Vec::new();
+    }
+    let left = glyphs.iter().map(|glyph| glyph.x).fold(f64::INFINITY, f64::min);
+    let right = glyphs.iter().map(|glyph| glyph.end).fold(f64::NEG_INFINITY, f64::max);
+    let advance = median(glyphs.iter().map(|glyph| glyph.end - glyph.x).collect());
+    let column_width = ((right - left) / columns.max(1) as f64).max(advance).max(f64::EPSILON);
+    let column_of = |x: f64| ((x - left) / column_width).round() as usize;
+    let pitch = median(glyphs.iter().map(|glyph| glyph.size).collect()) * LINE_PITCH;
+
+    glyphs.sort_by(by(|glyph: &&Glyph| glyph.y));
+    let mut lines: Vec<Vec<&Glyph>> = Vec::new();
+    for glyph in glyphs {
+        match lines.last_mut() {
+            Some(line) if (glyph.y - line[0].y).abs() < glyph.size.min(line[0].size) * SAME_LINE => line.push(glyph),
+            _ => lines.push(vec![glyph]),
+        }
+    }
+
+    let mut rows: Vec<String> = Vec::new();
+    let mut last_y = lines[0][0].y;
+    for mut line in lines {
+        line.sort_by(by(|glyph: &&Glyph| glyph.x));
+        // Rows from the line above, so spacing a little over one line
+        // does not add blank rows; always at least one row down, and a few
+        // blank rows stand for any larger gap
+        let below = ((line[0].y - last_y) / pitch).round().clamp(1.0, (MAX_BLANK_ROWS + 1) as f64) as usize;
+        if !rows.is_empty() {
+            rows.resize(rows.len() + below - 1, String::new());
+        }
+        last_y = line[0].y;
+        let mut text = String::new();
+        let mut column = 0;
+        let mut previous: Option<Word> = None;
+        for word in words(&line) {
+            let length = word.text.chars().count();
+            let amount = align_amounts && is_amount(&word.text);
+            let printed_at = if amount { column_of(word.end).saturating_sub(length) } else { column_of(word.x) };
+            let at = match previous {
+                // Word spaces stay single; only wider gaps, as between
+                // columns, keep their printed width
+                Some(previous) if !amount && word.x - previous.end < previous.size => column + 1,
+                Some(_) => printed_at.max(column + 1),
+                None => printed_at,
+            };
+            text.extend(std::iter::repeat_n(' ', at - column));
+            text.push_str(&word.text);
+            column = at + length;
+            previous = Some(word);
+        }
+        rows.push(text);
+    }
+    rows
+}
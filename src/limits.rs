@@ -0,0 +1,88 @@
+use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config::LimitsConfig;
+
+/// `[limits]`, set once at startup; 0 means no limit.
+static PAGE_MICROS: AtomicU64 = AtomicU64::new(10_000_000);
+static PAGE_BYTES: AtomicUsize = AtomicUsize::new(1024 * 1024);
+static MAX_OBJECTS: AtomicUsize = AtomicUsize::new(1_000_000);
+
+/// Objects read so far by the PDF being parsed.
+static OBJECTS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set(limits: &LimitsConfig) {
+    PAGE_MICROS.store((limits.page_seconds.max(0.0) * 1_000_000.0) as u64, Ordering::Relaxed);
+    PAGE_BYTES.store(limits.page_kb * 1024, Ordering::Relaxed);
+    MAX_OBJECTS.store(limits.max_objects, Ordering::Relaxed);
+}
+
+/// Start counting the objects of a new PDF.
+pub fn start_parse() {
+    OBJECTS.store(0, Ordering::Relaxed);
+}
+
+/// Count one more object, and whether it is still within `limits.max_objects`.
+/// Objects past the limit are dropped while parsing, so they take no memory.
+pub fn count_object() -> bool {
+    let max = MAX_OBJECTS.load(Ordering::Relaxed);
+    OBJECTS.fetch_add(1, Ordering::Relaxed) < max || max == 0
+}
+
+/// Fail when the PDF just parsed had more objects than `limits.max_objects`.
+pub fn check_objects() -> Result<()> {
+    let max = MAX_OBJECTS.load(Ordering::Relaxed);
+    if max > 0 && OBJECTS.load(Ordering::Relaxed) > max {
+        bail!("the PDF has more than {} objects (limits.max_objects)", max);
+    }
+    Ok(())
+}
+
+/// The time and text one page may take while it is extracted; the default
+/// has no limits. Both are checked as the page's content is drawn, character
+/// by character and path by path, so time spent before that, loading fonts
+/// and decoding the content stream, is not cut short.
+#[derive(Default)]
+pub struct PageLimits {
+    deadline: Option<Instant>,
+    max_bytes: Option<usize>,
+    exceeded: Option<String>,
+}
+
+impl PageLimits {
+    pub fn start() -> Self {
+        let micros = PAGE_MICROS.load(Ordering::Relaxed);
+        let bytes = PAGE_BYTES.load(Ordering::Relaxed);
+        Self {
+            deadline: (micros > 0).then(|| Instant::now() + Duration::from_micros(micros)),
+            max_bytes: (bytes > 0).then_some(bytes),
+            exceeded: None,
+        }
+    }
+
+    /// Whether the page may go on with `bytes` of text so far; once it may
+    /// not, `notice` says why.
+    pub fn check(&mut self, bytes: usize) -> bool {
+        if self.exceeded.is_some() {
+            return false;
+        }
+        if let Some(max) = self.max_bytes
+            && bytes >= max
+        {
+            self.exceeded = Some(format!("{} KB of text (limits.page_kb)", max / 1024));
+        } else if let Some(deadline) = self.deadline
+            && Instant::now() >= deadline
+        {
+            let seconds = PAGE_MICROS.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+            self.exceeded = Some(format!("{} seconds (limits.page_seconds)", seconds));
+        }
+        self.exceeded.is_none()
+    }
+
+    /// What to show where the rest of a stopped page would be.
+    pub fn notice(&self) -> Option<String> {
+        let exceeded = self.exceeded.as_ref()?;
+        Some(format!("[Extraction of this page stopped after {}; the rest of it is left out]", exceeded))
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+use crate::search;
+
+const HELP: &str = "\
+Enter or n   next page
+p            previous page
+N            go to page N
+.            print this page again
+/TEXT        list the lines with TEXT, from this page on
+i            title and page count
+h or ?       this help
+q            quit";
+
+/// The document as plain lines in the terminal's normal buffer, one page at a
+/// time, with commands typed a line at a time as in `ed`: nothing is redrawn
+/// or moved, so screen readers can follow along.
+pub struct Linear {
+    pages: Vec<String>,
+    title: Option<String>,
+    page: usize,
+}
+
+impl Linear {
+    pub fn new(pages: Vec<String>, title: Option<String>) -> Self {
+        Linear { pages, title, page: 0 }
+    }
+
+    /// Read commands until `q` or the end of input, starting at `page`
+    /// (0-based) or at the first line with `search`. Commands come from the
+    /// terminal when the PDF itself was piped in on standard input.
+    pub fn run(mut self, page: Option<usize>, search: Option<&str>, pdf_piped: bool) -> Result<()> {
+        let input: Box<dyn BufRead> = match File::open("/dev/tty") {
+            Ok(tty) if pdf_piped => Box::new(BufReader::new(tty)),
+            _ => Box::new(io::stdin().lock()),
+        };
+        let mut out = io::stdout().lock();
+        self.page = page.unwrap_or(0).min(self.pages.len().saturating_sub(1));
+        match search.map(|query| self.first_match(query)) {
+            Some(Some(page)) => self.page = page,
+            Some(None) => writeln!(out, "Not found: {}", search.unwrap_or_default())?,
+            None => {}
+        }
+        self.print_page(&mut out)?;
+        self.prompt(&mut out)?;
+        for line in input.lines() {
+            let line = line?;
+            if !self.command(line.trim(), &mut out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Carry out one command; false to quit.
+    fn command(&mut self, command: &str, out: &mut impl Write) -> Result<bool> {
+        let last = self.pages.len().saturating_sub(1);
+        match command {
+            "q" | "quit" => return Ok(false),
+            "" | "n" if self.page < last => {
+                self.page += 1;
+                self.print_page(out)?;
+            }
+            "" | "n" => writeln!(out, "Last page")?,
+            "p" if self.page > 0 => {
+                self.page -= 1;
+                self.print_page(out)?;
+            }
+            "p" => writeln!(out, "First page")?,
+            "." => self.print_page(out)?,
+            "i" | "info" => {
+                if let Some(title) = &self.title {
+                    writeln!(out, "{}", title)?;
+                }
+                writeln!(out, "Page {} of {}", self.page + 1, self.pages.len())?;
+            }
+            "h" | "?" | "help" => writeln!(out, "{}", HELP)?,
+            _ if command.starts_with('/') => self.list_matches(&command[1..], out)?,
+            _ => match command.parse::<usize>() {
+                Ok(page) if (1..=self.pages.len()).contains(&page) => {
+                    self.page = page - 1;
+                    self.print_page(out)?;
+                }
+                Ok(_) => writeln!(out, "No such page; there are {}", self.pages.len())?,
+                Err(_) => writeln!(out, "Unknown command {}; h for help", command)?,
+            },
+        }
+        self.prompt(out)?;
+        Ok(true)
+    }
+
+    fn print_page(&self, out: &mut impl Write) -> Result<()> {
+        writeln!(out, "Page {} of {}", self.page + 1, self.pages.len())?;
+        let text = self.pages.get(self.page).map_or("", |page| page.trim_end());
+        if text.trim().is_empty() {
+            writeln!(out, "(no text on this page)")?;
+        } else {
+            writeln!(out, "{}", text)?;
+        }
+        writeln!(out, "End of page {}", self.page + 1)?;
+        Ok(())
+    }
+
+    fn prompt(&self, out: &mut impl Write) -> Result<()> {
+        write!(out, "{}> ", self.page + 1)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// The first page from the current one on with `query`.
+    fn first_match(&self, query: &str) -> Option<usize> {
+        let query = query.to_lowercase();
+        (self.page..self.pages.len()).find(|&page| self.pages[page].lines().any(|line| !search::matches(line, &query).is_empty()))
+    }
+
+    /// Print each line with `query` from the current page to the end, with
+    /// its page and line number.
+    fn list_matches(&self, query: &str, out: &mut impl Write) -> Result<()> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            writeln!(out, "Search for what? /TEXT")?;
+            return Ok(());
+        }
+        let mut found = 0;
+        for (page, text) in self.pages.iter().enumerate().skip(self.page) {
+            for (line_idx, line) in text.lines().enumerate() {
+                if !search::matches(line, &query).is_empty() {
+                    writeln!(out, "Page {}, line {}: {}", page + 1, line_idx + 1, line.trim())?;
+                    found += 1;
+                }
+            }
+        }
+        match found {
+            0 => writeln!(out, "Not found from page {} on", self.page + 1)?,
+            1 => writeln!(out, "1 line found")?,
+            _ => writeln!(out, "{} lines found", found)?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Links into a document, for notes apps to open the viewer at a passage:
+/// `pdfreader:///home/me/paper.pdf#page=12&line=30`.
+pub const SCHEME: &str = "pdfreader://";
+
+/// Where a link points: a PDF and, optionally, a 1-based page and line.
+pub struct Location {
+    pub path: PathBuf,
+    pub page: Option<usize>,
+    pub line: Option<usize>,
+}
+
+/// The link to 1-based `page` (and `line`, if known) of the PDF at `path`,
+/// made absolute so it works from anywhere.
+pub fn uri(path: &Path, page: usize, line: Option<usize>) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = format!("{}{}#page={}", SCHEME, encode(&path.to_string_lossy()), page);
+    if let Some(line) = line {
+        uri.push_str(&format!("&line={}", line));
+    }
+    uri
+}
+
+/// Read a link made by `uri`, or `None` when `text` is not one.
+pub fn parse(text: &str) -> Option<Result<Location>> {
+    let rest = text.strip_prefix(SCHEME)?;
+    Some(parse_location(rest))
+}
+
+fn parse_location(rest: &str) -> Result<Location> {
+    let (path, fragment) = rest.split_once('#').unwrap_or((rest, ""));
+    if path.is_empty() {
+        bail!("No file in the link {}{}", SCHEME, rest);
+    }
+    let mut location = Location {
+        path: PathBuf::from(decode(path)),
+        page: None,
+        line: None,
+    };
+    for pair in fragment.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let number = value.parse::<usize>().ok().filter(|&n| n > 0);
+        match key {
+            "page" => location.page = Some(number.ok_or_else(|| anyhow::anyhow!("Invalid page in link: {}", value))?),
+            "line" => location.line = Some(number.ok_or_else(|| anyhow::anyhow!("Invalid line in link: {}", value))?),
+            // Links from newer versions may say more
+            _ => {}
+        }
+    }
+    Ok(location)
+}
+
+/// Percent-encode what would end the path or break the link in Markdown.
+fn encode(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
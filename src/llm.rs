@@ -0,0 +1,54 @@
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use std::env;
+
+use crate::config::LlmConfig;
+use crate::sandbox;
+
+/// Send a single-turn prompt to an OpenAI-compatible chat completions endpoint.
+pub fn complete(config: &LlmConfig, prompt: &str) -> Result<String> {
+    chat(config, &[("user".to_string(), prompt.to_string())])
+}
+
+/// Send a conversation of `(role, content)` messages and return the reply.
+pub fn chat(config: &LlmConfig, messages: &[(String, String)]) -> Result<String> {
+    sandbox::check("Network access")?;
+    if config.endpoint.is_empty() {
+        return Err(anyhow!("No LLM endpoint configured (set llm.endpoint in the config file)"));
+    }
+
+    let url = format!("{}/chat/completions", config.endpoint.trim_end_matches('/'));
+    let body = json!({
+        "model": config.model,
+        "messages": messages
+            .iter()
+            .map(|(role, content)| json!({ "role": role, "content": content }))
+            .collect::<Vec<_>>(),
+    });
+
+    let mut request = ureq::post(&url);
+    let api_key = match config.api_key.as_str() {
+        "" => env::var("OPENAI_API_KEY").unwrap_or_default(),
+        key => key.to_string(),
+    };
+    if !api_key.is_empty() {
+        request = request.set("Authorization", &format!("Bearer {}", api_key));
+    }
+
+    let response: serde_json::Value = request
+        .send_json(body)
+        .map_err(|e| anyhow!("LLM request failed: {}", e))?
+        .into_json()?;
+
+    response["choices"][0]["message"]["content"]
+        .as_str()
+        .map(|content| content.trim().to_string())
+        .ok_or_else(|| anyhow!("Unexpected response from {}", url))
+}
+
+/// Fill `{name}` placeholders in a prompt template.
+pub fn fill_template(template: &str, vars: &[(&str, &str)]) -> String {
+    vars.iter().fold(template.to_string(), |prompt, (name, value)| {
+        prompt.replace(&format!("{{{name}}}"), value)
+    })
+}
@@ -0,0 +1,67 @@
+use crossterm::event::KeyEvent;
+use std::collections::HashMap;
+
+/// Replays may call other registers, but not without end.
+const MAX_DEPTH: usize = 16;
+
+/// Key sequences recorded into named registers, vim style.
+#[derive(Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<KeyEvent>>,
+    /// Register being recorded into, with the keys so far
+    recording: Option<(char, Vec<KeyEvent>)>,
+    /// Register replayed last, for `@@`
+    last: Option<char>,
+    /// Nesting of replays in progress; replayed keys are not recorded again
+    depth: usize,
+}
+
+impl Macros {
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    pub fn start_recording(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+    }
+
+    /// Stop recording and store the keys. Returns the register and key count.
+    pub fn stop_recording(&mut self) -> Option<(char, usize)> {
+        let (register, keys) = self.recording.take()?;
+        let count = keys.len();
+        self.registers.insert(register, keys);
+        Some((register, count))
+    }
+
+    pub fn record(&mut self, key: KeyEvent) {
+        if self.depth == 0
+            && let Some((_, keys)) = self.recording.as_mut()
+        {
+            keys.push(key);
+        }
+    }
+
+    /// Keys to replay for `register`, with `@` meaning the last register replayed.
+    /// Call [`Macros::end_replay`] once they have been handled.
+    pub fn begin_replay(&mut self, register: char) -> Result<Vec<KeyEvent>, String> {
+        let register = match register {
+            '@' => self.last.ok_or("No previous macro")?,
+            register => register,
+        };
+        if self.depth >= MAX_DEPTH {
+            return Err("Macro calls itself too deeply".to_string());
+        }
+        let keys = self
+            .registers
+            .get(&register)
+            .cloned()
+            .ok_or_else(|| format!("Register {} is empty", register))?;
+        self.last = Some(register);
+        self.depth += 1;
+        Ok(keys)
+    }
+
+    pub fn end_replay(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+}
@@ -1,28 +1,360 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Result};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode,
+        KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use pdf_extract::extract_text;
 use ratatui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    symbols,
+    widgets::{Block, Borders, Clear, LineGauge, Paragraph, Wrap},
     Frame, Terminal,
 };
-use std::io;
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::process::Command as ProcessCommand;
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
+
+mod bench;
+mod citations;
+mod clip;
+mod clipboard;
+mod code;
+mod command;
+mod config;
+mod corpus;
+mod cursor;
+mod diff;
+mod display;
+mod editor;
+mod export;
+mod external;
+mod footnotes;
+mod format;
+mod freq;
+mod hooks;
+mod images;
+mod keyboard;
+mod kitty;
+mod koreader;
+mod language;
+mod large;
+mod layout;
+mod linear;
+mod link;
+mod limits;
+mod llm;
+mod math;
+mod macros;
+mod memory;
+mod messages;
+mod minimap;
+mod ocr;
+mod page_view;
+mod pager;
+mod paginate;
+mod paths;
+mod pdf;
+mod plugin;
+mod popup;
+mod registers;
+mod remote;
+mod retrieve;
+mod sandbox;
+mod search;
+mod spell;
+mod state;
+mod structure;
+mod sync;
+mod synctex;
+mod tables;
+mod task;
+mod theme;
+mod thumbnails;
+mod translate;
+mod tts;
+mod wrap;
+
+use config::{Config, ExcludeConfig, FormatConfig, KeyboardConfig, OcrConfig, PaginationConfig};
+use hooks::ShellHooks;
+use corpus::FileHit;
+use cursor::Cursor;
+use diff::DiffView;
+use editor::LineEditor;
+use macros::Macros;
+use memory::PageBudget;
+use language::Language;
+use linear::Linear;
+use messages::Messages;
+use command::{Command, SummaryScope};
+use citations::Citation;
+use page_view::PageView;
+use pdf::{Attachment, OutlineEntry, PageImage};
+use plugin::{Action, Plugins};
+use popup::{ListPopup, Popup};
+use registers::{Registers, Yanked};
+use state::{DocState, Position};
+use remote::{RemoteCommand, Server};
+use code::CodeCache;
+use footnotes::FootnoteCache;
+use search::{MatchCache, PageFind};
+use spell::SpellCheck;
+use task::{Stream, Task};
+use theme::Theme;
+use thumbnails::{PageShape, Thumbnails};
+use tts::Speech;
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
-    /// PDF file to read
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// PDF file to read, or a `pdfreader://` link to a page of one; `-` or
+    /// none reads it from a pipe. Like less, `+N` before it opens at page N
+    /// and `+/pattern` at the first match
     #[arg(value_name = "FILE")]
-    file: PathBuf,
+    file: Option<PathBuf>,
+
+    /// Config file to use instead of the default location
+    #[arg(long, value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
+
+    /// Keep config, data, cache and state under this folder instead of the
+    /// XDG directories, for a portable setup
+    #[arg(long, value_name = "DIR", global = true)]
+    state_dir: Option<PathBuf>,
+
+    /// For PDFs from untrusted sources: no external commands, hooks, links or
+    /// network, and streams that would decompress to huge sizes are left out
+    #[arg(long, global = true)]
+    sandbox: bool,
+
+    /// Save nothing about this session: no marks, tags or view choices, no
+    /// reading position, no cached text, OCR results or thumbnails
+    #[arg(long, global = true)]
+    private: bool,
+
+    /// Open at this page (1-based) instead of where the document was left;
+    /// `-P` as in zathura
+    #[arg(short = 'P', long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    page: Option<usize>,
+
+    /// Open at the first line containing this text, as zathura's `--find`
+    /// and less's `+/pattern` do
+    #[arg(short, long, value_name = "TEXT")]
+    find: Option<String>,
+
+    /// Reading speed in words per minute (overrides the config file)
+    #[arg(long, value_name = "N")]
+    wpm: Option<u32>,
+
+    /// OCR languages, e.g. `deu+eng` (overrides the config file)
+    #[arg(long, value_name = "LANGS")]
+    ocr_lang: Option<String>,
+
+    /// Page splitting for text without page boundaries: `lines`, `footers`,
+    /// `chapters`, or a number of lines per page (overrides the config file)
+    #[arg(long, value_name = "SPLIT", global = true, value_parser = paginate::parse_flag)]
+    paginate: Option<(paginate::SplitBy, Option<usize>)>,
+
+    /// Blank lines and spacing in page text: `strip`, `paragraphs`, `collapse`
+    /// or `raw` (overrides the config file)
+    #[arg(long, value_name = "MODE", global = true)]
+    whitespace: Option<format::Whitespace>,
+
+    /// Leave margins out of the text, in percent of the page:
+    /// `top,right,bottom,left`, e.g. `5,0,8,0` (overrides the config file)
+    #[arg(long, value_name = "T,R,B,L", global = true, value_parser = pdf::parse_crop)]
+    crop: Option<pdf::Crop>,
+
+    /// Hide lines matching this regular expression from the text and search,
+    /// e.g. `^CONFIDENTIAL`; repeatable, added to `[exclude]` patterns
+    #[arg(long, value_name = "REGEX", global = true)]
+    exclude: Vec<String>,
+
+    /// Accept commands from `pdf_reader ctl` on this Unix socket
+    #[arg(long, value_name = "PATH")]
+    listen: Option<PathBuf>,
+
+    /// Print the document's text instead of opening the viewer, as `extract`
+    /// does; the default when stdout is not a terminal
+    #[arg(long)]
+    dump: bool,
+
+    /// Show the formatted text with page headers through `$PAGER` instead of
+    /// opening the viewer, for a quick skim
+    #[arg(short, long)]
+    plain: bool,
+
+    /// Number the lines of each page in `--plain` output
+    #[arg(short = 'n', long, requires = "plain")]
+    line_numbers: bool,
+
+    /// Print pages one after another in the terminal's normal buffer and read
+    /// commands a line at a time instead of drawing a full-screen view, for
+    /// screen readers
+    #[arg(long)]
+    no_alt_screen: bool,
+}
+
+/// Settings given as flags, which win over the config file and any
+/// per-document overrides.
+#[derive(Default)]
+struct Overrides {
+    wpm: Option<u32>,
+    ocr_lang: Option<String>,
+    paginate: Option<(paginate::SplitBy, Option<usize>)>,
+    whitespace: Option<format::Whitespace>,
+    crop: Option<pdf::Crop>,
+    /// `--exclude` patterns, added to `[exclude]` ones
+    exclude: Vec<String>,
+}
+
+impl Overrides {
+    fn apply(&self, config: &mut Config) -> Result<()> {
+        if let Some(wpm) = self.wpm {
+            config.reading.wpm = wpm;
+        }
+        if let Some(lang) = &self.ocr_lang {
+            config.ocr.lang = lang.clone();
+        }
+        if let Some((split, lines)) = self.paginate {
+            config.pagination.split = split;
+            config.pagination.lines_per_page = lines.unwrap_or(config.pagination.lines_per_page);
+        }
+        if let Some(whitespace) = self.whitespace {
+            config.format.whitespace = whitespace;
+        }
+        if let Some(crop) = self.crop {
+            config.format.crop = crop;
+        }
+        if !self.exclude.is_empty() {
+            config.exclude.lines.extend(self.exclude.iter().cloned());
+            config.exclude.compile()?;
+        }
+        Ok(())
+    }
+
+    /// `config` with the overrides for `pdf` and then these applied.
+    fn document_config(&self, config: &Config, pdf: &Path) -> Result<Config> {
+        let mut config = config.for_document(pdf)?;
+        self.apply(&mut config)?;
+        Ok(config)
+    }
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the document's text without starting the viewer
+    Extract {
+        /// PDF file to convert
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ExtractFormat::Text)]
+        format: ExtractFormat,
+
+        /// Write to this file instead of standard output
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Mark matches of this term: `<mark>` in HTML, colors in text and Markdown
+        #[arg(long, value_name = "TERM")]
+        highlight: Option<String>,
+
+        /// When to mark `--highlight` matches: `auto` colors text and Markdown
+        /// only on a terminal (and not with NO_COLOR set), `never` marks nothing
+        #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+        color: ColorChoice,
+    },
+    /// Save every embedded image, named after the PDF, its page and the image
+    ExtractImages {
+        /// PDF file to read
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Directory to write the images to
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        output: PathBuf,
+    },
+    /// Compare the text of two PDFs, e.g. two revisions of a contract
+    Diff {
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        /// Start in the side-by-side view instead of the unified one
+        #[arg(long)]
+        side_by_side: bool,
+    },
+    /// Print document properties, encryption and permissions, and attachments
+    Info {
+        /// PDF file to describe
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Index every PDF in a folder and browse and search them together
+    Dir {
+        /// Folder of PDFs
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+    /// Time parsing, extraction (pdf-extract, pdftotext, the layout view), caches, search and rendering
+    Bench {
+        /// PDF file to measure
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Also time OCR of the first page with the configured command
+        #[arg(long)]
+        ocr: bool,
+    },
+    /// Send a command to a viewer started with --listen
+    ///
+    /// Commands: `goto N`, `search TEXT`, `reload`, `open FILE`, `synctex FILE.tex:LINE`
+    Ctl {
+        /// Socket the viewer is listening on
+        #[arg(long, short, value_name = "PATH")]
+        socket: PathBuf,
+
+        /// Command and its arguments
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExtractFormat {
+    /// Formatted text with a header per page
+    Text,
+    /// Markdown with detected headings, paragraphs and lists
+    Md,
+    /// Standalone HTML page with a section per page and outline navigation
+    Html,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Clone, PartialEq)]
@@ -30,6 +362,113 @@ enum InputMode {
     Normal,
     PageJump,
     Search,
+    /// Find on the current page only
+    Find,
+    Command,
+    Confirm,
+}
+
+/// An action waiting for the user to answer a y/n prompt.
+enum PendingAction {
+    Print(RangeInclusive<usize>),
+}
+
+/// What the next key names a register or mark for.
+#[derive(Clone, Copy)]
+enum PendingKey {
+    Record,
+    Replay,
+    SetMark,
+    JumpToMark,
+    /// `"`: the register the next yank goes to
+    Register,
+    /// `Ctrl+r` in a prompt: the register to paste
+    PasteRegister,
+    /// `"` in the clip history: the register to put that clip in, by its place in the history
+    StoreClip(usize),
+}
+
+/// A piece of view state as it was before a change, for undo and redo.
+enum Snapshot {
+    Search {
+        query: String,
+        results: Vec<SearchResult>,
+        current: usize,
+    },
+    SidePanel(Option<Popup>),
+    List(Option<(ListKind, ListPopup)>),
+    Mark(char, Option<Position>),
+}
+
+/// Entries kept on the undo stack.
+const UNDO_LIMIT: usize = 100;
+
+/// The page thumbnail grid shown over the content.
+struct ThumbnailGrid {
+    selected: usize,
+    /// First visible row of thumbnails
+    top_row: usize,
+    /// Images, when the terminal can show them; otherwise cells show page text
+    thumbnails: Option<Thumbnails>,
+    /// Page and image area of each visible cell, set while drawing
+    cells: Vec<(usize, Rect)>,
+}
+
+/// A page laid out as printed, shown over the content.
+struct LayoutView {
+    page: usize,
+    /// Width the grid was laid out for; a different pane width lays it out again
+    columns: usize,
+    /// Place numbers by their right edge, for statements and invoices
+    /// (`a` toggles it)
+    align_amounts: bool,
+    rows: Vec<String>,
+    scroll: usize,
+}
+
+/// Lifecycle events with a configurable shell hook.
+enum Hook {
+    Open,
+    Close,
+    PageChange,
+}
+
+type CorpusSearch = (String, Vec<FileHit>, Task<(Vec<FileHit>, Vec<String>)>);
+
+/// What the open list popup holds, and so what its keys act on.
+enum ListKind {
+    Citations(Vec<Citation>),
+    /// Images of a 0-based page
+    Images(usize, Vec<PageImage>),
+    Attachments(Vec<Attachment>),
+    Terms(Vec<String>),
+    /// Cross-document search results for a query: a header row (`None`) per file, then its hits
+    FileHits(String, Vec<Option<FileHit>>),
+    /// PDFs of the corpus folder
+    Documents(Vec<PathBuf>),
+    /// Page tags of the document
+    Tags(Vec<String>),
+    /// Yanks and clips of the session, newest first
+    Clips(Vec<Yanked>),
+    /// Yank registers with text
+    Registers(Vec<char>),
+}
+
+/// An image shown over the popup with the graphics protocol.
+struct ImagePreview {
+    path: PathBuf,
+    /// Where it was placed, once it has been
+    area: Option<Rect>,
+}
+
+/// Kitty image id for previews, above the ids used by page thumbnails.
+const PREVIEW_IMAGE_ID: u32 = 1_000_000;
+
+struct ChatTurn {
+    question: String,
+    answer: Option<String>,
+    /// 1-based pages sent as context
+    pages: Vec<usize>,
 }
 
 #[derive(Clone)]
@@ -38,446 +477,4886 @@ struct SearchResult {
     line: usize,
 }
 
+/// Every line containing `query_lower`, ignoring case, other than those
+/// `hidden` leaves out.
+fn find_lines(pages: &[String], query_lower: &str, hidden: impl Fn(&str) -> bool) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    for (page_idx, page_content) in pages.iter().enumerate() {
+        find_in_page(page_idx, page_content, query_lower, &hidden, &mut results);
+    }
+    results
+}
+
+/// Add the lines of one page that `find_lines` would find to `results`.
+fn find_in_page(page: usize, text: &str, query_lower: &str, hidden: impl Fn(&str) -> bool, results: &mut Vec<SearchResult>) {
+    for (line_idx, line) in text.lines().enumerate() {
+        if line.to_lowercase().contains(query_lower) && !hidden(line) {
+            results.push(SearchResult { page, line: line_idx });
+        }
+    }
+}
+
 struct App {
+    path: PathBuf,
+    title: Option<String>,
     pages: Vec<String>,
+    outline: Vec<OutlineEntry>,
+    word_counts: Vec<usize>,
+    /// Pages still being extracted in the background, and the document's full page count
+    loading: Option<Stream<StreamedPage>>,
+    page_total: usize,
+    /// Labels printed on the pages, such as `iv` or `A-3`, when the PDF has them
+    page_labels: Vec<String>,
+    /// Position to go to once its page has been extracted
+    pending_position: Option<Position>,
+    /// Search to run once every page has been extracted, from `+/pattern`
+    pending_search: Option<String>,
+    /// Moves page text out of memory past `memory.max_text_mb`
+    budget: PageBudget,
+    /// Search matches on the shown page, reused between frames
+    match_cache: MatchCache,
+    footnote_cache: FootnoteCache,
+    /// Syntax highlighting of code listings on the shown page
+    code_cache: CodeCache,
+    /// Where the footnote shown in the popup is, for `Enter` to go there, and
+    /// the line and end of the marker it was reached from, for `f` to go on
+    footnote_target: Option<(Position, (usize, usize))>,
+    /// Columns table rows are scrolled to the left
+    table_scroll: usize,
+    /// Pages that are images with little or no text layer, until OCR'd
+    scanned: Vec<bool>,
+    /// Dominant language of the text, when one could be detected
+    language: Option<Language>,
+    /// Settings for the open document: the config file with its overrides
+    config: Config,
+    /// Flags to apply again when another document's settings are loaded
+    overrides: Overrides,
     current_page: usize,
     scroll_offset: usize,
     should_quit: bool,
     input_mode: InputMode,
-    input_buffer: String,
+    /// What is typed into the page, search or command prompt
+    input: LineEditor,
     search_query: String,
+    /// `Ctrl+f` matches on one page, stepped through with `n`/`N`
+    page_find: Option<PageFind>,
     search_results: Vec<SearchResult>,
     current_search_result: usize,
+    /// Result shown in the preview pane by `(`/`)`, without going to it
+    result_preview: Option<usize>,
     status_message: String,
+    cursor: Option<Cursor>,
+    viewport_height: usize,
+    /// Where the progress bar was drawn, for mouse clicks
+    progress_area: Rect,
+    /// Show the minimap strip beside the content (`:minimap` toggles it)
+    minimap: bool,
+    /// Show the line being read in large letters below the content (`L` or
+    /// `:large` toggles it)
+    large_print: bool,
+    /// Where the minimap was drawn, for mouse clicks; empty when hidden
+    minimap_area: Rect,
+    /// Wrap long lines instead of cutting them at the pane (`:wrap` toggles it)
+    wrap: bool,
+    /// Number the page's lines in a gutter (`:numbers` toggles it)
+    line_numbers: bool,
+    /// Tint over everything drawn (`D` or `:display` changes it)
+    display_filter: display::Filter,
+    /// Show lines matching `[exclude]` patterns (`X` or `:excluded` toggles it)
+    show_excluded: bool,
+    /// Colors of everything drawn, from `[theme]`
+    theme: Theme,
+    /// Where the page text was drawn, right of any line number gutter; wraps
+    /// and mouse clicks are worked out from it
+    text_area: Rect,
+    /// The left button went down on the text and has not come up yet
+    dragging: bool,
+    /// What the mouse is over, shown in the status line until it moves off
+    hover_status: Option<String>,
+    /// A line just jumped to, lit up for `ANCHOR_FLASH` so the eye finds it
+    anchor: Option<(Position, Instant)>,
+    /// When the visual bell went off; the screen shows reversed for `BELL_FLASH`
+    visual_bell: Option<Instant>,
+    popup: Option<Popup>,
+    selection_anchor: Option<Cursor>,
+    speech: Option<Speech>,
+    side_panel: Option<Popup>,
+    side_panel_focused: bool,
+    summary_task: Option<Task<String>>,
+    /// Translation running in the background, with the text being translated
+    translation_task: Option<(String, Task<String>)>,
+    /// Dictionary lookup running in the background, with the word looked up
+    definition_task: Option<(String, Task<String>)>,
+    /// Pages being sent to the print command in the background
+    print_task: Option<(RangeInclusive<usize>, Task<String>)>,
+    /// OCR running in the background: page, start time for the spinner, and the task
+    ocr_task: Option<(usize, Instant, Task<String>)>,
+    /// Images of a page being read in the background for `:images`
+    images_task: Option<(usize, Task<Vec<PageImage>>)>,
+    /// Misspelled words found so far, while spell highlighting is on
+    spelling: Option<SpellCheck>,
+    chat: Vec<ChatTurn>,
+    chat_task: Option<Task<String>>,
+    /// Whether `/` searches every PDF in the document's folder
+    search_all: bool,
+    /// Cross-document search running in the background: query, hits in the open document, and the task
+    corpus_task: Option<CorpusSearch>,
+    /// Folder opened with the `dir` subcommand; searches and `:docs` cover it
+    corpus_dir: Option<PathBuf>,
+    /// Text cache being filled for the corpus folder: documents indexed and those that failed
+    index_task: Option<Task<(usize, Vec<String>)>>,
+    list: Option<(ListKind, ListPopup)>,
+    pending_action: Option<PendingAction>,
+    /// A program to run in the foreground, with the TUI suspended, on the next loop pass
+    foreground: Option<ProcessCommand>,
+    server: Option<Server>,
+    plugins: Plugins,
+    /// Plugin actions are being carried out; hooks they trigger are skipped
+    /// so a plugin cannot call itself without end
+    in_plugin_actions: bool,
+    shell_hooks: ShellHooks,
+    macros: Macros,
+    registers: Registers,
+    /// Set after `Q`, `@`, `m`, `'`, `"` or `Ctrl+r` while waiting for the register or mark name
+    pending_key: Option<PendingKey>,
+    doc_state: DocState,
+    /// Tag `n`/`p` are limited to, set with `:filter`
+    tag_filter: Option<String>,
+    /// Where the last mark jump started, for `''`
+    previous_position: Option<Position>,
+    messages: Messages,
+    thumbnail_grid: Option<ThumbnailGrid>,
+    /// Quarter turns given to pages in the thumbnail grid, kept while the
+    /// document is open
+    thumbnail_turns: HashMap<usize, u16>,
+    /// The page laid out as printed (`V` or `:layout` toggles it)
+    layout_view: Option<LayoutView>,
+    /// The page rendered as an image (`I` or `:page-image` toggles it)
+    page_view: Option<PageView>,
+    image_preview: Option<ImagePreview>,
+    /// Inner area of the centered popup, set while drawing
+    popup_area: Rect,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    /// Page last reported to the page-change hooks
+    reported_page: usize,
 }
 
 impl App {
-    fn new(pdf_content: Vec<String>) -> Self {
+    fn new(path: PathBuf, pdf_content: Vec<String>, outline: Vec<OutlineEntry>, config: Config) -> Self {
+        let word_counts = pdf_content.iter().map(|page| count_words(page)).collect();
+        let budget = PageBudget::new(config.memory.max_text_mb, &path);
+        // Trouble with the theme shows once it is up rather than stopping it
+        let (theme, theme_warnings) = Theme::load(&config.theme);
         Self {
+            path,
+            title: None,
+            scanned: Vec::new(),
+            language: None,
             pages: pdf_content,
+            outline,
+            word_counts,
+            loading: None,
+            page_total: 0,
+            page_labels: Vec::new(),
+            pending_position: None,
+            pending_search: None,
+            budget,
+            match_cache: MatchCache::default(),
+            footnote_cache: FootnoteCache::default(),
+            code_cache: CodeCache::default(),
+            footnote_target: None,
+            table_scroll: 0,
             current_page: 0,
             scroll_offset: 0,
             should_quit: false,
             input_mode: InputMode::Normal,
-            input_buffer: String::new(),
+            input: LineEditor::default(),
             search_query: String::new(),
+            page_find: None,
             search_results: Vec::new(),
             current_search_result: 0,
-            status_message: String::new(),
+            result_preview: None,
+            status_message: theme_warnings.join(" | "),
+            cursor: None,
+            viewport_height: 0,
+            progress_area: Rect::default(),
+            minimap: config.layout.minimap,
+            large_print: config.layout.large_print,
+            minimap_area: Rect::default(),
+            wrap: config.layout.wrap,
+            line_numbers: config.layout.line_numbers,
+            display_filter: config.layout.display_filter,
+            show_excluded: false,
+            theme,
+            text_area: Rect::default(),
+            dragging: false,
+            hover_status: None,
+            anchor: None,
+            visual_bell: None,
+            popup: None,
+            selection_anchor: None,
+            speech: None,
+            side_panel: None,
+            side_panel_focused: false,
+            summary_task: None,
+            translation_task: None,
+            definition_task: None,
+            print_task: None,
+            ocr_task: None,
+            images_task: None,
+            spelling: None,
+            chat: Vec::new(),
+            chat_task: None,
+            search_all: false,
+            corpus_task: None,
+            corpus_dir: None,
+            index_task: None,
+            list: None,
+            pending_action: None,
+            foreground: None,
+            server: None,
+            plugins: Plugins::new(),
+            in_plugin_actions: false,
+            shell_hooks: ShellHooks::default(),
+            macros: Macros::default(),
+            registers: Registers::default(),
+            pending_key: None,
+            doc_state: DocState::default(),
+            tag_filter: None,
+            previous_position: None,
+            messages: Messages::new(),
+            thumbnail_grid: None,
+            thumbnail_turns: HashMap::new(),
+            layout_view: None,
+            page_view: None,
+            image_preview: None,
+            popup_area: Rect::default(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            reported_page: 0,
+            config,
+            overrides: Overrides::default(),
         }
     }
 
     fn next_page(&mut self) {
+        if let Some(tag) = self.tag_filter.clone() {
+            let next = self.tagged_pages(&tag).find(|&page| page > self.current_page);
+            self.go_to_tagged_page(&tag, next);
+            return;
+        }
         if self.current_page < self.pages.len().saturating_sub(1) {
-            self.current_page += 1;
+            self.set_page(self.current_page + 1);
             self.scroll_offset = 0;
+            self.reset_cursor();
+        } else if self.loading.is_some() {
+            self.status_message = "The next pages are still being read".to_string();
+        } else {
+            self.status_message = "Already on the last page".to_string();
+            self.bell();
         }
     }
 
     fn prev_page(&mut self) {
+        if let Some(tag) = self.tag_filter.clone() {
+            let prev = self.tagged_pages(&tag).rev().find(|&page| page < self.current_page);
+            self.go_to_tagged_page(&tag, prev);
+            return;
+        }
         if self.current_page > 0 {
-            self.current_page -= 1;
+            self.set_page(self.current_page - 1);
             self.scroll_offset = 0;
+            self.reset_cursor();
+        } else {
+            self.status_message = "Already on the first page".to_string();
+            self.bell();
         }
     }
 
-    fn scroll_down(&mut self) {
-        self.scroll_offset += 1;
+    /// 0-based pages carrying `tag`, in order.
+    fn tagged_pages(&self, tag: &str) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        let pages = self.pages.len();
+        self.doc_state.tags.get(tag).into_iter().flatten().copied().filter(move |&page| page < pages)
     }
 
-    fn scroll_up(&mut self) {
-        self.scroll_offset = self.scroll_offset.saturating_sub(1);
+    fn go_to_tagged_page(&mut self, tag: &str, page: Option<usize>) {
+        match page {
+            Some(page) => {
+                self.set_page(page);
+                self.scroll_offset = 0;
+                self.reset_cursor();
+            }
+            None => {
+                self.status_message = format!("No more pages tagged '{}' (:filter to show all)", tag);
+                self.bell();
+            }
+        }
     }
 
-    fn quit(&mut self) {
-        self.should_quit = true;
+    fn scroll_down(&mut self) {
+        let max = self.max_scroll();
+        let lines = self.current_lines();
+        // A hidden line at the top draws nothing, so move past it as well
+        let mut offset = self.scroll_offset;
+        while offset < max {
+            offset += 1;
+            if !lines.get(offset).is_some_and(|line| self.hidden(line)) {
+                break;
+            }
+        }
+        self.scroll_offset = offset;
     }
 
-    fn jump_to_page(&mut self, page_num: usize) {
-        if page_num > 0 && page_num <= self.pages.len() {
-            self.current_page = page_num - 1;
-            self.scroll_offset = 0;
-            self.status_message = format!("Jumped to page {}", page_num);
-        } else {
-            self.status_message = format!("Invalid page number: {}", page_num);
+    fn scroll_up(&mut self) {
+        let lines = self.current_lines();
+        let mut offset = self.scroll_offset;
+        while offset > 0 {
+            offset -= 1;
+            if !lines.get(offset).is_some_and(|line| self.hidden(line)) {
+                break;
+            }
         }
+        self.scroll_offset = offset;
     }
 
-    fn start_page_jump(&mut self) {
-        self.input_mode = InputMode::PageJump;
-        self.input_buffer.clear();
-        self.status_message = "Enter page number:".to_string();
+    fn current_lines(&self) -> Vec<&str> {
+        self.pages
+            .get(self.current_page)
+            .map_or(Vec::new(), |page| page.lines().collect())
     }
 
-    fn start_search(&mut self) {
-        self.input_mode = InputMode::Search;
-        self.input_buffer.clear();
-        self.status_message = "Enter search term:".to_string();
+    fn toggle_cursor(&mut self) {
+        self.selection_anchor = None;
+        if self.cursor.take().is_none() {
+            self.cursor = Some(Cursor::default());
+            self.reset_cursor();
+            self.status_message = "Cursor mode: h/j/k/l move, w/b words, 0/$ line, c/Esc exit".to_string();
+        } else {
+            self.status_message.clear();
+        }
     }
 
-    fn execute_search(&mut self) {
-        if self.input_buffer.is_empty() {
-            self.status_message = "Search query is empty".to_string();
-            return;
+    /// Put the cursor at the top of the view, if cursor mode is on.
+    fn reset_cursor(&mut self) {
+        self.selection_anchor = None;
+        if self.cursor.is_some() {
+            // The cursor is clamped to the page's lines, so they must be in memory
+            let page = self.current_page;
+            self.load_pages(page..page + 1);
+            let lines = self.current_lines();
+            let line = (self.scroll_offset..lines.len()).find(|&line| !self.hidden(lines[line])).unwrap_or(self.scroll_offset);
+            let mut cursor = Cursor { line, col: 0 };
+            cursor.clamp_to(&lines);
+            self.cursor = Some(cursor);
         }
+    }
 
-        self.search_query = self.input_buffer.clone();
-        self.search_results.clear();
-        
-        let query_lower = self.search_query.to_lowercase();
-        
-        for (page_idx, page_content) in self.pages.iter().enumerate() {
-            for (line_idx, line) in page_content.lines().enumerate() {
-                if line.to_lowercase().contains(&query_lower) {
-                    self.search_results.push(SearchResult {
-                        page: page_idx,
-                        line: line_idx,
-                    });
-                }
+    /// Apply a cursor motion key. Returns false if the key is not a motion.
+    fn move_cursor(&mut self, code: KeyCode) -> bool {
+        let Some(mut cursor) = self.cursor else {
+            return false;
+        };
+        let lines = self.current_lines();
+        // Motions that land on a hidden line go on past it, unless nothing is past it
+        loop {
+            let before = cursor;
+            match code {
+                KeyCode::Char('h') | KeyCode::Left => cursor.left(),
+                KeyCode::Char('l') | KeyCode::Right => cursor.right(&lines),
+                KeyCode::Char('j') | KeyCode::Down => cursor.down(&lines),
+                KeyCode::Char('k') | KeyCode::Up => cursor.up(&lines),
+                KeyCode::Char('w') => cursor.next_word(&lines),
+                KeyCode::Char('b') => cursor.prev_word(&lines),
+                KeyCode::Char('0') => cursor.line_start(),
+                KeyCode::Char('$') => cursor.line_end(&lines),
+                _ => return false,
+            }
+            if cursor == before || !lines.get(cursor.line).is_some_and(|line| self.hidden(line)) {
+                break;
             }
         }
+        if lines.get(cursor.line).is_some_and(|line| self.hidden(line)) {
+            return true;
+        }
+        self.cursor = Some(cursor);
+        self.scroll_to_line(cursor.line);
+        true
+    }
 
-        if self.search_results.is_empty() {
-            self.status_message = format!("No results found for '{}'", self.search_query);
+    /// Scroll just enough to bring `line`, all its rows if they fit, into the viewport.
+    fn scroll_to_line(&mut self, line: usize) {
+        if line < self.scroll_offset {
+            self.scroll_offset = line;
         } else {
-            self.current_search_result = 0;
-            self.go_to_search_result();
+            let bottom = self.offset_above(line + 1, self.viewport_height.max(1)).min(line);
+            self.scroll_offset = self.scroll_offset.max(bottom);
         }
     }
 
-    fn go_to_search_result(&mut self) {
-        if let Some(result) = self.search_results.get(self.current_search_result) {
-            self.current_page = result.page;
-            self.scroll_offset = result.line.saturating_sub(5); // Show some context
-            self.status_message = format!(
-                "Result {} of {} for '{}'",
-                self.current_search_result + 1,
-                self.search_results.len(),
-                self.search_query
-            );
+    /// Widths of the first row of a line and of the rows it continues on,
+    /// which leave room for the `↪` mark unless the line number gutter holds
+    /// it; `None` when lines are cut instead, or before the first draw.
+    fn wrap_widths(&self) -> Option<(usize, usize)> {
+        let width = self.text_area.width as usize;
+        if !self.wrap || width == 0 {
+            return None;
         }
+        Some((width, if self.line_numbers { width } else { width.saturating_sub(2) }))
     }
 
-    fn next_search_result(&mut self) {
-        if !self.search_results.is_empty() {
-            self.current_search_result = (self.current_search_result + 1) % self.search_results.len();
-            self.go_to_search_result();
+    /// The byte ranges of `line` of the current page on each screen row;
+    /// none for a hidden line.
+    fn line_row_ranges(&self, line: &str) -> Vec<Range<usize>> {
+        if self.hidden(line) {
+            return Vec::new();
+        }
+        match self.wrap_widths() {
+            // Table rows are cut to the pane, not wrapped
+            Some((first, rest)) if !tables::is_row(line) => wrap::rows(line, first, rest),
+            _ => std::iter::once(0..line.len()).collect(),
         }
     }
 
-    fn prev_search_result(&mut self) {
-        if !self.search_results.is_empty() {
-            self.current_search_result = if self.current_search_result == 0 {
-                self.search_results.len() - 1
-            } else {
-                self.current_search_result - 1
-            };
-            self.go_to_search_result();
+    fn line_rows(&self, line: &str) -> usize {
+        self.line_row_ranges(line).len()
+    }
+
+    /// The first line to show for `line` to have `above` rows of the page
+    /// above it, or as many as there are.
+    fn offset_above(&self, line: usize, above: usize) -> usize {
+        let lines = self.current_lines();
+        if lines.is_empty() {
+            return line.saturating_sub(above);
         }
+        let mut offset = line.min(lines.len());
+        let mut rows = 0;
+        while offset > 0 {
+            let taken = self.line_rows(lines[offset - 1]);
+            if rows + taken > above {
+                break;
+            }
+            rows += taken;
+            offset -= 1;
+        }
+        offset
     }
 
-    fn cancel_input(&mut self) {
-        self.input_mode = InputMode::Normal;
-        self.input_buffer.clear();
-        self.status_message.clear();
+    /// How far the current page scrolls: until its last line is at the bottom.
+    fn max_scroll(&self) -> usize {
+        self.offset_above(self.current_lines().len(), self.viewport_height)
     }
 
-    fn clear_search(&mut self) {
-        self.search_query.clear();
-        self.search_results.clear();
-        self.current_search_result = 0;
-        self.status_message = "Search cleared".to_string();
+    /// The lines of the current page with at least one row in view.
+    fn visible_lines(&self) -> Range<usize> {
+        let lines = self.current_lines();
+        let mut rows = 0;
+        let mut end = self.scroll_offset.min(lines.len());
+        while end < lines.len() && rows < self.viewport_height {
+            rows += self.line_rows(lines[end]);
+            end += 1;
+        }
+        self.scroll_offset..end.max(self.scroll_offset)
     }
 
-    fn handle_input(&mut self, c: char) {
-        match self.input_mode {
-            InputMode::PageJump => {
-                if c.is_ascii_digit() {
-                    self.input_buffer.push(c);
-                }
+    /// The line and character drawn at a screen cell, if it is in the text.
+    fn position_at(&self, column: u16, row: u16) -> Option<Cursor> {
+        let area = self.text_area;
+        if !area.intersects(Rect::new(column, row, 1, 1)) {
+            return None;
+        }
+        let mut top = area.y;
+        for (index, line) in self.current_lines().into_iter().enumerate().skip(self.scroll_offset) {
+            if top >= area.bottom() {
+                break;
             }
-            InputMode::Search => {
-                self.input_buffer.push(c);
+            let ranges = self.line_row_ranges(line);
+            if row < top + ranges.len() as u16 {
+                let row = (row - top) as usize;
+                let mut x = (column - area.x) as usize;
+                if tables::is_row(line) {
+                    x += self.table_scroll;
+                } else if let (true, Some((_, rest))) = (row > 0, self.wrap_widths()) {
+                    let mark = if self.line_numbers { 0 } else { 2 };
+                    x = x.saturating_sub(mark + wrap::hanging_indent(line, rest));
+                }
+                return Some(Cursor { line: index, col: wrap::char_at_column(line, ranges[row].clone(), x) });
             }
-            _ => {}
+            top += ranges.len() as u16;
         }
+        None
     }
 
-    fn submit_input(&mut self) {
-        match self.input_mode {
-            InputMode::PageJump => {
-                if let Ok(page_num) = self.input_buffer.parse::<usize>() {
-                    self.jump_to_page(page_num);
-                } else {
-                    self.status_message = "Invalid page number".to_string();
-                }
-                self.input_mode = InputMode::Normal;
-                self.input_buffer.clear();
-            }
-            InputMode::Search => {
-                self.execute_search();
-                self.input_mode = InputMode::Normal;
-                self.input_buffer.clear();
+    /// Put the cursor on the character under a click in the text, turning
+    /// cursor mode on.
+    fn click_text(&mut self, column: u16, row: u16) {
+        if let Some(position) = self.position_at(column, row) {
+            self.selection_anchor = None;
+            self.cursor = Some(position);
+            self.dragging = true;
+        }
+    }
+
+    /// Extend a selection from where the mouse went down to the character
+    /// under it, scrolling when dragged past the top or bottom of the text.
+    fn drag_text(&mut self, column: u16, row: u16) {
+        let Some(cursor) = self.cursor.filter(|_| self.dragging) else {
+            return;
+        };
+        let area = self.text_area;
+        let column = column.clamp(area.x, area.right().saturating_sub(1));
+        let row = if row < area.y {
+            self.scroll_up();
+            area.y
+        } else if row >= area.bottom() {
+            self.scroll_down();
+            area.bottom().saturating_sub(1)
+        } else {
+            row
+        };
+        // Past the last line of a short page the selection runs to its end
+        let lines = self.current_lines();
+        let position = self.position_at(column, row).unwrap_or_else(|| {
+            let line = self.visible_lines().end.saturating_sub(1);
+            Cursor { line, col: lines.get(line).map_or(0, |text| text.chars().count().saturating_sub(1)) }
+        });
+        if self.selection_anchor.is_none() {
+            if position == cursor {
+                return;
             }
-            _ => {}
+            self.selection_anchor = Some(cursor);
         }
+        self.cursor = Some(position);
     }
 
-    fn backspace(&mut self) {
-        self.input_buffer.pop();
+    /// Show where a link or footnote marker under the mouse leads in the
+    /// status line, and clear it again once the mouse moves off.
+    fn hover(&mut self, column: u16, row: u16) {
+        let target = self.position_at(column, row).and_then(|position| {
+            let text = self.pages.get(self.current_page)?;
+            let line = text.lines().nth(position.line)?;
+            let offset = cursor::byte_offset(line, position.col);
+            let footnotes = self.footnote_cache.get(self.current_page, text);
+            if let Some(marker) = footnotes.on_line(position.line).find(|marker| marker.range.contains(&offset)) {
+                let note = &footnotes.notes[marker.note];
+                return Some(format!("Footnote {}: {}", note.mark, note.text));
+            }
+            citations::link_at(line, offset).map(|url| format!("Link: {}", url))
+        });
+        if let Some(message) = &target {
+            self.status_message = message.clone();
+        } else if self.hover_status.as_ref() == Some(&self.status_message) {
+            // Only if nothing else has been said since
+            self.status_message.clear();
+        }
+        self.hover_status = target;
     }
-}
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+    /// Say what can be done with a selection made with the mouse.
+    fn end_drag(&mut self) {
+        if std::mem::take(&mut self.dragging)
+            && let Some(text) = self.selected_text()
+        {
+            self.status_message = format!(
+                "Selected {} characters: y copy, C clip, A notes, v/Esc cancel",
+                text.chars().count()
+            );
+        }
+    }
 
-    // Read and parse PDF
-    let pages = read_pdf(&args.file)?;
-    
-    if pages.is_empty() {
-        println!("PDF file is empty or could not be parsed.");
-        return Ok(());
+    fn toggle_visual(&mut self) {
+        if self.cursor.is_none() {
+            self.toggle_cursor();
+        }
+        if self.selection_anchor.take().is_none() {
+            self.selection_anchor = self.cursor;
+            self.status_message = "Visual mode: move to extend the selection, v/Esc to cancel".to_string();
+        } else {
+            self.status_message.clear();
+        }
     }
 
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    fn selected_text(&self) -> Option<String> {
+        let (anchor, cursor) = (self.selection_anchor?, self.cursor?);
+        Some(cursor::text_between(&self.current_lines(), anchor, cursor))
+    }
 
-    // Create app and run
-    let mut app = App::new(pages);
-    let res = run_app(&mut terminal, &mut app);
+    /// The visual selection, or the cursor's line when nothing is selected.
+    fn selection_or_line(&self) -> Option<String> {
+        self.selected_text().or_else(|| {
+            let cursor = self.cursor?;
+            self.current_lines().get(cursor.line).map(|line| line.to_string())
+        })
+    }
 
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    fn translate_selection(&mut self) {
+        let Some(text) = self.selection_or_line() else {
+            self.status_message = "Translation needs cursor mode (c) or a selection (v)".to_string();
+            return;
+        };
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
 
-    if let Err(err) = res {
-        println!("{err:?}");
+        let config = self.config.translation.clone();
+        let task_text = text.clone();
+        self.translation_task = Some((text, Task::spawn(move || translate::translate(&config, &task_text))));
+        self.status_message = "Translating…".to_string();
     }
 
-    Ok(())
-}
-
-fn read_pdf(path: &PathBuf) -> Result<Vec<String>> {
-    // Try pdf-extract first
-    match extract_text(path) {
-        Ok(text) => {
-            // Split text into pages based on form feed characters or heuristics
-            let pages = split_into_pages(&text);
-            Ok(pages)
+    fn toggle_speech(&mut self) {
+        if self.speech.take().is_some() {
+            self.status_message = "Stopped reading aloud".to_string();
+            return;
         }
-        Err(e) => {
-            // Fallback: try to read as plain text or return error
-            Err(anyhow::anyhow!("Could not extract text from PDF: {}. The PDF might be image-based or use unsupported encoding.", e))
+        let lines = self.current_lines();
+        match Speech::start(&self.config.tts.command, self.current_page, &lines) {
+            Ok(speech) => {
+                self.speech = Some(speech);
+                self.status_message = "Reading aloud: Space pause, [/] paragraph, S stop".to_string();
+            }
+            Err(e) => self.status_message = format!("Text-to-speech failed: {}", e),
         }
     }
-}
 
-fn split_into_pages(text: &str) -> Vec<String> {
-    // Try to split by form feed characters first
-    if text.contains('\x0C') {
-        return text.split('\x0C')
-            .map(|page| format_pdf_content(page))
-            .filter(|page| !page.trim().is_empty())
-            .collect();
-    }
-    
-    // If no form feed, split by estimated page breaks
-    let lines: Vec<&str> = text.lines().collect();
-    let mut pages = Vec::new();
-    let lines_per_page = 50; // Estimate
-    
-    for chunk in lines.chunks(lines_per_page) {
-        let page_content = chunk.join("\n");
-        let formatted = format_pdf_content(&page_content);
-        if !formatted.trim().is_empty() {
-            pages.push(formatted);
+    /// Handle playback keys while reading aloud. Returns false for other keys.
+    fn speech_key(&mut self, code: KeyCode) -> bool {
+        let Some(mut speech) = self.speech.take() else {
+            return false;
+        };
+        let lines = self.current_lines();
+        let result = match code {
+            KeyCode::Char(' ') => speech.toggle_pause(&lines),
+            KeyCode::Char(']') => speech.next_paragraph(&lines),
+            KeyCode::Char('[') => speech.prev_paragraph(&lines),
+            _ => {
+                self.speech = Some(speech);
+                return false;
+            }
+        };
+        if let Err(e) = result {
+            self.status_message = format!("Text-to-speech failed: {}", e);
+            return true;
         }
+        if let Some(range) = speech.current_lines() {
+            self.scroll_to_line(range.start);
+        }
+        self.status_message = if speech.paused { "Reading paused" } else { "Reading aloud" }.to_string();
+        self.speech = Some(speech);
+        true
     }
-    
-    if pages.is_empty() {
-        pages.push(format_pdf_content(text));
-    }
-    
-    pages
-}
 
-fn format_pdf_content(content: &str) -> String {
-    // Basic text processing to maintain some structure
-    content
-        .lines()
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty())
-        .collect::<Vec<_>>()
-        .join("\n")
-}
-
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
-    loop {
-        terminal.draw(|f| ui(f, app))?;
+    /// Background work run on every pass of the event loop.
+    fn tick(&mut self) {
+        // Prompts live on the status line too, so only expire messages in normal mode
+        self.messages.observe(&self.status_message);
+        // A timeout too large for a Duration, or not a number, keeps messages
+        if self.config.status.timeout > 0.0
+            && let Ok(ttl) = Duration::try_from_secs_f64(self.config.status.timeout)
+            && self.input_mode == InputMode::Normal
+            && self.messages.expired(ttl)
+        {
+            self.status_message.clear();
+            self.messages.observe("");
+        }
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match app.input_mode {
-                    InputMode::Normal => {
-                        match key.code {
-                            KeyCode::Char('q') => app.quit(),
-                            KeyCode::Esc => {
-                                if !app.search_query.is_empty() {
-                                    app.clear_search();
-                                } else {
-                                    app.quit();
-                                }
-                            },
-                            KeyCode::Right | KeyCode::Char('n') => app.next_page(),
-                            KeyCode::Left | KeyCode::Char('p') => app.prev_page(),
-                            KeyCode::Down | KeyCode::Char('j') => app.scroll_down(),
-                            KeyCode::Up | KeyCode::Char('k') => app.scroll_up(),
-                            KeyCode::Char('g') => app.start_page_jump(),
-                            KeyCode::Char('/') => app.start_search(),
-                            KeyCode::Char('F') => app.next_search_result(),
-                            KeyCode::Char('B') => app.prev_search_result(),
-                            KeyCode::Home => {
-                                app.current_page = 0;
-                                app.scroll_offset = 0;
-                            },
-                            KeyCode::End => {
-                                app.current_page = app.pages.len().saturating_sub(1);
-                                app.scroll_offset = 0;
-                            },
-                            _ => {}
-                        }
-                    }
-                    InputMode::PageJump | InputMode::Search => {
-                        match key.code {
-                            KeyCode::Enter => app.submit_input(),
-                            KeyCode::Esc => app.cancel_input(),
-                            KeyCode::Backspace => app.backspace(),
-                            KeyCode::Char(c) => app.handle_input(c),
-                            _ => {}
-                        }
-                    }
+        self.receive_pages();
+        let mut pinned = self.pages_in_use();
+        pinned.extend([self.current_page.saturating_sub(1), self.current_page + 1]);
+        match self.budget.enforce(&mut self.pages, &pinned) {
+            Ok(true) => self.forget_page_caches(),
+            Ok(false) => {}
+            Err(e) => self.status_message = e.to_string(),
+        }
+        if let Some(result) = self.summary_task.as_ref().and_then(Task::poll) {
+            self.summary_task = None;
+            let text = result.unwrap_or_else(|e| format!("Summarization failed: {}", e));
+            if let Some(panel) = self.side_panel.as_mut().filter(|panel| panel.title.starts_with("Summary")) {
+                panel.text = text;
+                panel.scroll = 0;
+            }
+            self.status_message = "Summary ready (Tab to focus the panel)".to_string();
+        }
+        if let Some((_, task)) = &self.translation_task
+            && let Some(result) = task.poll()
+        {
+            let (text, _) = self.translation_task.take().expect("checked above");
+            match result {
+                Ok(translation) => {
+                    let title = format!("Translation → {}", self.config.translation.target);
+                    self.popup = Some(Popup::new(title, format!("{}\n\n{}", text, translation.trim())));
+                    self.status_message.clear();
                 }
+                Err(e) => self.status_message = format!("Translation failed: {}", e),
             }
         }
-
-        if app.should_quit {
-            break;
+        if let Some((_, task)) = &self.definition_task
+            && let Some(result) = task.poll()
+        {
+            let (word, _) = self.definition_task.take().expect("checked above");
+            self.status_message.clear();
+            match result {
+                Ok(definition) if !definition.trim().is_empty() => {
+                    self.popup = Some(Popup::new(format!("Definition: {}", word), definition));
+                }
+                Ok(_) => self.status_message = format!("No definition found for '{}'", word),
+                Err(e) => self.status_message = format!("Dictionary lookup failed: {}", e),
+            }
         }
-    }
-    Ok(())
-}
-
-fn ui(f: &mut Frame, app: &App) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3), 
-            Constraint::Min(1), 
-            Constraint::Length(3),
-            Constraint::Length(if app.input_mode != InputMode::Normal || !app.status_message.is_empty() { 3 } else { 0 })
-        ])
-        .split(f.size());
-
-    // Header
-    let header_text = if app.input_mode != InputMode::Normal {
-        match app.input_mode {
-            InputMode::PageJump => format!("Enter page number (1-{}): {}", app.pages.len(), app.input_buffer),
-            InputMode::Search => format!("Search: {}", app.input_buffer),
-            _ => format!("PDF Reader - Page {} of {}", app.current_page + 1, app.pages.len()),
+        if let Some((_, task)) = &self.print_task
+            && let Some(result) = task.poll()
+        {
+            let (range, _) = self.print_task.take().expect("checked above");
+            self.status_message = match result {
+                Ok(_) => format!("Sent pages {}-{} to '{}'", range.start(), range.end(), self.config.print.command),
+                Err(e) => format!("Printing failed: {}", e),
+            };
         }
-    } else {
-        format!("PDF Reader - Page {} of {}", app.current_page + 1, app.pages.len())
-    };
-    
+        if let Some(result) = self.chat_task.as_ref().and_then(Task::poll) {
+            self.chat_task = None;
+            if let Some(turn) = self.chat.last_mut() {
+                turn.answer = Some(result.unwrap_or_else(|e| format!("(failed: {})", e)));
+            }
+            self.show_chat();
+            self.status_message.clear();
+        }
+        if let Some((page, _, task)) = &self.ocr_task
+            && let Some(result) = task.poll()
+        {
+            let page = *page;
+            self.ocr_task = None;
+            match result {
+                Ok(text) => {
+                    self.apply_ocr(page, &text);
+                    self.status_message = format!("OCR'd page {}", page + 1);
+                }
+                Err(e) => self.status_message = format!("OCR failed: {}", e),
+            }
+        }
+        if let Some((page, task)) = &self.images_task
+            && let Some(result) = task.poll()
+        {
+            let page = *page;
+            self.images_task = None;
+            match result {
+                Ok(images) => self.list_images(page, images),
+                Err(e) => self.status_message = format!("Could not read images: {}", e),
+            }
+        }
+        if self.reported_page != self.current_page {
+            self.reported_page = self.current_page;
+            self.run_shell_hook(Hook::PageChange);
+            let page = self.current_page as i64 + 1;
+            self.run_plugin(|plugins| plugins.hook("on_page_change", vec![page.into()]));
+        }
+        self.shell_hooks.reap();
+        if let Some(thumbnails) = self.thumbnail_grid.as_mut().and_then(|grid| grid.thumbnails.as_mut()) {
+            thumbnails.poll();
+            if let Some(error) = thumbnails.failed.take() {
+                self.status_message = format!("Thumbnails: {}", error);
+            }
+        }
+        if let Some(view) = self.page_view.as_mut() {
+            view.poll();
+            if let Some(error) = view.failed.take() {
+                self.status_message = format!("Page image: {}", error);
+            }
+        }
+        if let Some(task) = &self.index_task
+            && let Some(result) = task.poll()
+        {
+            self.index_task = None;
+            self.status_message = match result {
+                Ok((indexed, failed)) if failed.is_empty() => format!("Indexed {} documents", indexed),
+                Ok((indexed, failed)) => format!("Indexed {} documents; could not read {}", indexed, failed.join(", ")),
+                Err(e) => format!("Indexing failed: {}", e),
+            };
+        }
+        if let Some((_, _, task)) = &self.corpus_task
+            && let Some(result) = task.poll()
+        {
+            let (query, mut hits, _) = self.corpus_task.take().expect("checked above");
+            match result {
+                Ok((others, failed)) => {
+                    hits.extend(others);
+                    self.show_file_hits(query, hits, &failed);
+                }
+                Err(e) => self.status_message = format!("Search failed: {}", e),
+            }
+        }
+        if let Some(spelling) = self.spelling.as_mut()
+            && let Err(e) = spelling.check(self.current_page, &self.pages[self.current_page])
+        {
+            self.spelling = None;
+            self.status_message = format!("Spell check failed: {}", e);
+        }
+        let requests = self.server.as_ref().map(Server::poll).unwrap_or_default();
+        for request in requests {
+            let result = RemoteCommand::parse(&request.line).and_then(|command| self.remote_command(command));
+            request.reply(result);
+        }
+
+        if let Some(mut speech) = self.speech.take() {
+            if speech.page() != self.current_page {
+                self.status_message = "Stopped reading aloud".to_string();
+                return;
+            }
+            let lines = self.current_lines();
+            let before = speech.current_lines();
+            match speech.poll(&lines) {
+                Ok(true) => {
+                    // Follow the reader only when it moves on, so manual scrolling still works
+                    if let Some(range) = speech.current_lines().filter(|range| Some(range) != before.as_ref()) {
+                        self.scroll_to_line(range.start);
+                    }
+                    self.speech = Some(speech);
+                }
+                Ok(false) => self.status_message = "Finished reading page".to_string(),
+                Err(e) => self.status_message = format!("Text-to-speech failed: {}", e),
+            }
+        }
+    }
+
+    /// Append the visual selection to the clippings file.
+    fn clip_selection(&mut self) {
+        let Some(text) = self.selected_text() else {
+            self.status_message = "Select text to clip with v first".to_string();
+            return;
+        };
+        self.registers.remember(&text, self.current_page);
+        if self.clip_text(&text, self.current_page, self.passage_line()) {
+            self.selection_anchor = None;
+        }
+    }
+
+    /// A `pdfreader://` link to 0-based `page` and `line` of this document.
+    fn link(&self, page: usize, line: Option<usize>) -> String {
+        link::uri(&self.path, page + 1, line.map(|line| line + 1))
+    }
+
+    /// The 0-based line a selection starts on, or else the cursor's line.
+    fn passage_line(&self) -> Option<usize> {
+        let cursor = self.cursor?;
+        Some(self.selection_anchor.map_or(cursor.line, |anchor| anchor.line.min(cursor.line)))
+    }
+
+    /// Send the selection, or the cursor line, to `notes.file` with a link to its page.
+    fn send_to_notes(&mut self) {
+        if self.config.notes.file.as_os_str().is_empty() {
+            self.status_message = "No notes file (set notes.file in the config)".to_string();
+            return;
+        }
+        let Some(text) = self.selection_or_line() else {
+            self.status_message = "Select text with v, or put the cursor on a line (c), to send to notes".to_string();
+            return;
+        };
+        self.registers.remember(&text, self.current_page);
+        let path = config::expand_tilde(&self.config.notes.file);
+        let pdf = fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone());
+        let link = self.link(self.current_page, self.passage_line());
+        self.status_message = match clip::append_note(&path, &text, &pdf, self.current_page + 1, &link) {
+            Ok(()) => {
+                self.selection_anchor = None;
+                format!("Sent to notes in {}", path.display())
+            }
+            Err(e) => format!("Could not write the note: {}", e),
+        };
+    }
+
+    /// Append `text` from 0-based `page` (and `line`) to the clippings file.
+    /// Returns whether it was written.
+    fn clip_text(&mut self, text: &str, page: usize, line: Option<usize>) -> bool {
+        let path = config::expand_tilde(&self.config.clippings.file);
+        let format = self.config.clippings.format;
+        let link = self.link(page, line);
+        let written = clip::append(&path, format, text, &self.file_name(), page + 1, &link);
+        self.status_message = match &written {
+            Ok(()) => format!("Clipped to {}", path.display()),
+            Err(e) => format!("Clip failed: {}", e),
+        };
+        written.is_ok()
+    }
+
+    fn word_under_cursor(&self) -> Option<String> {
+        let cursor = self.cursor?;
+        cursor.word(&self.current_lines()).map(str::to_string)
+    }
+
+    fn lookup_word(&mut self) {
+        if self.cursor.is_none() {
+            self.status_message = "Dictionary lookup needs cursor mode (c)".to_string();
+            return;
+        }
+        let Some(word) = self.word_under_cursor() else {
+            self.status_message = "No word under cursor".to_string();
+            return;
+        };
+
+        let lang = self.language.map_or("en", Language::code);
+        let command = self.config.dictionary.command.clone();
+        let task_word = word.clone();
+        self.status_message = format!("Looking up '{}'…", word);
+        self.definition_task = Some((
+            word,
+            Task::spawn(move || external::run(&command, &[("word", &task_word), ("lang", lang)], None)),
+        ));
+    }
+
+    fn total_words(&self) -> usize {
+        self.word_counts.iter().sum()
+    }
+
+    /// Words left from the top of the current view to the end of the document.
+    fn remaining_words(&self) -> usize {
+        let current = self.pages.get(self.current_page).map_or(0, |page| {
+            page.lines()
+                .skip(self.scroll_offset)
+                .map(count_words)
+                .sum()
+        });
+        let after: usize = self.word_counts.iter().skip(self.current_page + 1).sum();
+        current + after
+    }
+
+    fn reading_status(&self) -> String {
+        let minutes = self.remaining_words().div_ceil(self.config.reading.wpm.max(1) as usize);
+        format!(
+            "{} words on page, {} total | ~{} min remaining",
+            self.word_counts.get(self.current_page).copied().unwrap_or(0),
+            self.total_words(),
+            minutes
+        )
+    }
+
+    /// Position in the whole document from 0 to 1, by words read so far.
+    fn document_progress(&self) -> f64 {
+        let total = self.total_words();
+        if total == 0 {
+            return self.current_page as f64 / self.pages.len().max(1) as f64;
+        }
+        let lines = self.current_lines().len().max(1);
+        let page_words = self.word_counts.get(self.current_page).copied().unwrap_or(0);
+        let before: usize = self.word_counts[..self.current_page].iter().sum();
+        let within = page_words as f64 * (self.scroll_offset as f64 / lines as f64).min(1.0);
+        ((before as f64 + within) / total as f64).clamp(0.0, 1.0)
+    }
+
+    /// Jump to the page under a click on the progress bar.
+    fn click_progress(&mut self, column: u16) {
+        let area = self.progress_area;
+        if area.width == 0 {
+            return;
+        }
+        let fraction = (column.saturating_sub(area.x)) as f64 / area.width as f64;
+        let page = ((fraction * self.pages.len() as f64) as usize).min(self.pages.len() - 1);
+        self.jump_to_page(page + 1);
+    }
+
+    /// Scroll so the lines under a click on the minimap are in the middle of the view.
+    fn click_minimap(&mut self, row: u16) {
+        let lines = self.current_lines().len();
+        let rows = self.minimap_area.height as usize;
+        let Some(range) = minimap::row_lines(lines, rows).get(row.saturating_sub(self.minimap_area.y) as usize).cloned() else {
+            return;
+        };
+        self.scroll_offset = self.offset_above(range.start, self.viewport_height / 2);
+        self.reset_cursor();
+    }
+
+    /// Fill a `layout.header`/`layout.footer` template from the current view.
+    fn fill_layout_template(&self, template: &str, controls: &str) -> String {
+        let pages = self.pages.len().max(1);
+        let search_status = match self.search_results.len() {
+            0 if self.search_query.is_empty() => String::new(),
+            0 => format!("no results for '{}'", self.search_query),
+            n => format!("{}/{} '{}'", self.current_search_result + 1, n, self.search_query),
+        };
+        let minutes = self.remaining_words().div_ceil(self.config.reading.wpm.max(1) as usize);
+        llm::fill_template(
+            template,
+            &[
+                ("title", self.title.as_deref().unwrap_or(&self.file_name())),
+                ("file", &self.file_name()),
+                ("page", &(self.current_page + 1).to_string()),
+                ("pages", &self.page_count_label()),
+                ("percent", &((self.current_page + 1) * 100 / pages).to_string()),
+                ("words", &self.word_counts.get(self.current_page).copied().unwrap_or(0).to_string()),
+                ("total_words", &self.total_words().to_string()),
+                ("minutes_left", &minutes.to_string()),
+                ("search_status", &search_status),
+                ("tags", &self.page_tags(self.current_page).join(", ")),
+                ("recording", &self.macros.recording().map_or_else(String::new, |r| format!("recording @{}", r))),
+                ("controls", controls),
+            ],
+        )
+    }
+
+    fn quit(&mut self) {
+        self.should_quit = true;
+    }
+
+    /// The page count for display: pages extracted so far and a `+` while more are coming.
+    fn page_count_label(&self) -> String {
+        match self.loading {
+            Some(_) => format!("{}+", self.pages.len()),
+            None => self.pages.len().to_string(),
+        }
+    }
+
+    /// Take over background extraction of the pages after the first ones.
+    fn start_loading(&mut self, stream: Option<PageStream>) {
+        self.pending_position = None;
+        (self.page_total, self.loading) = match stream {
+            Some((total, stream)) => (total, Some(stream)),
+            None => (self.pages.len(), None),
+        };
+    }
+
+    /// Append pages extracted since the last tick, and go to a position that was waiting for its page.
+    fn receive_pages(&mut self) {
+        let Some(stream) = &self.loading else {
+            return;
+        };
+        let (pages, done) = stream.poll();
+        for (text, scanned) in pages {
+            let page = self.pages.len();
+            self.word_counts.push(count_words(&text));
+            self.pages.push(text);
+            self.scanned.push(scanned);
+            if scanned && let Some(text) = ocr::cached(&self.path, page, &self.ocr_lang()) {
+                self.apply_ocr(page, &text);
+            }
+        }
+        if let Some(position) = self.pending_position.filter(|position| position.page < self.pages.len()) {
+            self.pending_position = None;
+            self.set_page(position.page);
+            self.scroll_offset = position.line;
+            self.reset_cursor();
+        }
+        if done {
+            self.loading = None;
+            self.page_total = self.pages.len();
+            // Pages moved out while the rest came in are read from their files
+            self.language = language::detect((0..self.pages.len()).map_while(|page| self.budget.read(&self.pages, page).ok()));
+            if !self.plugins.is_empty() {
+                let pages: Vec<String> = (0..self.pages.len())
+                    .map_while(|page| self.budget.read(&self.pages, page).ok().map(Cow::into_owned))
+                    .collect();
+                self.plugins.set_document(&self.file_name(), &pages);
+            }
+            if let Some(position) = self.pending_position.take() {
+                self.status_message = format!("Page {} does not exist", position.page + 1);
+            }
+            match self.pending_search.take() {
+                Some(query) => self.search(query),
+                // Searches made while pages were coming only saw some of them
+                None => self.refresh_search(),
+            }
+        }
+    }
+
+    /// Open at the first line containing `query`, searching once the whole
+    /// document has been extracted.
+    fn search_on_open(&mut self, query: String) {
+        if self.loading.is_some() {
+            self.status_message = format!("Searching for '{}' once every page is read", query);
+            self.pending_search = Some(query);
+        } else {
+            self.search(query);
+        }
+    }
+
+    /// Read back pages moved out under the memory budget before working on
+    /// them; false, with the error shown, when that fails.
+    fn load_pages(&mut self, range: Range<usize>) -> bool {
+        match self.budget.load(&mut self.pages, range) {
+            Ok(loaded) => {
+                if loaded {
+                    self.forget_page_caches();
+                }
+                true
+            }
+            Err(e) => {
+                self.status_message = e.to_string();
+                false
+            }
+        }
+    }
+
+    /// Run `scan` over the text of the pages in `range`, in order, reading
+    /// pages moved out under the memory budget back one at a time instead of
+    /// bringing them all into memory; None, with the error shown, when one
+    /// cannot be read.
+    fn scan_pages<T>(&mut self, range: Range<usize>, scan: impl FnOnce(&mut dyn Iterator<Item = Cow<'_, str>>) -> T) -> Option<T> {
+        let mut error = None;
+        let mut texts = range.map_while(|page| self.budget.read(&self.pages, page).map_err(|e| error = Some(e)).ok());
+        let value = scan(&mut texts);
+        match error {
+            Some(e) => {
+                self.status_message = e.to_string();
+                None
+            }
+            None => Some(value),
+        }
+    }
+
+    /// Show a 0-based page, reading its text back first if it was moved out.
+    fn set_page(&mut self, page: usize) {
+        self.current_page = page;
+        match self.budget.touch(&mut self.pages, page) {
+            Ok(true) => self.forget_page_caches(),
+            Ok(false) => {}
+            Err(e) => self.status_message = e.to_string(),
+        }
+    }
+
+    /// Pages whose text is on screen: the current one, the one in the
+    /// result preview and those in the thumbnail grid's cells.
+    fn pages_in_use(&self) -> Vec<usize> {
+        let mut pages = vec![self.current_page];
+        pages.extend(self.result_preview.and_then(|index| self.search_results.get(index)).map(|result| result.page));
+        if let Some(grid) = &self.thumbnail_grid {
+            pages.extend(grid.cells.iter().map(|(page, _)| *page));
+        }
+        pages
+    }
+
+    /// Read back the pages about to be drawn that were moved out since the
+    /// last frame, counting the current page as viewed.
+    fn load_pages_in_use(&mut self) {
+        self.set_page(self.current_page);
+        for page in self.pages_in_use() {
+            self.load_pages(page..page + 1);
+        }
+    }
+
+    /// Drop what was worked out from the current page's text, after it changed
+    /// or came back from disk.
+    fn forget_page_caches(&mut self) {
+        self.match_cache.clear();
+        self.footnote_cache.clear();
+        self.code_cache.clear();
+    }
+
+    /// Go to a 0-based page and line, or wait for the page if it is still being extracted.
+    fn go_to_position(&mut self, page: usize, line: usize) {
+        if page < self.pages.len() {
+            self.set_page(page);
+            self.scroll_offset = line;
+            self.reset_cursor();
+        } else {
+            self.pending_position = Some(Position { page, line });
+        }
+    }
+
+    fn jump_to_page(&mut self, page_num: usize) {
+        if page_num > self.pages.len() && page_num <= self.page_total {
+            self.go_to_position(page_num - 1, 0);
+            self.status_message = format!("Page {} is still being read; going there once it is", page_num);
+        } else if page_num > 0 && page_num <= self.pages.len() {
+            self.set_page(page_num - 1);
+            self.scroll_offset = 0;
+            self.reset_cursor();
+            self.status_message = match self.page_labels.get(page_num - 1).filter(|label| **label != page_num.to_string()) {
+                Some(label) => format!("Jumped to page {} ({})", page_num, label),
+                None => format!("Jumped to page {}", page_num),
+            };
+        } else {
+            self.status_message = format!("Invalid page number: {}", page_num);
+            self.bell();
+        }
+    }
+
+    /// The 0-based page the page-jump input leads to: a page number, `+N` or
+    /// `-N` pages from here, or a page label.
+    fn page_jump_target(&self, input: &str) -> Result<usize, String> {
+        let page = if let Some(count) = input.strip_prefix('+') {
+            count.parse::<usize>().ok().map(|count| self.current_page + 1 + count)
+        } else if let Some(count) = input.strip_prefix('-') {
+            count.parse::<usize>().ok().map(|count| (self.current_page + 1).saturating_sub(count))
+        } else if !input.is_empty() && input.bytes().all(|b| b.is_ascii_digit()) {
+            input.parse::<usize>().ok()
+        } else {
+            return self
+                .page_labels
+                .iter()
+                .position(|label| label.eq_ignore_ascii_case(input))
+                .ok_or_else(|| format!("No page number or label '{}'", input));
+        };
+        match page {
+            Some(page) if (1..=self.page_total).contains(&page) => Ok(page - 1),
+            Some(page) => Err(format!("No page {} (1-{})", page, self.page_total)),
+            None => Err(format!("Not a page: '{}'", input)),
+        }
+    }
+
+    /// Whether typing more could still turn `input` into a page to jump to,
+    /// so a keystroke that cannot is refused.
+    fn page_jump_possible(&self, input: &str) -> bool {
+        if matches!(input, "+" | "-") || self.page_jump_target(input).is_ok() {
+            return true;
+        }
+        let input = input.to_lowercase();
+        self.page_labels.iter().any(|label| label.to_lowercase().starts_with(&input))
+    }
+
+    /// What the page-jump input leads to, when that is not plain from it.
+    fn page_jump_hint(&self) -> String {
+        let input = self.input.text();
+        let Ok(page) = self.page_jump_target(input) else {
+            return String::new();
+        };
+        let label = self.page_labels.get(page).filter(|label| **label != (page + 1).to_string());
+        match label {
+            Some(label) => format!("  → page {} ({})", page + 1, label),
+            None if input != (page + 1).to_string() => format!("  → page {}", page + 1),
+            None => String::new(),
+        }
+    }
+
+    fn start_page_jump(&mut self) {
+        self.input_mode = InputMode::PageJump;
+        self.input.clear();
+        self.status_message = "Enter page number:".to_string();
+    }
+
+    fn start_search(&mut self) {
+        self.input_mode = InputMode::Search;
+        self.input.clear();
+        self.status_message = "Enter search term (Tab: this document / all PDFs in its folder):".to_string();
+    }
+
+    /// Ctrl+Shift+letter and Ctrl+I, which only the kitty keyboard protocol
+    /// tells apart from the plain letter and Tab. Returns whether `key` was one.
+    fn chord(&mut self, key: KeyEvent) -> bool {
+        let KeyCode::Char(c) = key.code else {
+            return false;
+        };
+        if key.modifiers == KeyModifiers::CONTROL && c == 'i' {
+            self.show_info();
+            return true;
+        }
+        if key.modifiers != KeyModifiers::CONTROL | KeyModifiers::SHIFT {
+            return false;
+        }
+        match c {
+            'z' => self.redo(),
+            'f' => {
+                self.search_all = true;
+                self.start_search();
+            }
+            'd' => self.show_documents(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn start_find(&mut self) {
+        self.input_mode = InputMode::Find;
+        self.input.clear();
+        self.status_message = "Find on this page (n/N: next/previous match, Esc: stop):".to_string();
+    }
+
+    /// Find `query` on the current page only, starting from the top of the view.
+    fn find_on_page(&mut self, query: &str) {
+        self.page_find = None;
+        if query.is_empty() || !self.load_pages(self.current_page..self.current_page + 1) {
+            return;
+        }
+        let Some(text) = self.pages.get(self.current_page) else {
+            return;
+        };
+        let find = PageFind::new(self.current_page, text, query, self.scroll_offset, |line| self.hidden(line));
+        if find.matches.is_empty() {
+            self.status_message = format!("No match for '{}' on this page", query);
+            self.bell();
+            return;
+        }
+        self.page_find = Some(find);
+        self.show_find_match();
+    }
+
+    /// Whether a page find is on for the page being shown.
+    fn finding(&self) -> bool {
+        self.page_find.as_ref().is_some_and(|find| find.page == self.current_page)
+    }
+
+    fn step_find(&mut self, forward: bool) {
+        if let Some(find) = self.page_find.as_mut() {
+            find.step(forward);
+        }
+        self.show_find_match();
+    }
+
+    fn show_find_match(&mut self) {
+        let Some(find) = self.page_find.as_ref() else {
+            return;
+        };
+        let Some((line, range)) = find.current().cloned() else {
+            return;
+        };
+        self.status_message = format!("Match {} of {} for '{}' on this page", find.current + 1, find.matches.len(), find.query);
+        if self.cursor.is_some() {
+            let col = self.current_lines().get(line).map_or(0, |text| text[..range.start].chars().count());
+            self.cursor = Some(Cursor { line, col });
+        }
+        self.scroll_to_line(line);
+    }
+
+    fn start_command(&mut self) {
+        self.input_mode = InputMode::Command;
+        self.input.clear();
+        self.status_message.clear();
+    }
+
+    fn execute_command(&mut self) {
+        let line = self.input.text().to_string();
+        self.run_command(&line);
+    }
+
+    fn run_command(&mut self, line: &str) {
+        let (name, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        if self.plugins.has_command(name) {
+            self.run_plugin(|plugins| plugins.run_command(name, args));
+        } else {
+            self.run_builtin_command(line);
+        }
+    }
+
+    fn run_builtin_command(&mut self, line: &str) {
+        match command::parse(line) {
+            Ok(Command::Summarize(scope)) => self.summarize(scope),
+            Ok(Command::Ask(question)) => self.ask(question),
+            Ok(Command::Chat) => self.show_chat(),
+            Ok(Command::Citations) => self.show_citations(),
+            Ok(Command::ExportOrg(path)) => self.export_org(&path),
+            Ok(Command::ExportSection(path)) => self.export_section(&path),
+            Ok(Command::Print(range)) => self.confirm_print(range),
+            Ok(Command::EditSource) => self.edit_source(),
+            Ok(Command::Filter(filter)) => self.filter(&filter),
+            Ok(Command::Messages) => self.show_messages(),
+            Ok(Command::Images) => self.show_images(),
+            Ok(Command::SaveImage(path)) => self.save_image(path.as_deref()),
+            Ok(Command::Info) => self.show_info(),
+            Ok(Command::Spell) => self.toggle_spelling(),
+            Ok(Command::Freq(chapter)) => self.show_frequent_terms(chapter),
+            Ok(Command::Attachments) => self.show_attachments(),
+            Ok(Command::Documents) => self.show_documents(),
+            Ok(Command::Tag(tags)) => self.tag_page(tags),
+            Ok(Command::Untag(tags)) => self.untag_page(&tags),
+            Ok(Command::Tags) => self.show_tags(),
+            Ok(Command::Registers) => self.show_registers(),
+            Ok(Command::Clips) => self.show_clips(),
+            Ok(Command::Raw) => self.show_raw_text(),
+            Ok(Command::Indent) => self.toggle_indent(),
+            Ok(Command::Math) => self.toggle_math(),
+            Ok(Command::Excluded) => self.toggle_excluded(),
+            Ok(Command::Reload) => self.reload(),
+            Ok(Command::Wrap) => {
+                self.wrap = !self.wrap;
+                self.status_message = if self.wrap { "Wrapping long lines" } else { "Cutting long lines at the pane" }.to_string();
+            }
+            Ok(Command::LineNumbers) => {
+                self.line_numbers = !self.line_numbers;
+                self.status_message = if self.line_numbers { "Line numbers shown" } else { "Line numbers hidden" }.to_string();
+            }
+            Ok(Command::Display(name)) => {
+                let filter = match name.as_deref() {
+                    None => Some(self.display_filter.next()),
+                    Some(name) => display::Filter::parse(name),
+                };
+                match filter {
+                    Some(filter) => {
+                        self.display_filter = filter;
+                        self.status_message = format!("Display filter: {}", filter.name());
+                    }
+                    None => self.status_message = format!("Unknown display filter: {} (none, sepia, night, gray)", name.unwrap_or_default()),
+                }
+            }
+            Ok(Command::Minimap) => {
+                self.minimap = !self.minimap;
+                self.status_message = if self.minimap { "Minimap shown" } else { "Minimap hidden" }.to_string();
+            }
+            Ok(Command::Layout) => {
+                if self.layout_view.take().is_none() {
+                    // Laid out once the pane's width is known
+                    self.layout_view = Some(LayoutView {
+                        page: self.current_page,
+                        columns: 0,
+                        align_amounts: self.config.layout.align_amounts,
+                        rows: Vec::new(),
+                        scroll: 0,
+                    });
+                }
+            }
+            Ok(Command::PageImage) => self.toggle_page_view(),
+            Ok(Command::LargePrint) => {
+                self.large_print = !self.large_print;
+                self.status_message = if self.large_print { "Large print shown" } else { "Large print hidden" }.to_string();
+            }
+            Ok(Command::FilterTag(tag)) => self.set_tag_filter(tag),
+            Ok(Command::SaveAttachment(which, dir)) => self.save_attachment(&which, dir.as_deref()),
+            Err(message) => self.status_message = message,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map_or_else(|| self.path.display().to_string(), |name| name.to_string_lossy().into_owned())
+    }
+
+    /// Send the current page, or a 1-based page range, to the LLM for a summary.
+    fn summarize(&mut self, scope: SummaryScope) {
+        let (range, chapter) = match scope {
+            SummaryScope::Page => (self.current_page + 1..=self.current_page + 1, None),
+            SummaryScope::Pages(range) => (range, None),
+            SummaryScope::Chapter => match self.current_chapter() {
+                Some((pages, title)) => (pages.start + 1..=pages.end, Some(title)),
+                None => {
+                    self.status_message = "No outline to find the chapter in".to_string();
+                    return;
+                }
+            },
+        };
+        if *range.end() > self.pages.len() {
+            self.status_message = format!("Page range {}-{} is past the last page", range.start(), range.end());
+            return;
+        }
+        if !self.load_pages(range.start() - 1..*range.end()) {
+            return;
+        }
+
+        let text = self.pages[range.start() - 1..*range.end()].join("\n\n");
+        let pages = if range.start() == range.end() {
+            range.start().to_string()
+        } else {
+            format!("{}-{}", range.start(), range.end())
+        };
+        let prompt = llm::fill_template(
+            &self.config.llm.summarize_prompt,
+            &[("file", &self.file_name()), ("pages", &pages), ("text", &text)],
+        );
+
+        let what = match chapter {
+            Some(title) => format!("chapter \"{}\" (pages {})", title, pages),
+            None => format!("pages {}", pages),
+        };
+        let config = self.config.llm.clone();
+        self.summary_task = Some(Task::spawn(move || llm::complete(&config, &prompt)));
+        self.side_panel = Some(Popup::new(format!("Summary: {}", what), "Summarizing..."));
+        self.status_message = format!("Summarizing {}...", what);
+    }
+
+    /// Ask the LLM a question, sending the most relevant pages as context.
+    fn ask(&mut self, question: String) {
+        if self.chat_task.is_some() {
+            self.status_message = "Still waiting for the previous answer".to_string();
+            return;
+        }
+
+        let context_pages = self.config.llm.context_pages;
+        let Some(pages) = self.scan_pages(0..self.pages.len(), |texts| retrieve::relevant_pages(texts, &question, context_pages)) else {
+            return;
+        };
+        let mut excerpts = Vec::new();
+        for &page in &pages {
+            match self.budget.read(&self.pages, page) {
+                Ok(text) => excerpts.push(format!("[Page {}]\n{}", page + 1, text)),
+                Err(e) => {
+                    self.status_message = e.to_string();
+                    return;
+                }
+            }
+        }
+        let context = excerpts.join("\n\n");
+        let prompt = llm::fill_template(
+            &self.config.llm.ask_prompt,
+            &[("file", &self.file_name()), ("context", &context), ("question", &question)],
+        );
+
+        // Earlier turns are sent without their excerpts to keep the request small
+        let mut messages = Vec::new();
+        for turn in &self.chat {
+            if let Some(answer) = &turn.answer {
+                messages.push(("user".to_string(), turn.question.clone()));
+                messages.push(("assistant".to_string(), answer.clone()));
+            }
+        }
+        messages.push(("user".to_string(), prompt));
+
+        let config = self.config.llm.clone();
+        self.chat_task = Some(Task::spawn(move || llm::chat(&config, &messages)));
+        self.chat.push(ChatTurn {
+            question,
+            answer: None,
+            pages: pages.iter().map(|page| page + 1).collect(),
+        });
+        self.show_chat();
+        self.status_message = "Asking...".to_string();
+    }
+
+    fn show_chat(&mut self) {
+        let mut text = String::new();
+        for turn in &self.chat {
+            text.push_str(&format!("Q: {}\n", turn.question));
+            match &turn.answer {
+                Some(answer) => text.push_str(&format!("A: {}\n", answer)),
+                None => text.push_str("A: ...\n"),
+            }
+            if !turn.pages.is_empty() {
+                let pages: Vec<String> = turn.pages.iter().map(usize::to_string).collect();
+                text.push_str(&format!("(context: pages {})\n", pages.join(", ")));
+            }
+            text.push('\n');
+        }
+        if text.is_empty() {
+            text = "Ask a question with :ask <question>".to_string();
+        }
+
+        // Keep the latest exchange in view
+        let scroll = text.lines().count().saturating_sub(self.viewport_height.max(1));
+        let mut panel = Popup::new("Chat", text);
+        panel.scroll = scroll;
+        self.side_panel = Some(panel);
+    }
+
+    fn export_org(&mut self, path: &Path) {
+        // Annotations are only needed here, so read them on demand
+        let annotations = pdf::load(&self.path)
+            .map(|doc| pdf::annotations(&doc))
+            .unwrap_or_default();
+        let file_name = self.file_name();
+        let source = export::ExportSource {
+            path: &self.path,
+            file_name: &file_name,
+            pages: &self.pages,
+            outline: &self.outline,
+            annotations: &annotations,
+            language: self.language,
+        };
+
+        let org = match export::org(&source, |page| self.budget.read(&self.pages, page)) {
+            Ok(org) => org,
+            Err(e) => {
+                self.status_message = e.to_string();
+                return;
+            }
+        };
+
+        let path = config::expand_tilde(path);
+        self.status_message = match fs::write(&path, org) {
+            Ok(()) => format!("Exported {} pages to {}", self.pages.len(), path.display()),
+            Err(e) => format!("Could not write {}: {}", path.display(), e),
+        };
+    }
+
+    /// Ask before sending anything to the printer.
+    fn confirm_print(&mut self, range: Option<RangeInclusive<usize>>) {
+        let range = range.unwrap_or(self.current_page + 1..=self.current_page + 1);
+        if *range.end() > self.pages.len() {
+            self.status_message = format!("Page range {}-{} is past the last page", range.start(), range.end());
+            return;
+        }
+        let count = range.end() - range.start() + 1;
+        self.status_message = format!(
+            "Print {} page{} with '{}'? (y/n)",
+            count,
+            if count == 1 { "" } else { "s" },
+            self.config.print.command
+        );
+        self.pending_action = Some(PendingAction::Print(range));
+        self.input_mode = InputMode::Confirm;
+    }
+
+    fn answer_confirm(&mut self, yes: bool) {
+        self.input_mode = InputMode::Normal;
+        let Some(action) = self.pending_action.take() else {
+            return;
+        };
+        if !yes {
+            self.status_message = "Cancelled".to_string();
+            return;
+        }
+        match action {
+            PendingAction::Print(range) => self.print(range),
+        }
+    }
+
+    fn print(&mut self, range: RangeInclusive<usize>) {
+        if !self.load_pages(range.start() - 1..*range.end()) {
+            return;
+        }
+        let pages: Vec<(usize, &String)> = self.pages.iter().enumerate().take(*range.end()).skip(range.start() - 1).collect();
+        let text = export::printable(&self.file_name(), &pages, self.config.print.width);
+        let command = self.config.print.command.clone();
+        self.status_message = format!("Printing pages {}-{}…", range.start(), range.end());
+        self.print_task = Some((range, Task::spawn(move || external::run(&command, &[], Some(&text)))));
+    }
+
+    /// Open the current page in the configured GUI viewer.
+    fn open_in_viewer(&mut self) {
+        let page = (self.current_page + 1).to_string();
+        let file = self.path.display().to_string();
+        self.status_message = match external::launch(&self.config.viewer.command, &[("file", &file), ("page", &page)]) {
+            Ok(()) => format!("Opened page {} in external viewer", page),
+            Err(e) => format!("Could not start viewer: {}", e),
+        };
+    }
+
+    fn show_messages(&mut self) {
+        // Log the current message first so it is included
+        self.messages.observe(&self.status_message);
+        let history = self.messages.history();
+        let text = if history.is_empty() { "No messages yet".to_string() } else { history };
+        self.popup = Some(Popup::new("Messages (UTC, newest first)", text));
+    }
+
+    /// Pipe the selection, or else the current page, through a shell command
+    /// and show what it prints.
+    fn filter(&mut self, filter: &str) {
+        let (text, what) = match self.selected_text() {
+            Some(text) => (text, "selection".to_string()),
+            None => (self.pages[self.current_page].clone(), format!("page {}", self.current_page + 1)),
+        };
+        match external::run(filter, &[], Some(&text)) {
+            Ok(output) if !output.trim().is_empty() => {
+                self.popup = Some(Popup::new(format!("!{} ({})", filter, what), output));
+            }
+            Ok(_) => self.status_message = format!("'{}' printed nothing", filter),
+            Err(e) => self.status_message = e.to_string(),
+        }
+    }
+
+    /// Open `$EDITOR` at the LaTeX source line for the current position using SyncTeX.
+    fn edit_source(&mut self) {
+        if synctex::synctex_file(&self.path).is_none() {
+            self.status_message = "No .synctex(.gz) file next to this PDF".to_string();
+            return;
+        }
+
+        // Text lines carry no coordinates, so estimate the point from the
+        // line's position within the page
+        let line = self.cursor.map_or(self.scroll_offset + self.viewport_height / 2, |c| c.line);
+        let line_count = self.current_lines().len().max(1);
+        let (width, height) = pdf::load(&self.path)
+            .ok()
+            .and_then(|doc| pdf::page_size(&doc, self.current_page))
+            .unwrap_or((612.0, 792.0));
+        let y = height * (line.min(line_count - 1) as f32 + 0.5) / line_count as f32;
+
+        match synctex::source_location(&self.path, self.current_page + 1, width / 2.0, y) {
+            Ok((source, line)) => {
+                let editor = env::var("VISUAL")
+                    .or_else(|_| env::var("EDITOR"))
+                    .unwrap_or_else(|_| "vi".to_string());
+                let template = format!("{} +{{line}} {{file}}", editor);
+                let line = line.to_string();
+                let file = source.display().to_string();
+                match external::interactive(&template, &[("line", &line), ("file", &file)]) {
+                    Ok(command) => {
+                        self.foreground = Some(command);
+                        self.status_message = format!("Opened {}:{}", file, line);
+                    }
+                    Err(e) => self.status_message = e.to_string(),
+                }
+            }
+            Err(e) => self.status_message = format!("SyncTeX lookup failed: {}", e),
+        }
+    }
+
+    /// Load plugin scripts and run the open hooks for the initial document.
+    fn load_plugins(&mut self) {
+        let errors = self.plugins.load(&config::expand_tilde(&self.config.plugins.dir));
+        if let Some(error) = errors.first() {
+            self.status_message = error.clone();
+        }
+        self.document_opened();
+    }
+
+    /// Tell hooks and plugins that a document was opened, and pick up where it was left.
+    fn document_opened(&mut self) {
+        self.doc_state = DocState::load(&self.path);
+        self.apply_cached_ocr();
+        self.previous_position = None;
+        self.restore_progress();
+        self.reported_page = self.current_page;
+        self.run_shell_hook(Hook::Open);
+        self.plugins.set_document(&self.file_name(), &self.pages);
+        self.run_plugin(|plugins| plugins.hook("on_open", vec![]));
+        if sandbox::defused() > 0 {
+            self.status_message = format!(
+                "--sandbox left out {} streams that would expand past {} MB",
+                sandbox::defused(),
+                sandbox::MAX_STREAM_BYTES / (1024 * 1024)
+            );
+        }
+    }
+
+    /// Go to where the document was left, on this machine or another, per the
+    /// sync file or KOReader's sidecar, whichever is newer.
+    fn restore_progress(&mut self) {
+        let pages = self.page_total;
+        // Page, line, when, and where from
+        let mut resume = None;
+        if !self.config.sync.dir.as_os_str().is_empty() {
+            let progress = sync::Progress::load(&config::expand_tilde(&self.config.sync.dir));
+            // A different edition under the same name would land somewhere arbitrary
+            if let Some(entry) = progress.documents.get(&self.file_name())
+                && entry.pages == pages
+                && entry.page < pages
+            {
+                resume = Some((entry.page, entry.line, entry.updated, format!("left on {}", entry.device)));
+            }
+        }
+        // The sidecar only knows the page, so on the same page the line from the sync file wins
+        if self.config.sync.koreader
+            && let Some(sidecar) = koreader::read(&self.path)
+            && sidecar.pages.is_none_or(|count| count == pages)
+            && sidecar.page < pages
+            && resume.as_ref().is_none_or(|&(page, _, updated, _)| sidecar.updated > updated && sidecar.page != page)
+        {
+            resume = Some((sidecar.page, 0, sidecar.updated, "from KOReader".to_string()));
+        }
+        let Some((page, line, updated, source)) = resume.filter(|&(page, line, _, _)| (page, line) != (0, 0)) else {
+            return;
+        };
+        self.go_to_position(page, line);
+        self.status_message = format!("Resumed at page {} ({}, {})", page + 1, source, sync::age(updated));
+    }
+
+    /// Record the current position in the sync file and KOReader's sidecar, as configured.
+    fn save_progress(&self) -> Result<()> {
+        if paths::private() {
+            return Ok(());
+        }
+        if self.config.sync.koreader {
+            koreader::write(&self.path, self.current_page, self.page_total)?;
+        }
+        if self.config.sync.dir.as_os_str().is_empty() {
+            return Ok(());
+        }
+        let device = match self.config.sync.device.as_str() {
+            "" => sync::hostname(),
+            device => device.to_string(),
+        };
+        let position = self.position();
+        let entry = sync::Entry {
+            page: position.page,
+            line: position.line,
+            pages: self.page_total,
+            updated: sync::now(),
+            device,
+        };
+        sync::save(&config::expand_tilde(&self.config.sync.dir), &self.file_name(), entry)
+    }
+
+    fn run_shell_hook(&mut self, hook: Hook) {
+        let command = match hook {
+            Hook::Open => &self.config.hooks.on_open,
+            Hook::Close => &self.config.hooks.on_close,
+            Hook::PageChange => &self.config.hooks.on_page_change,
+        };
+        if let Err(e) = self.shell_hooks.run(command, &self.path, self.current_page, self.pages.len()) {
+            self.status_message = e.to_string();
+        }
+    }
+
+    /// Call into the plugins with the current view state, then carry out what they asked for.
+    fn run_plugin(&mut self, call: impl FnOnce(&mut Plugins) -> Result<Vec<Action>, String>) {
+        if self.plugins.is_empty() || self.in_plugin_actions {
+            return;
+        }
+        self.plugins.set_view(self.current_page, &self.search_query, self.selected_text());
+        match call(&mut self.plugins) {
+            Ok(actions) => {
+                self.in_plugin_actions = true;
+                for action in actions {
+                    match action {
+                        Action::Goto(page) => self.jump_to_page(page),
+                        Action::Search(query) => self.search(query),
+                        Action::Status(message) => self.status_message = message,
+                        Action::Popup(title, text) => self.popup = Some(Popup::new(title, text)),
+                        Action::Command(line) => self.run_builtin_command(&line),
+                    }
+                }
+                self.in_plugin_actions = false;
+            }
+            Err(message) => self.status_message = message,
+        }
+    }
+
+    /// Carry out a command from the remote-control socket.
+    fn remote_command(&mut self, command: RemoteCommand) -> Result<String, String> {
+        match command {
+            RemoteCommand::Goto(page) => {
+                if page == 0 || page > self.pages.len() {
+                    return Err(format!("Invalid page number: {}", page));
+                }
+                self.jump_to_page(page);
+            }
+            RemoteCommand::Search(query) => {
+                self.search(query);
+                if self.search_results.is_empty() {
+                    return Err(self.status_message.clone());
+                }
+            }
+            RemoteCommand::Reload => self.try_reload().map_err(|e| e.to_string())?,
+            RemoteCommand::Open(path) => {
+                self.load_document(path).map_err(|e| e.to_string())?;
+                self.status_message = format!("Opened {}", self.file_name());
+            }
+            RemoteCommand::Synctex(source, line) => {
+                let (page, y) = synctex::pdf_location(&self.path, &source, line).map_err(|e| e.to_string())?;
+                if page == 0 || page > self.pages.len() {
+                    return Err(format!("SyncTeX pointed at missing page {}", page));
+                }
+                self.jump_to_page(page);
+                let height = pdf::load(&self.path)
+                    .ok()
+                    .and_then(|doc| pdf::page_size(&doc, page - 1))
+                    .map_or(792.0, |(_, height)| height);
+                let line = (y / height * self.current_lines().len() as f32) as usize;
+                self.scroll_to_line(line);
+                if self.cursor.is_some() {
+                    self.cursor = Some(Cursor { line, col: 0 });
+                }
+            }
+        }
+        Ok(self.status_message.clone())
+    }
+
+    /// Replace the open document with the one at `path`, starting from its first page.
+    fn load_document(&mut self, path: PathBuf) -> Result<()> {
+        let read = self.read_document(&path)?;
+        if let Err(e) = self.save_progress() {
+            self.status_message = format!("Could not save reading progress: {}", e);
+        }
+        self.replace_document(path, read);
+        // The last search was for the other document
+        self.search_query.clear();
+        self.search_results.clear();
+        self.current_search_result = 0;
+        // Undo would bring back the other document's searches, panels and marks
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.list = None;
+        self.side_panel = None;
+        self.side_panel_focused = false;
+        self.popup = None;
+        self.chat.clear();
+        self.chat_task = None;
+        self.document_opened();
+        Ok(())
+    }
+
+    /// Extract the first pages of `path` with its settings, streaming the rest.
+    fn read_document(&self, path: &Path) -> Result<(Config, PdfText, Option<PageStream>)> {
+        let config = self.overrides.document_config(&self.config, path)?;
+        let (text, stream) = read_pdf_streaming(path, FIRST_PAGES, config.pagination, document_format(path, config.format))?;
+        if text.pages.is_empty() {
+            anyhow::bail!("{} has no pages", path.display());
+        }
+        Ok((config, text, stream))
+    }
+
+    /// Put what `read_document` read in place of the open document's text, on
+    /// its first page. What opening a document does besides is left to the callers.
+    fn replace_document(&mut self, path: PathBuf, (config, text, stream): (Config, PdfText, Option<PageStream>)) {
+        let PdfText { pages, outline, title, scanned, language, labels } = text;
+        self.config = config;
+        self.close_thumbnails();
+        self.close_page_view();
+        self.thumbnail_turns.clear();
+        self.ocr_task = None;
+        self.images_task = None;
+        self.translation_task = None;
+        self.definition_task = None;
+        self.word_counts = pages.iter().map(|page| count_words(page)).collect();
+        self.pages = pages;
+        self.outline = outline;
+        self.title = title;
+        self.scanned = scanned;
+        self.language = language;
+        self.page_labels = labels;
+        self.budget = PageBudget::new(self.config.memory.max_text_mb, &path);
+        self.match_cache.clear();
+        self.footnote_cache.clear();
+        self.code_cache.clear();
+        self.path = path;
+        self.start_loading(stream);
+        if self.spelling.is_some() {
+            self.spelling = Some(self.spell_check());
+        }
+        self.set_page(0);
+        self.scroll_offset = 0;
+        self.result_preview = None;
+        self.selection_anchor = None;
+        self.speech = None;
+        self.reset_cursor();
+    }
+
+    /// `:reload` and `R`: re-read the document, or say why it could not be.
+    fn reload(&mut self) {
+        if let Err(e) = self.try_reload() {
+            self.status_message = format!("Could not reload: {}", e);
+        }
+    }
+
+    /// Extract the open document again, as after it was regenerated, and stay
+    /// on the same page and line with the search, marks and tags kept. Open
+    /// hooks and plugins' `on_open` do not run again.
+    fn try_reload(&mut self) -> Result<()> {
+        let (page, line, page_total) = (self.current_page, self.scroll_offset, self.page_total);
+        // Nothing is replaced when this fails; the document stays as it was
+        let read = self.read_document(&self.path)?;
+        let results = std::mem::take(&mut self.search_results);
+        self.replace_document(self.path.clone(), read);
+        self.apply_cached_ocr();
+        self.plugins.set_document(&self.file_name(), &self.pages);
+        // Undo entries and lists point at pages; they only still fit the same number of them
+        if self.page_total != page_total {
+            self.undo_stack.clear();
+            self.redo_stack.clear();
+            self.list = None;
+        }
+        self.go_to_position(page.min(self.page_total.saturating_sub(1)), line);
+        self.search_results = results;
+        self.refresh_search();
+        self.status_message = format!("Reloaded {}", self.file_name());
+        Ok(())
+    }
+
+    /// Run the search again over the pages as they are now, staying on the
+    /// same result where it is still found.
+    fn refresh_search(&mut self) {
+        if self.search_query.is_empty() {
+            return;
+        }
+        let Some(results) = self.find_all(&self.search_query.to_lowercase()) else {
+            return;
+        };
+        let current = self.search_results.get(self.current_search_result).map(|result| (result.page, result.line));
+        self.search_results = results;
+        self.current_search_result = current
+            .and_then(|current| self.search_results.iter().position(|result| (result.page, result.line) == current))
+            .unwrap_or(0);
+    }
+
+    /// OCR the current page in the background, or use the text cached by an earlier run.
+    fn ocr_page(&mut self) {
+        let page = self.current_page;
+        if self.ocr_task.is_some() {
+            self.status_message = "OCR is already running".to_string();
+            return;
+        }
+        if let Some(text) = ocr::cached(&self.path, page, &self.ocr_lang()) {
+            self.apply_ocr(page, &text);
+            self.status_message = format!("OCR'd page {} (cached)", page + 1);
+            return;
+        }
+        let ocr = OcrConfig {
+            lang: self.ocr_lang(),
+            ..self.config.ocr.clone()
+        };
+        self.status_message = format!("Running OCR ({}) on page {}…", ocr.lang, page + 1);
+        let path = self.path.clone();
+        self.ocr_task = Some((page, Instant::now(), Task::spawn(move || ocr::recognize(&ocr, &path, page))));
+    }
+
+    /// OCR languages: configured ones, or else the document's detected language.
+    fn ocr_lang(&self) -> String {
+        match (&self.config.ocr.lang, self.language) {
+            (lang, _) if !lang.is_empty() => lang.clone(),
+            (_, Some(language)) => language.tesseract().to_string(),
+            _ => "eng".to_string(),
+        }
+    }
+
+    /// List the most frequent terms of the document or of the current chapter.
+    fn show_frequent_terms(&mut self, chapter: bool) {
+        const TERMS: usize = 50;
+        let (pages, scope) = match self.current_chapter() {
+            Some((range, title)) if chapter => (range, format!("chapter \"{}\"", title)),
+            None if chapter => {
+                self.status_message = "No outline to find the chapter in".to_string();
+                return;
+            }
+            _ => (0..self.pages.len(), "document".to_string()),
+        };
+        let language = self.language;
+        let Some(terms) = self.scan_pages(pages, |texts| freq::top_terms(texts, language, TERMS)) else {
+            return;
+        };
+        if terms.is_empty() {
+            self.status_message = "No terms to count".to_string();
+            return;
+        }
+        let width = terms.iter().map(|(_, count)| count.to_string().len()).max().unwrap_or(0);
+        let items = terms.iter().map(|(term, count)| format!("{:>width$}  {}", count, term)).collect();
+        let list = ListPopup::new(format!("Frequent terms ({})", scope), items, "Enter search, Esc close");
+        self.list = Some((ListKind::Terms(terms.into_iter().map(|(term, _)| term).collect()), list));
+    }
+
+    /// Pages and title of the top-level outline entry the current page falls under.
+    fn current_chapter(&self) -> Option<(Range<usize>, String)> {
+        let (chapter, next) = self.chapter_entries()?;
+        let end = next.map_or(self.pages.len(), |next| next.page.clamp(self.current_page + 1, self.pages.len()));
+        Some((chapter.page..end, chapter.title.clone()))
+    }
+
+    /// The top-level outline entry the current page falls under, and the one after it.
+    fn chapter_entries(&self) -> Option<(&OutlineEntry, Option<&OutlineEntry>)> {
+        let top = self.outline.iter().map(|entry| entry.level).min()?;
+        let chapters: Vec<&OutlineEntry> = self.outline.iter().filter(|entry| entry.level == top).collect();
+        let index = chapters.iter().rposition(|entry| entry.page <= self.current_page)?;
+        Some((chapters[index], chapters.get(index + 1).copied()))
+    }
+
+    /// Write the text of the current chapter, cut at the outline's chapter
+    /// headings, to `path`.
+    fn export_section(&mut self, path: &Path) {
+        let Some((chapter, next)) = self.chapter_entries() else {
+            self.status_message = "No outline to find the chapter in".to_string();
+            return;
+        };
+        let (first, title) = (chapter.page, chapter.title.clone());
+        let next = next.map(|next| (next.page, next.title.clone()));
+        let last = next.as_ref().map_or(self.page_total, |(page, _)| page + 1).min(self.page_total);
+        if last > self.pages.len() {
+            self.status_message = format!("The chapter runs to page {}, which is still being read", last);
+            return;
+        }
+        if !self.load_pages(first..last) {
+            return;
+        }
+        let text = export::section_text(&self.pages, first, &title, next.as_ref().map(|(page, title)| (*page, title.as_str())));
+        let path = config::expand_tilde(path);
+        self.status_message = match fs::write(&path, text) {
+            Ok(()) => format!("Exported \"{}\" (pages {}-{}) to {}", title, first + 1, last, path.display()),
+            Err(e) => format!("Could not write {}: {}", path.display(), e),
+        };
+    }
+
+    fn toggle_spelling(&mut self) {
+        if self.spelling.take().is_some() {
+            self.status_message = "Spell highlighting off".to_string();
+        } else {
+            self.spelling = Some(self.spell_check());
+            self.status_message = format!("Underlining words not in the {} dictionary", self.spell_lang());
+        }
+    }
+
+    fn spell_check(&self) -> SpellCheck {
+        SpellCheck::new(&self.config.spelling.command, self.spell_lang())
+    }
+
+    fn spell_lang(&self) -> &'static str {
+        self.language.map_or("en", Language::code)
+    }
+
+    /// Scanned pages OCR'd in an earlier session come back without asking.
+    fn apply_cached_ocr(&mut self) {
+        for page in 0..self.scanned.len() {
+            if self.scanned[page]
+                && let Some(text) = ocr::cached(&self.path, page, &self.ocr_lang())
+            {
+                self.apply_ocr(page, &text);
+            }
+        }
+    }
+
+    /// Keep or drop leading whitespace for this document, and remember the choice.
+    fn toggle_indent(&mut self) {
+        let indent = !self.text_format().indent;
+        self.doc_state.indent = Some(indent);
+        self.reformat(if indent { "Keeping indentation" } else { "Trimming indentation" });
+    }
+
+    /// Render math as Unicode or show it as extracted for this document, and
+    /// remember the choice.
+    fn toggle_math(&mut self) {
+        let math = !self.text_format().math;
+        self.doc_state.math = Some(math);
+        self.reformat(if math { "Rendering math" } else { "Showing math as extracted" });
+    }
+
+    /// Show or hide the lines matching exclude patterns. They stay in the
+    /// page text either way, so positions on the page keep their lines.
+    fn toggle_excluded(&mut self) {
+        self.show_excluded = !self.show_excluded;
+        // Matches on the lines just hidden or shown come and go
+        self.refresh_search();
+        self.page_find = None;
+        self.scroll_offset = self.scroll_offset.min(self.max_scroll());
+        self.reset_cursor();
+        self.status_message = if self.show_excluded { "Showing excluded lines" } else { "Hiding excluded lines" }.to_string();
+    }
+
+    /// Whether `line` of page text is left out of the view and of searches.
+    fn hidden(&self, line: &str) -> bool {
+        !self.show_excluded && self.config.exclude.matches(line)
+    }
+
+    /// Save a formatting choice just made in `doc_state` and show the text with it.
+    fn reformat(&mut self, state: &str) {
+        let saved = self.doc_state.save();
+        if let Err(e) = self.reread_text() {
+            self.status_message = format!("Could not re-read the document: {}", e);
+            return;
+        }
+        self.status_message = match saved {
+            Ok(()) => state.to_string(),
+            Err(e) => format!("{}, but not saved: {}", state, e),
+        };
+    }
+
+    fn text_format(&self) -> FormatConfig {
+        FormatConfig {
+            indent: self.doc_state.indent.unwrap_or(self.config.format.indent),
+            math: self.doc_state.math.unwrap_or(self.config.format.math),
+            ..self.config.format
+        }
+    }
+
+    /// Extract the open document's text again after its formatting changed,
+    /// staying at the same place.
+    fn reread_text(&mut self) -> Result<()> {
+        let (PdfText { pages, scanned, labels, .. }, stream) =
+            read_pdf_streaming(&self.path, FIRST_PAGES, self.config.pagination, self.text_format())?;
+        let position = Position { page: self.current_page, line: self.scroll_offset };
+        self.word_counts = pages.iter().map(|page| count_words(page)).collect();
+        self.pages = pages;
+        self.scanned = scanned;
+        self.page_labels = labels;
+        self.budget = PageBudget::new(self.config.memory.max_text_mb, &self.path);
+        self.match_cache.clear();
+        self.footnote_cache.clear();
+        self.code_cache.clear();
+        self.page_find = None;
+        // Matches point at lines that may have moved
+        self.search_results.clear();
+        self.current_search_result = 0;
+        self.result_preview = None;
+        self.selection_anchor = None;
+        self.start_loading(stream);
+        if self.spelling.is_some() {
+            self.spelling = Some(self.spell_check());
+        }
+        self.apply_cached_ocr();
+        self.go_to_position(position.page, position.line);
+        Ok(())
+    }
+
+    /// Show OCR'd text in place of a page's text layer.
+    fn apply_ocr(&mut self, page: usize, text: &str) {
+        self.budget.changed(page);
+        self.match_cache.clear();
+        if self.page_find.as_ref().is_some_and(|find| find.page == page) {
+            self.page_find = None;
+        }
+        self.footnote_cache.clear();
+        self.code_cache.clear();
+        self.pages[page] = format::page(text, self.text_format());
+        self.word_counts[page] = count_words(&self.pages[page]);
+        if let Some(spelling) = self.spelling.as_mut() {
+            spelling.forget(page);
+        }
+        if let Some(scanned) = self.scanned.get_mut(page) {
+            *scanned = false;
+        }
+        if page == self.current_page {
+            self.scroll_offset = 0;
+            self.reset_cursor();
+        }
+    }
+
+    fn show_citations(&mut self) {
+        let Some(citations) = self.scan_pages(0..self.pages.len(), |texts| citations::extract(texts)) else {
+            return;
+        };
+        if citations.is_empty() {
+            self.status_message = "No DOIs, arXiv IDs or references found".to_string();
+            return;
+        }
+        let items = citations.iter().map(Citation::label).collect();
+        let list = ListPopup::new(
+            format!("Citations ({})", citations.len()),
+            items,
+            "Enter go to page, o open, y copy BibTeX, Y copy all, Esc close",
+        );
+        self.list = Some((ListKind::Citations(citations), list));
+    }
+
+    /// Open the page thumbnail grid at the current page, or close it.
+    fn toggle_thumbnails(&mut self) {
+        if self.thumbnail_grid.is_some() {
+            self.close_thumbnails();
+            return;
+        }
+        // Rendered thumbnails are cached on disk
+        let thumbnails = (kitty::supported() && !paths::private()).then(|| {
+            let doc = pdf::load(&self.path).ok();
+            let shapes = (0..self.pages.len())
+                .map(|page| PageShape {
+                    size: doc.as_ref().and_then(|doc| pdf::page_size(doc, page)),
+                    rotate: doc.as_ref().map_or(0, |doc| pdf::page_rotation(doc, page)),
+                })
+                .collect();
+            Thumbnails::start(&self.config.thumbnails.command, &self.path, shapes, self.thumbnail_turns.clone())
+        });
+        if thumbnails.is_none() {
+            self.status_message = if paths::private() {
+                "Private mode; showing page text instead of thumbnails".to_string()
+            } else {
+                "No Kitty graphics support; showing page text instead".to_string()
+            };
+        }
+        self.thumbnail_grid = Some(ThumbnailGrid {
+            selected: self.current_page,
+            top_row: 0,
+            thumbnails,
+            cells: Vec::new(),
+        });
+    }
+
+    fn close_thumbnails(&mut self) {
+        if let Some(mut grid) = self.thumbnail_grid.take()
+            && let Some(thumbnails) = grid.thumbnails.as_mut()
+        {
+            let _ = thumbnails.clear();
+            self.thumbnail_turns = std::mem::take(&mut thumbnails.turns);
+        }
+    }
+
+    fn thumbnail_key(&mut self, code: KeyCode) {
+        let Some(grid) = self.thumbnail_grid.as_mut() else {
+            return;
+        };
+        let columns = self.config.thumbnails.columns.max(1);
+        let last = self.pages.len() - 1;
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('t') => self.close_thumbnails(),
+            KeyCode::Enter => {
+                let page = grid.selected;
+                self.close_thumbnails();
+                self.jump_to_page(page + 1);
+            }
+            KeyCode::Right | KeyCode::Char('l') => grid.selected = (grid.selected + 1).min(last),
+            KeyCode::Left | KeyCode::Char('h') => grid.selected = grid.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => grid.selected = (grid.selected + columns).min(last),
+            KeyCode::Up | KeyCode::Char('k') => grid.selected = grid.selected.saturating_sub(columns),
+            KeyCode::Char(key @ ('r' | 'R')) => match grid.thumbnails.as_mut() {
+                Some(thumbnails) => {
+                    if let Err(e) = thumbnails.rotate(grid.selected, key == 'r') {
+                        self.status_message = format!("Could not rotate the thumbnail: {}", e);
+                    }
+                }
+                None => self.status_message = "Only page images can be rotated".to_string(),
+            },
+            _ => {}
+        }
+    }
+
+    fn toggle_page_view(&mut self) {
+        if self.page_view.is_some() {
+            self.close_page_view();
+            return;
+        }
+        if paths::private() {
+            self.status_message = "Private mode; page images are not rendered".to_string();
+            return;
+        }
+        if !kitty::supported() {
+            self.status_message = "Page images need Kitty graphics; V shows the page laid out as text".to_string();
+            return;
+        }
+        let turns = self.thumbnail_turns.clone();
+        self.page_view = Some(PageView::open(&self.config.page_view.command, &self.path, self.current_page, turns, self.config.page_view.fit));
+    }
+
+    fn close_page_view(&mut self) {
+        if let Some(mut view) = self.page_view.take() {
+            let _ = view.clear();
+            self.thumbnail_turns = std::mem::take(&mut view.turns);
+        }
+    }
+
+    fn page_view_key(&mut self, code: KeyCode) {
+        let Some(view) = self.page_view.as_mut() else {
+            return;
+        };
+        let last = self.pages.len().saturating_sub(1);
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('I') => self.close_page_view(),
+            KeyCode::Char('+') | KeyCode::Char('=') => view.zoom(1),
+            KeyCode::Char('-') => view.zoom(-1),
+            KeyCode::Char('0') => view.set_zoom(1.0),
+            KeyCode::Char('w') => view.toggle_fit(),
+            KeyCode::Char(key @ ('r' | 'R')) => view.rotate(key == 'r'),
+            KeyCode::Down | KeyCode::Char('j') => view.pan(0, 2),
+            KeyCode::Up | KeyCode::Char('k') => view.pan(0, -2),
+            KeyCode::Right | KeyCode::Char('l') => view.pan(4, 0),
+            KeyCode::Left | KeyCode::Char('h') => view.pan(-4, 0),
+            // Paging on past the end of the page goes on to the next
+            KeyCode::PageDown | KeyCode::Char(' ') if !view.page_down(true) && view.page < last => {
+                let page = view.page + 1;
+                self.page_view_go_to(page, false);
+            }
+            KeyCode::PageUp | KeyCode::Backspace if !view.page_down(false) && view.page > 0 => {
+                let page = view.page - 1;
+                self.page_view_go_to(page, true);
+            }
+            KeyCode::Char('n') if view.page < last => {
+                let page = view.page + 1;
+                self.page_view_go_to(page, false);
+            }
+            KeyCode::Char('p') if view.page > 0 => {
+                let page = view.page - 1;
+                self.page_view_go_to(page, false);
+            }
+            _ => {}
+        }
+    }
+
+    /// Show `page` in the page view, with the viewer following.
+    fn page_view_go_to(&mut self, page: usize, at_bottom: bool) {
+        if let Some(view) = self.page_view.as_mut() {
+            view.go_to(page, at_bottom);
+        }
+        self.set_page(page);
+        self.scroll_offset = 0;
+        self.reset_cursor();
+    }
+
+    /// Place the text of the layout view's page on a grid `columns` wide.
+    fn lay_out_page(&mut self, columns: usize) {
+        let Some(view) = self.layout_view.as_mut() else {
+            return;
+        };
+        view.columns = columns;
+        view.rows = match pdf::load(&self.path) {
+            Ok(doc) => layout::grid(&pdf::page_glyphs(&doc, view.page as u32 + 1, self.config.format.crop), columns, view.align_amounts),
+            Err(e) => {
+                self.status_message = format!("Could not read the page layout: {}", e);
+                Vec::new()
+            }
+        };
+        view.scroll = view.scroll.min(view.rows.len().saturating_sub(1));
+    }
+
+    fn layout_key(&mut self, code: KeyCode) {
+        let Some(view) = self.layout_view.as_mut() else {
+            return;
+        };
+        let last = self.pages.len().saturating_sub(1);
+        let page = match code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('V') => {
+                self.layout_view = None;
+                return;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                view.scroll = (view.scroll + 1).min(view.rows.len().saturating_sub(1));
+                return;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                view.scroll = view.scroll.saturating_sub(1);
+                return;
+            }
+            KeyCode::Char('a') => {
+                view.align_amounts = !view.align_amounts;
+                view.columns = 0;
+                return;
+            }
+            KeyCode::Right | KeyCode::Char('n') => (view.page + 1).min(last),
+            KeyCode::Left | KeyCode::Char('p') => view.page.saturating_sub(1),
+            _ => return,
+        };
+        if page != view.page {
+            *view = LayoutView { page, columns: 0, rows: Vec::new(), scroll: 0, ..*view };
+            self.set_page(page);
+            self.scroll_offset = 0;
+            self.reset_cursor();
+        }
+    }
+
+    /// List the images of the current page, paired in order with the figure captions found in its text.
+    /// The PDF is parsed in the background; the list opens when it is done.
+    fn show_images(&mut self) {
+        let (path, page) = (self.path.clone(), self.current_page);
+        self.images_task = Some((page, Task::spawn(move || Ok(pdf::page_images(&pdf::load(&path)?, page)))));
+        self.status_message = format!("Reading the images on page {}…", page + 1);
+    }
+
+    fn list_images(&mut self, page: usize, images: Vec<PageImage>) {
+        if images.is_empty() {
+            self.status_message = format!("No images on page {}", page + 1);
+            return;
+        }
+
+        self.status_message.clear();
+        let captions = images::captions(&self.pages[page]);
+        let items = images
+            .iter()
+            .enumerate()
+            .map(|(i, image)| match captions.get(i) {
+                Some(caption) => format!("{}  — {}", image.describe(), caption),
+                None => image.describe(),
+            })
+            .collect();
+        let list = ListPopup::new(
+            format!("Images on page {} ({}, {} captions)", page + 1, images.len(), captions.len()),
+            items,
+            "Enter preview, e export, :save-image PATH, Esc close",
+        );
+        self.list = Some((ListKind::Images(page, images), list));
+    }
+
+    /// Save an image to `path`, or into `images.export_dir` when no path is given.
+    /// A directory gets the image's default file name.
+    fn export_image(&mut self, page: usize, image: &PageImage, path: Option<&Path>) {
+        let mut path = config::expand_tilde(path.unwrap_or(&self.config.images.export_dir));
+        if path.is_dir() {
+            path.push(images::export_name(&self.path, page, image));
+        }
+        let result = pdf::load(&self.path).and_then(|doc| images::export(&doc, image, &path));
+        self.status_message = match result {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(e) => format!("Export failed: {}", e),
+        };
+    }
+
+    fn preview_image(&mut self, image: &PageImage) {
+        if !kitty::supported() {
+            self.status_message = "Image preview needs Kitty graphics; press e to export instead".to_string();
+            return;
+        }
+        let path = env::temp_dir().join(format!("pdf_reader-{}-preview.png", std::process::id()));
+        match pdf::load(&self.path).and_then(|doc| images::export_png(&doc, image, &path)) {
+            Ok(()) => {
+                self.popup = Some(Popup::new(image.describe(), ""));
+                self.image_preview = Some(ImagePreview { path, area: None });
+            }
+            Err(e) => self.status_message = format!("Preview failed: {}", e),
+        }
+    }
+
+    /// The `:save-image` command: export the image selected in the `:images` list.
+    fn save_image(&mut self, path: Option<&Path>) {
+        let Some((ListKind::Images(page, images), list)) = self.list.as_ref() else {
+            self.status_message = "No image selected; list them with :images first".to_string();
+            return;
+        };
+        let (page, image) = (*page, images[list.selected].clone());
+        self.export_image(page, &image, path);
+    }
+
+    /// Show the file's and the PDF's properties, and what is attached to it.
+    fn show_info(&mut self) {
+        match document_info(&self.path, self.language) {
+            Ok(info) => self.popup = Some(Popup::new("Document info", info)),
+            Err(e) => self.status_message = format!("Could not read {}: {}", self.path.display(), e),
+        }
+    }
+
+    /// Show what the extractor returned for the current page, with invisible
+    /// characters spelled out, for tracking down extraction problems.
+    fn show_raw_text(&mut self) {
+        let page = self.current_page;
+        if !self.load_pages(page..page + 1) {
+            return;
+        }
+        let raw = match pdf::load(&self.path) {
+            Ok(doc) => doc.get_pages().keys().nth(page).map(|&page_num| pdf::extract_page(&doc, page_num, self.config.format.crop)),
+            Err(e) => {
+                self.status_message = format!("Could not read {}: {}", self.path.display(), e);
+                return;
+            }
+        };
+        let Some(raw) = raw else {
+            self.status_message = format!("No PDF page {}", page + 1);
+            return;
+        };
+        let summary = format!(
+            "{} chars in {} lines as extracted; {} lines after cleanup\n\
+             ↵ line end  → tab  · trailing space  ␍ CR  ␌ form feed  ⍽ no-break space  ⟨SHY⟩ soft hyphen\n\n",
+            raw.chars().count(),
+            raw.split('\n').count(),
+            self.pages.get(page).map_or(0, |text| text.lines().count()),
+        );
+        self.popup = Some(Popup::new(format!("Raw text, page {}", page + 1), format!("{}{}", summary, visible_controls(&raw))));
+    }
+
+    fn show_attachments(&mut self) {
+        let attachments = match pdf::load(&self.path) {
+            Ok(doc) => pdf::attachments(&doc),
+            Err(e) => {
+                self.status_message = format!("Could not read attachments: {}", e);
+                return;
+            }
+        };
+        if attachments.is_empty() {
+            self.status_message = "No attachments".to_string();
+            return;
+        }
+        let list = ListPopup::new(
+            format!("Attachments ({})", attachments.len()),
+            attachments.iter().map(describe_attachment).collect(),
+            "e save, a save all, Esc close",
+        );
+        self.list = Some((ListKind::Attachments(attachments), list));
+    }
+
+    /// The `:save-attachment` command: `which` is a file name, a 1-based number or `all`.
+    fn save_attachment(&mut self, which: &str, dir: Option<&Path>) {
+        let attachments = match pdf::load(&self.path) {
+            Ok(doc) => pdf::attachments(&doc),
+            Err(e) => {
+                self.status_message = format!("Could not read attachments: {}", e);
+                return;
+            }
+        };
+        let chosen: Vec<Attachment> = match which {
+            "all" => attachments,
+            _ => match which.parse::<usize>() {
+                Ok(n) => attachments.into_iter().skip(n.wrapping_sub(1)).take(1).collect(),
+                Err(_) => attachments.into_iter().filter(|a| a.name == which).collect(),
+            },
+        };
+        if chosen.is_empty() {
+            self.status_message = format!("No attachment {}", which);
+            return;
+        }
+        self.save_attachments(&chosen, dir);
+    }
+
+    /// Write attachments into `dir`, or `attachments.dir`, under their own file names.
+    fn save_attachments(&mut self, attachments: &[Attachment], dir: Option<&Path>) {
+        let dir = config::expand_tilde(dir.unwrap_or(&self.config.attachments.dir));
+        let result = pdf::load(&self.path).and_then(|doc| {
+            fs::create_dir_all(&dir)?;
+            let mut saved = Vec::new();
+            for attachment in attachments {
+                // Stored names may carry directories; never write outside `dir`
+                let name = Path::new(&attachment.name).file_name().map_or_else(
+                    || PathBuf::from(format!("attachment-{}", saved.len() + 1)),
+                    PathBuf::from,
+                );
+                let path = dir.join(name);
+                fs::write(&path, pdf::attachment_data(&doc, attachment)?)
+                    .map_err(|e| anyhow::anyhow!("Could not write {}: {}", path.display(), e))?;
+                saved.push(path);
+            }
+            Ok(saved)
+        });
+        self.status_message = match result {
+            Ok(saved) if saved.len() == 1 => format!("Saved {}", saved[0].display()),
+            Ok(saved) => format!("Saved {} attachments to {}", saved.len(), dir.display()),
+            Err(e) => format!("Saving attachments failed: {}", e),
+        };
+    }
+
+    /// Place, move or remove the previewed image to match the popup.
+    fn update_image_preview(&mut self) -> io::Result<()> {
+        let Some(preview) = self.image_preview.as_mut() else {
+            return Ok(());
+        };
+        if self.popup.is_none() {
+            kitty::delete(PREVIEW_IMAGE_ID)?;
+            let _ = fs::remove_file(&preview.path);
+            self.image_preview = None;
+        } else if preview.area != Some(self.popup_area) {
+            kitty::delete(PREVIEW_IMAGE_ID)?;
+            kitty::show(PREVIEW_IMAGE_ID, &preview.path, self.popup_area)?;
+            preview.area = Some(self.popup_area);
+        }
+        Ok(())
+    }
+
+    /// Handle a key aimed at the open list popup.
+    fn list_key(&mut self, code: KeyCode) {
+        let Some((kind, list)) = self.list.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                let list = self.list.take();
+                self.push_undo(Snapshot::List(list));
+                return;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                list.next();
+                return;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                list.prev();
+                return;
+            }
+            _ => {}
+        }
+
+        let selected = list.selected;
+        match kind {
+            ListKind::Citations(citations) => {
+                let citation = citations[selected].clone();
+                match code {
+                    KeyCode::Enter => {
+                        self.list = None;
+                        self.jump_to_page(citation.page + 1);
+                    }
+                    KeyCode::Char('o') => match citation.url() {
+                        Some(url) => self.open_url(&url),
+                        None => self.status_message = "No DOI or arXiv link in this entry".to_string(),
+                    },
+                    KeyCode::Char('y') => self.copy_to_clipboard(&citation.bibtex(selected + 1), "BibTeX stub"),
+                    KeyCode::Char('Y') => {
+                        let all: String = citations
+                            .iter()
+                            .enumerate()
+                            .map(|(i, c)| c.bibtex(i + 1))
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        self.copy_to_clipboard(&all, "All BibTeX stubs");
+                    }
+                    _ => {}
+                }
+            }
+            ListKind::Images(page, images) => {
+                let (page, image) = (*page, images[selected].clone());
+                match code {
+                    KeyCode::Enter => {
+                        self.list = None;
+                        self.preview_image(&image);
+                    }
+                    KeyCode::Char('e') => self.export_image(page, &image, None),
+                    KeyCode::Char(':') => self.start_command(),
+                    _ => {}
+                }
+            }
+            ListKind::FileHits(query, rows) => {
+                if code == KeyCode::Enter
+                    && let Some(hit) = rows[selected].clone()
+                {
+                    let query = query.clone();
+                    self.list = None;
+                    self.open_file_hit(query, &hit);
+                }
+            }
+            ListKind::Documents(paths) => match code {
+                KeyCode::Enter => {
+                    let path = paths[selected].clone();
+                    self.list = None;
+                    if path != self.path
+                        && let Err(e) = self.load_document(path.clone())
+                    {
+                        self.status_message = format!("Could not open {}: {}", path.display(), e);
+                    }
+                }
+                KeyCode::Char('/') => {
+                    self.list = None;
+                    self.search_all = true;
+                    self.start_search();
+                }
+                _ => {}
+            },
+            ListKind::Registers(names) => {
+                let name = names[selected];
+                match code {
+                    KeyCode::Enter => {
+                        let text = self.registers.get(name).unwrap_or_default().to_string();
+                        self.copy_to_clipboard(&text, &format!("Register {}", name));
+                    }
+                    KeyCode::Char('d') => {
+                        self.registers.remove(name);
+                        self.show_registers();
+                        self.status_message = format!("Register {} deleted", name);
+                    }
+                    _ => {}
+                }
+            }
+            ListKind::Clips(clips) => {
+                let clip = clips[selected].clone();
+                match code {
+                    KeyCode::Enter => self.copy_to_clipboard(&clip.text, &format!("Clip from page {}", clip.page + 1)),
+                    KeyCode::Char('c') => {
+                        self.clip_text(&clip.text, clip.page, None);
+                    }
+                    KeyCode::Char('"') => self.pending_key = Some(PendingKey::StoreClip(selected)),
+                    _ => {}
+                }
+            }
+            ListKind::Tags(tags) => {
+                if code == KeyCode::Enter {
+                    let tag = tags[selected].clone();
+                    self.list = None;
+                    self.set_tag_filter(Some(tag));
+                }
+            }
+            ListKind::Terms(terms) => {
+                if code == KeyCode::Enter {
+                    let term = terms[selected].clone();
+                    self.list = None;
+                    self.search(term);
+                }
+            }
+            ListKind::Attachments(attachments) => {
+                let attachments = attachments.clone();
+                match code {
+                    KeyCode::Char('e') => self.save_attachments(&attachments[selected..=selected], None),
+                    KeyCode::Char('a') => self.save_attachments(&attachments, None),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Copy the visual selection, the cursor line, or else the page, to the
+    /// clipboard. `other_form` flips the configured choice between lines as
+    /// displayed and reflowed paragraphs.
+    /// After `"a`, the text goes into register `a` instead.
+    fn yank(&mut self, other_form: bool) {
+        let (text, what) = match self.selection_or_line() {
+            Some(text) if self.selection_anchor.is_some() => (text, "Selection"),
+            Some(text) => (text, "Line"),
+            None => (self.pages[self.current_page].clone(), "Page"),
+        };
+        let (text, what) = if self.config.clipboard.reflow != other_form {
+            (structure::reflow(&text, self.language), format!("{} (reflowed)", what))
+        } else {
+            (text, what.to_string())
+        };
+        self.registers.store(registers::UNNAMED, &text);
+        self.registers.remember(&text, self.current_page);
+        match self.registers.take_selected() {
+            Some(name) => {
+                let register = self.registers.store(name, &text);
+                let verb = if name.is_ascii_uppercase() { "added to" } else { "yanked into" };
+                self.status_message = format!("{} {} register {}", what, verb, register);
+            }
+            None => self.copy_to_clipboard(&text, &what),
+        }
+    }
+
+    fn show_clips(&mut self) {
+        let clips: Vec<Yanked> = self.registers.history().iter().cloned().collect();
+        if clips.is_empty() {
+            self.status_message = "Nothing yanked or clipped yet".to_string();
+            return;
+        }
+        let items = clips
+            .iter()
+            .map(|clip| {
+                let preview: String = clip.text.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(70).collect();
+                format!("p.{}  {}", clip.page + 1, preview)
+            })
+            .collect();
+        let list = ListPopup::new(
+            format!("Clips ({}, newest first)", clips.len()),
+            items,
+            "Enter copy, c clip to file, \"{a-z} to register, Esc close",
+        );
+        self.list = Some((ListKind::Clips(clips), list));
+    }
+
+    /// Paste register `name` into the prompt being typed in.
+    fn paste_register(&mut self, name: char) {
+        match self.registers.get(name).map(str::to_string) {
+            Some(text) => self.paste(&text),
+            None => self.status_message = format!("Register {} is empty", name),
+        }
+    }
+
+    fn show_registers(&mut self) {
+        let names = self.registers.names();
+        if names.is_empty() {
+            self.status_message = "No registers yet (\"a then y yanks into register a)".to_string();
+            return;
+        }
+        let items = names
+            .iter()
+            .map(|&name| {
+                let text = self.registers.get(name).unwrap_or_default();
+                let preview: String = text.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(70).collect();
+                format!("\"{}  {} ({} lines)", name, preview, text.lines().count())
+            })
+            .collect();
+        let list = ListPopup::new(format!("Registers ({})", names.len()), items, "Enter copy to clipboard, d delete, Esc close");
+        self.list = Some((ListKind::Registers(names), list));
+    }
+
+    /// Act on a register or mark name typed after `Q`, `@`, `m` or `'`.
+    fn pending_key(&mut self, op: PendingKey, register: char) {
+        match op {
+            PendingKey::SetMark => self.set_mark(register),
+            PendingKey::Register => match self.registers.select(register) {
+                Ok(()) => self.status_message = format!("\"{}: y/Y yanks into register {}", register, register.to_ascii_lowercase()),
+                Err(message) => self.status_message = message,
+            },
+            PendingKey::PasteRegister => self.paste_register(register),
+            PendingKey::StoreClip(index) if register.is_ascii_alphabetic() => {
+                let text = self.registers.history().get(index).map(|clip| clip.text.clone()).unwrap_or_default();
+                let register = self.registers.store(register, &text);
+                self.status_message = format!("Clip put in register {}", register);
+            }
+            PendingKey::StoreClip(_) => self.status_message = format!("Invalid register: {}", register),
+            PendingKey::JumpToMark => self.jump_to_mark(register),
+            PendingKey::Record if register.is_ascii_alphanumeric() => {
+                self.macros.start_recording(register);
+                self.status_message = format!("Recording @{}", register);
+            }
+            PendingKey::Record => self.status_message = format!("Invalid register: {}", register),
+            PendingKey::Replay => match self.macros.begin_replay(register) {
+                Ok(keys) => {
+                    for key in keys {
+                        handle_key(self, key);
+                    }
+                    self.macros.end_replay();
+                }
+                Err(message) => self.status_message = message,
+            },
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            page: self.current_page,
+            line: self.cursor.map_or(self.scroll_offset, |c| c.line),
+        }
+    }
+
+    fn set_mark(&mut self, mark: char) {
+        if !mark.is_ascii_lowercase() {
+            self.status_message = format!("Invalid mark: {}", mark);
+            return;
+        }
+        let previous = self.doc_state.marks.insert(mark, self.position());
+        self.push_undo(Snapshot::Mark(mark, previous));
+        self.status_message = match self.doc_state.save() {
+            Ok(()) => format!("Mark {} set on page {}", mark, self.current_page + 1),
+            Err(e) => format!("Mark {} set but not saved: {}", mark, e),
+        };
+    }
+
+    /// Tags of a 0-based page, in name order.
+    fn page_tags(&self, page: usize) -> Vec<&str> {
+        self.doc_state.tags.iter().filter(|(_, pages)| pages.contains(&page)).map(|(tag, _)| tag.as_str()).collect()
+    }
+
+    fn tag_page(&mut self, tags: Vec<String>) {
+        let page = self.current_page;
+        for tag in &tags {
+            self.doc_state.tags.entry(tag.clone()).or_default().insert(page);
+        }
+        self.status_message = match self.doc_state.save() {
+            Ok(()) => format!("Tagged page {}: {}", page + 1, tags.join(", ")),
+            Err(e) => format!("Tagged page {} but not saved: {}", page + 1, e),
+        };
+    }
+
+    /// Remove `tags` from the current page, or every tag when empty. Tags left
+    /// without pages are dropped.
+    fn untag_page(&mut self, tags: &[String]) {
+        let page = self.current_page;
+        let mut removed = Vec::new();
+        for (tag, pages) in self.doc_state.tags.iter_mut() {
+            if (tags.is_empty() || tags.contains(tag)) && pages.remove(&page) {
+                removed.push(tag.clone());
+            }
+        }
+        self.doc_state.tags.retain(|_, pages| !pages.is_empty());
+        if removed.is_empty() {
+            self.status_message = format!("Page {} has no such tag", page + 1);
+            return;
+        }
+        self.status_message = match self.doc_state.save() {
+            Ok(()) => format!("Removed from page {}: {}", page + 1, removed.join(", ")),
+            Err(e) => format!("Removed from page {} but not saved: {}", page + 1, e),
+        };
+    }
+
+    fn show_tags(&mut self) {
+        if self.doc_state.tags.is_empty() {
+            self.status_message = "No tagged pages (:tag <name> tags the current page)".to_string();
+            return;
+        }
+        let tags: Vec<String> = self.doc_state.tags.keys().cloned().collect();
+        let items = self
+            .doc_state
+            .tags
+            .iter()
+            .map(|(tag, pages)| {
+                let pages: Vec<String> = pages.iter().map(|page| (page + 1).to_string()).collect();
+                format!("{} — pages {}", tag, pages.join(", "))
+            })
+            .collect();
+        let list = ListPopup::new(format!("Tags ({})", tags.len()), items, "Enter filter by tag, Esc close");
+        self.list = Some((ListKind::Tags(tags), list));
+    }
+
+    /// Limit `n`/`p` to pages tagged `tag`, moving to the first one unless
+    /// the current page has it; `None` shows every page again.
+    fn set_tag_filter(&mut self, tag: Option<String>) {
+        let Some(tag) = tag else {
+            self.tag_filter = None;
+            self.status_message = "Showing all pages".to_string();
+            return;
+        };
+        let Some(first) = self.tagged_pages(&tag).next() else {
+            self.status_message = format!("No pages tagged '{}'", tag);
+            return;
+        };
+        if !self.page_tags(self.current_page).contains(&tag.as_str()) {
+            self.go_to_tagged_page(&tag, Some(first));
+        }
+        self.status_message = format!("n/p move between pages tagged '{}'", tag);
+        self.tag_filter = Some(tag);
+    }
+
+    /// Jump to a mark; `'` is the position before the last jump. Marks are kept
+    /// with the document state; the previous position is not.
+    fn jump_to_mark(&mut self, mark: char) {
+        let target = if mark == '\'' {
+            self.previous_position
+        } else {
+            self.doc_state.marks.get(&mark).copied()
+        };
+        let Some(target) = target.filter(|p| p.page < self.pages.len()) else {
+            self.status_message = format!("Mark {} is not set", mark);
+            return;
+        };
+        self.jump_to(target);
+        self.status_message = format!("Mark {}: page {}", mark, target.page + 1);
+    }
+
+    /// Go to `target`, remembering where we were for `''`.
+    fn jump_to(&mut self, target: Position) {
+        self.previous_position = Some(self.position());
+        self.set_page(target.page);
+        self.scroll_offset = 0;
+        self.reset_cursor();
+        let line = target.line.min(self.current_lines().len().saturating_sub(1));
+        if self.cursor.is_some() {
+            self.cursor = Some(Cursor { line, col: 0 });
+        }
+        self.scroll_offset = line;
+        self.flash_anchor(line);
+    }
+
+    /// Light up `line` of the current page for a moment after a jump.
+    fn flash_anchor(&mut self, line: usize) {
+        self.anchor = Some((Position { page: self.current_page, line }, Instant::now()));
+    }
+
+    /// The line lit up after a jump, while it still is.
+    fn flashing_anchor(&self) -> Option<Position> {
+        self.anchor.filter(|(_, at)| at.elapsed() < ANCHOR_FLASH).map(|(position, _)| position)
+    }
+
+    /// Ring or flash for a failed action as `status.bell` asks, for those who
+    /// would miss its status message.
+    fn bell(&mut self) {
+        let bell = self.config.status.bell;
+        if bell.audible() {
+            let mut out = io::stdout();
+            let _ = out.write_all(b"\x07").and_then(|()| out.flush());
+        }
+        if bell.visual() {
+            self.visual_bell = Some(Instant::now());
+        }
+    }
+
+    /// Show the footnote of the next marker from the cursor, or the first
+    /// marker on screen, in a popup.
+    fn follow_footnote(&mut self) {
+        let from = match self.cursor {
+            Some(cursor) => (cursor.line, self.current_lines().get(cursor.line).map_or(0, |line| cursor::byte_offset(line, cursor.col))),
+            None => (self.scroll_offset, 0),
+        };
+        self.show_footnote_after(from);
+    }
+
+    /// Show the footnote of the first marker ending after `from`, a line and byte offset.
+    fn show_footnote_after(&mut self, from: (usize, usize)) {
+        let Some(text) = self.pages.get(self.current_page) else {
+            return;
+        };
+        let footnotes = self.footnote_cache.get(self.current_page, text);
+        let Some(marker) = footnotes.markers.iter().find(|marker| (marker.line, marker.range.end) > from) else {
+            self.status_message = if footnotes.notes.is_empty() {
+                "No footnotes on this page".to_string()
+            } else {
+                "No footnote marker after this point".to_string()
+            };
+            return;
+        };
+        let note = &footnotes.notes[marker.note];
+        let target = Position { page: self.current_page, line: note.line };
+        self.footnote_target = Some((target, (marker.line, marker.range.end)));
+        self.popup = Some(Popup::new(
+            format!("Footnote {}", note.mark),
+            format!("{}\n\nEnter: go to the footnote, then '' to come back · f: next marker", note.text),
+        ));
+    }
+
+    fn close_side_panel(&mut self) {
+        let panel = self.side_panel.take();
+        self.push_undo(Snapshot::SidePanel(panel));
+        self.side_panel_focused = false;
+    }
+
+    /// Remember state about to be changed. A new change discards the redo history.
+    fn push_undo(&mut self, snapshot: Snapshot) {
+        self.undo_stack.push(snapshot);
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    fn undo(&mut self) {
+        match self.undo_stack.pop() {
+            Some(snapshot) => {
+                let current = self.restore(snapshot);
+                self.redo_stack.push(current);
+            }
+            None => self.status_message = "Nothing to undo".to_string(),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.redo_stack.pop() {
+            Some(snapshot) => {
+                let current = self.restore(snapshot);
+                self.undo_stack.push(current);
+            }
+            None => self.status_message = "Nothing to redo".to_string(),
+        }
+    }
+
+    /// Put a snapshot back into place and return the state it replaced.
+    fn restore(&mut self, snapshot: Snapshot) -> Snapshot {
+        match snapshot {
+            Snapshot::Search { query, results, current } => {
+                let replaced = Snapshot::Search {
+                    query: std::mem::replace(&mut self.search_query, query),
+                    results: std::mem::replace(&mut self.search_results, results),
+                    current: self.current_search_result,
+                };
+                self.current_search_result = current;
+                self.status_message = if self.search_query.is_empty() {
+                    "Search cleared".to_string()
+                } else {
+                    format!("Search for '{}' restored", self.search_query)
+                };
+                replaced
+            }
+            Snapshot::SidePanel(panel) => {
+                self.status_message = match &panel {
+                    Some(panel) => format!("{} restored", panel.title),
+                    None => "Side panel closed".to_string(),
+                };
+                self.side_panel_focused = false;
+                Snapshot::SidePanel(std::mem::replace(&mut self.side_panel, panel))
+            }
+            Snapshot::List(list) => {
+                self.status_message = match &list {
+                    Some((_, list)) => format!("{} restored", list.title),
+                    None => "List closed".to_string(),
+                };
+                Snapshot::List(std::mem::replace(&mut self.list, list))
+            }
+            Snapshot::Mark(mark, position) => {
+                let replaced = match position {
+                    Some(position) => self.doc_state.marks.insert(mark, position),
+                    None => self.doc_state.marks.remove(&mark),
+                };
+                self.status_message = match (position, self.doc_state.save()) {
+                    (_, Err(e)) => format!("Mark {} restored but not saved: {}", mark, e),
+                    (Some(position), Ok(())) => format!("Mark {} back on page {}", mark, position.page + 1),
+                    (None, Ok(())) => format!("Mark {} removed", mark),
+                };
+                Snapshot::Mark(mark, replaced)
+            }
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str, what: &str) {
+        self.status_message = match clipboard::copy(&self.config.clipboard.command, text) {
+            Ok(()) => format!("{} copied to clipboard", what),
+            Err(e) => format!("Copy failed: {}", e),
+        };
+    }
+
+    fn open_url(&mut self, url: &str) {
+        if let Err(e) = sandbox::check("Opening links") {
+            self.status_message = e.to_string();
+            return;
+        }
+        self.status_message = match external::launch(&self.config.browser.command, &[("url", url)]) {
+            Ok(()) => format!("Opened {}", url),
+            Err(e) => format!("Could not open {}: {}", url, e),
+        };
+    }
+
+    fn execute_search(&mut self) {
+        if self.input.is_empty() {
+            self.status_message = "Search query is empty".to_string();
+            return;
+        }
+        if self.search_all {
+            self.search_all_documents(self.input.text().to_string());
+        } else {
+            self.search(self.input.text().to_string());
+        }
+    }
+
+    /// Search the open document and every other PDF in its folder, reading
+    /// the others in the background.
+    fn search_all_documents(&mut self, query: String) {
+        let current = self.path.canonicalize().unwrap_or_else(|_| self.path.clone());
+        let others: Vec<PathBuf> = corpus::pdfs_in(&self.corpus_folder())
+            .into_iter()
+            .filter(|path| path.canonicalize().ok().as_ref() != Some(&current))
+            .collect();
+        let path = self.path.clone();
+        let Some(hits) = self.scan_pages(0..self.pages.len(), |texts| corpus::search_pages(&path, texts, &query)) else {
+            return;
+        };
+        self.status_message = format!("Searching {} documents for '{}'…", others.len() + 1, query);
+        let task_query = query.clone();
+        let (pagination, format) = (self.config.pagination, self.config.format);
+        let task = Task::spawn(move || {
+            let settings = corpus::settings(pagination, format);
+            let read = |path: &Path| corpus::cached_pages(path, &settings, |path| read_pages(path, pagination, format));
+            Ok(corpus::search(&others, &task_query, read))
+        });
+        self.corpus_task = Some((query, hits, task));
+    }
+
+    /// The folder cross-document search and `:docs` cover.
+    fn corpus_folder(&self) -> PathBuf {
+        match &self.corpus_dir {
+            Some(dir) => dir.clone(),
+            None => self.path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf(),
+        }
+    }
+
+    /// Fill the text cache for the corpus folder in the background, so searches only parse changed files.
+    fn index_corpus(&mut self) {
+        let paths = corpus::pdfs_in(&self.corpus_folder());
+        self.status_message = format!("Indexing {} documents…", paths.len());
+        let (pagination, format) = (self.config.pagination, self.config.format);
+        let settings = corpus::settings(pagination, format);
+        self.index_task = Some(Task::spawn(move || Ok(corpus::index(&paths, &settings, |path| read_pages(path, pagination, format)))));
+    }
+
+    fn show_documents(&mut self) {
+        let paths = corpus::pdfs_in(&self.corpus_folder());
+        if paths.is_empty() {
+            self.status_message = "No PDFs in this folder".to_string();
+            return;
+        }
+        let items = paths
+            .iter()
+            .map(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if *path == self.path { format!("● {}", name) } else { format!("  {}", name) }
+            })
+            .collect();
+        let mut list = ListPopup::new(
+            format!("Documents in {} ({})", self.corpus_folder().display(), paths.len()),
+            items,
+            "Enter open, / search all, Esc close",
+        );
+        list.selected = paths.iter().position(|path| *path == self.path).unwrap_or(0);
+        self.list = Some((ListKind::Documents(paths), list));
+    }
+
+    /// List cross-document hits grouped by file.
+    fn show_file_hits(&mut self, query: String, mut hits: Vec<FileHit>, failed: &[String]) {
+        hits.retain(|hit| !self.hidden(&hit.text));
+        if hits.is_empty() {
+            self.status_message = format!("No results found for '{}' in any document", query);
+            self.bell();
+            return;
+        }
+        let mut rows: Vec<Option<FileHit>> = Vec::new();
+        let mut items = Vec::new();
+        let mut files = 0;
+        for hit in hits {
+            if rows.last().and_then(Option::as_ref).is_none_or(|last| last.path != hit.path) {
+                let name = hit.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                items.push(format!("▸ {}", name));
+                rows.push(None);
+                files += 1;
+            }
+            items.push(format!("    p{}: {}", hit.page + 1, hit.text));
+            rows.push(Some(hit));
+        }
+        let matches = rows.iter().flatten().count();
+        if !failed.is_empty() {
+            self.status_message = format!("Could not read {}", failed.join(", "));
+        }
+        let list = ListPopup::new(
+            format!("'{}': {} matches in {} documents", query, matches, files),
+            items,
+            "Enter open, Esc close",
+        );
+        self.list = Some((ListKind::FileHits(query, rows), list));
+    }
+
+    /// Open the document of a cross-document hit, if it is another one, and go to the hit.
+    fn open_file_hit(&mut self, query: String, hit: &FileHit) {
+        if hit.path != self.path
+            && let Err(e) = self.load_document(hit.path.clone())
+        {
+            self.status_message = format!("Could not open {}: {}", hit.path.display(), e);
+            return;
+        }
+        self.search(query);
+        if let Some(index) = self.search_results.iter().position(|r| r.page == hit.page && r.line == hit.line) {
+            self.current_search_result = index;
+            self.go_to_search_result();
+        }
+    }
+
+    /// Every line of the document containing `query_lower`, reading pages that
+    /// were moved out from their files without keeping them in memory.
+    fn find_all(&mut self, query_lower: &str) -> Option<Vec<SearchResult>> {
+        let mut results = Vec::new();
+        for page in 0..self.pages.len() {
+            match self.budget.read(&self.pages, page) {
+                Ok(text) => find_in_page(page, &text, query_lower, |line| self.hidden(line), &mut results),
+                Err(e) => {
+                    self.status_message = e.to_string();
+                    return None;
+                }
+            }
+        }
+        Some(results)
+    }
+
+    fn search(&mut self, query: String) {
+        let Some(results) = self.find_all(&query.to_lowercase()) else {
+            return;
+        };
+        self.search_query = query;
+        self.search_results = results;
+        self.result_preview = None;
+
+        if self.search_results.is_empty() {
+            self.status_message = format!("No results found for '{}'", self.search_query);
+            self.bell();
+        } else {
+            self.current_search_result = 0;
+            self.go_to_search_result();
+        }
+        let query = self.search_query.clone();
+        self.run_plugin(|plugins| plugins.hook("on_search", vec![query.into()]));
+    }
+
+    fn go_to_search_result(&mut self) {
+        if let Some(&SearchResult { page, line }) = self.search_results.get(self.current_search_result) {
+            self.set_page(page);
+            let above = if self.config.search.center {
+                self.viewport_height / 2
+            } else {
+                self.config.search.context_lines
+            };
+            self.scroll_offset = self.offset_above(line, above);
+            self.flash_anchor(line);
+            if self.cursor.is_some() {
+                self.cursor = Some(Cursor { line, col: 0 });
+            }
+            self.status_message = format!(
+                "Result {} of {} for '{}'",
+                self.current_search_result + 1,
+                self.search_results.len(),
+                self.search_query
+            );
+        }
+    }
+
+    /// Show the result after (or before) the previewed one in the preview
+    /// pane, starting from the current result, and leave the view where it is.
+    fn preview_result(&mut self, forward: bool) {
+        let count = self.search_results.len();
+        if count == 0 {
+            self.status_message = "No search results to preview".to_string();
+            return;
+        }
+        let from = self.result_preview.unwrap_or(self.current_search_result);
+        let index = if forward { (from + 1) % count } else { (from + count - 1) % count };
+        self.result_preview = Some(index);
+        self.status_message = format!(
+            "Previewing result {} of {} for '{}' (Enter: go there, Esc: close)",
+            index + 1,
+            count,
+            self.search_query
+        );
+    }
+
+    fn go_to_previewed_result(&mut self) {
+        if let Some(index) = self.result_preview.take() {
+            self.current_search_result = index;
+            self.go_to_search_result();
+        }
+    }
+
+    fn next_search_result(&mut self) {
+        self.result_preview = None;
+        if !self.search_results.is_empty() {
+            self.current_search_result = (self.current_search_result + 1) % self.search_results.len();
+            self.go_to_search_result();
+        }
+    }
+
+    fn prev_search_result(&mut self) {
+        self.result_preview = None;
+        if !self.search_results.is_empty() {
+            self.current_search_result = if self.current_search_result == 0 {
+                self.search_results.len() - 1
+            } else {
+                self.current_search_result - 1
+            };
+            self.go_to_search_result();
+        }
+    }
+
+    fn cancel_input(&mut self) {
+        self.input_mode = InputMode::Normal;
+        self.input.clear();
+        self.status_message.clear();
+    }
+
+    fn clear_search(&mut self) {
+        let snapshot = Snapshot::Search {
+            query: std::mem::take(&mut self.search_query),
+            results: std::mem::take(&mut self.search_results),
+            current: self.current_search_result,
+        };
+        self.push_undo(snapshot);
+        self.result_preview = None;
+        self.current_search_result = 0;
+        self.status_message = "Search cleared".to_string();
+    }
+
+    fn handle_input(&mut self, c: char) {
+        match self.input_mode {
+            InputMode::PageJump => {
+                let mut typed = self.input.clone();
+                typed.insert(c);
+                if self.page_jump_possible(typed.text()) {
+                    self.input = typed;
+                } else if let Err(e) = self.page_jump_target(typed.text()) {
+                    self.status_message = e;
+                }
+            }
+            InputMode::Search | InputMode::Find | InputMode::Command => self.input.insert(c),
+            _ => {}
+        }
+    }
+
+    /// Add pasted text to the prompt being typed in, as one line: a query
+    /// copied across a line break arrives whole instead of being submitted
+    /// at the first newline.
+    fn paste(&mut self, text: &str) {
+        if !matches!(self.input_mode, InputMode::PageJump | InputMode::Search | InputMode::Find | InputMode::Command) {
+            self.status_message = "Paste into the search, page or command prompt (/, g or :)".to_string();
+            return;
+        }
+        let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        for c in line.chars().filter(|c| !c.is_control()) {
+            self.handle_input(c);
+        }
+    }
+
+    fn submit_input(&mut self) {
+        match self.input_mode {
+            InputMode::PageJump => {
+                match self.page_jump_target(self.input.text()) {
+                    Ok(page) => self.jump_to_page(page + 1),
+                    Err(e) => self.status_message = e,
+                }
+                self.input_mode = InputMode::Normal;
+                self.input.clear();
+            }
+            InputMode::Search => {
+                self.execute_search();
+                self.input_mode = InputMode::Normal;
+                self.input.clear();
+            }
+            InputMode::Find => {
+                let query = self.input.text().to_string();
+                self.input_mode = InputMode::Normal;
+                self.input.clear();
+                self.find_on_page(&query);
+            }
+            InputMode::Command => {
+                self.input_mode = InputMode::Normal;
+                self.execute_command();
+                self.input.clear();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let (argv, pager_start) = pager::take_start(env::args_os().collect(), &Args::command())?;
+    let args = Args::parse_from(argv);
+    if let Some(dir) = args.state_dir.as_deref() {
+        paths::set_portable(config::expand_tilde(dir));
+    }
+    if args.private {
+        paths::set_private();
+    }
+    if args.sandbox {
+        sandbox::enable();
+    }
+    let overrides = Overrides {
+        wpm: args.wpm,
+        ocr_lang: args.ocr_lang,
+        paginate: args.paginate,
+        whitespace: args.whitespace,
+        crop: args.crop,
+        exclude: args.exclude,
+    };
+    let mut config = Config::load(args.config.as_deref())?;
+    limits::set(&config.limits);
+
+    // A piped-in PDF stays on disk until the viewer closes
+    let stdin_pdf = match args.command {
+        None if pager::reads_stdin(args.file.as_deref()) => {
+            // Nothing to remember about a temporary copy
+            paths::set_private();
+            Some(pager::StdinPdf::read()?)
+        }
+        _ => None,
+    };
+    let mut corpus_dir = None;
+    // PDFs of a `dir` folder passed over because they could not be read
+    let mut skipped: Vec<String> = Vec::new();
+    let file = match args.command {
+        Some(Commands::Extract { file, format, output, highlight, color }) => {
+            let config = overrides.document_config(&config, &file)?;
+            let highlight = highlight.as_deref().map(|term| (term, color));
+            return extract(&file, format, output.as_deref(), highlight, config.pagination, config.format, &config.exclude);
+        }
+        Some(Commands::ExtractImages { file, output }) => return extract_images(&file, &output),
+        Some(Commands::Info { file }) => {
+            let config = overrides.document_config(&config, &file)?;
+            return print_info(&file, config.pagination, config.format);
+        }
+        Some(Commands::Diff { old, new, side_by_side }) => {
+            overrides.apply(&mut config)?;
+            return show_diff(&old, &new, side_by_side, &config);
+        }
+        Some(Commands::Bench { file, ocr }) => return bench::run(&file, overrides.document_config(&config, &file)?, ocr),
+        Some(Commands::Dir { dir }) => {
+            // Open the first PDF that loads; broken ones are still listed
+            let mut first = None;
+            for path in corpus::pdfs_in(&dir) {
+                match pdf::load(&path) {
+                    Ok(_) => {
+                        first = Some(path);
+                        break;
+                    }
+                    Err(_) => skipped.push(path.file_name().unwrap_or_default().to_string_lossy().into_owned()),
+                }
+            }
+            corpus_dir = Some(dir.clone());
+            match first {
+                Some(first) => first,
+                None if skipped.is_empty() => bail!("No PDF files in {}", dir.display()),
+                None => bail!("No readable PDF files in {}; could not read {}", dir.display(), skipped.join(", ")),
+            }
+        }
+        Some(Commands::Ctl { socket, command }) => {
+            let reply = remote::send(&socket, &remote::command_line(&command))?;
+            if !reply.is_empty() {
+                println!("{}", reply);
+            }
+            return Ok(());
+        }
+        None => match (&stdin_pdf, args.file) {
+            (Some(piped), _) => piped.path.clone(),
+            (None, Some(file)) => file,
+            (None, None) => Args::command().error(clap::error::ErrorKind::MissingRequiredArgument, "FILE is required unless a PDF is piped in").exit(),
+        },
+    };
+    // 1-based page and line to open at; `--page` wins over `+N`, and both
+    // over a link's. `--find` likewise wins over `+/pattern`
+    let mut search_start = args.find;
+    let mut start = args.page.map(|page| (page, None));
+    match pager_start {
+        Some(pager::Start::Page(page)) => start = start.or(Some((page, None))),
+        Some(pager::Start::Search(query)) => search_start = search_start.or(Some(query)),
+        None => {}
+    }
+    let file = match link::parse(&file.to_string_lossy()) {
+        Some(location) => {
+            let location = location?;
+            start = start.or(location.page.map(|page| (page, location.line)));
+            location.path
+        }
+        None => file,
+    };
+    let config = overrides.document_config(&config, &file)?;
+
+    if corpus_dir.is_none() && args.plain {
+        return plain(&file, args.line_numbers, config.pagination, config.format, &config.exclude);
+    }
+    // Piped or redirected, like `pdf_reader file.pdf | grep foo`: print the
+    // text as `bat` and friends do instead of drawing into the pipe
+    if corpus_dir.is_none() && (args.dump || !io::stdout().is_terminal()) {
+        return extract(&file, ExtractFormat::Text, None, None, config.pagination, config.format, &config.exclude);
+    }
+    if corpus_dir.is_none() && args.no_alt_screen {
+        let PdfText { pages, title, .. } = read_pdf(&file, config.pagination, document_format(&file, config.format))?;
+        let pages = pages.iter().map(|page| config.exclude.hide(page)).collect();
+        let page = start.map(|(page, _)| page - 1);
+        return Linear::new(pages, title).run(page, search_start.as_deref(), stdin_pdf.is_some());
+    }
+
+    // Read and parse PDF
+    let (PdfText { pages, outline, title, scanned, language, labels }, stream) = read_pdf_streaming(&file, FIRST_PAGES, config.pagination, document_format(&file, config.format))?;
+    
+    if corpus_dir.is_none() && stream.is_none() && pages.iter().all(|page| page.trim().is_empty()) && !scanned.contains(&true) {
+        println!("PDF file is empty or could not be parsed.");
+        return Ok(());
+    }
+
+    // Bind before touching the terminal so errors print normally
+    let server = args.listen.as_deref().map(Server::bind).transpose()?;
+
+    let mut terminal = enter_terminal(&config.keyboard)?;
+
+    // Create app and run
+    let mut app = App::new(file, pages, outline, config);
+    app.overrides = overrides;
+    app.title = title;
+    app.scanned = scanned;
+    app.language = language;
+    app.page_labels = labels;
+    app.start_loading(stream);
+    if app.config.spelling.enabled {
+        app.spelling = Some(app.spell_check());
+    }
+    app.server = server;
+    if corpus_dir.is_some() {
+        app.corpus_dir = corpus_dir;
+        app.search_all = true;
+        app.show_documents();
+        app.index_corpus();
+        if !skipped.is_empty() {
+            app.status_message = format!("Could not read {}; indexing the rest", skipped.join(", "));
+        }
+    }
+    app.load_plugins();
+    match start {
+        Some((page, Some(line))) => {
+            app.go_to_position(page - 1, line - 1);
+            app.anchor = Some((Position { page: page - 1, line: line - 1 }, Instant::now()));
+            app.status_message = format!("Opened at page {}, line {}", page, line);
+        }
+        Some((page, None)) => app.jump_to_page(page),
+        None => {}
+    }
+    if let Some(query) = search_start {
+        app.search_on_open(query);
+    }
+    let res = run_app(&mut terminal, &mut app);
+    app.run_shell_hook(Hook::Close);
+    let saved = app.save_progress();
+
+    leave_terminal(&mut terminal)?;
+    if let Err(err) = res {
+        println!("{err:?}");
+    }
+    if let Err(err) = saved {
+        eprintln!("Could not save reading progress: {err}");
+    }
+
+    Ok(())
+}
+
+/// The `extract` subcommand: convert the document and print or save it.
+fn extract(
+    path: &Path,
+    format: ExtractFormat,
+    output: Option<&Path>,
+    highlight: Option<(&str, ColorChoice)>,
+    pagination: PaginationConfig,
+    formatting: FormatConfig,
+    exclude: &ExcludeConfig,
+) -> Result<()> {
+    let PdfText { pages, outline, language, .. } = read_pdf(path, pagination, formatting)?;
+    let pages: Vec<String> = pages.iter().map(|page| exclude.hide(page)).collect();
+    if sandbox::defused() > 0 {
+        eprintln!("--sandbox left out {} streams that would expand past {} MB", sandbox::defused(), sandbox::MAX_STREAM_BYTES / (1024 * 1024));
+    }
+    let file_name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let source = export::ExportSource {
+        path,
+        file_name: &file_name,
+        pages: &pages,
+        outline: &outline,
+        annotations: &[],
+        language,
+    };
+    let text = match format {
+        ExtractFormat::Text => export::text(&source),
+        ExtractFormat::Md => export::markdown(&source),
+        ExtractFormat::Html => export::html(&source, highlight.filter(|(_, color)| *color != ColorChoice::Never).map(|(term, _)| term)),
+    };
+    let colored = |color| match color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => output.is_none() && io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+    let text = match highlight {
+        Some((term, color)) if !matches!(format, ExtractFormat::Html) && colored(color) => export::highlight_ansi(&text, term),
+        _ => text,
+    };
+
+    match output {
+        Some(output) => fs::write(output, text)?,
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// `--plain`: the text with page headers, and line numbers if asked, through
+/// the pager when on a terminal.
+fn plain(path: &Path, line_numbers: bool, pagination: PaginationConfig, formatting: FormatConfig, exclude: &ExcludeConfig) -> Result<()> {
+    let PdfText { pages, outline, language, .. } = read_pdf(path, pagination, document_format(path, formatting))?;
+    // Numbered lines keep the viewer's numbers, so only they are left out one by one
+    let pages: Vec<String> = if line_numbers { pages } else { pages.iter().map(|page| exclude.hide(page)).collect() };
+    let file_name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let source = export::ExportSource {
+        path,
+        file_name: &file_name,
+        pages: &pages,
+        outline: &outline,
+        annotations: &[],
+        language,
+    };
+    let text = if line_numbers { export::numbered_text(&source, |line| exclude.matches(line)) } else { export::text(&source) };
+    if io::stdout().is_terminal() {
+        page_output(&text)
+    } else {
+        print!("{}", text);
+        Ok(())
+    }
+}
+
+/// Show `text` in `$PAGER`, `less` by default, or print it where no pager
+/// can run.
+fn page_output(text: &str) -> Result<()> {
+    let pager = env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()).unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let child = external::interactive(&pager, &[]).and_then(|mut command| Ok(command.stdin(std::process::Stdio::piped()).spawn()?));
+    let Ok(mut child) = child else {
+        print!("{}", text);
+        return Ok(());
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // Quitting the pager early closes the pipe; that is not an error
+        let _ = io::Write::write_all(&mut stdin, text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+fn enter_terminal(config: &KeyboardConfig) -> io::Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    enable_raw_mode()?;
+    keyboard::enable(config.kitty_protocol)?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+    Terminal::new(CrosstermBackend::new(stdout))
+}
+
+fn leave_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
+    keyboard::disable()?;
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture, DisableBracketedPaste)?;
+    terminal.show_cursor()
+}
+
+/// The `diff` subcommand: browse the line differences between two documents.
+fn show_diff(old: &Path, new: &Path, side_by_side: bool, config: &Config) -> Result<()> {
+    let (pagination, format) = (config.pagination, config.format);
+    let (old_text, new_text) = (read_pdf(old, pagination, format)?, read_pdf(new, pagination, format)?);
+    let name = |path: &Path| path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+    let hide = |pages: &[String]| pages.iter().map(|page| config.exclude.hide(page)).collect::<Vec<_>>();
+    let lines = diff::diff_pages(&hide(&old_text.pages), &hide(&new_text.pages));
+    let mut view = DiffView::new(name(old), name(new), lines, side_by_side);
+
+    let mut terminal = enter_terminal(&config.keyboard)?;
+    let res = (|| -> io::Result<()> {
+        loop {
+            terminal.draw(|f| view.render(f))?;
+            if let Event::Key(key) = event::read()?
+                && keyboard::pressed(&key)
+                && !view.key(key.code)
+            {
+                return Ok(());
+            }
+        }
+    })();
+    leave_terminal(&mut terminal)?;
+    Ok(res?)
+}
+
+/// The `extract-images` subcommand: export the images of every page into `dir`.
+/// Images that cannot be converted are reported and skipped.
+fn extract_images(path: &Path, dir: &Path) -> Result<()> {
+    let doc = pdf::load(path)?;
+    fs::create_dir_all(dir)?;
+    let (mut saved, mut failed) = (0, 0);
+    for page in 0..doc.get_pages().len() {
+        for image in pdf::page_images(&doc, page) {
+            let output = dir.join(images::export_name(path, page, &image));
+            match images::export(&doc, &image, &output) {
+                Ok(()) => {
+                    println!("{}", output.display());
+                    saved += 1;
+                }
+                Err(e) => {
+                    eprintln!("Page {}: {}", page + 1, e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    match (saved, failed) {
+        (0, 0) => eprintln!("No embedded images in {}", path.display()),
+        (_, 0) => eprintln!("Saved {} images", saved),
+        _ => eprintln!("Saved {} images, {} could not be converted", saved, failed),
+    }
+    Ok(())
+}
+
+/// The `info` subcommand.
+fn print_info(path: &Path, pagination: PaginationConfig, format: FormatConfig) -> Result<()> {
+    println!("{}", document_info(path, read_pdf(path, pagination, format)?.language)?);
+    Ok(())
+}
+
+/// File and PDF properties, encryption and attachments, one `Label: value` per line.
+fn document_info(path: &Path, language: Option<Language>) -> Result<String> {
+    let doc = pdf::load(path)?;
+    let mut lines = vec![format!("File: {}", path.display())];
+    if let Ok(meta) = fs::metadata(path) {
+        lines.push(format!("Size: {}", format_size(meta.len())));
+    }
+    lines.push(format!("Pages: {}", doc.get_pages().len()));
+    lines.push(match language {
+        Some(language) => format!("Language: {} ({}, detected)", language.name(), language.code()),
+        None => "Language: unknown".to_string(),
+    });
+    lines.extend(pdf::info(&doc).into_iter().map(|(label, value)| format!("{}: {}", label, value)));
+
+    lines.push(String::new());
+    match pdf::encryption(path)? {
+        None => lines.push("Encryption: none".to_string()),
+        Some(encryption) => {
+            lines.push(format!("Encryption: {} ({} security handler, revision {})", encryption.algorithm, encryption.filter, encryption.revision));
+            if !encryption.metadata_encrypted {
+                lines.push("Metadata: not encrypted".to_string());
+            }
+            lines.push("Permissions:".to_string());
+            for (permission, allowed) in encryption.permission_list() {
+                lines.push(format!("  {}: {}", permission, if allowed { "allowed" } else { "not allowed" }));
+            }
+        }
+    }
+
+    let attachments = pdf::attachments(&doc);
+    lines.push(String::new());
+    if attachments.is_empty() {
+        lines.push("No attachments".to_string());
+    } else {
+        lines.push(format!("Attachments ({})", attachments.len()));
+        lines.extend(attachments.iter().map(|a| format!("  {}", describe_attachment(a))));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// A byte count in the largest unit that keeps it at or above 1, e.g. `12.3 KB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// One line describing an attachment: name, size, page and description.
+fn describe_attachment(attachment: &Attachment) -> String {
+    let mut line = attachment.name.clone();
+    if let Some(size) = attachment.size {
+        line += &format!("  {}", format_size(size));
+    }
+    if let Some(page) = attachment.page {
+        line += &format!("  (page {})", page + 1);
+    }
+    if let Some(description) = &attachment.description {
+        line += &format!("  — {}", description);
+    }
+    line
+}
+
+/// What the viewer reads from a PDF.
+struct PdfText {
+    pages: Vec<String>,
+    outline: Vec<OutlineEntry>,
+    /// Title from the document information dictionary
+    title: Option<String>,
+    /// Per page, whether it looks scanned: images but hardly any text
+    scanned: Vec<bool>,
+    language: Option<Language>,
+    /// Page labels from the PDF, when its pages are the viewer's pages
+    labels: Vec<String>,
+}
+
+/// Pages extracted up front when the viewer opens a document; the rest follow in the background.
+const FIRST_PAGES: usize = 10;
+
+/// Pager for `--plain` when `$PAGER` is unset: quit if the text fits on one
+/// screen, and pass colors through.
+const DEFAULT_PAGER: &str = "less -FR";
+
+/// A page extracted in the background: its text and whether it looks scanned.
+type StreamedPage = (String, bool);
+
+/// The document's page count and the pages after the first ones, as they are extracted.
+type PageStream = (usize, Stream<StreamedPage>);
+
+fn read_pdf(path: &Path, pagination: PaginationConfig, format: FormatConfig) -> Result<PdfText> {
+    read_pdf_streaming(path, usize::MAX, pagination, format).map(|(text, _)| text)
+}
+
+/// Read the first `first` pages now and stream the others from a background
+/// thread, so a long document opens as fast as a short one.
+fn read_pdf_streaming(path: &Path, first: usize, pagination: PaginationConfig, format: FormatConfig) -> Result<(PdfText, Option<PageStream>)> {
+    let doc = pdf::load(path).map_err(|e| {
+        anyhow::anyhow!("Could not extract text from PDF: {}. The PDF might be image-based or use unsupported encoding.", e)
+    })?;
+
+    let page_nums: Vec<u32> = doc.get_pages().keys().copied().collect();
+    if page_nums.len() > first {
+        let read_page = move |doc: &lopdf::Document, index: usize, page_num: u32| {
+            let text = format::page(&pdf::extract_page(doc, page_num, format.crop), format);
+            let scanned = is_scanned(&text) && !pdf::page_images(doc, index).is_empty();
+            (text, scanned)
+        };
+        let (pages, scanned): (Vec<String>, Vec<bool>) =
+            page_nums[..first].iter().enumerate().map(|(index, &page_num)| read_page(&doc, index, page_num)).unzip();
+        let text = PdfText {
+            language: language::detect(&pages),
+            pages,
+            outline: pdf::outline(&doc),
+            title: pdf::title(&doc),
+            scanned,
+            labels: pdf::page_labels(&doc),
+        };
+        let total = page_nums.len();
+        let stream = Stream::spawn(move |tx| {
+            for (index, &page_num) in page_nums.iter().enumerate().skip(first) {
+                if tx.send(read_page(&doc, index, page_num)).is_err() {
+                    return;
+                }
+            }
+        });
+        return Ok((text, Some((total, stream))));
+    }
+
+    // Real page boundaries keep page numbers in line with the PDF and its outline;
+    // blank pages are kept for the same reason
+    let raw_pages = pdf::extract_pages(&doc, format.crop);
+    let pages = if raw_pages.len() > 1 {
+        raw_pages.iter().map(|page| format::page(page, format)).collect()
+    } else {
+        paginate::split(&raw_pages.concat(), pagination, format)
+    };
+    let (scanned, labels) = if raw_pages.len() == pages.len() {
+        let scanned = (0..pages.len())
+            .map(|page| is_scanned(&pages[page]) && !pdf::page_images(&doc, page).is_empty())
+            .collect();
+        (scanned, pdf::page_labels(&doc))
+    } else {
+        (vec![false; pages.len()], Vec::new())
+    };
+    let text = PdfText {
+        language: language::detect(&pages),
+        pages,
+        outline: pdf::outline(&doc),
+        title: pdf::title(&doc),
+        scanned,
+        labels,
+    };
+    Ok((text, None))
+}
+
+/// The formatting for `pdf`, with the `:indent` and `:math` choices saved for it.
+fn document_format(pdf: &Path, format: FormatConfig) -> FormatConfig {
+    let state = DocState::load(pdf);
+    FormatConfig {
+        indent: state.indent.unwrap_or(format.indent),
+        math: state.math.unwrap_or(format.math),
+        ..format
+    }
+}
+
+/// Just the page texts, for the corpus text cache.
+fn read_pages(path: &Path, pagination: PaginationConfig, format: FormatConfig) -> Result<Vec<String>> {
+    Ok(read_pdf(path, pagination, format)?.pages)
+}
+
+/// Whether a page's text layer is too thin to be what the page shows.
+fn is_scanned(text: &str) -> bool {
+    // Page numbers and running heads survive on otherwise scanned pages
+    const MIN_TEXT_CHARS: usize = 40;
+    text.chars().filter(|c| !c.is_whitespace()).count() < MIN_TEXT_CHARS
+}
+
+/// `text` with whitespace and control characters replaced by visible marks,
+/// keeping its line breaks.
+fn visible_controls(text: &str) -> String {
+    let mut visible = String::with_capacity(text.len());
+    for line in text.split('\n') {
+        let content = line.trim_end_matches(' ');
+        for c in content.chars() {
+            match c {
+                '\t' => visible.push('→'),
+                '\u{a0}' => visible.push('⍽'),
+                c => match format::escape(c) {
+                    Some(mark) => visible.push_str(&mark),
+                    None => visible.push(c),
+                },
+            }
+        }
+        visible.extend(std::iter::repeat_n('·', line.len() - content.len()));
+        visible.push_str("↵\n");
+    }
+    visible
+}
+
+/// Split `line` into spans, patching each highlight's style over the text it covers.
+/// Later highlights are layered on top of earlier ones.
+fn styled_line<'a>(line: &'a str, highlights: &[(Range<usize>, Style)]) -> Line<'a> {
+    if highlights.is_empty() {
+        return Line::from(vec![Span::raw(line)]);
+    }
+
+    let mut bounds: Vec<usize> = vec![0, line.len()];
+    for (range, _) in highlights {
+        bounds.push(range.start.min(line.len()));
+        bounds.push(range.end.min(line.len()));
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let spans = bounds
+        .windows(2)
+        .filter(|w| w[0] < w[1])
+        .map(|w| {
+            let style = highlights
+                .iter()
+                .filter(|(range, _)| range.start <= w[0] && w[1] <= range.end)
+                .fold(Style::default(), |style, (_, patch)| style.patch(*patch));
+            Span::styled(&line[w[0]..w[1]], style)
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans)
+}
+
+/// The `width` columns of `line` starting at column `start`.
+fn slice_columns(line: Line<'_>, start: usize, width: usize) -> Line<'_> {
+    let mut spans = Vec::new();
+    let mut column = 0;
+    for span in line.spans {
+        let chars = span.content.chars().count();
+        let from = start.saturating_sub(column).min(chars);
+        let to = (start + width).saturating_sub(column).min(chars);
+        if from < to {
+            spans.push(Span::styled(span.content.chars().skip(from).take(to - from).collect::<String>(), span.style));
+        }
+        column += chars;
+    }
+    Line::from(spans)
+}
+
+fn count_words(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Columns `<` and `>` move wide tables by.
+const TABLE_SCROLL_STEP: usize = 8;
+
+/// Lines shown above and below a search hit in the result preview.
+const PREVIEW_CONTEXT: usize = 2;
+
+/// How long the line jumped to stays lit up.
+const ANCHOR_FLASH: Duration = Duration::from_secs(1);
+
+/// How long the visual bell keeps the screen reversed.
+const BELL_FLASH: Duration = Duration::from_millis(150);
+
+/// How often the event loop wakes up to run background work without input.
+const TICK_RATE: Duration = Duration::from_millis(100);
+
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    loop {
+        // Pages moved out to keep within `memory.max_text_mb` come back before they are drawn
+        app.load_pages_in_use();
+        terminal.draw(|f| ui(f, app))?;
+        // Images go on top of the text grid ratatui just drew
+        if let Some(grid) = app.thumbnail_grid.as_mut()
+            && let Some(thumbnails) = grid.thumbnails.as_mut()
+        {
+            thumbnails.place(&grid.cells)?;
+        }
+        if let Some(view) = app.page_view.as_mut() {
+            view.place()?;
+        }
+        app.update_image_preview()?;
+
+        if event::poll(TICK_RATE)? {
+            match event::read()? {
+                Event::Key(key) if keyboard::pressed(&key) => handle_key(app, key),
+                Event::Mouse(mouse) => handle_mouse(app, mouse),
+                Event::Paste(text) => app.paste(&text),
+                _ => {}
+            }
+        }
+        app.tick();
+
+        if let Some(mut command) = app.foreground.take()
+            && let Err(e) = run_foreground(terminal, &mut command)
+        {
+            app.status_message = format!("Could not run command: {}", e);
+        }
+
+        if app.should_quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Hand the terminal to `command` until it exits, then restore the TUI.
+fn run_foreground<B: Backend>(terminal: &mut Terminal<B>, command: &mut ProcessCommand) -> io::Result<()> {
+    keyboard::disable()?;
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, DisableBracketedPaste)?;
+    let status = command.status();
+    enable_raw_mode()?;
+    keyboard::enable(true)?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+    terminal.clear()?;
+    status.map(|_| ())
+}
+
+/// Columns of the minimap strip.
+const MINIMAP_WIDTH: u16 = 12;
+
+/// Lines of large print shown below the content.
+const LARGE_PRINT_LINES: usize = 2;
+
+/// The line being read in large block letters, from the cursor if there is
+/// one: the cursor's line and the part of it around the cursor, or else the
+/// first line in view.
+fn render_large_print(f: &mut Frame, app: &App, area: Rect) {
+    let lines = app.current_lines();
+    let (line_idx, offset) = match app.cursor {
+        Some(cursor) => (cursor.line, lines.get(cursor.line).map_or(0, |line| cursor::byte_offset(line, cursor.col))),
+        None => (app.scroll_offset, 0),
+    };
+    let text = lines.get(line_idx).map_or("", |line| line.trim_end());
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let parts = large::lines(text, inner_width);
+    let first = parts.iter().rposition(|part| part.start <= offset).unwrap_or(0);
+    let rows: Vec<Line> = parts[first..]
+        .iter()
+        .take(LARGE_PRINT_LINES)
+        .enumerate()
+        .flat_map(|(i, part)| {
+            let gap = (i > 0).then(String::new);
+            gap.into_iter().chain(large::render(&text[part.clone()]))
+        })
+        .map(Line::from)
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Large print: line {} (L to close) ", line_idx + 1))
+        .style(Style::default().fg(app.theme.header));
+    let paragraph = Paragraph::new(rows).block(block).style(Style::default().fg(app.theme.text));
+    f.render_widget(paragraph, area);
+}
+
+/// The page in miniature beside the content: shaded by how much text each
+/// cell covers, with the lines in view on a lighter ground and the rows with
+/// search hits colored.
+fn render_minimap(f: &mut Frame, app: &App, area: Rect) {
+    let lines = app.current_lines();
+    let rows = area.height as usize;
+    let page = app.current_page;
+    let mut colors = vec![None; rows];
+    let hits = app.search_results.iter().filter(|result| result.page == page).map(|result| (result.line, HIT_TICK));
+    let finds = app.page_find.iter().filter(|find| find.page == page).flat_map(|find| find.matches.iter().map(|(line, _)| (*line, FIND_TICK)));
+    for (line, color) in hits.chain(finds) {
+        if let Some(row) = colors.get_mut(minimap::row_of(line, lines.len(), rows)) {
+            *row = Some(color);
+        }
+    }
+    let in_view = app.visible_lines();
+    let text: Vec<Line> = minimap::render(&lines, rows, area.width as usize)
+        .into_iter()
+        .zip(minimap::row_lines(lines.len(), rows))
+        .zip(colors)
+        .map(|((shades, range), color)| {
+            let mut style = Style::default().fg(color.unwrap_or(Color::Gray));
+            if range.start < in_view.end && in_view.start < range.end {
+                style = style.bg(Color::DarkGray);
+            }
+            Line::from(Span::styled(shades, style))
+        })
+        .collect();
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(text), area);
+}
+
+/// Colors of the ticks for search hits, finds on the page, tags and marks.
+const HIT_TICK: Color = Color::Yellow;
+const FIND_TICK: Color = Color::LightMagenta;
+const TAG_TICK: Color = Color::Green;
+const MARK_TICK: Color = Color::Red;
+
+/// A scrollbar on the content pane's right border: a thumb for the lines in
+/// view and a tick at each line of the page with a search hit or a mark.
+fn render_page_marks(f: &mut Frame, app: &App, area: Rect) {
+    let rows = area.height.saturating_sub(2) as usize;
+    let lines = app.current_lines();
+    if rows == 0 || area.width < 2 || lines.is_empty() {
+        return;
+    }
+    // Placed by the rows lines wrap to, not by line count
+    let mut starts = Vec::with_capacity(lines.len());
+    let mut total = 0;
+    for line in &lines {
+        starts.push(total);
+        total += app.line_rows(line);
+    }
+    let scale = |page_row: usize| (page_row.min(total - 1) * rows / total) as u16;
+    let row_of = |line: usize| scale(starts[line.min(lines.len() - 1)]);
+    let x = area.right() - 1;
+    let buffer = f.buffer_mut();
+    if total > rows {
+        let top = starts[app.scroll_offset.min(lines.len() - 1)];
+        let (first, last) = (scale(top), scale(top + rows - 1));
+        for row in first..=last.max(first) {
+            buffer.get_mut(x, area.y + 1 + row).set_symbol("┃");
+        }
+    }
+
+    let page = app.current_page;
+    let hits = app.search_results.iter().filter(|result| result.page == page).map(|result| (result.line, HIT_TICK));
+    let finds = app.page_find.iter().filter(|find| find.page == page).flat_map(|find| find.matches.iter().map(|(line, _)| (*line, FIND_TICK)));
+    let marks = app.doc_state.marks.values().filter(|mark| mark.page == page).map(|mark| (mark.line, MARK_TICK));
+    for (line, color) in hits.chain(finds).chain(marks) {
+        buffer.get_mut(x, area.y + 1 + row_of(line)).set_symbol("━").set_fg(color);
+    }
+}
+
+/// Ticks on the progress bar at the pages with search hits, tags or marks,
+/// placed the way a click there picks the page.
+fn render_document_marks(f: &mut Frame, app: &App, area: Rect) {
+    let pages = app.pages.len();
+    if pages == 0 || area.width == 0 || area.height == 0 {
+        return;
+    }
+    let column_of = |page: usize| area.x + (page * area.width as usize / pages) as u16;
+    let hits = app.search_results.iter().map(|result| (result.page, HIT_TICK));
+    let tags = app.doc_state.tags.values().flatten().map(|&page| (page, TAG_TICK));
+    let marks = app.doc_state.marks.values().map(|mark| (mark.page, MARK_TICK));
+    let buffer = f.buffer_mut();
+    for (page, color) in hits.chain(tags).chain(marks).filter(|(page, _)| *page < pages) {
+        buffer.get_mut(column_of(page), area.y).set_symbol("┃").set_fg(color);
+    }
+}
+
+/// Draw the previewed search result with a few lines around it, along the
+/// bottom of the content pane.
+fn render_result_preview(f: &mut Frame, app: &App, area: Rect) {
+    let Some(index) = app.result_preview else {
+        return;
+    };
+    let (Some(result), count) = (app.search_results.get(index), app.search_results.len()) else {
+        return;
+    };
+    let lines: Vec<&str> = app.pages.get(result.page).map_or(Vec::new(), |page| page.lines().collect());
+    let first = result.line.saturating_sub(PREVIEW_CONTEXT);
+    let last = (result.line + PREVIEW_CONTEXT + 1).min(lines.len());
+    let query_lower = app.search_query.to_lowercase();
+    let match_style = Style::default().fg(app.theme.highlight).bg(app.theme.r#match);
+    let text: Vec<Line> = lines[first.min(last)..last]
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !app.hidden(line))
+        .map(|(i, line)| {
+            let mut highlights: Vec<(Range<usize>, Style)> = Vec::new();
+            if first + i == result.line {
+                highlights.push((0..line.len(), Style::default().add_modifier(Modifier::BOLD)));
+                highlights.extend(search::matches(line, &query_lower).into_iter().map(|range| (range, match_style)));
+            }
+            styled_line(line, &highlights)
+        })
+        .collect();
+
+    let height = (PREVIEW_CONTEXT as u16 * 2 + 3).min(area.height);
+    let area = Rect { y: area.bottom() - height, height, ..area };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " Result {} of {}, page {} ((/) more, Enter go, Esc close) ",
+            index + 1,
+            count,
+            result.page + 1
+        ))
+        .style(Style::default().fg(app.theme.header).bg(app.theme.background));
+    let paragraph = Paragraph::new(text).block(block).style(Style::default().fg(app.theme.text));
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+/// Draw the thumbnail grid: a bordered cell per page, labelled with its number.
+/// Cells show the start of the page's text until an image is placed over them.
+fn render_thumbnail_grid(f: &mut Frame, app: &mut App, area: Rect) {
+    let columns = app.config.thumbnails.columns.max(1);
+    let Some(grid) = app.thumbnail_grid.as_mut() else {
+        return;
+    };
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Pages (h/j/k/l move, r/R rotate, Enter open, t/Esc close) ")
+        .style(Style::default().fg(app.theme.header).bg(app.theme.background));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // Terminal cells are about twice as tall as wide; pages are about 1.3 times taller than wide
+    let cell_width = (inner.width / columns as u16).max(4);
+    let cell_height = (cell_width * 2 / 3).max(4);
+    let visible_rows = (inner.height / cell_height).max(1) as usize;
+    let row = grid.selected / columns;
+    if row < grid.top_row {
+        grid.top_row = row;
+    } else if row >= grid.top_row + visible_rows {
+        grid.top_row = row + 1 - visible_rows;
+    }
+
+    grid.cells.clear();
+    let first = grid.top_row * columns;
+    let last = (first + visible_rows * columns).min(app.pages.len());
+    for page in first..last {
+        let cell = Rect {
+            x: inner.x + ((page - first) % columns) as u16 * cell_width,
+            y: inner.y + ((page - first) / columns) as u16 * cell_height,
+            width: cell_width,
+            height: cell_height,
+        }
+        .intersection(inner);
+        let style = if page == grid.selected {
+            Style::default().fg(app.theme.highlight).bg(app.theme.r#match)
+        } else {
+            Style::default().fg(app.theme.text)
+        };
+        let cell_block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} ", page + 1))
+            .border_style(style);
+        let image_area = cell_block.inner(cell);
+        let has_image = grid.thumbnails.as_ref().is_some_and(|t| t.rendered(page));
+        let preview = if has_image {
+            String::new()
+        } else {
+            app.budget.read(&app.pages, page).map(|text| text.trim().to_string()).unwrap_or_default()
+        };
+        let text = Paragraph::new(preview)
+            .block(cell_block)
+            .style(Style::default().fg(app.theme.gutter))
+            .wrap(Wrap { trim: true });
+        f.render_widget(text, cell);
+        grid.cells.push((page, image_area));
+    }
+}
+
+/// Draw the frame of the page view over the content pane; the tiles go on
+/// top once the text grid is drawn.
+fn render_page_view(f: &mut Frame, app: &mut App, area: Rect) {
+    let Some(view) = app.page_view.as_mut() else {
+        return;
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " Page {} at {} (+/- zoom, w fit, h/j/k/l pan, n/p pages, I/Esc close) ",
+            view.page + 1,
+            view.describe()
+        ))
+        .style(Style::default().fg(app.theme.header).bg(app.theme.background));
+    view.set_area(block.inner(area));
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+}
+
+/// Draw the layout view over the content pane, laying the page out again
+/// when the pane's width has changed.
+fn render_layout_view(f: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL);
+    let inner = block.inner(area);
+    if app.layout_view.as_ref().is_some_and(|view| view.columns != inner.width as usize) {
+        app.lay_out_page(inner.width as usize);
+    }
+    let Some(view) = &app.layout_view else {
+        return;
+    };
+    let rows: Vec<Line> = if view.rows.is_empty() {
+        vec![Line::from("(no text on this page)")]
+    } else {
+        view.rows.iter().skip(view.scroll).map(|row| Line::from(row.as_str())).collect()
+    };
+    let block = block
+        .title(format!(
+            " Layout of page {}{} (j/k scroll, n/p pages, a amounts, V/Esc close) ",
+            view.page + 1,
+            if view.align_amounts { ", amounts aligned right" } else { "" }
+        ))
+        .style(Style::default().fg(app.theme.header).bg(app.theme.background));
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(rows).block(block).style(Style::default().fg(app.theme.text)), area);
+}
+
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    if app.input_mode == InputMode::Normal
+        && let Some(view) = app.page_view.as_mut()
+    {
+        let (zoom, sideways) = (mouse.modifiers.contains(KeyModifiers::CONTROL), mouse.modifiers.contains(KeyModifiers::SHIFT));
+        match mouse.kind {
+            MouseEventKind::ScrollDown if zoom => view.zoom(-1),
+            MouseEventKind::ScrollUp if zoom => view.zoom(1),
+            MouseEventKind::ScrollDown if sideways => view.pan(4, 0),
+            MouseEventKind::ScrollUp if sideways => view.pan(-4, 0),
+            MouseEventKind::ScrollDown => view.pan(0, 3),
+            MouseEventKind::ScrollUp => view.pan(0, -3),
+            _ => {}
+        }
+        return;
+    }
+    if let MouseEventKind::Down(MouseButton::Left) = mouse.kind
+        && app.input_mode == InputMode::Normal
+        && app.progress_area.height > 0
+        && mouse.row == app.progress_area.y
+    {
+        app.click_progress(mouse.column);
+    } else if let MouseEventKind::Down(MouseButton::Left) = mouse.kind
+        && app.input_mode == InputMode::Normal
+        && app.minimap_area.intersects(Rect::new(mouse.column, mouse.row, 1, 1))
+    {
+        app.click_minimap(mouse.row);
+    } else if app.input_mode == InputMode::Normal && app.popup.is_none() && app.list.is_none() {
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => app.click_text(mouse.column, mouse.row),
+            MouseEventKind::Drag(MouseButton::Left) => app.drag_text(mouse.column, mouse.row),
+            MouseEventKind::Up(MouseButton::Left) => app.end_drag(),
+            MouseEventKind::Moved => app.hover(mouse.column, mouse.row),
+            _ => {}
+        }
+    }
+}
+
+fn handle_key(app: &mut App, key: KeyEvent) {
+    if let Some(op) = app.pending_key.take() {
+        // The register named when recording starts is not part of the macro
+        if !matches!(op, PendingKey::Record) {
+            app.macros.record(key);
+        }
+        if let KeyCode::Char(register) = key.code {
+            app.pending_key(op, register);
+        }
+        return;
+    }
+    let plain_normal = app.input_mode == InputMode::Normal
+        && app.popup.is_none()
+        && app.list.is_none()
+        && !app.side_panel_focused;
+    if plain_normal && key.code == KeyCode::Char('Q') {
+        match app.macros.stop_recording() {
+            Some((register, count)) => app.status_message = format!("Recorded {} keys into @{}", count, register),
+            None => app.pending_key = Some(PendingKey::Record),
+        }
+        return;
+    }
+    app.macros.record(key);
+    if plain_normal && key.code == KeyCode::Char('@') {
+        app.pending_key = Some(PendingKey::Replay);
+        return;
+    }
+
+    match app.input_mode.clone() {
+        InputMode::Normal if app.popup.is_some() => {
+            if let Some(popup) = app.popup.as_mut() {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.popup = None;
+                        app.footnote_target = None;
+                    }
+                    KeyCode::Char('f') => {
+                        if let Some((_, marker)) = app.footnote_target {
+                            app.show_footnote_after(marker);
+                        }
+                    }
+                    KeyCode::Enter => {
+                        if let Some((target, _)) = app.footnote_target.take() {
+                            app.popup = None;
+                            app.jump_to(target);
+                            app.status_message = "At the footnote; '' goes back".to_string();
+                        }
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => popup.scroll_down(),
+                    KeyCode::Up | KeyCode::Char('k') => popup.scroll_up(),
+                    _ => {}
+                }
+            }
+        }
+        InputMode::Normal if app.list.is_some() => app.list_key(key.code),
+        InputMode::Normal if app.thumbnail_grid.is_some() => app.thumbnail_key(key.code),
+        InputMode::Normal if app.layout_view.is_some() => app.layout_key(key.code),
+        InputMode::Normal if app.page_view.is_some() => app.page_view_key(key.code),
+        InputMode::Normal if app.side_panel_focused => {
+            if let Some(panel) = app.side_panel.as_mut() {
+                match key.code {
+                    KeyCode::Tab | KeyCode::Esc => app.side_panel_focused = false,
+                    KeyCode::Char('q') => app.close_side_panel(),
+                    KeyCode::Down | KeyCode::Char('j') => panel.scroll_down(),
+                    KeyCode::Up | KeyCode::Char('k') => panel.scroll_up(),
+                    _ => {}
+                }
+            } else {
+                app.side_panel_focused = false;
+            }
+        }
+        InputMode::Normal if app.chord(key) => {}
+        InputMode::Normal if app.speech_key(key.code) => {}
+        InputMode::Normal if app.move_cursor(key.code) => {}
+        InputMode::Normal => {
+            match key.code {
+                KeyCode::Char('q') => app.quit(),
+                KeyCode::Esc => {
+                    if app.result_preview.is_some() {
+                        app.result_preview = None;
+                        app.status_message.clear();
+                    } else if app.selection_anchor.is_some() {
+                        app.toggle_visual();
+                    } else if app.cursor.is_some() {
+                        app.toggle_cursor();
+                    } else if app.page_find.is_some() {
+                        app.page_find = None;
+                        app.status_message.clear();
+                    } else if !app.search_query.is_empty() {
+                        app.clear_search();
+                    } else {
+                        app.quit();
+                    }
+                },
+                KeyCode::Char('n') if app.finding() => app.step_find(true),
+                KeyCode::Char('N') if app.finding() => app.step_find(false),
+                KeyCode::Right | KeyCode::Char('n') => app.next_page(),
+                KeyCode::Left | KeyCode::Char('p') => app.prev_page(),
+                KeyCode::Down | KeyCode::Char('j') => app.scroll_down(),
+                KeyCode::Up | KeyCode::Char('k') => app.scroll_up(),
+                KeyCode::Char('g') => app.start_page_jump(),
+                KeyCode::Char('/') => app.start_search(),
+                KeyCode::Char('F') => app.next_search_result(),
+                KeyCode::Char('B') => app.prev_search_result(),
+                KeyCode::Char(')') => app.preview_result(true),
+                KeyCode::Char('(') => app.preview_result(false),
+                KeyCode::Enter if app.result_preview.is_some() => app.go_to_previewed_result(),
+                KeyCode::Char('c') => app.toggle_cursor(),
+                KeyCode::Char('K') => app.lookup_word(),
+                KeyCode::Char('v') => app.toggle_visual(),
+                KeyCode::Char('T') => app.translate_selection(),
+                KeyCode::Char('S') => app.toggle_speech(),
+                KeyCode::Char('C') => app.clip_selection(),
+                KeyCode::Char('A') => app.send_to_notes(),
+                KeyCode::Char('o') => app.open_in_viewer(),
+                KeyCode::Char('O') => app.ocr_page(),
+                KeyCode::Char('R') => app.reload(),
+                KeyCode::Char('D') => app.run_builtin_command("display"),
+                KeyCode::Char('L') => app.run_builtin_command("large"),
+                KeyCode::Char('V') => app.run_builtin_command("layout"),
+                KeyCode::Char('I') => app.run_builtin_command("page-image"),
+                KeyCode::Char('X') => app.run_builtin_command("excluded"),
+                KeyCode::Char('E') => app.edit_source(),
+                KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => app.start_find(),
+                KeyCode::Char('f') => app.follow_footnote(),
+                KeyCode::Char('>') => app.table_scroll += TABLE_SCROLL_STEP,
+                KeyCode::Char('<') => app.table_scroll = app.table_scroll.saturating_sub(TABLE_SCROLL_STEP),
+                KeyCode::Char('y') => app.yank(false),
+                KeyCode::Char('Y') => app.yank(true),
+                KeyCode::Char('t') => app.toggle_thumbnails(),
+                KeyCode::Char('u') => app.undo(),
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => app.redo(),
+                KeyCode::Char('m') => app.pending_key = Some(PendingKey::SetMark),
+                KeyCode::Char('"') => app.pending_key = Some(PendingKey::Register),
+                KeyCode::Char('\'') => app.pending_key = Some(PendingKey::JumpToMark),
+                KeyCode::Char(':') => app.start_command(),
+                KeyCode::Tab if app.side_panel.is_some() => app.side_panel_focused = true,
+                KeyCode::Home => {
+                    app.set_page(0);
+                    app.scroll_offset = 0;
+                    app.reset_cursor();
+                },
+                KeyCode::End => {
+                    app.set_page(app.pages.len().saturating_sub(1));
+                    app.scroll_offset = 0;
+                    app.reset_cursor();
+                },
+                KeyCode::Char(c) if app.plugins.has_key(c) => app.run_plugin(|plugins| plugins.run_key(c)),
+                _ => {}
+            }
+        }
+        InputMode::Confirm => app.answer_confirm(matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y'))),
+        InputMode::PageJump | InputMode::Search | InputMode::Find | InputMode::Command => {
+            match key.code {
+                KeyCode::Enter => app.submit_input(),
+                KeyCode::Esc => app.cancel_input(),
+                KeyCode::Tab if app.input_mode == InputMode::Search => app.search_all = !app.search_all,
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    app.pending_key = Some(PendingKey::PasteRegister);
+                }
+                _ if app.input.edit(key) => {}
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => app.handle_input(c),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn ui(f: &mut Frame, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(if app.config.layout.progress_bar { 1 } else { 0 }),
+            Constraint::Min(1), 
+            Constraint::Length(3),
+            Constraint::Length(if app.input_mode != InputMode::Normal || !app.status_message.is_empty() { 3 } else { 0 })
+        ])
+        .split(f.size());
+    app.viewport_height = chunks[2].height.saturating_sub(2) as usize;
+    f.render_widget(Block::default().style(Style::default().bg(app.theme.background)), f.size());
+
+    // Header
+    let prompt = match app.input_mode {
+        InputMode::PageJump => Some(format!(
+            "Enter page number (1-{}, +N/-N{}): ",
+            app.page_total,
+            if app.page_labels.is_empty() { "" } else { " or label" }
+        )),
+        InputMode::Search if app.search_all => Some("Search all PDFs in folder: ".to_string()),
+        InputMode::Search => Some("Search: ".to_string()),
+        InputMode::Find => Some(format!("Find on page {}: ", app.current_page + 1)),
+        InputMode::Command => Some(":".to_string()),
+        InputMode::Normal | InputMode::Confirm => None,
+    };
+    let header_text = if let Some(prompt) = &prompt {
+        let hint = if app.input_mode == InputMode::PageJump { app.page_jump_hint() } else { String::new() };
+        format!("{}{}{}", prompt, app.input.text(), hint)
+    } else if app.input_mode == InputMode::Confirm {
+        "Confirm (y/n)".to_string()
+    } else if !app.config.layout.header.is_empty() {
+        app.fill_layout_template(&app.config.layout.header, "")
+    } else {
+        let tags = app.page_tags(app.current_page);
+        format!(
+            "PDF Reader - Page {} of {} | {}{}{}{}{}",
+            app.current_page + 1,
+            app.page_count_label(),
+            app.reading_status(),
+            if tags.is_empty() { String::new() } else { format!(" | tags: {}", tags.join(", ")) },
+            app.tag_filter.as_ref().map_or_else(String::new, |tag| format!(" | filter: {}", tag)),
+            app.macros.recording().map_or_else(String::new, |register| format!(" | recording @{}", register)),
+            if paths::private() { " | private" } else { "" }
+        )
+    };
+    
     let header = Paragraph::new(header_text)
         .block(Block::default().borders(Borders::ALL))
-        .style(Style::default().fg(if app.input_mode != InputMode::Normal { Color::Yellow } else { Color::Cyan }));
+        .style(Style::default().fg(if app.input_mode != InputMode::Normal { app.theme.prompt } else { app.theme.header }));
     f.render_widget(header, chunks[0]);
+    // The terminal cursor marks where typing goes, and input methods open
+    // their composition window there; wide characters take two columns
+    if let Some(prompt) = &prompt {
+        let typed = prompt.width() + app.input.before_cursor().width();
+        let last = chunks[0].width.saturating_sub(2).saturating_sub(1) as usize;
+        f.set_cursor(chunks[0].x + 1 + typed.min(last) as u16, chunks[0].y + 1);
+    }
+
+    app.progress_area = chunks[1];
+    if app.config.layout.progress_bar {
+        let progress = LineGauge::default()
+            .ratio(app.document_progress())
+            .label("")
+            .line_set(symbols::line::THICK)
+            .gauge_style(Style::default().fg(app.theme.header).bg(app.theme.flash));
+        f.render_widget(progress, chunks[1]);
+        render_document_marks(f, app, chunks[1]);
+    }
+
+    // Optional side panel to the right of the content
+    let (content_area, panel_area) = if app.side_panel.is_some() {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(chunks[2]);
+        (columns[0], Some(columns[1]))
+    } else {
+        (chunks[2], None)
+    };
+    let (content_area, large_print_area) = if app.large_print {
+        let height = (LARGE_PRINT_LINES * (large::GLYPH_ROWS + 1) + 1) as u16;
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(height)])
+            .split(content_area);
+        (rows[0], Some(rows[1]))
+    } else {
+        (content_area, None)
+    };
+    if let Some(area) = large_print_area {
+        app.viewport_height = content_area.height.saturating_sub(2) as usize;
+        render_large_print(f, app, area);
+    }
+    let (content_area, minimap_area) = if app.minimap {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(1), Constraint::Length(MINIMAP_WIDTH)])
+            .split(content_area);
+        // Level with the text inside the content pane's border
+        (columns[0], Rect { y: columns[1].y + 1, height: columns[1].height.saturating_sub(2), ..columns[1] })
+    } else {
+        (content_area, Rect::default())
+    };
+    app.minimap_area = minimap_area;
+    if app.minimap {
+        render_minimap(f, app, minimap_area);
+    }
 
     // Content with search highlighting
     if let Some(content) = app.pages.get(app.current_page) {
+        // Every line takes at least one row, wrapped or not, so no more than
+        // the area's height can show; long pages only style what is seen
+        let visible_rows = content_area.height.saturating_sub(2) as usize;
+        // Line numbers count the page's lines, not the rows they wrap to
+        let number_width = if app.line_numbers { content.lines().count().to_string().len() + 1 } else { 0 };
+        let content_width = (content_area.width.saturating_sub(2) as usize).saturating_sub(number_width).max(1);
+        app.text_area = Rect {
+            x: content_area.x + 1 + number_width as u16,
+            y: content_area.y + 1,
+            width: content_width as u16,
+            height: visible_rows as u16,
+        };
+        let wrap_widths = app.wrap_widths();
+        let gutter_style = Style::default().fg(app.theme.gutter);
+        // Table rows are cut to the pane instead of wrapped; `<`/`>` scroll them sideways
+        let widest_row = content.lines().filter(|line| tables::is_row(line)).map(|line| line.chars().count()).max().unwrap_or(0);
+        app.table_scroll = app.table_scroll.min(widest_row.saturating_sub(content_width));
+        let table_scroll = app.table_scroll;
+
+        let anchor_style = Style::default().bg(app.theme.flash).add_modifier(Modifier::BOLD);
+        let anchor = app.flashing_anchor().filter(|anchor| anchor.page == app.current_page).map(|anchor| anchor.line);
         let search_query_lower = app.search_query.to_lowercase();
-        
+        let match_style = Style::default().fg(app.theme.highlight).bg(app.theme.r#match);
+        let find_style = Style::default().fg(app.theme.highlight).bg(app.theme.find);
+        let find_current_style = Style::default().fg(app.theme.highlight).bg(app.theme.find_current).add_modifier(Modifier::BOLD);
+        let cursor_style = Style::default().add_modifier(Modifier::REVERSED);
+        let selection_style = Style::default().bg(app.theme.selection);
+        let spoken_lines = app.speech.as_ref().and_then(Speech::current_lines);
+        let misspelled_style = Style::default().fg(app.theme.error).add_modifier(Modifier::UNDERLINED);
+        let matches = app.match_cache.get(app.current_page, content, &search_query_lower);
+        let footnote_style = Style::default().fg(app.theme.link).add_modifier(Modifier::UNDERLINED);
+        let footnotes = app.footnote_cache.get(app.current_page, content);
+        let code = app.code_cache.get(app.current_page, content, &app.config.code);
+
         let lines: Vec<Line> = content
             .lines()
-            .skip(app.scroll_offset)
             .enumerate()
-            .map(|(_line_idx, line)| {
-                if !app.search_query.is_empty() && line.to_lowercase().contains(&search_query_lower) {
-                    // Highlight search results
-                    let mut spans = Vec::new();
-                    let line_lower = line.to_lowercase();
-                    let mut last_end = 0;
-                    
-                    while let Some(start) = line_lower[last_end..].find(&search_query_lower) {
-                        let actual_start = last_end + start;
-                        let actual_end = actual_start + app.search_query.len();
-                        
-                        // Add text before match
-                        if actual_start > last_end {
-                            spans.push(Span::raw(&line[last_end..actual_start]));
-                        }
-                        
-                        // Add highlighted match
-                        spans.push(Span::styled(
-                            &line[actual_start..actual_end],
-                            Style::default().fg(Color::Black).bg(Color::Yellow)
-                        ));
-                        
-                        last_end = actual_end;
-                    }
-                    
-                    // Add remaining text
-                    if last_end < line.len() {
-                        spans.push(Span::raw(&line[last_end..]));
-                    }
-                    
-                    Line::from(spans)
-                } else {
-                    Line::from(vec![Span::raw(line)])
+            .skip(app.scroll_offset)
+            // Not `app.hidden`, which would borrow all of `app` with the caches in use
+            .filter(|(_, line)| app.show_excluded || !app.config.exclude.matches(line))
+            .take(visible_rows)
+            .flat_map(|(line_idx, line)| {
+                // Code colors come first so search matches and the selection paint over them
+                let mut highlights: Vec<(Range<usize>, Style)> = code.get(line_idx).cloned().unwrap_or_default();
+                if anchor == Some(line_idx) {
+                    highlights.insert(0, (0..line.len(), anchor_style));
+                }
+                highlights.extend(matches.get(line_idx).into_iter().flatten().map(|range| (range.clone(), match_style)));
+                if let Some(find) = app.page_find.as_ref().filter(|find| find.page == app.current_page) {
+                    highlights.extend(find.on_line(line_idx).map(|(range, current)| (range, if current { find_current_style } else { find_style })));
                 }
+
+                highlights.extend(footnotes.on_line(line_idx).map(|marker| (marker.range.clone(), footnote_style)));
+                if let Some(spelling) = &app.spelling {
+                    highlights.extend(spelling.ranges(app.current_page, line).into_iter().map(|range| (range, misspelled_style)));
+                }
+                if spoken_lines.as_ref().is_some_and(|lines| lines.contains(&line_idx)) {
+                    highlights.insert(0, (0..line.len(), Style::default().add_modifier(Modifier::UNDERLINED)));
+                }
+
+                if let (Some(anchor), Some(cursor)) = (app.selection_anchor, app.cursor)
+                    && let Some(range) = cursor::selection_range(line, line_idx, anchor, cursor)
+                {
+                    highlights.push((range, selection_style));
+                }
+                if let Some(cursor) = app.cursor.filter(|c| c.line == line_idx) {
+                    let start = cursor::byte_offset(line, cursor.col);
+                    let end = cursor::byte_offset(line, cursor.col + 1);
+                    highlights.push((start..end, cursor_style));
+                }
+
+                let mut styled = styled_line(line, &highlights);
+                if app.cursor.is_some_and(|c| c.line == line_idx && cursor::byte_offset(line, c.col) == line.len()) {
+                    styled.spans.push(Span::styled(" ", cursor_style));
+                }
+                if tables::is_row(line) {
+                    styled = slice_columns(styled, table_scroll, content_width);
+                }
+                let rows = match wrap_widths {
+                    Some((first, rest)) => wrap::wrap_line(&styled, first, rest),
+                    None => vec![slice_columns(styled, 0, content_width)],
+                };
+                rows.into_iter().enumerate().map(move |(row, mut styled)| {
+                    let gutter = match (row, number_width) {
+                        (0, 0) => None,
+                        (0, width) => Some(format!("{:>w$} ", line_idx + 1, w = width - 1)),
+                        (_, 0) => Some(format!("{} ", wrap::CONTINUATION)),
+                        (_, width) => Some(format!("{:>w$} ", wrap::CONTINUATION, w = width - 1)),
+                    };
+                    if let Some(gutter) = gutter {
+                        styled.spans.insert(0, Span::styled(gutter, gutter_style));
+                    }
+                    styled
+                })
             })
+            .take(visible_rows)
             .collect();
 
         let text = Text::from(lines);
+        let title = match &app.ocr_task {
+            Some((page, started, _)) if *page == app.current_page => {
+                const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+                let frame = (started.elapsed().as_millis() / TICK_RATE.as_millis()) as usize % SPINNER.len();
+                format!("Content [{} running OCR…]", SPINNER[frame])
+            }
+            _ if app.scanned.get(app.current_page).copied().unwrap_or(false) => {
+                "Content [scanned page — press O to OCR]".to_string()
+            }
+            _ if widest_row > content_width => format!(
+                "Content [table columns {}–{} of {}, </> scroll]",
+                table_scroll + 1,
+                table_scroll + content_width,
+                widest_row
+            ),
+            _ => "Content".to_string(),
+        };
+        // Wrapped above, so rows with continuation marks are known
         let paragraph = Paragraph::new(text)
-            .block(Block::default().borders(Borders::ALL).title("Content"))
-            .wrap(Wrap { trim: true })
-            .style(Style::default().fg(Color::White));
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(Style::default().fg(app.theme.text));
         
-        f.render_widget(paragraph, chunks[1]);
+        f.render_widget(paragraph, content_area);
+        if app.config.layout.scrollbar {
+            render_page_marks(f, app, content_area);
+        }
+    }
+
+    if app.result_preview.is_some() {
+        render_result_preview(f, app, content_area);
     }
 
     // Controls footer
     let controls = if app.input_mode == InputMode::Normal {
-        if !app.search_query.is_empty() {
-            "g (goto page) | / (search) | F/B (next/prev result) | ←/→ (pages) | ↑/↓ (scroll) | Home/End | Esc (clear search) | q (quit)"
+        if app.cursor.is_some() {
+            "h/j/k/l (move) | w/b (word) | 0/$ (line) | v (select) | C (clip) | K (define) | T (translate) | n/p (pages) | c/Esc (exit) | q (quit)"
+        } else if app.finding() {
+            "n/N (next/prev match on page) | Ctrl+f (find again) | / (search) | ←/→ (pages) | ↑/↓ (scroll) | Esc (stop finding) | q (quit)"
+        } else if !app.search_query.is_empty() {
+            "g (goto page) | / (search) | F/B (next/prev result) | (/) (preview) | ←/→ (pages) | ↑/↓ (scroll) | Home/End | Esc (clear search) | q (quit)"
         } else {
-            "g (goto page) | / (search) | ←/→ (pages) | ↑/↓ (scroll) | Home/End | q/Esc (quit)"
+            "g (goto page) | / (search) | c (cursor) | ←/→ (pages) | ↑/↓ (scroll) | Home/End | q/Esc (quit)"
         }
+    } else if app.input_mode == InputMode::Confirm {
+        "y (yes) | any other key (no)"
     } else {
-        "Enter (submit) | Esc (cancel) | Backspace (delete)"
+        "Enter (submit) | Esc (cancel) | ←/→ Home/End (move) | Ctrl-W (delete word) | Ctrl-U (delete to start)"
     };
     
-    let footer = Paragraph::new(controls)
+    let footer_text = if app.input_mode == InputMode::Normal && !app.config.layout.footer.is_empty() {
+        app.fill_layout_template(&app.config.layout.footer, controls)
+    } else {
+        controls.to_string()
+    };
+    let footer = Paragraph::new(footer_text)
         .block(Block::default().borders(Borders::ALL))
-        .style(Style::default().fg(Color::Yellow));
-    f.render_widget(footer, chunks[2]);
+        .style(Style::default().fg(app.theme.footer));
+    f.render_widget(footer, chunks[3]);
 
     // Status message
     if app.input_mode != InputMode::Normal || !app.status_message.is_empty() {
         let status = Paragraph::new(app.status_message.as_str())
             .block(Block::default().borders(Borders::ALL).title("Status"))
-            .style(Style::default().fg(Color::Green));
-        f.render_widget(status, chunks[3]);
+            .style(Style::default().fg(app.theme.status));
+        f.render_widget(status, chunks[4]);
+    }
+
+    if let (Some(panel), Some(area)) = (&app.side_panel, panel_area) {
+        let hint = if app.side_panel_focused { "j/k scroll, Tab back, q close" } else { "Tab to focus" };
+        panel.render_in(f, area, hint);
+    }
+
+    if let Some((_, list)) = &app.list {
+        list.render(f, chunks[2]);
+    }
+
+    if app.thumbnail_grid.is_some() {
+        render_thumbnail_grid(f, app, chunks[2]);
+    }
+
+    if app.layout_view.is_some() {
+        render_layout_view(f, app, chunks[2]);
+    }
+
+    if app.page_view.is_some() {
+        render_page_view(f, app, chunks[2]);
+    }
+
+    if let Some(popup) = &app.popup {
+        popup.render(f, chunks[2]);
+        app.popup_area = popup::inner_area(chunks[2]);
+    }
+
+    if app.visual_bell.is_some_and(|rung| rung.elapsed() < BELL_FLASH) {
+        for cell in &mut f.buffer_mut().content {
+            cell.modifier.toggle(Modifier::REVERSED);
+        }
     }
+    display::apply(f.buffer_mut(), app.display_filter);
 }
@@ -0,0 +1,264 @@
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+/// LaTeX commands that stand for one symbol.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"), ("epsilon", "ε"), ("varepsilon", "ε"),
+    ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"), ("vartheta", "ϑ"), ("iota", "ι"), ("kappa", "κ"),
+    ("lambda", "λ"), ("mu", "μ"), ("nu", "ν"), ("xi", "ξ"), ("pi", "π"), ("varpi", "ϖ"), ("rho", "ρ"),
+    ("varrho", "ϱ"), ("sigma", "σ"), ("varsigma", "ς"), ("tau", "τ"), ("upsilon", "υ"), ("phi", "ϕ"),
+    ("varphi", "φ"), ("chi", "χ"), ("psi", "ψ"), ("omega", "ω"), ("Gamma", "Γ"), ("Delta", "Δ"),
+    ("Theta", "Θ"), ("Lambda", "Λ"), ("Xi", "Ξ"), ("Pi", "Π"), ("Sigma", "Σ"), ("Upsilon", "Υ"),
+    ("Phi", "Φ"), ("Psi", "Ψ"), ("Omega", "Ω"),
+    ("times", "×"), ("cdot", "·"), ("pm", "±"), ("mp", "∓"), ("div", "÷"), ("ast", "∗"), ("circ", "∘"),
+    ("le", "≤"), ("leq", "≤"), ("ge", "≥"), ("geq", "≥"), ("ne", "≠"), ("neq", "≠"), ("ll", "≪"), ("gg", "≫"),
+    ("approx", "≈"), ("equiv", "≡"), ("sim", "∼"), ("simeq", "≃"), ("cong", "≅"), ("propto", "∝"),
+    ("infty", "∞"), ("partial", "∂"), ("nabla", "∇"), ("sum", "∑"), ("prod", "∏"), ("int", "∫"),
+    ("iint", "∬"), ("oint", "∮"), ("in", "∈"), ("notin", "∉"), ("ni", "∋"), ("subset", "⊂"),
+    ("subseteq", "⊆"), ("supset", "⊃"), ("supseteq", "⊇"), ("cup", "∪"), ("cap", "∩"), ("setminus", "∖"),
+    ("emptyset", "∅"), ("varnothing", "∅"), ("forall", "∀"), ("exists", "∃"), ("neg", "¬"), ("lnot", "¬"),
+    ("wedge", "∧"), ("land", "∧"), ("vee", "∨"), ("lor", "∨"), ("oplus", "⊕"), ("otimes", "⊗"),
+    ("to", "→"), ("rightarrow", "→"), ("leftarrow", "←"), ("gets", "←"), ("leftrightarrow", "↔"),
+    ("Rightarrow", "⇒"), ("implies", "⇒"), ("Leftarrow", "⇐"), ("Leftrightarrow", "⇔"), ("iff", "⇔"),
+    ("mapsto", "↦"), ("uparrow", "↑"), ("downarrow", "↓"), ("ldots", "…"), ("dots", "…"), ("cdots", "⋯"),
+    ("vdots", "⋮"), ("ddots", "⋱"), ("langle", "⟨"), ("rangle", "⟩"), ("lceil", "⌈"), ("rceil", "⌉"),
+    ("lfloor", "⌊"), ("rfloor", "⌋"), ("mid", "∣"), ("parallel", "∥"), ("perp", "⊥"), ("angle", "∠"),
+    ("prime", "′"), ("ell", "ℓ"), ("hbar", "ℏ"), ("Re", "ℜ"), ("Im", "ℑ"), ("aleph", "ℵ"), ("degree", "°"),
+    ("top", "⊤"), ("bot", "⊥"), ("vdash", "⊢"), ("models", "⊨"), ("star", "⋆"), ("bullet", "•"),
+    ("quad", " "), ("qquad", "  "), ("lbrace", "{"), ("rbrace", "}"), ("backslash", "\\"),
+];
+
+/// Commands whose argument is shown as it is, e.g. `\mathrm{d}x`.
+const STYLES: &[&str] = &[
+    "text", "textrm", "textit", "textbf", "mathrm", "mathit", "mathbf", "mathsf", "mathtt", "mathcal",
+    "mathscr", "mathfrak", "boldsymbol", "bm", "operatorname", "hat", "bar", "vec", "tilde", "dot", "overline",
+];
+
+/// Sizing commands that only change how the next delimiter is drawn.
+const SIZING: &[&str] = &["left", "right", "big", "Big", "bigg", "Bigg", "bigl", "bigr", "Bigl", "Bigr", "displaystyle", "limits"];
+
+const SUPERSCRIPTS: &[(char, char)] = &[
+    ('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'), ('5', '⁵'), ('6', '⁶'), ('7', '⁷'), ('8', '⁸'),
+    ('9', '⁹'), ('+', '⁺'), ('-', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'), ('a', 'ᵃ'), ('b', 'ᵇ'), ('c', 'ᶜ'),
+    ('d', 'ᵈ'), ('e', 'ᵉ'), ('f', 'ᶠ'), ('g', 'ᵍ'), ('h', 'ʰ'), ('i', 'ⁱ'), ('j', 'ʲ'), ('k', 'ᵏ'), ('l', 'ˡ'),
+    ('m', 'ᵐ'), ('n', 'ⁿ'), ('o', 'ᵒ'), ('p', 'ᵖ'), ('r', 'ʳ'), ('s', 'ˢ'), ('t', 'ᵗ'), ('u', 'ᵘ'), ('v', 'ᵛ'),
+    ('w', 'ʷ'), ('x', 'ˣ'), ('y', 'ʸ'), ('z', 'ᶻ'), ('T', 'ᵀ'), ('′', '′'), ('∗', '*'),
+];
+
+const SUBSCRIPTS: &[(char, char)] = &[
+    ('0', '₀'), ('1', '₁'), ('2', '₂'), ('3', '₃'), ('4', '₄'), ('5', '₅'), ('6', '₆'), ('7', '₇'), ('8', '₈'),
+    ('9', '₉'), ('+', '₊'), ('-', '₋'), ('=', '₌'), ('(', '₍'), (')', '₎'), ('a', 'ₐ'), ('e', 'ₑ'), ('h', 'ₕ'),
+    ('i', 'ᵢ'), ('j', 'ⱼ'), ('k', 'ₖ'), ('l', 'ₗ'), ('m', 'ₘ'), ('n', 'ₙ'), ('o', 'ₒ'), ('p', 'ₚ'), ('r', 'ᵣ'),
+    ('s', 'ₛ'), ('t', 'ₜ'), ('u', 'ᵤ'), ('v', 'ᵥ'), ('x', 'ₓ'),
+];
+
+const DOUBLE_STRUCK: &[(char, char)] = &[('C', 'ℂ'), ('N', 'ℕ'), ('P', 'ℙ'), ('Q', 'ℚ'), ('R', 'ℝ'), ('Z', 'ℤ')];
+
+/// Math set off in the text: `$…$`, `$$…$$`, `\(…\)` and `\[…\]`. As in
+/// Pandoc, a `$` pair only counts when the math touches both dollars and no
+/// digit follows the closing one, so "$5 and $10" stays prices.
+static MATH_SPAN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\$\$(.+?)\$\$|\$([^\s$](?:[^$]*[^\s$])?)\$|\\\((.+?)\\\)|\\\[(.+?)\\\]").unwrap()
+});
+
+/// A symbol command in running text, outside any math span.
+static COMMAND: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\\([A-Za-z]+)\b").unwrap());
+
+/// Render recognizable math in a page as Unicode: LaTeX source left in the
+/// text layer, and letters from the Mathematical Alphanumeric Symbols block
+/// that math fonts map to and many terminal fonts lack.
+pub fn render(text: &str) -> Cow<'_, str> {
+    let plain = plain_letters(text);
+    if !plain.contains(['$', '\\']) {
+        return plain;
+    }
+    let spans = MATH_SPAN.replace_all(&plain, |caps: &Captures| {
+        let whole = &caps[0];
+        let end = caps.get(0).map_or(0, |m| m.end());
+        let priced = caps.get(2).is_some() && plain[end..].starts_with(|c: char| c.is_ascii_digit());
+        match (1..=4).find_map(|group| caps.get(group)) {
+            Some(math) if !priced => convert(math.as_str()),
+            _ => whole.to_string(),
+        }
+    });
+    let symbols = COMMAND.replace_all(&spans, |caps: &Captures| {
+        symbol(&caps[1]).map_or_else(|| caps[0].to_string(), str::to_string)
+    });
+    Cow::Owned(symbols.into_owned())
+}
+
+fn symbol(name: &str) -> Option<&'static str> {
+    SYMBOLS.iter().find(|(command, _)| *command == name).map(|(_, symbol)| *symbol)
+}
+
+fn lookup(table: &[(char, char)], c: char) -> Option<char> {
+    table.iter().find(|(from, _)| *from == c).map(|(_, to)| *to)
+}
+
+/// Convert the LaTeX of one math span.
+fn convert(math: &str) -> String {
+    let mut parser = Parser { chars: math.chars().collect(), at: 0 };
+    let mut out = parser.expression();
+    // A stray `}` ends a group that never opened
+    while parser.peek().is_some() {
+        parser.at += 1;
+        out.push('}');
+        out.push_str(&parser.expression());
+    }
+    out
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    /// Everything up to the end or an unmatched `}`.
+    fn expression(&mut self) -> String {
+        let mut out = String::new();
+        while let Some(c) = self.peek() {
+            if c == '}' {
+                break;
+            }
+            out.push_str(&self.token());
+        }
+        out
+    }
+
+    /// One unit: a group, a command with its arguments, a script or a character.
+    fn token(&mut self) -> String {
+        let Some(c) = self.peek() else {
+            return String::new();
+        };
+        self.at += 1;
+        match c {
+            '{' => {
+                let inner = self.expression();
+                if self.peek() == Some('}') {
+                    self.at += 1;
+                }
+                inner
+            }
+            '\\' => self.command(),
+            '^' => script(&self.argument(), SUPERSCRIPTS, '^'),
+            '_' => script(&self.argument(), SUBSCRIPTS, '_'),
+            '~' => " ".to_string(),
+            c => c.to_string(),
+        }
+    }
+
+    /// The argument of a command or script: a group, or the next single token.
+    fn argument(&mut self) -> String {
+        while self.peek() == Some(' ') {
+            self.at += 1;
+        }
+        self.token()
+    }
+
+    fn command(&mut self) -> String {
+        let start = self.at;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.at += 1;
+        }
+        if self.at == start {
+            // `\,` and friends are spacing; `\{` and friends escape a character
+            let Some(c) = self.peek() else {
+                return "\\".to_string();
+            };
+            self.at += 1;
+            return match c {
+                ',' | ';' | ':' | ' ' | '\\' => " ".to_string(),
+                '!' => String::new(),
+                c => c.to_string(),
+            };
+        }
+        let name: String = self.chars[start..self.at].iter().collect();
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.argument();
+                let denominator = self.argument();
+                format!("{}/{}", operand(&numerator), operand(&denominator))
+            }
+            "sqrt" => {
+                let mut degree = String::new();
+                if self.peek() == Some('[') {
+                    self.at += 1;
+                    while let Some(c) = self.peek().filter(|&c| c != ']') {
+                        degree.push(c);
+                        self.at += 1;
+                    }
+                    self.at += 1;
+                }
+                let radicand = operand(&self.argument());
+                match degree.as_str() {
+                    "" => format!("√{}", radicand),
+                    "3" => format!("∛{}", radicand),
+                    "4" => format!("∜{}", radicand),
+                    _ => format!("{}√{}", script(&degree, SUPERSCRIPTS, '^'), radicand),
+                }
+            }
+            "mathbb" => self.argument().chars().map(|c| lookup(DOUBLE_STRUCK, c).unwrap_or(c)).collect(),
+            name if STYLES.contains(&name) => self.argument(),
+            name if SIZING.contains(&name) => String::new(),
+            name => symbol(name).map_or_else(|| format!("\\{}", name), str::to_string),
+        }
+    }
+}
+
+/// `x` or `(x + 1)`: parenthesized unless it is a single term.
+fn operand(text: &str) -> String {
+    if text.chars().all(|c| c.is_alphanumeric() || c == '.') {
+        text.to_string()
+    } else {
+        format!("({})", text)
+    }
+}
+
+/// A superscript or subscript in script characters when each has one, or
+/// marked with `^`/`_` otherwise.
+fn script(text: &str, table: &[(char, char)], mark: char) -> String {
+    match text.chars().map(|c| lookup(table, c)).collect::<Option<String>>() {
+        Some(scripted) if !scripted.is_empty() => scripted,
+        _ if text.chars().count() == 1 => format!("{}{}", mark, text),
+        _ => format!("{}({})", mark, text),
+    }
+}
+
+/// Replace letters and digits from the Mathematical Alphanumeric Symbols
+/// block (𝑥, 𝐀, 𝛼, 𝟙) with their plain forms.
+fn plain_letters(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(|c| ('\u{1d400}'..='\u{1d7ff}').contains(&c) || c == 'ℎ') {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.chars().map(plain_letter).collect())
+}
+
+fn plain_letter(c: char) -> char {
+    const GREEK: &str = "ΑΒΓΔΕΖΗΘϴΙΚΛΜΝΞΟΠΡΣΤΥΦΧΨΩ∇αβγδεζηθικλμνξοπρςστυφχψω∂ϵϑϰϕϱϖ";
+    let code = c as u32;
+    let mapped = match code {
+        // Thirteen styles of A–Z and a–z
+        0x1d400..=0x1d6a3 => {
+            let index = (code - 0x1d400) % 52;
+            char::from_u32(if index < 26 { 'A' as u32 + index } else { 'a' as u32 + index - 26 })
+        }
+        0x1d6a4 => Some('ı'),
+        0x1d6a5 => Some('ȷ'),
+        // Five styles of Greek, each with nabla and the variant letters
+        0x1d6a8..=0x1d7c9 => GREEK.chars().nth(((code - 0x1d6a8) % 58) as usize),
+        0x1d7ca => Some('Ϝ'),
+        0x1d7cb => Some('ϝ'),
+        // Five styles of digits
+        0x1d7ce..=0x1d7ff => char::from_u32('0' as u32 + (code - 0x1d7ce) % 10),
+        // Italic h lives outside the block
+        0x210e => Some('h'),
+        _ => None,
+    };
+    mapped.unwrap_or(c)
+}
@@ -0,0 +1,150 @@
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::{paths, state};
+
+/// Keeps a document's extracted text within a memory budget by moving the
+/// least recently viewed pages out to files, and reading them back when they
+/// are shown or a whole-document feature needs them.
+///
+/// A page that is out is an empty string in the page list.
+pub struct PageBudget {
+    /// Bytes of page text to keep in memory; 0 means no limit
+    limit: usize,
+    dir: PathBuf,
+    /// Per page, the clock reading when it was last shown
+    last_used: Vec<u64>,
+    clock: u64,
+    /// Per page, whether its text is in its file rather than in memory
+    out: Vec<bool>,
+    /// Per page, whether its file holds the current text
+    saved: Vec<bool>,
+}
+
+impl PageBudget {
+    /// In private mode there is no limit, so no page text goes to disk.
+    pub fn new(limit_mb: usize, pdf: &Path) -> Self {
+        Self {
+            limit: if paths::private() { 0 } else { limit_mb * 1024 * 1024 },
+            // Per process: the files only stand in for memory of this session
+            dir: env::temp_dir()
+                .join(format!("pdf_reader-{}", process::id()))
+                .join(state::file_key(pdf)),
+            last_used: Vec::new(),
+            clock: 0,
+            out: Vec::new(),
+            saved: Vec::new(),
+        }
+    }
+
+    /// Mark a page as just viewed, reading it back in if it was out.
+    /// Returns whether it was out.
+    pub fn touch(&mut self, pages: &mut [String], page: usize) -> Result<bool> {
+        self.grow(pages.len());
+        let loaded = self.load(pages, page..page + 1)?;
+        self.clock += 1;
+        if let Some(used) = self.last_used.get_mut(page) {
+            *used = self.clock;
+        }
+        Ok(loaded)
+    }
+
+    /// Read back the pages in `range` that are out, without counting them as
+    /// viewed, so they are the first to go again. Returns whether any were out.
+    pub fn load(&mut self, pages: &mut [String], range: Range<usize>) -> Result<bool> {
+        self.grow(pages.len());
+        let mut loaded = false;
+        for page in range.start..range.end.min(pages.len()) {
+            if self.out[page] {
+                let path = self.page_path(page);
+                pages[page] = fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))?;
+                self.out[page] = false;
+                loaded = true;
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// The text of a page, read from its file for the moment if it is out,
+    /// for going through the whole document without bringing it all back.
+    pub fn read<'a>(&self, pages: &'a [String], page: usize) -> Result<Cow<'a, str>> {
+        if !self.out.get(page).copied().unwrap_or(false) {
+            return Ok(Cow::Borrowed(pages.get(page).map_or("", String::as_str)));
+        }
+        let path = self.page_path(page);
+        let text = fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))?;
+        Ok(Cow::Owned(text))
+    }
+
+    /// A page's text was replaced, so its file is stale.
+    pub fn changed(&mut self, page: usize) {
+        if let Some(saved) = self.saved.get_mut(page) {
+            *saved = false;
+        }
+        if let Some(out) = self.out.get_mut(page) {
+            *out = false;
+        }
+    }
+
+    /// Move least recently viewed pages out until the text fits the budget.
+    /// The `pinned` pages stay: those on screen, and the current page's
+    /// neighbours so paging back and forth stays instant. Returns whether
+    /// any page went out.
+    pub fn enforce(&mut self, pages: &mut [String], pinned: &[usize]) -> Result<bool> {
+        if self.limit == 0 {
+            return Ok(false);
+        }
+        self.grow(pages.len());
+        let mut size: usize = pages.iter().map(String::capacity).sum();
+        if size <= self.limit {
+            return Ok(false);
+        }
+        let mut candidates: Vec<usize> = (0..pages.len())
+            .filter(|&page| !self.out[page] && !pages[page].is_empty() && !pinned.contains(&page))
+            .collect();
+        candidates.sort_by_key(|&page| self.last_used[page]);
+        let mut evicted = false;
+        for page in candidates {
+            if size <= self.limit {
+                break;
+            }
+            if !self.saved[page] {
+                fs::create_dir_all(&self.dir).with_context(|| format!("Could not create {}", self.dir.display()))?;
+                let path = self.page_path(page);
+                fs::write(&path, &pages[page]).with_context(|| format!("Could not write {}", path.display()))?;
+                self.saved[page] = true;
+            }
+            size -= pages[page].capacity();
+            pages[page] = String::new();
+            self.out[page] = true;
+            evicted = true;
+        }
+        Ok(evicted)
+    }
+
+    /// Pages appended since the last call start out never viewed.
+    fn grow(&mut self, len: usize) {
+        self.last_used.resize(len, 0);
+        self.out.resize(len, false);
+        self.saved.resize(len, false);
+    }
+
+    fn page_path(&self, page: usize) -> PathBuf {
+        self.dir.join(format!("page-{}.txt", page + 1))
+    }
+}
+
+impl Drop for PageBudget {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+        // Only goes once no other document of this session has files left
+        if let Some(session) = self.dir.parent() {
+            let _ = fs::remove_dir(session);
+        }
+    }
+}
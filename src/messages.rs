@@ -0,0 +1,88 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Entries kept for `:messages`.
+const LOG_LIMIT: usize = 200;
+
+/// Tracks the status line: when the message on it appeared, and a log of past ones.
+pub struct Messages {
+    /// UTC `HH:MM:SS` and message, oldest first
+    log: VecDeque<(String, String)>,
+    current: String,
+    since: Instant,
+}
+
+impl Messages {
+    pub fn new() -> Self {
+        Self {
+            log: VecDeque::new(),
+            current: String::new(),
+            since: Instant::now(),
+        }
+    }
+
+    /// Note the message now on the status line, logging it if it is new.
+    pub fn observe(&mut self, status: &str) {
+        if status == self.current {
+            return;
+        }
+        self.current = status.to_string();
+        self.since = Instant::now();
+        if !status.is_empty() {
+            if self.log.len() == LOG_LIMIT {
+                self.log.pop_front();
+            }
+            self.log.push_back((clock(), status.to_string()));
+        }
+    }
+
+    /// Whether the current message has been shown for longer than `ttl`.
+    pub fn expired(&self, ttl: Duration) -> bool {
+        !self.current.is_empty() && self.since.elapsed() >= ttl
+    }
+
+    /// The log as text, newest first.
+    pub fn history(&self) -> String {
+        self.log
+            .iter()
+            .rev()
+            .map(|(time, message)| format!("{}  {}", time, message))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// How a failed action, like a search with no results or paging past the
+/// last page, is signalled besides its status message.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Bell {
+    /// The status message alone
+    #[default]
+    None,
+    /// The terminal bell
+    Audible,
+    /// The screen flashes
+    Visual,
+    Both,
+}
+
+impl Bell {
+    pub fn audible(self) -> bool {
+        matches!(self, Bell::Audible | Bell::Both)
+    }
+
+    pub fn visual(self) -> bool {
+        matches!(self, Bell::Visual | Bell::Both)
+    }
+}
+
+/// Current UTC time of day as `HH:MM:SS`.
+fn clock() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+        % 86_400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
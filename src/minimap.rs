@@ -0,0 +1,46 @@
+use std::ops::Range;
+
+/// Shades from empty to full, for how much of a cell's text is ink.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+/// Which lines of a page each of `rows` minimap rows stands for. Pages
+/// shorter than the strip get a row per line and leave the rest empty.
+pub fn row_lines(lines: usize, rows: usize) -> Vec<Range<usize>> {
+    if lines <= rows {
+        return (0..lines).map(|line| line..line + 1).collect();
+    }
+    (0..rows).map(|row| row * lines / rows..(row + 1) * lines / rows).collect()
+}
+
+/// The row standing for `line`, the inverse of `row_lines`.
+pub fn row_of(line: usize, lines: usize, rows: usize) -> usize {
+    if lines <= rows { line } else { line * rows / lines }
+}
+
+/// The page drawn `width` columns wide, one string per row of `row_lines`:
+/// each cell is shaded by the share of non-space characters in the block of
+/// text it covers, so paragraphs, indentation and blank lines show.
+pub fn render(lines: &[&str], rows: usize, width: usize) -> Vec<String> {
+    let widest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    if width == 0 || widest == 0 {
+        return row_lines(lines.len(), rows).iter().map(|_| " ".repeat(width)).collect();
+    }
+    let columns_per_cell = widest.div_ceil(width);
+    row_lines(lines.len(), rows)
+        .into_iter()
+        .map(|range| {
+            let mut ink = vec![0; width];
+            for line in &lines[range.clone()] {
+                for (column, c) in line.chars().enumerate() {
+                    if !c.is_whitespace() {
+                        ink[column / columns_per_cell] += 1;
+                    }
+                }
+            }
+            let cell_size = (columns_per_cell * range.len()).max(1);
+            ink.iter()
+                .map(|&count| SHADES[(count * (SHADES.len() - 1)).div_ceil(cell_size).min(SHADES.len() - 1)])
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,42 @@
+use anyhow::{bail, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::OcrConfig;
+use crate::{external, paths, state};
+
+/// Text recognised earlier for a 0-based page in `lang`, unless the PDF changed since.
+pub fn cached(pdf: &Path, page: usize, lang: &str) -> Option<String> {
+    let path = cache_path(pdf, page, lang);
+    let fresh = fs::metadata(&path).ok()?.modified().ok()? >= fs::metadata(pdf).ok()?.modified().ok()?;
+    if !fresh {
+        return None;
+    }
+    fs::read_to_string(path).ok()
+}
+
+/// Run the OCR command on a 0-based page and cache what it reads.
+pub fn recognize(ocr: &OcrConfig, pdf: &Path, page: usize) -> Result<String> {
+    let page_number = (page + 1).to_string();
+    let vars = [("page", page_number.as_str()), ("file", &pdf.display().to_string()), ("lang", &ocr.lang)];
+    let text = external::run(&ocr.command, &vars, None)?;
+    if text.trim().is_empty() {
+        bail!("no text found on page {}", page + 1);
+    }
+    // A cache that cannot be written only costs a rerun next time
+    let path = cache_path(pdf, page, &ocr.lang);
+    if !paths::private() && let Some(dir) = path.parent() && fs::create_dir_all(dir).is_ok() {
+        let _ = fs::write(&path, &text);
+    }
+    Ok(text)
+}
+
+/// Results differ by language, so each gets its own file.
+fn cache_path(pdf: &Path, page: usize, lang: &str) -> PathBuf {
+    paths::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("ocr")
+        .join(state::file_key(pdf))
+        .join(format!("page-{}.{}.txt", page + 1, lang))
+}
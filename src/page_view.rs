@@ -0,0 +1,460 @@
+use anyhow::{bail, Context, Result};
+use ratatui::layout::Rect;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::{external, images, kitty, paths, pdf, state};
+
+/// Pixels of rendered page per terminal column; rows are taken to be twice as tall.
+const CELL_PIXELS: u32 = 8;
+/// Width and height of a rendered tile in pixels.
+const TILE_PIXELS: u32 = 256;
+const TILE_COLUMNS: u32 = TILE_PIXELS / CELL_PIXELS;
+const TILE_ROWS: u32 = TILE_PIXELS / (CELL_PIXELS * 2);
+/// Kitty image id of the first tile on screen, above the preview's.
+const FIRST_TILE_ID: u32 = 1_000_001;
+/// Each zoom step scales the page by this much.
+const ZOOM_STEP: f32 = 1.25;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 8.0;
+/// Rendered tiles kept on disk, over all documents; the least recently shown
+/// go first when a view closes.
+const CACHE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// What the page fits in the view at a zoom of 100%.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// The page's width fills the view; scroll down for the rest
+    #[default]
+    Width,
+    /// The whole page shows at once
+    Page,
+}
+
+/// A square of a page rendered at one resolution, counted in columns and
+/// rows of the page as turned.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Tile {
+    page: usize,
+    dpi: u32,
+    /// Quarter turns clockwise
+    turns: u16,
+    column: u32,
+    row: u32,
+}
+
+/// A page rendered as an image over the content, in tiles rendered on a
+/// background thread and cached on disk for each zoom level.
+pub struct PageView {
+    pub page: usize,
+    /// Width and height in points of each page after /Rotate, once the
+    /// render thread has read them
+    sizes: Option<Vec<(f32, f32)>>,
+    sizes_rx: Receiver<Result<Vec<(f32, f32)>>>,
+    /// Quarter turns clockwise given to pages, as in the thumbnail grid
+    pub turns: HashMap<usize, u16>,
+    pub fit: Fit,
+    /// Times the fitted size
+    pub zoom: f32,
+    /// Column and row of the page at the view's top left
+    left: u32,
+    top: u32,
+    /// Where the page was last drawn
+    area: Rect,
+    requests: Sender<Tile>,
+    rx: Receiver<(Tile, Result<PathBuf>)>,
+    tiles: HashMap<Tile, PathBuf>,
+    requested: HashSet<Tile>,
+    /// Kitty ids of the tiles on screen
+    placed: Vec<u32>,
+    /// The view moved or tiles came in since the tiles were placed
+    dirty: bool,
+    pub failed: Option<String>,
+}
+
+impl PageView {
+    /// Show `page` of `pdf`, turned by `turns`, rendering tiles with
+    /// `command`. The page sizes are read on the render thread, and tiles
+    /// are placed once they are known.
+    pub fn open(command: &str, pdf: &Path, page: usize, turns: HashMap<usize, u16>, fit: Fit) -> Self {
+        let (requests, pending) = mpsc::channel::<Tile>();
+        let (tx, rx) = mpsc::channel();
+        let (sizes_tx, sizes_rx) = mpsc::channel();
+        let command = command.to_string();
+        let pdf = pdf.to_path_buf();
+        thread::spawn(move || {
+            let sizes = page_sizes(&pdf);
+            let page_sizes = sizes.as_ref().map_or_else(|_| Vec::new(), Vec::clone);
+            if sizes_tx.send(sizes).is_err() {
+                return;
+            }
+            let root = paths::cache_dir().unwrap_or_else(env::temp_dir).join("pages");
+            let dir = root.join(state::file_key(&pdf));
+            let mut queue: Vec<Tile> = Vec::new();
+            loop {
+                if queue.is_empty() {
+                    match pending.recv() {
+                        Ok(tile) => queue.push(tile),
+                        Err(_) => break,
+                    }
+                }
+                // The newest requests are what is on screen now
+                queue.extend(pending.try_iter());
+                let Some(tile) = queue.pop() else {
+                    continue;
+                };
+                let size = page_sizes.get(tile.page).copied().unwrap_or((612.0, 792.0));
+                if tx.send((tile, render(&command, &pdf, &dir, tile, size))).is_err() {
+                    break;
+                }
+            }
+            prune(&root, CACHE_BYTES);
+        });
+        Self {
+            page,
+            sizes: None,
+            sizes_rx,
+            turns,
+            fit,
+            zoom: 1.0,
+            left: 0,
+            top: 0,
+            area: Rect::default(),
+            requests,
+            rx,
+            tiles: HashMap::new(),
+            requested: HashSet::new(),
+            placed: Vec::new(),
+            dirty: true,
+            failed: None,
+        }
+    }
+
+    fn page_turns(&self) -> u16 {
+        self.turns.get(&self.page).copied().unwrap_or(0)
+    }
+
+    /// Width and height in points of the page shown, as turned.
+    fn size(&self) -> (f32, f32) {
+        let (width, height) = self.sizes.as_ref().and_then(|sizes| sizes.get(self.page)).copied().unwrap_or((612.0, 792.0));
+        if self.page_turns() % 2 == 1 { (height, width) } else { (width, height) }
+    }
+
+    /// Resolution the page is rendered at for the view's size and zoom, and
+    /// its size there in pixels and in columns and rows.
+    fn scale(&self) -> (u32, (u32, u32), (u32, u32)) {
+        let (width, height) = self.size();
+        let fitted = match self.fit {
+            Fit::Width => self.area.width as f32,
+            Fit::Page => (self.area.width as f32).min(self.area.height as f32 * 2.0 * width / height),
+        };
+        let columns = (fitted * self.zoom).round().max(1.0);
+        let dpi = (columns * CELL_PIXELS as f32 * 72.0 / width).round().max(1.0) as u32;
+        let pixels = ((width * dpi as f32 / 72.0).ceil() as u32, (height * dpi as f32 / 72.0).ceil() as u32);
+        let cells = (pixels.0.div_ceil(CELL_PIXELS), pixels.1.div_ceil(CELL_PIXELS * 2));
+        (dpi, pixels, cells)
+    }
+
+    /// Keep the view inside the page.
+    fn clamp(&mut self) {
+        let (_, _, (columns, rows)) = self.scale();
+        self.left = self.left.min(columns.saturating_sub(self.area.width as u32));
+        self.top = self.top.min(rows.saturating_sub(self.area.height as u32));
+        self.dirty = true;
+    }
+
+    /// Size the view to `area`, where it has just been drawn.
+    pub fn set_area(&mut self, area: Rect) {
+        if area != self.area {
+            self.area = area;
+            self.clamp();
+        }
+    }
+
+    /// Zoom in (`steps` above 0) or out.
+    pub fn zoom(&mut self, steps: i32) {
+        self.set_zoom(self.zoom * ZOOM_STEP.powi(steps));
+    }
+
+    /// Show the page at `zoom` times its fitted size, keeping the middle of
+    /// the view in place.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        let (_, _, (columns, rows)) = self.scale();
+        let middle = |offset: u32, size: u16, total: u32| (offset as f32 + size as f32 / 2.0) / total.max(1) as f32;
+        let (x, y) = (middle(self.left, self.area.width, columns), middle(self.top, self.area.height, rows));
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        let (_, _, (columns, rows)) = self.scale();
+        self.left = (x * columns as f32 - self.area.width as f32 / 2.0).max(0.0) as u32;
+        self.top = (y * rows as f32 - self.area.height as f32 / 2.0).max(0.0) as u32;
+        self.clamp();
+    }
+
+    /// Switch between fitting the width and the whole page, at 100%.
+    pub fn toggle_fit(&mut self) {
+        self.fit = if self.fit == Fit::Width { Fit::Page } else { Fit::Width };
+        self.zoom = 1.0;
+        self.left = 0;
+        self.top = 0;
+        self.clamp();
+    }
+
+    /// Move the view by `columns` and `rows`.
+    pub fn pan(&mut self, columns: i32, rows: i32) {
+        self.left = self.left.saturating_add_signed(columns);
+        self.top = self.top.saturating_add_signed(rows);
+        self.clamp();
+    }
+
+    /// Move the view a screen down, or up; false at the end of the page.
+    pub fn page_down(&mut self, down: bool) -> bool {
+        let top = self.top;
+        let rows = self.area.height.saturating_sub(2).max(1) as i32;
+        self.pan(0, if down { rows } else { -rows });
+        self.top != top
+    }
+
+    /// Show `page` from its top, at the bottom when coming back to it.
+    pub fn go_to(&mut self, page: usize, at_bottom: bool) {
+        self.page = page;
+        self.left = 0;
+        self.top = if at_bottom { u32::MAX } else { 0 };
+        self.clamp();
+    }
+
+    /// Turn the page shown a quarter clockwise or counterclockwise, showing
+    /// it from the top.
+    pub fn rotate(&mut self, clockwise: bool) {
+        let turns = self.turns.entry(self.page).or_default();
+        *turns = (*turns + if clockwise { 1 } else { 3 }) % 4;
+        self.left = 0;
+        self.top = 0;
+        self.clamp();
+    }
+
+    /// The zoom and fit for the title, and whether tiles are still coming.
+    pub fn describe(&self) -> String {
+        let fit = if self.fit == Fit::Width { "fit width" } else { "fit page" };
+        let rendering = self.sizes.is_none() || self.visible().iter().any(|(tile, _)| !self.tiles.contains_key(tile));
+        format!("{:.0}% {}{}", self.zoom * 100.0, fit, if rendering { ", rendering…" } else { "" })
+    }
+
+    /// Collect the page sizes and tiles finished since the last call.
+    pub fn poll(&mut self) {
+        if self.sizes.is_none() {
+            match self.sizes_rx.try_recv() {
+                Ok(Ok(sizes)) => {
+                    self.sizes = Some(sizes);
+                    self.clamp();
+                }
+                Ok(Err(e)) => self.failed = Some(format!("Could not read the page sizes: {}", e)),
+                Err(_) => {}
+            }
+        }
+        while let Ok((tile, result)) = self.rx.try_recv() {
+            match result {
+                Ok(path) => {
+                    self.tiles.insert(tile, path);
+                    self.dirty = true;
+                }
+                Err(e) => self.failed = Some(e.to_string()),
+            }
+        }
+    }
+
+    /// The tiles in view and the part of each that shows, in columns and
+    /// rows of the page.
+    fn visible(&self) -> Vec<(Tile, Rect)> {
+        let (dpi, _, (columns, rows)) = self.scale();
+        let turns = self.page_turns();
+        let right = (self.left + self.area.width as u32).min(columns);
+        let bottom = (self.top + self.area.height as u32).min(rows);
+        let mut tiles = Vec::new();
+        for row in self.top / TILE_ROWS..bottom.div_ceil(TILE_ROWS) {
+            for column in self.left / TILE_COLUMNS..right.div_ceil(TILE_COLUMNS) {
+                let x = (column * TILE_COLUMNS).max(self.left);
+                let y = (row * TILE_ROWS).max(self.top);
+                let part = Rect {
+                    x: x as u16,
+                    y: y as u16,
+                    width: (((column + 1) * TILE_COLUMNS).min(right) - x) as u16,
+                    height: (((row + 1) * TILE_ROWS).min(bottom) - y) as u16,
+                };
+                tiles.push((Tile { page: self.page, dpi, turns, column, row }, part));
+            }
+        }
+        tiles
+    }
+
+    /// Ask for the tiles in view and draw those rendered over the text grid,
+    /// unless nothing changed since the last time.
+    pub fn place(&mut self) -> io::Result<()> {
+        if !self.dirty || self.sizes.is_none() || self.area.width == 0 || self.area.height == 0 {
+            return Ok(());
+        }
+        self.dirty = false;
+        self.clear()?;
+        let (_, pixels, _) = self.scale();
+        for (tile, part) in self.visible() {
+            let Some(path) = self.tiles.get(&tile) else {
+                if self.requested.insert(tile) {
+                    let _ = self.requests.send(tile);
+                }
+                continue;
+            };
+            let x = (part.x as u32 - tile.column * TILE_COLUMNS) * CELL_PIXELS;
+            let y = (part.y as u32 - tile.row * TILE_ROWS) * CELL_PIXELS * 2;
+            // Tiles at the page's right and bottom edges are cut short
+            let width = (part.width as u32 * CELL_PIXELS).min(pixels.0.saturating_sub(tile.column * TILE_PIXELS + x));
+            let height = (part.height as u32 * CELL_PIXELS * 2).min(pixels.1.saturating_sub(tile.row * TILE_PIXELS + y));
+            if width == 0 || height == 0 {
+                continue;
+            }
+            let screen = Rect {
+                x: self.area.x + (part.x as u32 - self.left) as u16,
+                y: self.area.y + (part.y as u32 - self.top) as u16,
+                width: part.width,
+                height: part.height,
+            };
+            let id = FIRST_TILE_ID + self.placed.len() as u32;
+            kitty::show_part(id, path, screen, (x, y, width, height))?;
+            self.placed.push(id);
+        }
+        Ok(())
+    }
+
+    /// Remove the tiles from the screen.
+    pub fn clear(&mut self) -> io::Result<()> {
+        for id in self.placed.drain(..) {
+            kitty::delete(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Width and height in points of each page of `pdf` after /Rotate.
+fn page_sizes(pdf: &Path) -> Result<Vec<(f32, f32)>> {
+    let doc = pdf::load(pdf)?;
+    Ok((0..doc.get_pages().len())
+        .map(|page| {
+            let (width, height) = pdf::page_size(&doc, page).unwrap_or((612.0, 792.0));
+            if pdf::page_rotation(&doc, page) % 180 == 90 { (height, width) } else { (width, height) }
+        })
+        .collect())
+}
+
+/// Remove the least recently shown tiles of every document under `root`
+/// until the rest fit in `max_bytes`.
+fn prune(root: &Path, max_bytes: u64) {
+    let mut tiles: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .flat_map(|doc| fs::read_dir(doc.path()).into_iter().flatten().flatten())
+        .filter_map(|tile| {
+            let metadata = tile.metadata().ok()?;
+            Some((metadata.modified().ok()?, metadata.len(), tile.path()))
+        })
+        .collect();
+    tiles.sort_by_key(|&(shown, _, _)| Reverse(shown));
+    let mut kept = 0;
+    for (_, len, path) in tiles {
+        kept += len;
+        if kept > max_bytes {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Render one tile of a page measuring `size` points to the cache unless
+/// it is already there. A turned page's tile is rendered from the part of
+/// the page it shows and then turned.
+fn render(command: &str, pdf: &Path, dir: &Path, tile: Tile, size: (f32, f32)) -> Result<PathBuf> {
+    let turned = if tile.turns == 0 { String::new() } else { format!("-turned{}", tile.turns) };
+    let name = format!("page-{}-{}dpi{}-{}-{}", tile.page + 1, tile.dpi, turned, tile.column, tile.row);
+    let png = dir.join(&name).with_extension("png");
+    let fresh = match (fs::metadata(&png), fs::metadata(pdf)) {
+        (Ok(image), Ok(doc)) => image.modified()? >= doc.modified()?,
+        _ => false,
+    };
+    if fresh {
+        // Shown again, so it is among the last to be pruned
+        let _ = fs::File::options().append(true).open(&png).and_then(|file| file.set_modified(SystemTime::now()));
+        return Ok(png);
+    }
+
+    fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    // The tile's square on the page as turned, taken back to the page as rendered
+    let (width, height) = ((size.0 * tile.dpi as f32 / 72.0).ceil() as u32, (size.1 * tile.dpi as f32 / 72.0).ceil() as u32);
+    let (x, y) = (tile.column * TILE_PIXELS, tile.row * TILE_PIXELS);
+    let (x, y) = match tile.turns {
+        1 => (y, height.saturating_sub(x + TILE_PIXELS)),
+        2 => (width.saturating_sub(x + TILE_PIXELS), height.saturating_sub(y + TILE_PIXELS)),
+        3 => (width.saturating_sub(y + TILE_PIXELS), x),
+        _ => (x, y),
+    };
+    // Squares at the far edges are cut so what is rendered starts at the tile's corner once turned
+    let (tile_width, tile_height) = match tile.turns {
+        1 => (TILE_PIXELS, TILE_PIXELS.min(height.saturating_sub(tile.column * TILE_PIXELS))),
+        2 => (
+            TILE_PIXELS.min(width.saturating_sub(tile.column * TILE_PIXELS)),
+            TILE_PIXELS.min(height.saturating_sub(tile.row * TILE_PIXELS)),
+        ),
+        3 => (TILE_PIXELS.min(width.saturating_sub(tile.row * TILE_PIXELS)), TILE_PIXELS),
+        _ => (TILE_PIXELS, TILE_PIXELS),
+    };
+    let output = if tile.turns == 0 { dir.join(&name) } else { dir.join(format!("{}-unturned", name)) };
+    external::run(
+        command,
+        &[
+            ("page", &(tile.page + 1).to_string()),
+            ("file", &pdf.display().to_string()),
+            ("output", &output.display().to_string()),
+            ("dpi", &tile.dpi.to_string()),
+            ("x", &x.to_string()),
+            ("y", &y.to_string()),
+            ("width", &tile_width.to_string()),
+            ("height", &tile_height.to_string()),
+        ],
+        None,
+    )?;
+    let rendered = output.with_extension("png");
+    if !rendered.exists() {
+        bail!("'{}' did not write {}", command, rendered.display());
+    }
+    if tile.turns != 0 {
+        images::rotate_png(&rendered, &png, tile.turns)?;
+        let _ = fs::remove_file(&rendered);
+    }
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn prune_keeps_the_most_recently_shown_tiles() {
+        let root = env::temp_dir().join(format!("pdf_reader-tiles-{}", std::process::id()));
+        let now = SystemTime::now();
+        for (doc, name, age) in [("a", "old.png", 30), ("a", "new.png", 0), ("b", "mid.png", 10)] {
+            fs::create_dir_all(root.join(doc)).unwrap();
+            let path = root.join(doc).join(name);
+            fs::write(&path, [0; 100]).unwrap();
+            fs::File::options().append(true).open(&path).unwrap().set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        prune(&root, 250);
+        let left = ["a/old.png", "a/new.png", "b/mid.png"].map(|tile| root.join(tile).exists());
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(left, [false, true, true]);
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+/// Start command read when the command line has none, for tools that set up
+/// their pager through the environment: `PDF_READER_START="+/Theorem 2"`.
+pub const START_VAR: &str = "PDF_READER_START";
+
+/// Where to open, as less takes it before the file name.
+pub enum Start {
+    /// 1-based page, from `+N`
+    Page(usize),
+    /// First line containing the text, from `+/pattern`
+    Search(String),
+}
+
+/// Read a less-style start command: `+N` or `+/pattern`.
+pub fn parse(arg: &str) -> Result<Start> {
+    let Some(command) = arg.strip_prefix('+') else {
+        bail!("Start commands begin with '+': {}", arg);
+    };
+    if let Some(pattern) = command.strip_prefix('/') {
+        if pattern.is_empty() {
+            bail!("No pattern after '+/'");
+        }
+        return Ok(Start::Search(pattern.to_string()));
+    }
+    match command.parse::<usize>() {
+        Ok(page) if page > 0 => Ok(Start::Page(page)),
+        _ => bail!("Unknown start command {} (+N for a page, +/pattern to search)", arg),
+    }
+}
+
+/// Take the `+` arguments out of the command line, leaving the rest for
+/// clap, with `$PDF_READER_START` as the fallback. The last one given wins.
+/// Values of options and everything from a subcommand of `command` on are
+/// left alone, so `extract --highlight +1` keeps its term.
+pub fn take_start(args: Vec<OsString>, command: &clap::Command) -> Result<(Vec<OsString>, Option<Start>)> {
+    let mut rest = Vec::with_capacity(args.len());
+    let mut start = None;
+    let mut options_done = false;
+    let mut positional_seen = false;
+    let mut option_value = false;
+    for (i, arg) in args.into_iter().enumerate() {
+        let text = arg.to_str().filter(|_| i > 0 && !options_done);
+        if let Some(text) = text
+            && !option_value
+            && text.starts_with('+')
+        {
+            start = Some(parse(text)?);
+            continue;
+        }
+        let after_option = std::mem::take(&mut option_value);
+        match text {
+            Some("--") => options_done = true,
+            Some(text) if !after_option && text.starts_with('-') && text.len() > 1 => option_value = takes_value(command, text),
+            Some(text) if !after_option && !positional_seen => {
+                positional_seen = true;
+                options_done = command.find_subcommand(text).is_some();
+            }
+            _ => {}
+        }
+        rest.push(arg);
+    }
+    if start.is_none()
+        && let Ok(text) = env::var(START_VAR)
+        && !text.trim().is_empty()
+    {
+        start = Some(parse(text.trim()).with_context(|| format!("In ${}", START_VAR))?);
+    }
+    Ok((rest, start))
+}
+
+/// Whether `option`, as given on the command line, takes the next argument
+/// as its value: `--find` does, `--find=x`, `-Px` and flags do not.
+fn takes_value(command: &clap::Command, option: &str) -> bool {
+    let arg = match option.strip_prefix("--") {
+        Some(long) if long.contains('=') => return false,
+        Some(long) => command.get_arguments().find(|arg| arg.get_long() == Some(long)),
+        None => {
+            let mut shorts = option[1..].chars();
+            let (Some(short), None) = (shorts.next(), shorts.next()) else {
+                return false;
+            };
+            command.get_arguments().find(|arg| arg.get_short() == Some(short))
+        }
+    };
+    arg.is_some_and(|arg| arg.get_action().takes_values())
+}
+
+/// Whether the PDF should come from standard input: named `-`, or not named
+/// while something is piped in, as when run as `$PAGER`.
+pub fn reads_stdin(file: Option<&Path>) -> bool {
+    match file {
+        Some(file) => file == Path::new("-"),
+        None => !io::stdin().is_terminal(),
+    }
+}
+
+/// A PDF piped in, kept in a temporary file while it is read since pages are
+/// extracted from the file in the background. Removed when dropped.
+pub struct StdinPdf {
+    pub path: PathBuf,
+}
+
+impl StdinPdf {
+    #[cfg(unix)]
+    pub fn read() -> Result<StdinPdf> {
+        use std::fs::OpenOptions;
+        use std::io::{Read, Write};
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes).context("Could not read the PDF from standard input")?;
+        if bytes.is_empty() {
+            bail!("Nothing was piped in");
+        }
+        // A new file under a name no one can guess, readable only by us, so
+        // nothing planted in the shared temporary folder can catch the write
+        for _ in 0..16 {
+            let path = env::temp_dir().join(format!("pdf_reader-stdin-{:016x}.pdf", random()));
+            let file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path);
+            match file {
+                Ok(mut file) => {
+                    let pdf = StdinPdf { path };
+                    file.write_all(&bytes).with_context(|| format!("Could not write {}", pdf.path.display()))?;
+                    return Ok(pdf);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e).with_context(|| format!("Could not create {}", path.display())),
+            }
+        }
+        bail!("Could not create a temporary file for the PDF in {}", env::temp_dir().display())
+    }
+
+    /// Without Unix file modes the copy could not be kept private.
+    #[cfg(not(unix))]
+    pub fn read() -> Result<StdinPdf> {
+        bail!("Reading a PDF from standard input is not supported on this platform; name the file instead")
+    }
+}
+
+/// 64 random bits, from the hasher keys the standard library seeds from the
+/// operating system.
+#[cfg(unix)]
+fn random() -> u64 {
+    use std::hash::{BuildHasher, Hasher, RandomState};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+    hasher.finish()
+}
+
+impl Drop for StdinPdf {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,157 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::LazyLock;
+
+use crate::config::{FormatConfig, PaginationConfig};
+use crate::format;
+
+/// A running footer or header that is just the page number: "12", "- 12 -",
+/// "Page 12", "12 of 340", "12/340".
+pub static PAGE_NUMBER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^[-–—\s]*(?:page|p\.|seite|página|pagina)?\s*(\d{1,5})\s*(?:(?:of|/|von|de|sur)\s*\d{1,5})?[-–—\s]*$").unwrap()
+});
+
+/// A line opening a chapter or part: "Chapter 3", "CHAPTER IV", "Part Two".
+static CHAPTER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(?:chapter|part|book|kapitel|teil|chapitre|partie|cap[íi]tulo|capitolo)\s+(?:\d+|[ivxlc]+|one|two|three|four|five|six|seven|eight|nine|ten)\b").unwrap()
+});
+
+/// How text without page breaks of its own is cut into pages.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitBy {
+    /// Fixed-size chunks of `lines_per_page` lines
+    #[default]
+    Lines,
+    /// After lines that are only a page number, as running footers are
+    Footers,
+    /// Before "Chapter N"-style headings
+    Chapters,
+}
+
+/// Parse `--paginate`: `lines`, `footers`, `chapters`, or a number of lines per page.
+pub fn parse_flag(arg: &str) -> Result<(SplitBy, Option<usize>), String> {
+    match arg {
+        "lines" => Ok((SplitBy::Lines, None)),
+        "footers" => Ok((SplitBy::Footers, None)),
+        "chapters" => Ok((SplitBy::Chapters, None)),
+        n => match n.parse() {
+            Ok(0) | Err(_) => Err("expected lines, footers, chapters or a number of lines per page".to_string()),
+            Ok(lines) => Ok((SplitBy::Lines, Some(lines))),
+        },
+    }
+}
+
+/// Split text extracted as one block into formatted pages. Form feeds win
+/// when there are any; footers and chapters fall back to fixed-size chunks
+/// when the text has fewer than two of them.
+pub fn split(text: &str, pagination: PaginationConfig, format: FormatConfig) -> Vec<String> {
+    if text.contains('\x0C') {
+        return text.split('\x0C')
+            .map(|page| format::page(page, format))
+            .filter(|page| !page.trim().is_empty())
+            .collect();
+    }
+
+    let lines: Vec<&str> = text.lines().collect();
+    let breaks = match pagination.split {
+        SplitBy::Lines => Vec::new(),
+        SplitBy::Footers => footer_breaks(&lines),
+        SplitBy::Chapters => lines
+            .iter()
+            .enumerate()
+            .filter(|(i, line)| *i > 0 && CHAPTER.is_match(line.trim()))
+            .map(|(i, _)| i)
+            .collect(),
+    };
+    let chunks: Vec<&[&str]> = if breaks.len() >= 2 {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for end in breaks.into_iter().chain([lines.len()]) {
+            chunks.push(&lines[start..end]);
+            start = end;
+        }
+        chunks
+    } else {
+        lines.chunks(pagination.lines_per_page.max(1)).collect()
+    };
+
+    let mut pages: Vec<String> = chunks
+        .into_iter()
+        .map(|chunk| format::page(&chunk.join("\n"), format))
+        .filter(|page| !page.trim().is_empty())
+        .collect();
+    if pages.is_empty() {
+        pages.push(format::page(text, format));
+    }
+    pages
+}
+
+/// Line indices just after page-number footers. Numbers must go up by one,
+/// so a stray number in the text (a year, a table cell) does not cut a page.
+fn footer_breaks(lines: &[&str]) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut last: Option<u32> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let Some(number) = PAGE_NUMBER.captures(line.trim()).and_then(|c| c[1].parse::<u32>().ok()) else {
+            continue;
+        };
+        if last.is_none_or(|last| number == last + 1) {
+            breaks.push(i + 1);
+            last = Some(number);
+        }
+    }
+    // The last footer ends the text; no empty page after it
+    breaks.retain(|&end| end < lines.len());
+    breaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pagination(split: SplitBy, lines_per_page: usize) -> PaginationConfig {
+        PaginationConfig { split, lines_per_page }
+    }
+
+    #[test]
+    fn form_feeds_win_and_blank_pages_are_dropped() {
+        let pages = split("one\x0C\x0Ctwo", pagination(SplitBy::Chapters, 1), FormatConfig::default());
+        assert_eq!(pages, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn lines_are_cut_into_fixed_chunks() {
+        let pages = split("a\nb\nc\nd\ne", pagination(SplitBy::Lines, 2), FormatConfig::default());
+        assert_eq!(pages, vec!["a\nb", "c\nd", "e"]);
+    }
+
+    #[test]
+    fn footers_must_count_up_to_cut_a_page() {
+        let text = "a\n1\nb\n1999\nc\n2\nd\n3";
+        let pages = split(text, pagination(SplitBy::Footers, 50), FormatConfig::default());
+        assert_eq!(pages, vec!["a\n1", "b\n1999\nc\n2", "d\n3"]);
+    }
+
+    #[test]
+    fn too_few_markers_fall_back_to_chunks() {
+        let text = "intro\nChapter 1\nbody";
+        let pages = split(text, pagination(SplitBy::Chapters, 2), FormatConfig::default());
+        assert_eq!(pages, vec!["intro\nChapter 1", "body"]);
+    }
+
+    #[test]
+    fn chapters_start_pages() {
+        let text = "intro\nChapter 1\nbody\nCHAPTER II\nmore";
+        let pages = split(text, pagination(SplitBy::Chapters, 50), FormatConfig::default());
+        assert_eq!(pages, vec!["intro", "Chapter 1\nbody", "CHAPTER II\nmore"]);
+    }
+
+    #[test]
+    fn flag_takes_a_mode_or_a_page_size() {
+        assert_eq!(parse_flag("footers"), Ok((SplitBy::Footers, None)));
+        assert_eq!(parse_flag("30"), Ok((SplitBy::Lines, Some(30))));
+        assert!(parse_flag("0").is_err());
+        assert!(parse_flag("pages").is_err());
+    }
+}
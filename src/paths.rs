@@ -0,0 +1,65 @@
+use directories::ProjectDirs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// One folder holding everything, set by `--state-dir`.
+static PORTABLE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set by `--private`: nothing about what was read is written to disk.
+static PRIVATE: AtomicBool = AtomicBool::new(false);
+
+/// Stop saving state, positions and caches, for reading confidential
+/// documents on a shared machine. What was saved before is still read.
+pub fn set_private() {
+    PRIVATE.store(true, Ordering::Relaxed);
+}
+
+pub fn private() -> bool {
+    PRIVATE.load(Ordering::Relaxed)
+}
+
+/// Keep config, data, cache and state in subfolders of `dir` instead of the
+/// user's directories, e.g. for a copy carried on a USB stick.
+pub fn set_portable(dir: PathBuf) {
+    let _ = PORTABLE.set(std::path::absolute(&dir).unwrap_or(dir));
+}
+
+/// The XDG base directories on Linux and the BSDs (`$XDG_CONFIG_HOME/pdf_reader`
+/// and so on, with the usual fallbacks under the home directory), and the
+/// platform's own places elsewhere.
+fn project() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "pdf_reader")
+}
+
+fn portable(folder: &str) -> Option<PathBuf> {
+    PORTABLE.get().map(|dir| dir.join(folder))
+}
+
+/// Where `config.toml` and plugins are.
+pub fn config_dir() -> Option<PathBuf> {
+    portable("config").or_else(|| Some(project()?.config_dir().to_path_buf()))
+}
+
+/// Files the user made: clippings and exported notes.
+pub fn data_dir() -> Option<PathBuf> {
+    portable("data").or_else(|| Some(project()?.data_dir().to_path_buf()))
+}
+
+/// Files that can be made again: extracted text, OCR results, thumbnails.
+pub fn cache_dir() -> Option<PathBuf> {
+    portable("cache").or_else(|| Some(project()?.cache_dir().to_path_buf()))
+}
+
+/// What the viewer remembers between sessions: marks, tags and view choices
+/// per document. Platforms without a state directory keep it with the data.
+pub fn state_dir() -> Option<PathBuf> {
+    portable("state").or_else(|| {
+        let project = project()?;
+        Some(project.state_dir().unwrap_or_else(|| project.data_dir()).to_path_buf())
+    })
+}
+
+pub fn config_file() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
@@ -0,0 +1,1036 @@
+use anyhow::Result;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use pdf_extract::{output_doc_page, ColorSpace, MediaBox, OutputDev, OutputError, Transform};
+use serde::Deserialize;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::limits::{self, PageLimits};
+use crate::sandbox;
+
+/// An entry of the document outline (table of contents).
+#[derive(Clone)]
+pub struct OutlineEntry {
+    /// Nesting depth, 0 for top-level entries
+    pub level: usize,
+    pub title: String,
+    /// 0-based page the entry points to
+    pub page: usize,
+}
+
+/// A markup or comment annotation stored in the PDF by another viewer.
+pub struct Annotation {
+    /// 0-based page the annotation is on
+    pub page: usize,
+    /// PDF annotation subtype, e.g. `Highlight` or `Text`
+    pub kind: String,
+    pub contents: String,
+}
+
+/// Load a PDF, decrypting it if it only has an empty user password.
+pub fn load(path: &Path) -> Result<Document> {
+    let mut doc = parse(path)?;
+    if doc.is_encrypted() {
+        doc.decrypt("")?;
+    }
+    Ok(doc)
+}
+
+/// One PDF is parsed at a time, so the counts kept while parsing belong to it.
+static PARSING: Mutex<()> = Mutex::new(());
+
+/// Parse a PDF, counting its objects against `limits.max_objects` and, under
+/// `--sandbox`, checking it for decompression bombs.
+fn parse(path: &Path) -> Result<Document> {
+    let _parsing = PARSING.lock().unwrap_or_else(PoisonError::into_inner);
+    if sandbox::enabled() {
+        sandbox::check_file(path)?;
+    }
+    limits::start_parse();
+    sandbox::start_parse();
+    let doc = Document::load_filtered(path, filter_object)?;
+    limits::check_objects()?;
+    Ok(doc)
+}
+
+fn filter_object(id: ObjectId, object: &mut Object) -> Option<(ObjectId, Object)> {
+    if !limits::count_object() {
+        return None;
+    }
+    if sandbox::enabled() {
+        sandbox::defuse(object);
+    }
+    Some((id, object.clone()))
+}
+
+/// How a PDF is encrypted, read from its encryption dictionary.
+pub struct Encryption {
+    /// Security handler, normally `Standard`
+    pub filter: String,
+    /// Cipher and key length, e.g. `RC4 128-bit` or `AES-256`
+    pub algorithm: String,
+    /// Standard security handler revision (`/R`)
+    pub revision: i64,
+    /// Permission flags (`/P`)
+    pub permissions: i64,
+    pub metadata_encrypted: bool,
+}
+
+impl Encryption {
+    /// What the permission flags allow, as (label, allowed) pairs.
+    pub fn permission_list(&self) -> Vec<(&'static str, bool)> {
+        // 1-based bit positions from the PDF reference's user access permissions table
+        let bit = |n: u32| self.permissions & (1 << (n - 1)) != 0;
+        let mut list = vec![
+            ("Print", bit(3)),
+            ("Modify", bit(4)),
+            ("Copy text and images", bit(5)),
+            ("Annotate", bit(6)),
+        ];
+        // Revision 2 has no finer-grained flags
+        if self.revision >= 3 {
+            list.extend([
+                ("Fill forms", bit(9)),
+                ("Extract for accessibility", bit(10)),
+                ("Assemble", bit(11)),
+                ("Print high quality", bit(12)),
+            ]);
+        }
+        list
+    }
+}
+
+/// The encryption of the PDF at `path`, or `None` when it is not encrypted.
+/// [`load`] drops the encryption dictionary once it decrypts, so this reads the file again.
+pub fn encryption(path: &Path) -> Result<Option<Encryption>> {
+    let doc = parse(path)?;
+    let Ok(dict) = doc.get_encrypted() else {
+        return Ok(None);
+    };
+    let number = |key: &[u8]| dict.get(key).and_then(Object::as_i64).ok();
+    let version = number(b"V").unwrap_or(0);
+    let length = number(b"Length").unwrap_or(40);
+    // Version 4 and up name the cipher in the standard crypt filter
+    let method = dict
+        .get(b"CF")
+        .and_then(Object::as_dict)
+        .and_then(|cf| cf.get(b"StdCF"))
+        .and_then(Object::as_dict)
+        .and_then(|std_cf| std_cf.get(b"CFM"))
+        .and_then(Object::as_name_str)
+        .ok();
+    let algorithm = match (version, method) {
+        (_, Some("AESV3")) | (5, _) => "AES-256".to_string(),
+        (_, Some("AESV2")) => "AES-128".to_string(),
+        (4, _) => "RC4 128-bit".to_string(),
+        (1, _) => "RC4 40-bit".to_string(),
+        _ => format!("RC4 {}-bit", length),
+    };
+    Ok(Some(Encryption {
+        filter: dict.get(b"Filter").and_then(Object::as_name_str).unwrap_or("Unknown").to_string(),
+        algorithm,
+        revision: number(b"R").unwrap_or(0),
+        permissions: number(b"P").unwrap_or(-1),
+        metadata_encrypted: dict.get(b"EncryptMetadata").and_then(Object::as_bool).unwrap_or(true),
+    }))
+}
+
+/// Margins cut off every page before its text is extracted, in percent of
+/// the page's width or height; for watermarks, side notes and running heads.
+/// Written `[top, right, bottom, left]` in the config file.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Crop {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Crop {
+    /// Where text may be on a page with `media_box`: left, bottom, right and
+    /// top in PDF space.
+    /// Whether the margins are percentages that leave some of the page.
+    pub fn check(&self) -> Result<(), String> {
+        let margins = [self.top, self.right, self.bottom, self.left];
+        if margins.iter().any(|value| !(0.0..100.0).contains(value)) || self.top + self.bottom >= 100.0 || self.left + self.right >= 100.0 {
+            return Err("margins are 0 to 100 percent and must leave some of the page".to_string());
+        }
+        Ok(())
+    }
+
+    fn bounds(&self, media_box: &MediaBox) -> (f64, f64, f64, f64) {
+        // Uncropped keeps text drawn past the media box too
+        if *self == Crop::default() {
+            return (f64::NEG_INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::INFINITY);
+        }
+        let (width, height) = (media_box.urx - media_box.llx, media_box.ury - media_box.lly);
+        (
+            media_box.llx + width * self.left / 100.0,
+            media_box.lly + height * self.bottom / 100.0,
+            media_box.urx - width * self.right / 100.0,
+            media_box.ury - height * self.top / 100.0,
+        )
+    }
+}
+
+/// Read `--crop top,right,bottom,left`, in percent.
+pub fn parse_crop(arg: &str) -> Result<Crop, String> {
+    let values = arg
+        .split(',')
+        .map(|value| value.trim().trim_end_matches('%').parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| "expected four percentages: top,right,bottom,left".to_string())?;
+    let [top, right, bottom, left] = values[..] else {
+        return Err("expected four percentages: top,right,bottom,left".to_string());
+    };
+    let crop = Crop { top, right, bottom, left };
+    crop.check()?;
+    Ok(crop)
+}
+
+/// Raw extracted text of every page, in order. Pages that fail to extract are empty.
+pub fn extract_pages(doc: &Document, crop: Crop) -> Vec<String> {
+    doc.get_pages().keys().map(|&page_num| extract_page(doc, page_num, crop)).collect()
+}
+
+/// Raw extracted text of one page, by its 1-based number in the page tree,
+/// leaving out what falls in `crop`'s margins; empty when it fails to
+/// extract. A page that runs past `[limits]` keeps what was extracted,
+/// followed by a notice.
+pub fn extract_page(doc: &Document, page_num: u32, crop: Crop) -> String {
+    let mut output = LayoutText {
+        limits: PageLimits::start(),
+        crop,
+        ..Default::default()
+    };
+    let result = catch_panic(|| output_doc_page(doc, &mut output, page_num));
+    if let Some(notice) = output.limits.notice() {
+        return format!("{}\n\n{}", output.text, notice);
+    }
+    match result {
+        Some(Ok(())) => output.text,
+        _ => String::new(),
+    }
+}
+
+thread_local! {
+    static EXTRACTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f`, with a panic becoming `None`. pdf-extract panics on some malformed
+/// pages; those fail like any other page instead of closing the viewer, and
+/// the panic message stays off the screen.
+fn catch_panic<T>(f: impl FnOnce() -> T) -> Option<T> {
+    let _quiet = QuietPanics::start();
+    panic::catch_unwind(AssertUnwindSafe(f)).ok()
+}
+
+/// The panic hook in place before extraction began, and how many threads are
+/// extracting. It is put back when the last of them finishes.
+static QUIET: Mutex<(usize, Option<Arc<PanicHook>>)> = Mutex::new((0, None));
+
+type PanicHook = dyn Fn(&PanicHookInfo) + Sync + Send;
+
+/// Keeps panics on this thread quiet while it lives, passing those of other
+/// threads to the usual hook; dropping it, however extraction ended, restores
+/// that hook once no other thread is extracting.
+struct QuietPanics;
+
+impl QuietPanics {
+    fn start() -> Self {
+        let mut quiet = QUIET.lock().unwrap_or_else(PoisonError::into_inner);
+        if quiet.0 == 0 {
+            let default: Arc<PanicHook> = Arc::from(panic::take_hook());
+            let hook = Arc::clone(&default);
+            panic::set_hook(Box::new(move |info| {
+                if !EXTRACTING.get() {
+                    hook(info);
+                }
+            }));
+            quiet.1 = Some(default);
+        }
+        quiet.0 += 1;
+        EXTRACTING.set(true);
+        QuietPanics
+    }
+}
+
+impl Drop for QuietPanics {
+    fn drop(&mut self) {
+        EXTRACTING.set(false);
+        let mut quiet = QUIET.lock().unwrap_or_else(PoisonError::into_inner);
+        quiet.0 -= 1;
+        if quiet.0 == 0
+            && let Some(default) = quiet.1.take()
+        {
+            panic::set_hook(Box::new(move |info| default(info)));
+        }
+    }
+}
+
+/// Stands for a stretch of empty space between words on one line wider than a
+/// word space, such as between table columns; formatting aligns the columns
+/// or turns the run back into one space.
+pub const COLUMN_GAP: char = '\u{2007}';
+
+/// pdf-extract's plain text output, except that a gap wider than one em
+/// becomes a run of `COLUMN_GAP` about as long as the gap, instead of one space.
+#[derive(Default)]
+struct LayoutText {
+    text: String,
+    page_height: f64,
+    last_end: f64,
+    last_y: f64,
+    first_char: bool,
+    limits: PageLimits,
+    crop: Crop,
+    /// Left, bottom, right and top of the page less `crop`
+    bounds: (f64, f64, f64, f64),
+}
+
+impl LayoutText {
+    /// Stop the page once it has run past its limits.
+    fn within_limits(&mut self) -> Result<(), OutputError> {
+        if self.limits.check(self.text.len()) {
+            Ok(())
+        } else {
+            Err(OutputError::IoError(io::Error::other("page limit exceeded")))
+        }
+    }
+}
+
+impl OutputDev for LayoutText {
+    fn begin_page(&mut self, _page_num: u32, media_box: &MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        self.page_height = media_box.ury - media_box.lly;
+        self.last_end = 100000.0;
+        self.bounds = self.crop.bounds(media_box);
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn output_character(&mut self, trm: &Transform, width: f64, _spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        self.within_limits()?;
+        if !within(self.bounds, trm) {
+            return Ok(());
+        }
+        // Page space with y growing downwards, and the font size after scaling
+        let (x, y) = (trm.m31, self.page_height - trm.m32);
+        let size = ((font_size * (trm.m11 + trm.m21)) * (font_size * (trm.m12 + trm.m22))).sqrt();
+        if self.first_char {
+            if (y - self.last_y).abs() > size * 1.5 {
+                self.text.push('\n');
+            }
+            // Moved left and down
+            if x < self.last_end && (y - self.last_y).abs() > size * 0.5 {
+                self.text.push('\n');
+            }
+            let gap = x - self.last_end;
+            if gap > size {
+                // Half an em is about one character
+                let columns = ((gap / (size * 0.5)).round() as usize).max(2);
+                self.text.extend(std::iter::repeat_n(COLUMN_GAP, columns));
+            } else if gap > size * 0.1 {
+                self.text.push(' ');
+            }
+        }
+        self.text.push_str(char);
+        self.first_char = false;
+        self.last_y = y;
+        self.last_end = x + width * size;
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.first_char = true;
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    // Pages of nothing but drawing are timed too
+    fn stroke(&mut self, _: &Transform, _: &ColorSpace, _: &[f64], _: &pdf_extract::Path) -> Result<(), OutputError> {
+        self.within_limits()
+    }
+
+    fn fill(&mut self, _: &Transform, _: &ColorSpace, _: &[f64], _: &pdf_extract::Path) -> Result<(), OutputError> {
+        self.within_limits()
+    }
+}
+
+/// Text drawn on a page with where it starts, in points from the top left.
+pub struct Glyph {
+    pub x: f64,
+    /// Baseline
+    pub y: f64,
+    /// Where the next glyph would start if none came between
+    pub end: f64,
+    /// Font size after scaling
+    pub size: f64,
+    pub text: String,
+}
+
+/// The text of a page by position, for laying it out as printed, without
+/// what falls in `crop`'s margins; empty when the page fails to extract.
+/// Stops at the page's `[limits]`.
+pub fn page_glyphs(doc: &Document, page_num: u32, crop: Crop) -> Vec<Glyph> {
+    let mut output = GlyphPositions {
+        limits: PageLimits::start(),
+        crop,
+        ..Default::default()
+    };
+    let _ = catch_panic(|| output_doc_page(doc, &mut output, page_num));
+    output.glyphs
+}
+
+#[derive(Default)]
+struct GlyphPositions {
+    glyphs: Vec<Glyph>,
+    page_height: f64,
+    bytes: usize,
+    limits: PageLimits,
+    crop: Crop,
+    bounds: (f64, f64, f64, f64),
+}
+
+impl OutputDev for GlyphPositions {
+    fn begin_page(&mut self, _page_num: u32, media_box: &MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        self.page_height = media_box.ury - media_box.lly;
+        self.bounds = self.crop.bounds(media_box);
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn output_character(&mut self, trm: &Transform, width: f64, _spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        if !self.limits.check(self.bytes) {
+            return Err(OutputError::IoError(io::Error::other("page limit exceeded")));
+        }
+        if !within(self.bounds, trm) {
+            return Ok(());
+        }
+        self.bytes += char.len();
+        let size = ((font_size * (trm.m11 + trm.m21)) * (font_size * (trm.m12 + trm.m22))).sqrt();
+        self.glyphs.push(Glyph {
+            x: trm.m31,
+            y: self.page_height - trm.m32,
+            end: trm.m31 + width * size,
+            size,
+            text: char.to_string(),
+        });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+/// Whether a glyph drawn at `trm` starts inside `bounds`.
+fn within((left, bottom, right, top): (f64, f64, f64, f64), trm: &Transform) -> bool {
+    (left..=right).contains(&trm.m31) && (bottom..=top).contains(&trm.m32)
+}
+
+/// Map from page object id to 0-based page index.
+fn page_indices(doc: &Document) -> HashMap<ObjectId, usize> {
+    doc.get_pages()
+        .into_iter()
+        .map(|(num, id)| (id, num as usize - 1))
+        .collect()
+}
+
+fn resolve<'a>(doc: &'a Document, obj: &'a Object) -> Option<&'a Object> {
+    match obj {
+        Object::Reference(id) => doc.get_object(*id).ok(),
+        obj => Some(obj),
+    }
+}
+
+fn resolve_dict<'a>(doc: &'a Document, obj: &'a Object) -> Option<&'a Dictionary> {
+    resolve(doc, obj)?.as_dict().ok()
+}
+
+fn text(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<String> {
+    let obj = resolve(doc, dict.get(key).ok()?)?;
+    lopdf::decode_text_string(obj).ok()
+}
+
+/// The document outline, flattened in reading order. Entries whose target
+/// cannot be resolved to a page are skipped.
+pub fn outline(doc: &Document) -> Vec<OutlineEntry> {
+    let mut entries = Vec::new();
+    let Some(root) = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|outlines| resolve_dict(doc, outlines))
+    else {
+        return entries;
+    };
+
+    let pages = page_indices(doc);
+    let mut seen = HashSet::new();
+    walk_outline(doc, root.get(b"First").ok(), 0, &pages, &mut seen, &mut entries);
+    entries
+}
+
+const MAX_OUTLINE_DEPTH: usize = 64;
+
+fn walk_outline(
+    doc: &Document,
+    first: Option<&Object>,
+    level: usize,
+    pages: &HashMap<ObjectId, usize>,
+    seen: &mut HashSet<ObjectId>,
+    entries: &mut Vec<OutlineEntry>,
+) {
+    // Deeper nesting is a crafted file trying to exhaust the stack
+    if level > MAX_OUTLINE_DEPTH {
+        return;
+    }
+    let mut next = first.and_then(|obj| obj.as_reference().ok());
+    // `seen` guards against malformed outlines that loop back on themselves
+    while let Some(id) = next.filter(|id| seen.insert(*id)) {
+        let Ok(node) = doc.get_dictionary(id) else {
+            break;
+        };
+
+        let target = node.get(b"Dest").ok().or_else(|| {
+            let action = resolve_dict(doc, node.get(b"A").ok()?)?;
+            action.get(b"D").ok()
+        });
+        if let Some(page) = target.and_then(|dest| destination_page(doc, dest, pages)) {
+            entries.push(OutlineEntry {
+                level,
+                title: text(doc, node, b"Title").unwrap_or_default().trim().to_string(),
+                page,
+            });
+        }
+
+        walk_outline(doc, node.get(b"First").ok(), level + 1, pages, seen, entries);
+        next = node.get(b"Next").ok().and_then(|obj| obj.as_reference().ok());
+    }
+}
+
+/// Resolve an explicit (`[page /XYZ ...]`) or named destination to a page index.
+fn destination_page(doc: &Document, dest: &Object, pages: &HashMap<ObjectId, usize>) -> Option<usize> {
+    match resolve(doc, dest)? {
+        Object::Array(array) => pages.get(&array.first()?.as_reference().ok()?).copied(),
+        Object::Dictionary(dict) => destination_page(doc, dict.get(b"D").ok()?, pages),
+        Object::Name(name) | Object::String(name, _) => {
+            let target = named_destination(doc, name)?;
+            destination_page(doc, &target, pages)
+        }
+        _ => None,
+    }
+}
+
+/// Look up a named destination in the catalog's `/Dests` dictionary or `/Names` tree.
+fn named_destination(doc: &Document, name: &[u8]) -> Option<Object> {
+    let catalog = doc.catalog().ok()?;
+    if let Some(dests) = catalog.get(b"Dests").ok().and_then(|d| resolve_dict(doc, d))
+        && let Ok(dest) = dests.get(name)
+    {
+        return Some(dest.clone());
+    }
+
+    let names = resolve_dict(doc, catalog.get(b"Names").ok()?)?;
+    let tree = resolve_dict(doc, names.get(b"Dests").ok()?)?;
+    search_name_tree(doc, tree, name, 0)
+}
+
+fn search_name_tree(doc: &Document, node: &Dictionary, name: &[u8], depth: usize) -> Option<Object> {
+    if depth > 32 {
+        return None;
+    }
+    if let Some(names) = node.get(b"Names").ok().and_then(|n| resolve(doc, n)?.as_array().ok()) {
+        for pair in names.chunks(2) {
+            if let [key, value] = pair
+                && key.as_str().ok() == Some(name)
+            {
+                return Some(value.clone());
+            }
+        }
+    }
+    let kids = node.get(b"Kids").ok().and_then(|k| resolve(doc, k)?.as_array().ok())?;
+    kids.iter()
+        .filter_map(|kid| resolve_dict(doc, kid))
+        .find_map(|kid| search_name_tree(doc, kid, name, depth + 1))
+}
+
+/// Every key/value pair of a name tree, in key order.
+fn name_tree_entries(doc: &Document, node: &Dictionary, entries: &mut Vec<(String, Object)>, depth: usize) {
+    if depth > 32 {
+        return;
+    }
+    if let Some(names) = node.get(b"Names").ok().and_then(|n| resolve(doc, n)?.as_array().ok()) {
+        for pair in names.chunks(2) {
+            if let [key, value] = pair
+                && let Ok(key) = lopdf::decode_text_string(key)
+            {
+                entries.push((key, value.clone()));
+            }
+        }
+    }
+    if let Some(kids) = node.get(b"Kids").ok().and_then(|k| resolve(doc, k)?.as_array().ok()) {
+        for kid in kids.iter().filter_map(|kid| resolve_dict(doc, kid)) {
+            name_tree_entries(doc, kid, entries, depth + 1);
+        }
+    }
+}
+
+/// Each page's label, such as `iv` or `A-3`, from the catalog's `/PageLabels`;
+/// empty when the document has none. Pages the labels do not cover keep their number.
+pub fn page_labels(doc: &Document) -> Vec<String> {
+    let Some(tree) = doc.catalog().ok().and_then(|catalog| resolve_dict(doc, catalog.get(b"PageLabels").ok()?)) else {
+        return Vec::new();
+    };
+    let mut ranges = Vec::new();
+    number_tree_entries(doc, tree, &mut ranges, 0);
+    ranges.sort_by_key(|&(start, _)| start);
+    if ranges.is_empty() {
+        return Vec::new();
+    }
+    (0..doc.get_pages().len())
+        .map(|page| {
+            let Some((start, style)) = ranges.iter().rev().find(|&&(start, _)| start <= page) else {
+                return (page + 1).to_string();
+            };
+            match resolve_dict(doc, style) {
+                Some(style) => page_label(doc, style, page - start),
+                None => (page + 1).to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Highest /St taken from a labelling range; larger ones are clamped.
+const MAX_LABEL_START: i64 = 100_000;
+
+/// Longest roman or letter numbering; numbers past it are written in decimal.
+const MAX_LABEL_LENGTH: usize = 16;
+
+/// The label of the page `offset` pages into a labelling range.
+fn page_label(doc: &Document, style: &Dictionary, offset: usize) -> String {
+    let prefix = text(doc, style, b"P").unwrap_or_default();
+    let first = style.get(b"St").and_then(Object::as_i64).unwrap_or(1).clamp(1, MAX_LABEL_START) as usize;
+    let n = first.saturating_add(offset);
+    let number = match style.get(b"S").and_then(Object::as_name_str).unwrap_or("") {
+        "D" => n.to_string(),
+        "R" => roman(n),
+        "r" => roman(n).to_lowercase(),
+        // A to Z, then AA to ZZ, and so on
+        "A" => letters(n),
+        "a" => letters(n).to_ascii_lowercase(),
+        _ => String::new(),
+    };
+    format!("{}{}", prefix, number)
+}
+
+/// `n` in roman numerals, or in decimal when that would be too long to read.
+fn roman(mut n: usize) -> String {
+    if n >= 4000 {
+        return n.to_string();
+    }
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+/// `n` as a letter repeated once per round of the alphabet, or in decimal
+/// past `MAX_LABEL_LENGTH` letters.
+fn letters(n: usize) -> String {
+    let count = (n - 1) / 26 + 1;
+    if count > MAX_LABEL_LENGTH {
+        return n.to_string();
+    }
+    ((b'A' + ((n - 1) % 26) as u8) as char).to_string().repeat(count)
+}
+
+/// Every key/value pair of a number tree.
+fn number_tree_entries<'a>(doc: &'a Document, node: &'a Dictionary, entries: &mut Vec<(usize, &'a Object)>, depth: usize) {
+    if depth > 32 {
+        return;
+    }
+    if let Some(nums) = node.get(b"Nums").ok().and_then(|n| resolve(doc, n)?.as_array().ok()) {
+        for pair in nums.chunks(2) {
+            if let [key, value] = pair
+                && let Ok(key) = key.as_i64()
+                && key >= 0
+            {
+                entries.push((key as usize, value));
+            }
+        }
+    }
+    if let Some(kids) = node.get(b"Kids").ok().and_then(|k| resolve(doc, k)?.as_array().ok()) {
+        for kid in kids.iter().filter_map(|kid| resolve_dict(doc, kid)) {
+            number_tree_entries(doc, kid, entries, depth + 1);
+        }
+    }
+}
+
+/// The /Title entry of the document information dictionary, if set.
+pub fn title(doc: &Document) -> Option<String> {
+    let info = resolve_dict(doc, doc.trailer.get(b"Info").ok()?)?;
+    text(doc, info, b"Title").map(|title| title.trim().to_string()).filter(|title| !title.is_empty())
+}
+
+/// Comment and markup annotations with their text, in page order.
+pub fn annotations(doc: &Document) -> Vec<Annotation> {
+    const KINDS: &[&str] = &[
+        "Text", "FreeText", "Highlight", "Underline", "StrikeOut", "Squiggly", "Ink",
+    ];
+
+    let mut annotations = Vec::new();
+    for (num, page_id) in doc.get_pages() {
+        let Ok(annots) = doc.get_page_annotations(page_id) else {
+            continue;
+        };
+        for annot in annots {
+            let kind = annot
+                .get(b"Subtype")
+                .and_then(Object::as_name_str)
+                .unwrap_or_default();
+            if !KINDS.contains(&kind) {
+                continue;
+            }
+            annotations.push(Annotation {
+                page: num as usize - 1,
+                kind: kind.to_string(),
+                contents: text(doc, annot, b"Contents").unwrap_or_default(),
+            });
+        }
+    }
+    annotations
+}
+
+/// A file embedded in the PDF, either document-wide or attached to a page.
+#[derive(Clone)]
+pub struct Attachment {
+    /// File name as stored in the PDF; may contain directories
+    pub name: String,
+    pub description: Option<String>,
+    /// Uncompressed size in bytes, when the PDF records it
+    pub size: Option<u64>,
+    /// 0-based page of a file attachment annotation, `None` for document attachments
+    pub page: Option<usize>,
+    /// The embedded file stream
+    pub id: ObjectId,
+}
+
+/// Files embedded via the `/EmbeddedFiles` name tree and file attachment annotations,
+/// each listed once.
+pub fn attachments(doc: &Document) -> Vec<Attachment> {
+    let mut specs = Vec::new();
+    let tree = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| resolve_dict(doc, catalog.get(b"Names").ok()?))
+        .and_then(|names| resolve_dict(doc, names.get(b"EmbeddedFiles").ok()?));
+    if let Some(tree) = tree {
+        let mut entries = Vec::new();
+        name_tree_entries(doc, tree, &mut entries, 0);
+        specs.extend(entries.into_iter().map(|(key, spec)| (Some(key), spec, None)));
+    }
+    for (num, page_id) in doc.get_pages() {
+        let Ok(annots) = doc.get_page_annotations(page_id) else {
+            continue;
+        };
+        for annot in annots {
+            if annot.get(b"Subtype").and_then(Object::as_name_str).ok() == Some("FileAttachment")
+                && let Ok(spec) = annot.get(b"FS")
+            {
+                specs.push((None, spec.clone(), Some(num as usize - 1)));
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut attachments = Vec::new();
+    for (key, spec, page) in specs {
+        let Some(spec) = resolve_dict(doc, &spec) else { continue };
+        let Some(Ok(id)) = spec
+            .get(b"EF")
+            .ok()
+            .and_then(|ef| resolve_dict(doc, ef))
+            .and_then(|ef| ef.get(b"UF").or_else(|_| ef.get(b"F")).ok())
+            .map(Object::as_reference)
+        else {
+            continue;
+        };
+        if !seen.insert(id) {
+            continue;
+        }
+        let name = text(doc, spec, b"UF")
+            .or_else(|| text(doc, spec, b"F"))
+            .or(key)
+            .unwrap_or_else(|| format!("attachment-{}", attachments.len() + 1));
+        let size = doc
+            .get_object(id)
+            .and_then(Object::as_stream)
+            .ok()
+            .and_then(|stream| resolve_dict(doc, stream.dict.get(b"Params").ok()?))
+            .and_then(|params| resolve(doc, params.get(b"Size").ok()?)?.as_i64().ok())
+            .map(|size| size as u64);
+        attachments.push(Attachment {
+            name,
+            description: text(doc, spec, b"Desc").filter(|desc| !desc.trim().is_empty()),
+            size,
+            page,
+            id,
+        });
+    }
+    attachments
+}
+
+/// The decoded contents of an embedded file.
+pub fn attachment_data(doc: &Document, attachment: &Attachment) -> Result<Vec<u8>> {
+    Ok(doc.get_object(attachment.id)?.as_stream()?.get_plain_content()?)
+}
+
+/// Document properties for the info panel: the PDF version and the
+/// information dictionary's text fields, in a fixed order.
+pub fn info(doc: &Document) -> Vec<(&'static str, String)> {
+    const FIELDS: &[(&str, &[u8])] = &[
+        ("Title", b"Title"),
+        ("Author", b"Author"),
+        ("Subject", b"Subject"),
+        ("Keywords", b"Keywords"),
+        ("Creator", b"Creator"),
+        ("Producer", b"Producer"),
+        ("Created", b"CreationDate"),
+        ("Modified", b"ModDate"),
+    ];
+    let mut info = vec![("PDF version", doc.version.clone())];
+    if let Some(dict) = doc.trailer.get(b"Info").ok().and_then(|i| resolve_dict(doc, i)) {
+        for &(label, key) in FIELDS {
+            if let Some(value) = text(doc, dict, key).filter(|v| !v.trim().is_empty()) {
+                info.push((label, value.trim().to_string()));
+            }
+        }
+    }
+    info
+}
+
+/// Width and height in points of a 0-based page, from its (possibly inherited) MediaBox.
+pub fn page_size(doc: &Document, page: usize) -> Option<(f32, f32)> {
+    let id = *doc.get_pages().get(&(page as u32 + 1))?;
+    let mut dict = doc.get_dictionary(id).ok()?;
+    for _ in 0..32 {
+        if let Some(rect) = dict.get(b"MediaBox").ok().and_then(|b| resolve(doc, b)?.as_array().ok()) {
+            let values: Vec<f32> = rect.iter().filter_map(|v| v.as_float().ok()).collect();
+            if let [x0, y0, x1, y1] = values[..] {
+                return Some(((x1 - x0).abs(), (y1 - y0).abs()));
+            }
+        }
+        dict = resolve_dict(doc, dict.get(b"Parent").ok()?)?;
+    }
+    None
+}
+
+/// Clockwise turn in degrees a 0-based page is meant to be shown at, from its
+/// (possibly inherited) /Rotate: 0, 90, 180 or 270.
+pub fn page_rotation(doc: &Document, page: usize) -> u16 {
+    let rotate = || {
+        let id = *doc.get_pages().get(&(page as u32 + 1))?;
+        let mut dict = doc.get_dictionary(id).ok()?;
+        for _ in 0..32 {
+            if let Some(degrees) = dict.get(b"Rotate").ok().and_then(|r| resolve(doc, r)?.as_i64().ok()) {
+                return Some(degrees);
+            }
+            dict = resolve_dict(doc, dict.get(b"Parent").ok()?)?;
+        }
+        None
+    };
+    match rotate() {
+        Some(degrees) if degrees % 90 == 0 => degrees.rem_euclid(360) as u16,
+        _ => 0,
+    }
+}
+
+/// An image XObject drawn on a page.
+#[derive(Clone)]
+pub struct PageImage {
+    pub id: ObjectId,
+    /// Resource name, e.g. `Im1`
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub bits: u32,
+    /// Base colour space name, e.g. `DeviceRGB` or `ICCBased`
+    pub color_space: String,
+    /// Colour components per pixel, when known
+    pub components: Option<u32>,
+    /// Last stream filter, which decides the file format, e.g. `DCTDecode`
+    pub filter: Option<String>,
+}
+
+/// Images on a 0-based page, including those inside form XObjects, each listed once.
+pub fn page_images(doc: &Document, page: usize) -> Vec<PageImage> {
+    let mut images = Vec::new();
+    let Some(&page_id) = doc.get_pages().get(&(page as u32 + 1)) else {
+        return images;
+    };
+    // Resources may be inherited from an ancestor in the page tree
+    let mut node = doc.get_dictionary(page_id).ok();
+    let mut resources = None;
+    for _ in 0..32 {
+        let Some(dict) = node else { break };
+        if let Some(found) = dict.get(b"Resources").ok().and_then(|r| resolve_dict(doc, r)) {
+            resources = Some(found);
+            break;
+        }
+        node = dict.get(b"Parent").ok().and_then(|p| resolve_dict(doc, p));
+    }
+    if let Some(resources) = resources {
+        collect_images(doc, resources, &mut HashSet::new(), &mut images, 0);
+    }
+    images
+}
+
+fn collect_images(
+    doc: &Document,
+    resources: &Dictionary,
+    seen: &mut HashSet<ObjectId>,
+    images: &mut Vec<PageImage>,
+    depth: usize,
+) {
+    let Some(xobjects) = resources.get(b"XObject").ok().and_then(|x| resolve_dict(doc, x)) else {
+        return;
+    };
+    for (name, value) in xobjects.iter() {
+        let Ok(id) = value.as_reference() else { continue };
+        if !seen.insert(id) {
+            continue;
+        }
+        let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else {
+            continue;
+        };
+        let dict = &stream.dict;
+        match dict.get(b"Subtype").and_then(Object::as_name_str) {
+            Ok("Image") => {
+                let number = |key: &[u8]| dict.get(key).ok().and_then(|v| resolve(doc, v)?.as_i64().ok());
+                let (color_space, components) = color_space(doc, dict.get(b"ColorSpace").ok());
+                let filter = match dict.get(b"Filter").ok().and_then(|f| resolve(doc, f)) {
+                    Some(Object::Name(name)) => Some(String::from_utf8_lossy(name).into_owned()),
+                    Some(Object::Array(filters)) => filters.last().and_then(|f| f.as_name_str().ok()).map(str::to_string),
+                    _ => None,
+                };
+                images.push(PageImage {
+                    id,
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    width: number(b"Width").unwrap_or(0) as u32,
+                    height: number(b"Height").unwrap_or(0) as u32,
+                    bits: number(b"BitsPerComponent").unwrap_or(8) as u32,
+                    color_space,
+                    components,
+                    filter,
+                });
+            }
+            Ok("Form") if depth < 8 => {
+                if let Some(inner) = dict.get(b"Resources").ok().and_then(|r| resolve_dict(doc, r)) {
+                    collect_images(doc, inner, seen, images, depth + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Name and component count of an image colour space.
+fn color_space(doc: &Document, obj: Option<&Object>) -> (String, Option<u32>) {
+    match obj.and_then(|obj| resolve(doc, obj)) {
+        Some(Object::Name(name)) => {
+            let name = String::from_utf8_lossy(name).into_owned();
+            let components = match name.as_str() {
+                "DeviceGray" | "CalGray" => Some(1),
+                "DeviceRGB" | "CalRGB" => Some(3),
+                "DeviceCMYK" => Some(4),
+                _ => None,
+            };
+            (name, components)
+        }
+        Some(Object::Array(array)) => {
+            let name = array.first().and_then(|n| n.as_name_str().ok()).unwrap_or("Unknown").to_string();
+            let components = match name.as_str() {
+                "ICCBased" => array
+                    .get(1)
+                    .and_then(|profile| resolve(doc, profile)?.as_stream().ok())
+                    .and_then(|profile| profile.dict.get(b"N").ok()?.as_i64().ok())
+                    .map(|n| n as u32),
+                "Indexed" => Some(1),
+                _ => None,
+            };
+            (name, components)
+        }
+        _ => ("Unknown".to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// A one-page document labelled with `style` from its first page.
+    fn labelled(style: Dictionary) -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 }));
+        let labels = dictionary! { "Nums" => vec![0.into(), Object::Dictionary(style)] };
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id, "PageLabels" => labels });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    #[test]
+    fn huge_label_start_is_clamped() {
+        for style in ["R", "r", "A", "a", "D"] {
+            let doc = labelled(dictionary! { "S" => style, "St" => 9_000_000_000_000_000_000i64 });
+            assert_eq!(page_labels(&doc), vec![MAX_LABEL_START.to_string()]);
+        }
+        let doc = labelled(dictionary! { "S" => "r", "St" => 4 });
+        assert_eq!(page_labels(&doc), vec!["iv"]);
+    }
+
+    #[test]
+    fn extractor_panics_are_caught_and_the_hook_put_back() {
+        assert_eq!(catch_panic(|| -> u8 { panic!("malformed page") }), None);
+        assert_eq!(catch_panic(|| 7), Some(7));
+        let quiet = QUIET.lock().unwrap();
+        assert_eq!(quiet.0, 0);
+        assert!(quiet.1.is_none());
+    }
+}
@@ -0,0 +1,238 @@
+use rhai::{Dynamic, Engine, FuncArgs, Scope, AST};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Something a plugin asked the viewer to do, applied once the script returns.
+pub enum Action {
+    /// Go to a 1-based page
+    Goto(usize),
+    Search(String),
+    Status(String),
+    Popup(String, String),
+    /// Run a built-in `:` command
+    Command(String),
+}
+
+/// What scripts can see of the viewer, and what they asked for.
+#[derive(Default)]
+struct State {
+    file: String,
+    pages: Vec<String>,
+    /// 1-based current page
+    page: usize,
+    search_query: String,
+    selection: String,
+    actions: Vec<Action>,
+    /// `register_command` and `bind_key` calls made while a script loads
+    commands: Vec<(String, String)>,
+    keys: Vec<(char, String)>,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+}
+
+/// Rhai scripts loaded from the plugin directory.
+///
+/// A script's top level runs once at startup and may call
+/// `register_command(name, fn)` and `bind_key(key, fn)`. Functions named
+/// `on_open()`, `on_page_change(page)` and `on_search(query)` are called as hooks.
+pub struct Plugins {
+    engine: Engine,
+    state: Rc<RefCell<State>>,
+    scripts: Vec<Script>,
+    /// Command name to (script index, function name)
+    commands: HashMap<String, (usize, String)>,
+    keys: HashMap<char, (usize, String)>,
+}
+
+impl Plugins {
+    pub fn new() -> Self {
+        let state = Rc::new(RefCell::new(State::default()));
+        Self {
+            engine: engine(&state),
+            state,
+            scripts: Vec::new(),
+            commands: HashMap::new(),
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Load every `*.rhai` file in `dir`, in name order. A missing directory
+    /// means no plugins. Scripts that fail to load are skipped and reported.
+    pub fn load(&mut self, dir: &Path) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+
+        let mut errors = Vec::new();
+        for path in paths {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let result = self
+                .engine
+                .compile_file(path.clone())
+                .and_then(|ast| self.engine.run_ast(&ast).map(|()| ast));
+            let mut state = self.state.borrow_mut();
+            let (commands, keys) = (std::mem::take(&mut state.commands), std::mem::take(&mut state.keys));
+            state.actions.clear();
+            match result {
+                Ok(ast) => {
+                    let index = self.scripts.len();
+                    self.commands.extend(commands.into_iter().map(|(cmd, f)| (cmd, (index, f))));
+                    self.keys.extend(keys.into_iter().map(|(key, f)| (key, (index, f))));
+                    self.scripts.push(Script { name, ast });
+                }
+                Err(e) => errors.push(format!("Plugin {}: {}", name, e)),
+            }
+        }
+        errors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    /// Make a newly opened document visible to scripts.
+    pub fn set_document(&mut self, file: &str, pages: &[String]) {
+        if self.is_empty() {
+            return;
+        }
+        let mut state = self.state.borrow_mut();
+        state.file = file.to_string();
+        state.pages = pages.to_vec();
+    }
+
+    /// Update the view state scripts see before calling into them.
+    pub fn set_view(&mut self, page: usize, search_query: &str, selection: Option<String>) {
+        let mut state = self.state.borrow_mut();
+        state.page = page + 1;
+        state.search_query = search_query.to_string();
+        state.selection = selection.unwrap_or_default();
+    }
+
+    pub fn has_command(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+    }
+
+    pub fn has_key(&self, key: char) -> bool {
+        self.keys.contains_key(&key)
+    }
+
+    /// Run a plugin command, passing the rest of the command line as one string.
+    pub fn run_command(&mut self, name: &str, args: &str) -> Result<Vec<Action>, String> {
+        let Some((script, function)) = self.commands.get(name).cloned() else {
+            return Err(format!("Unknown command: {}", name));
+        };
+        self.call(script, &function, (args.to_string(),))
+    }
+
+    pub fn run_key(&mut self, key: char) -> Result<Vec<Action>, String> {
+        let Some((script, function)) = self.keys.get(&key).cloned() else {
+            return Ok(Vec::new());
+        };
+        self.call(script, &function, ())
+    }
+
+    /// Call `hook` in every script that defines it with a matching parameter count.
+    /// Stops at the first failing script.
+    pub fn hook(&mut self, hook: &str, args: Vec<Dynamic>) -> Result<Vec<Action>, String> {
+        let mut actions = Vec::new();
+        for index in 0..self.scripts.len() {
+            let defined = self.scripts[index]
+                .ast
+                .iter_functions()
+                .any(|f| f.name == hook && f.params.len() == args.len());
+            if defined {
+                actions.extend(self.call(index, hook, args.clone())?);
+            }
+        }
+        Ok(actions)
+    }
+
+    fn call(&mut self, index: usize, function: &str, args: impl FuncArgs) -> Result<Vec<Action>, String> {
+        let script = &self.scripts[index];
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &script.ast, function, args);
+        let actions = std::mem::take(&mut self.state.borrow_mut().actions);
+        match result {
+            Ok(_) => Ok(actions),
+            Err(e) => Err(format!("Plugin {}: {}", script.name, e)),
+        }
+    }
+}
+
+/// The scripting engine with the viewer API registered.
+fn engine(state: &Rc<RefCell<State>>) -> Engine {
+    let mut engine = Engine::new();
+    // A runaway script must not hang the viewer
+    engine.set_max_operations(10_000_000);
+
+    let s = state.clone();
+    engine.register_fn("register_command", move |name: &str, function: &str| {
+        s.borrow_mut().commands.push((name.to_string(), function.to_string()));
+    });
+    let s = state.clone();
+    engine.register_fn("bind_key", move |key: &str, function: &str| {
+        if let Some(key) = key.chars().next() {
+            s.borrow_mut().keys.push((key, function.to_string()));
+        }
+    });
+
+    let s = state.clone();
+    engine.register_fn("file", move || s.borrow().file.clone());
+    let s = state.clone();
+    engine.register_fn("page", move || s.borrow().page as i64);
+    let s = state.clone();
+    engine.register_fn("page_count", move || s.borrow().pages.len() as i64);
+    let s = state.clone();
+    engine.register_fn("page_text", move |page: i64| {
+        let state = s.borrow();
+        usize::try_from(page - 1)
+            .ok()
+            .and_then(|i| state.pages.get(i).cloned())
+            .unwrap_or_default()
+    });
+    let s = state.clone();
+    engine.register_fn("search_query", move || s.borrow().search_query.clone());
+    let s = state.clone();
+    engine.register_fn("selection", move || s.borrow().selection.clone());
+
+    let s = state.clone();
+    engine.register_fn("goto", move |page: i64| {
+        if let Ok(page) = usize::try_from(page) {
+            s.borrow_mut().actions.push(Action::Goto(page));
+        }
+    });
+    let s = state.clone();
+    engine.register_fn("search", move |query: &str| {
+        s.borrow_mut().actions.push(Action::Search(query.to_string()));
+    });
+    let s = state.clone();
+    engine.register_fn("status", move |message: &str| {
+        s.borrow_mut().actions.push(Action::Status(message.to_string()));
+    });
+    let s = state.clone();
+    engine.register_fn("popup", move |title: &str, text: &str| {
+        s.borrow_mut().actions.push(Action::Popup(title.to_string(), text.to_string()));
+    });
+    let s = state.clone();
+    engine.register_fn("command", move |line: &str| {
+        s.borrow_mut().actions.push(Action::Command(line.to_string()));
+    });
+    // `print` goes to the status line rather than the terminal the TUI is drawing on
+    let s = state.clone();
+    engine.on_print(move |message| s.borrow_mut().actions.push(Action::Status(message.to_string())));
+    engine.on_debug(|_, _, _| {});
+
+    engine
+}
@@ -0,0 +1,116 @@
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+/// A scrollable text overlay drawn on top of the content pane.
+pub struct Popup {
+    pub title: String,
+    pub text: String,
+    pub scroll: usize,
+}
+
+impl Popup {
+    pub fn new(title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            text: text.into(),
+            scroll: 0,
+        }
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.scroll + 1 < self.text.lines().count() {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    /// Draw centered over `area`.
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        self.render_in(f, centered(area, 80, 70), "j/k scroll, Esc close");
+    }
+
+    /// Draw filling `area`, with `hint` shown after the title.
+    pub fn render_in(&self, f: &mut Frame, area: Rect, hint: &str) {
+        let paragraph = Paragraph::new(self.text.as_str())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("{} ({})", self.title, hint)),
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((self.scroll.min(u16::MAX as usize) as u16, 0))
+            .style(Style::default().fg(Color::White).bg(Color::Black));
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+    }
+}
+
+/// A selectable list overlay, e.g. for picking a citation or a search hit.
+pub struct ListPopup {
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+    /// Key help shown after the title
+    pub hint: String,
+}
+
+impl ListPopup {
+    pub fn new(title: impl Into<String>, items: Vec<String>, hint: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            items,
+            selected: 0,
+            hint: hint.into(),
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.items.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let area = centered(area, 80, 70);
+        let items: Vec<ListItem> = self.items.iter().map(|item| ListItem::new(item.as_str())).collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("{} ({})", self.title, self.hint)),
+            )
+            .style(Style::default().fg(Color::White).bg(Color::Black))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+/// The text area inside a popup drawn with [`Popup::render`] over `area`.
+pub fn inner_area(area: Rect) -> Rect {
+    Block::default().borders(Borders::ALL).inner(centered(area, 80, 70))
+}
+
+/// A rectangle of `percent_x` by `percent_y` centered in `area`.
+fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let width = (u32::from(area.width) * u32::from(percent_x.min(100)) / 100) as u16;
+    let height = (u32::from(area.height) * u32::from(percent_y.min(100)) / 100) as u16;
+    Rect {
+        x: area.x.saturating_add((area.width - width) / 2),
+        y: area.y.saturating_add((area.height - height) / 2),
+        width,
+        height,
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::{BTreeMap, VecDeque};
+
+/// Register that always holds the last yank, wherever it went.
+pub const UNNAMED: char = '"';
+
+/// Yanks and clips remembered for `:clips`; older ones are forgotten.
+const MAX_HISTORY: usize = 100;
+
+/// Text yanked or clipped during the session.
+#[derive(Clone)]
+pub struct Yanked {
+    pub text: String,
+    /// 0-based page it was taken from
+    pub page: usize,
+}
+
+/// Yanked text in named registers, vim style: `"a` before `y` or `Y` yanks
+/// into `a` instead of the clipboard, and `"A` appends to it.
+#[derive(Default)]
+pub struct Registers {
+    texts: BTreeMap<char, String>,
+    /// Register named with `"` for the next yank
+    selected: Option<char>,
+    /// Every yank and clip, newest first
+    history: VecDeque<Yanked>,
+}
+
+impl Registers {
+    /// Name the register the next yank goes to.
+    pub fn select(&mut self, name: char) -> Result<(), String> {
+        if !name.is_ascii_alphabetic() {
+            return Err(format!("Invalid register: {} (a-z, or A-Z to append)", name));
+        }
+        self.selected = Some(name);
+        Ok(())
+    }
+
+    pub fn take_selected(&mut self) -> Option<char> {
+        self.selected.take()
+    }
+
+    /// Put `text` in register `name`, or add it on a new line when `name` is
+    /// uppercase. Returns the register's lowercase name.
+    pub fn store(&mut self, name: char, text: &str) -> char {
+        let register = name.to_ascii_lowercase();
+        let entry = self.texts.entry(register).or_default();
+        if name.is_ascii_uppercase() && !entry.is_empty() {
+            entry.push('\n');
+            entry.push_str(text);
+        } else {
+            *entry = text.to_string();
+        }
+        register
+    }
+
+    /// Remember text yanked or clipped from 0-based `page`. Taking the same
+    /// text again moves it to the front instead of repeating it.
+    pub fn remember(&mut self, text: &str, page: usize) {
+        self.history.retain(|yanked| yanked.text != text);
+        self.history.push_front(Yanked { text: text.to_string(), page });
+        self.history.truncate(MAX_HISTORY);
+    }
+
+    pub fn history(&self) -> &VecDeque<Yanked> {
+        &self.history
+    }
+
+    pub fn get(&self, name: char) -> Option<&str> {
+        self.texts.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    pub fn remove(&mut self, name: char) {
+        self.texts.remove(&name);
+    }
+
+    /// Registers with text, the unnamed one first, then `a` to `z`.
+    pub fn names(&self) -> Vec<char> {
+        let unnamed = self.texts.contains_key(&UNNAMED).then_some(UNNAMED);
+        unnamed.into_iter().chain(self.texts.keys().copied().filter(|&name| name != UNNAMED)).collect()
+    }
+}
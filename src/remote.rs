@@ -0,0 +1,238 @@
+use std::path::PathBuf;
+
+pub use socket::{send, Server};
+
+/// A command sent to a running viewer by `pdf_reader ctl`.
+pub enum RemoteCommand {
+    /// Go to a 1-based page
+    Goto(usize),
+    Search(String),
+    /// Re-read the open file from disk
+    Reload,
+    Open(PathBuf),
+    /// Show the PDF position of a `.tex` source line (SyncTeX forward search)
+    Synctex(PathBuf, usize),
+}
+
+impl RemoteCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arg = arg.trim();
+        match name {
+            "goto" => arg
+                .parse()
+                .map(RemoteCommand::Goto)
+                .map_err(|_| format!("Invalid page number: {}", arg)),
+            "search" if !arg.is_empty() => Ok(RemoteCommand::Search(arg.to_string())),
+            "reload" => Ok(RemoteCommand::Reload),
+            "open" if !arg.is_empty() => Ok(RemoteCommand::Open(PathBuf::from(arg))),
+            "synctex" => {
+                let (file, line) = arg
+                    .rsplit_once(':')
+                    .and_then(|(file, line)| Some((file, line.parse().ok()?)))
+                    .ok_or_else(|| "Usage: synctex FILE.tex:LINE".to_string())?;
+                Ok(RemoteCommand::Synctex(PathBuf::from(file), line))
+            }
+            "search" | "open" => Err(format!("Usage: {} ARG", name)),
+            _ => Err(format!("Unknown command: {}", name)),
+        }
+    }
+}
+
+/// Join `ctl` arguments into a command line. File arguments are made absolute
+/// since the viewer may be running in another directory.
+pub fn command_line(args: &[String]) -> String {
+    let absolute = |path: &str| {
+        std::path::absolute(path).map_or_else(|_| path.to_string(), |p| p.display().to_string())
+    };
+    match args {
+        [name, file] if name == "open" => format!("open {}", absolute(file)),
+        [name, spec] if name == "synctex" => match spec.rsplit_once(':') {
+            Some((file, line)) => format!("synctex {}:{}", absolute(file), line),
+            None => args.join(" "),
+        },
+        _ => args.join(" "),
+    }
+}
+
+/// `--listen` and `ctl` talk over a Unix socket.
+#[cfg(unix)]
+mod socket {
+    use anyhow::{anyhow, bail, Context, Result};
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::Shutdown;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    /// A command received on the socket, answered once it has been carried out.
+    pub struct Request {
+        pub line: String,
+        stream: UnixStream,
+    }
+
+    impl Request {
+        pub fn reply(mut self, result: Result<String, String>) {
+            let reply = match result {
+                Ok(message) => format!("ok {}\n", message),
+                Err(message) => format!("error {}\n", message),
+            };
+            // The client may have given up waiting; nothing to do about that
+            let _ = self.stream.write_all(reply.as_bytes());
+        }
+    }
+
+    /// Listens on a Unix socket for commands from `pdf_reader ctl`.
+    pub struct Server {
+        listener: UnixListener,
+        path: PathBuf,
+    }
+
+    impl Server {
+        pub fn bind(path: &Path) -> Result<Self> {
+            if path.exists() {
+                if UnixStream::connect(path).is_ok() {
+                    bail!("Another instance is already listening on {}", path.display());
+                }
+                // Left behind by an instance that did not shut down cleanly
+                fs::remove_file(path)
+                    .with_context(|| format!("Could not remove stale socket {}", path.display()))?;
+            }
+            let listener = bind_private(path)
+                .with_context(|| format!("Could not listen on {}", path.display()))?;
+            listener.set_nonblocking(true)?;
+            Ok(Self {
+                listener,
+                path: path.to_path_buf(),
+            })
+        }
+
+        /// Commands from clients that connected since the last call.
+        pub fn poll(&self) -> Vec<Request> {
+            let mut requests = Vec::new();
+            while let Ok((stream, _)) = self.listener.accept() {
+                // A client that connects but never sends a line must not freeze the viewer
+                if stream.set_nonblocking(false).is_err()
+                    || stream.set_read_timeout(Some(Duration::from_millis(500))).is_err()
+                {
+                    continue;
+                }
+                let mut line = String::new();
+                if let Ok(reader) = stream.try_clone()
+                    && BufReader::new(reader).read_line(&mut line).is_ok()
+                    && !line.trim().is_empty()
+                {
+                    requests.push(Request { line, stream });
+                }
+            }
+            requests
+        }
+    }
+
+    /// Bind the socket in a folder only we can enter, make it ours alone and
+    /// only then move it to `path`, so no other user can connect to it even
+    /// for a moment and drive the viewer.
+    fn bind_private(path: &Path) -> std::io::Result<UnixListener> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let dir = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+        fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        let staged = dir.join("socket");
+        let bound = UnixListener::bind(&staged).and_then(|listener| {
+            fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+            fs::rename(&staged, path)?;
+            Ok(listener)
+        });
+        let _ = fs::remove_file(&staged);
+        let _ = fs::remove_dir(&dir);
+        bound
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    /// Send one command to the instance listening on `path` and return its reply.
+    pub fn send(path: &Path, command: &str) -> Result<String> {
+        let mut stream = UnixStream::connect(path)
+            .with_context(|| format!("No pdf_reader is listening on {}", path.display()))?;
+        stream.write_all(format!("{}\n", command).as_bytes())?;
+        stream.shutdown(Shutdown::Write)?;
+
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        let reply = reply.trim_end();
+        match reply.split_once(' ').unwrap_or((reply, "")) {
+            ("ok", message) => Ok(message.to_string()),
+            ("error", message) => Err(anyhow!("{}", message)),
+            _ => bail!("Unexpected reply: {}", reply),
+        }
+    }
+}
+
+/// Without Unix sockets there is no remote control; `--listen` and `ctl` say so.
+#[cfg(not(unix))]
+mod socket {
+    use anyhow::{bail, Result};
+    use std::path::Path;
+
+    const UNSUPPORTED: &str = "Remote control (--listen, ctl) is not supported on this platform";
+
+    pub struct Request {
+        pub line: String,
+    }
+
+    impl Request {
+        pub fn reply(self, _result: Result<String, String>) {}
+    }
+
+    pub struct Server;
+
+    impl Server {
+        pub fn bind(_path: &Path) -> Result<Self> {
+            bail!(UNSUPPORTED)
+        }
+
+        pub fn poll(&self) -> Vec<Request> {
+            Vec::new()
+        }
+    }
+
+    pub fn send(_path: &Path, _command: &str) -> Result<String> {
+        bail!(UNSUPPORTED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn commands_take_their_arguments() {
+        assert!(matches!(RemoteCommand::parse("goto 42\n"), Ok(RemoteCommand::Goto(42))));
+        assert!(matches!(RemoteCommand::parse("search  gradient descent"), Ok(RemoteCommand::Search(q)) if q == "gradient descent"));
+        assert!(matches!(RemoteCommand::parse("reload"), Ok(RemoteCommand::Reload)));
+        assert!(matches!(RemoteCommand::parse("open /tmp/a b.pdf"), Ok(RemoteCommand::Open(p)) if p == Path::new("/tmp/a b.pdf")));
+    }
+
+    #[test]
+    fn synctex_splits_at_the_last_colon() {
+        let Ok(RemoteCommand::Synctex(file, line)) = RemoteCommand::parse("synctex C:/thesis/ch1.tex:120") else {
+            panic!("synctex was not parsed");
+        };
+        assert_eq!((file, line), (PathBuf::from("C:/thesis/ch1.tex"), 120));
+        assert!(RemoteCommand::parse("synctex ch1.tex").is_err());
+    }
+
+    #[test]
+    fn bad_commands_are_explained() {
+        assert_eq!(RemoteCommand::parse("goto x").err().unwrap(), "Invalid page number: x");
+        assert_eq!(RemoteCommand::parse("search").err().unwrap(), "Usage: search ARG");
+        assert_eq!(RemoteCommand::parse("quit").err().unwrap(), "Unknown command: quit");
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::{HashMap, HashSet};
+
+/// Very common English words that say nothing about relevance.
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "are", "but", "not", "you", "all", "any", "can", "had", "her", "was",
+    "one", "our", "out", "has", "his", "how", "its", "who", "did", "does", "what", "when",
+    "where", "which", "why", "with", "this", "that", "from", "they", "them", "then", "than",
+    "there", "their", "these", "those", "have", "been", "were", "will", "would", "about",
+    "into", "more", "some", "such", "only", "other", "also", "each", "most", "over", "under",
+];
+
+/// Lowercased words of `text` worth matching on.
+pub fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 2)
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Indices of the `limit` pages most relevant to `query`, best first, scored
+/// by TF-IDF over the query's terms. Pages sharing no terms are left out.
+pub fn relevant_pages(pages: impl IntoIterator<Item = impl AsRef<str>>, query: &str, limit: usize) -> Vec<usize> {
+    let query_terms: HashSet<String> = terms(query).into_iter().collect();
+    if query_terms.is_empty() {
+        return Vec::new();
+    }
+
+    let counts: Vec<HashMap<String, usize>> = pages
+        .into_iter()
+        .map(|page| {
+            let mut counts = HashMap::new();
+            for term in terms(page.as_ref()).into_iter().filter(|t| query_terms.contains(t)) {
+                *counts.entry(term).or_insert(0) += 1;
+            }
+            counts
+        })
+        .collect();
+
+    let idf = |term: &String| {
+        let containing = counts.iter().filter(|c| c.contains_key(term)).count();
+        ((counts.len() + 1) as f64 / (containing + 1) as f64).ln() + 1.0
+    };
+    let idfs: HashMap<&String, f64> = query_terms.iter().map(|t| (t, idf(t))).collect();
+
+    let mut scored: Vec<(usize, f64)> = counts
+        .iter()
+        .enumerate()
+        .map(|(page, counts)| {
+            let score = counts
+                .iter()
+                .map(|(term, &n)| (1.0 + (n as f64).ln()) * idfs[term])
+                .sum();
+            (page, score)
+        })
+        .filter(|&(_, score)| score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    scored.into_iter().take(limit).map(|(page, _)| page).collect()
+}
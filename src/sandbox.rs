@@ -0,0 +1,97 @@
+use anyhow::{bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use lopdf::Object;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Set by `--sandbox`: no external commands, hooks, links or network, and
+/// PDFs are checked for decompression bombs as they are loaded.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Streams blanked while parsing the last PDF.
+static DEFUSED: AtomicUsize = AtomicUsize::new(0);
+
+/// Largest PDF the sandbox opens.
+const MAX_FILE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Most one stream may decompress to; past it the stream is left empty.
+pub const MAX_STREAM_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Longest chain of filters on one stream; each stage can multiply the size.
+const MAX_FILTERS: usize = 4;
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Fail with a message naming `what` when the sandbox is on.
+pub fn check(what: &str) -> Result<()> {
+    if enabled() {
+        bail!("{} is disabled by --sandbox", what);
+    }
+    Ok(())
+}
+
+/// Refuse files too large to open under the sandbox.
+pub fn check_file(path: &Path) -> Result<()> {
+    let size = fs::metadata(path).with_context(|| format!("Could not read {}", path.display()))?.len();
+    if size > MAX_FILE_BYTES {
+        bail!("{} is larger than the {} MB --sandbox opens", path.display(), MAX_FILE_BYTES / (1024 * 1024));
+    }
+    Ok(())
+}
+
+/// Start counting the blanked streams of a new PDF.
+pub fn start_parse() {
+    DEFUSED.store(0, Ordering::Relaxed);
+}
+
+/// How many streams were blanked while parsing the last PDF.
+pub fn defused() -> usize {
+    DEFUSED.load(Ordering::Relaxed)
+}
+
+/// Blank a stream that would decompress past `MAX_STREAM_BYTES`, before the
+/// parser expands object streams or anything reads the content.
+pub fn defuse(object: &mut Object) {
+    if let Object::Stream(stream) = object
+        && !fits(&stream.content, stream.filters().unwrap_or_default())
+    {
+        stream.set_content(Vec::new());
+        stream.dict.remove(b"Filter");
+        stream.dict.remove(b"DecodeParms");
+        DEFUSED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Whether stream data decoded through `filters` stays within bounds. Flate
+/// stages, the compression bombs are made of, are inflated with a cap; the
+/// stages after a different filter are not looked at.
+fn fits(content: &[u8], filters: Vec<String>) -> bool {
+    if filters.len() > MAX_FILTERS {
+        return false;
+    }
+    let mut data = content.to_vec();
+    for filter in filters {
+        if filter != "FlateDecode" {
+            break;
+        }
+        let mut inflated = Vec::new();
+        let read = ZlibDecoder::new(data.as_slice()).take(MAX_STREAM_BYTES + 1).read_to_end(&mut inflated);
+        if inflated.len() as u64 > MAX_STREAM_BYTES {
+            return false;
+        }
+        if read.is_err() {
+            // Damaged data is the parser's to report
+            break;
+        }
+        data = inflated;
+    }
+    true
+}
@@ -0,0 +1,139 @@
+use std::ops::Range;
+
+/// Byte ranges of case-insensitive matches of `query_lower` in `line`.
+pub fn matches(line: &str, query_lower: &str) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    if query_lower.is_empty() {
+        return matches;
+    }
+
+    let line_lower = line.to_lowercase();
+    let mut last_end = 0;
+    while let Some(start) = line_lower[last_end..].find(query_lower) {
+        let actual_start = last_end + start;
+        let actual_end = actual_start + query_lower.len();
+        // Lowercasing can shift byte offsets for some scripts; skip those matches
+        if line.is_char_boundary(actual_start) && line.is_char_boundary(actual_end) && actual_end <= line.len() {
+            matches.push(actual_start..actual_end);
+        }
+        last_end = actual_end;
+    }
+    matches
+}
+
+/// Match ranges for every line of the shown page, kept between frames so
+/// only a new page, query or page text costs a rescan.
+#[derive(Default)]
+pub struct MatchCache {
+    key: Option<(usize, String)>,
+    lines: Vec<Vec<Range<usize>>>,
+}
+
+impl MatchCache {
+    /// Ranges of `query_lower` per line of `text`, which is 0-based `page`.
+    pub fn get(&mut self, page: usize, text: &str, query_lower: &str) -> &[Vec<Range<usize>>] {
+        if self.key.as_ref().is_none_or(|(p, q)| *p != page || q != query_lower) {
+            self.lines = text.lines().map(|line| matches(line, query_lower)).collect();
+            self.key = Some((page, query_lower.to_string()));
+        }
+        &self.lines
+    }
+
+    /// Forget the ranges after page text changed.
+    pub fn clear(&mut self) {
+        self.key = None;
+    }
+}
+
+/// A find scoped to one page, kept apart from the document search so that
+/// `n`/`N` step through its matches while it is on.
+pub struct PageFind {
+    /// 0-based page searched
+    pub page: usize,
+    pub query: String,
+    /// Line and byte range of each match, in reading order
+    pub matches: Vec<(usize, Range<usize>)>,
+    pub current: usize,
+}
+
+impl PageFind {
+    /// Case-insensitive matches of `query` in `text`, outside lines `hidden`
+    /// leaves out, starting at the first one on or after line `from`, or the
+    /// first on the page.
+    pub fn new(page: usize, text: &str, query: &str, from: usize, hidden: impl Fn(&str) -> bool) -> Self {
+        let query_lower = query.to_lowercase();
+        let matches: Vec<(usize, Range<usize>)> = text
+            .lines()
+            .enumerate()
+            .filter(|(_, text)| !hidden(text))
+            .flat_map(|(line, text)| matches(text, &query_lower).into_iter().map(move |range| (line, range)))
+            .collect();
+        let current = matches.iter().position(|(line, _)| *line >= from).unwrap_or(0);
+        Self {
+            page,
+            query: query.to_string(),
+            matches,
+            current,
+        }
+    }
+
+    /// Move to the next match, or the previous one, wrapping around the page.
+    pub fn step(&mut self, forward: bool) {
+        let count = self.matches.len();
+        if count > 0 {
+            self.current = if forward { (self.current + 1) % count } else { (self.current + count - 1) % count };
+        }
+    }
+
+    pub fn current(&self) -> Option<&(usize, Range<usize>)> {
+        self.matches.get(self.current)
+    }
+
+    /// Ranges on `line`, with whether each is the current match.
+    pub fn on_line(&self, line: usize) -> impl Iterator<Item = (Range<usize>, bool)> + '_ {
+        self.matches
+            .iter()
+            .enumerate()
+            .filter(move |(_, (l, _))| *l == line)
+            .map(|(i, (_, range))| (range.clone(), i == self.current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_ignore_case_and_do_not_overlap() {
+        assert_eq!(matches("Café and CAFÉ", "café"), vec![0..5, 10..15]);
+        assert_eq!(matches("aaaa", "aa"), vec![0..2, 2..4]);
+        assert!(matches("anything", "").is_empty());
+    }
+
+    #[test]
+    fn matches_shifted_by_lowercasing_are_skipped() {
+        // "İ" lowercases to two characters, so offsets after it no longer line up
+        assert!(matches("İx ab", "ab").is_empty());
+        assert_eq!(matches("ab İ", "ab"), vec![0..2]);
+    }
+
+    #[test]
+    fn page_find_starts_at_the_line_and_wraps() {
+        let text = "one fish\nhidden fish\ntwo fish\nred fish";
+        let mut find = PageFind::new(4, text, "FISH", 2, |line| line.starts_with("hidden"));
+        assert_eq!(find.matches.iter().map(|(line, _)| *line).collect::<Vec<_>>(), vec![0, 2, 3]);
+        assert_eq!(find.current(), Some(&(2, 4..8)));
+        find.step(true);
+        find.step(true);
+        assert_eq!(find.current(), Some(&(0, 4..8)));
+        find.step(false);
+        assert_eq!(find.current(), Some(&(3, 4..8)));
+        assert_eq!(find.on_line(3).collect::<Vec<_>>(), vec![(4..8, true)]);
+    }
+
+    #[test]
+    fn page_find_past_the_last_match_starts_at_the_first() {
+        let find = PageFind::new(0, "fish\nnone", "fish", 1, |_| false);
+        assert_eq!(find.current(), Some(&(0, 0..4)));
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::external;
+use crate::task::Task;
+
+/// Words a spell checker rejects, found page by page as pages are shown.
+pub struct SpellCheck {
+    command: String,
+    lang: String,
+    pages: HashMap<usize, HashSet<String>>,
+    /// The page being checked in the background
+    running: Option<(usize, Task<HashSet<String>>)>,
+}
+
+impl SpellCheck {
+    /// `command` reads text on stdin and prints each misspelled word on its own line.
+    pub fn new(command: &str, lang: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            lang: lang.to_string(),
+            pages: HashMap::new(),
+            running: None,
+        }
+    }
+
+    /// Collect the page checked in the background if it has finished, and
+    /// start on a 0-based page unless it has been checked already or
+    /// another page is still being checked.
+    pub fn check(&mut self, page: usize, text: &str) -> Result<()> {
+        if let Some((checked, task)) = &self.running
+            && let Some(result) = task.poll()
+        {
+            let checked = *checked;
+            self.running = None;
+            self.pages.insert(checked, result?);
+        }
+        if self.running.is_none() && !self.pages.contains_key(&page) {
+            let (command, lang, text) = (self.command.clone(), self.lang.clone(), text.to_string());
+            self.running = Some((
+                page,
+                Task::spawn(move || {
+                    let output = external::run(&command, &[("lang", &lang)], Some(&text))?;
+                    Ok(output.lines().map(str::trim).filter(|w| !w.is_empty()).map(str::to_string).collect())
+                }),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Drop a page's results after its text changed.
+    pub fn forget(&mut self, page: usize) {
+        self.pages.remove(&page);
+        if self.running.as_ref().is_some_and(|(checked, _)| *checked == page) {
+            self.running = None;
+        }
+    }
+
+    /// Byte ranges of the misspelled words in one line of a checked page.
+    pub fn ranges(&self, page: usize, line: &str) -> Vec<Range<usize>> {
+        let Some(words) = self.pages.get(&page).filter(|words| !words.is_empty()) else {
+            return Vec::new();
+        };
+        let mut ranges = Vec::new();
+        let mut start = None;
+        // A trailing boundary flushes the last word
+        for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+            let in_word = c.is_alphabetic() || (c == '\'' && start.is_some());
+            match (in_word, start) {
+                (true, None) => start = Some(i),
+                (false, Some(s)) => {
+                    let word = line[s..i].trim_end_matches('\'');
+                    if words.contains(word) {
+                        ranges.push(s..s + word.len());
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        ranges
+    }
+}
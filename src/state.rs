@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+
+/// A remembered position: 0-based page and line within it.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Position {
+    pub page: usize,
+    pub line: usize,
+}
+
+/// Per-document state kept between sessions, one file per PDF under the state directory.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct DocState {
+    /// The PDF this state belongs to, for anyone reading the file
+    pub file: PathBuf,
+    /// Named marks set with `m{a-z}`
+    pub marks: BTreeMap<char, Position>,
+    /// Page tags set with `:tag`, each with its 0-based pages
+    pub tags: BTreeMap<String, BTreeSet<usize>>,
+    /// Whether `:indent` turned leading whitespace on or off, overriding the config
+    pub indent: Option<bool>,
+    /// Whether `:math` turned math rendering on or off, overriding the config
+    pub math: Option<bool>,
+}
+
+impl DocState {
+    /// State saved for `pdf`, or an empty one when there is none yet.
+    pub fn load(pdf: &Path) -> Self {
+        let state = [state_path(pdf), legacy_state_path(pdf)]
+            .into_iter()
+            .flatten()
+            .find_map(|path| fs::read_to_string(path).ok())
+            .and_then(|raw| toml::from_str(&raw).ok());
+        Self {
+            file: canonical(pdf),
+            ..state.unwrap_or_default()
+        }
+    }
+
+    /// Write the state to disk; in private mode it is only kept for this session.
+    pub fn save(&self) -> Result<()> {
+        if paths::private() {
+            return Ok(());
+        }
+        let path = state_path(&self.file).context("No state directory to save state in")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Could not write {}", path.display()))?;
+        if let Some(legacy) = legacy_state_path(&self.file) {
+            let _ = fs::remove_file(legacy);
+        }
+        Ok(())
+    }
+}
+
+fn canonical(pdf: &Path) -> PathBuf {
+    pdf.canonicalize().unwrap_or_else(|_| pdf.to_path_buf())
+}
+
+/// A stable file name stem for per-document files: a hash of the PDF's absolute path.
+pub fn file_key(pdf: &Path) -> String {
+    // FNV-1a, which unlike std's hasher is stable across Rust releases
+    let hash = canonical(pdf)
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+fn state_path(pdf: &Path) -> Option<PathBuf> {
+    Some(paths::state_dir()?.join("documents").join(format!("{}.toml", file_key(pdf))))
+}
+
+/// Where state was kept before it moved to the state directory; read until
+/// the document's state is next saved, then removed.
+fn legacy_state_path(pdf: &Path) -> Option<PathBuf> {
+    Some(paths::data_dir()?.join("state").join(format!("{}.toml", file_key(pdf))))
+}
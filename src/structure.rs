@@ -0,0 +1,183 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::language::Language;
+use crate::pdf::OutlineEntry;
+
+static BULLET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?:[•◦▪‣∙·*–-]|\[\d{1,3}\]|\(?[0-9a-z]{1,2}[.)])\s+").unwrap());
+static NUMBERED_HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^((?:\d+\.)*\d+)\.?\s+\p{Lu}").unwrap());
+
+/// A structural element recovered from extracted text.
+pub enum Block {
+    /// Heading with level 1 (top) and deeper
+    Heading(usize, String),
+    Paragraph(String),
+    /// List item with its marker, e.g. `1.` or `•`
+    ListItem(String, String),
+    /// Code listing with the language guessed for it, as a file extension
+    /// ("" when unknown), and its lines as printed
+    Code(String, String),
+}
+
+/// Split a list item marker from the rest of the line.
+fn bullet(line: &str) -> Option<(String, String)> {
+    let m = BULLET.find(line)?;
+    Some((line[..m.end()].trim().to_string(), line[m.end()..].to_string()))
+}
+
+/// Heading level for a line, if it looks like a heading.
+/// Outline titles win; otherwise short numbered or all-caps lines qualify.
+fn heading_level(line: &str, outline: &[&OutlineEntry]) -> Option<usize> {
+    let normalized = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let line_norm = normalized(line);
+    if let Some(entry) = outline.iter().find(|e| !e.title.is_empty() && normalized(&e.title) == line_norm) {
+        return Some(entry.level + 1);
+    }
+
+    let chars = line.chars().count();
+    if !(3..=70).contains(&chars) || line.ends_with(['.', ',', ';', ':']) {
+        return None;
+    }
+    if let Some(caps) = NUMBERED_HEADING.captures(line) {
+        // "2.1 Methods" is a level 2 heading; long sentences starting with a number are not
+        let words = line.split_whitespace().count();
+        return (words <= 10).then(|| caps[1].split('.').count().min(6));
+    }
+    let letters: Vec<char> = line.chars().filter(|c| c.is_alphabetic()).collect();
+    (letters.len() >= 4 && letters.iter().all(|c| c.is_uppercase())).then_some(1)
+}
+
+/// A short, capitalized line with no closing punctuation, e.g. "Related Work".
+/// Only considered at the start of a block, where a heading can appear.
+fn is_title_like(line: &str, full_width: usize) -> bool {
+    let chars = line.chars().count();
+    chars * 10 < full_width * 6
+        && line.split_whitespace().count() <= 8
+        && line.starts_with(|c: char| c.is_uppercase())
+        && line.ends_with(|c: char| c.is_alphabetic())
+}
+
+/// Join a continuation line onto `text`, undoing end-of-line hyphenation
+/// unless `language` marks the hyphen as a suspended one.
+pub fn join_line(text: &mut String, line: &str, language: Option<Language>) {
+    let suspended = || {
+        let next = line.split_whitespace().next().unwrap_or_default();
+        language.is_some_and(|language| language.suspended_hyphen_words().contains(&next))
+    };
+    if text.is_empty() {
+        text.push_str(line);
+    } else if text.ends_with('-') && line.starts_with(|c: char| c.is_lowercase()) && !suspended() {
+        text.pop();
+        text.push_str(line);
+    } else {
+        text.push(' ');
+        text.push_str(line);
+    }
+}
+
+/// Undo hard line wrapping: each run of lines becomes one line, and blank
+/// lines between paragraphs stay as paragraph breaks.
+pub fn reflow(text: &str, language: Option<Language>) -> String {
+    let mut paragraphs = Vec::new();
+    let mut paragraph = String::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            if !paragraph.is_empty() {
+                paragraphs.push(std::mem::take(&mut paragraph));
+            }
+        } else {
+            join_line(&mut paragraph, line, language);
+        }
+    }
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph);
+    }
+    paragraphs.join("\n\n")
+}
+
+/// Recover headings, paragraphs and list items from a page's lines.
+///
+/// Paragraph breaks are not preserved by extraction, so a paragraph is taken
+/// to end at a line that closes a sentence while falling noticeably short of
+/// the page's usual line length.
+pub fn blocks(page: &str, page_index: usize, outline: &[OutlineEntry], language: Option<Language>) -> Vec<Block> {
+    let outline: Vec<&OutlineEntry> = outline.iter().filter(|e| e.page == page_index).collect();
+    let printed: Vec<&str> = page.lines().collect();
+    let lines: Vec<&str> = printed.iter().map(|line| line.trim()).collect();
+    let full_width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let mut listings = crate::code::regions(&lines).into_iter().peekable();
+
+    let mut blocks = Vec::new();
+    let mut current: Option<Block> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        if let Some(listing) = listings.next_if(|listing| listing.start == i - 1) {
+            blocks.extend(current.take());
+            let language = crate::code::guess_language(&lines[listing.clone()]).unwrap_or_default();
+            blocks.push(Block::Code(language.to_string(), printed[listing.clone()].join("\n")));
+            i = listing.end;
+            continue;
+        }
+        if line.is_empty() {
+            blocks.extend(current.take());
+            continue;
+        }
+        let level = heading_level(line, &outline)
+            .or_else(|| (current.is_none() && is_title_like(line, full_width)).then_some(2));
+        if let Some(level) = level {
+            blocks.extend(current.take());
+            blocks.push(Block::Heading(level, line.to_string()));
+            continue;
+        }
+        if let Some((marker, text)) = bullet(line) {
+            blocks.extend(current.take());
+            current = Some(Block::ListItem(marker, text));
+        } else {
+            match current.as_mut() {
+                Some(Block::Paragraph(text) | Block::ListItem(_, text)) => join_line(text, line, language),
+                _ => current = Some(Block::Paragraph(line.to_string())),
+            }
+        }
+
+        let short = line.chars().count() * 10 < full_width * 8;
+        if short && line.ends_with(['.', '!', '?', ':', '"', '”']) {
+            blocks.extend(current.take());
+        }
+    }
+    blocks.extend(current);
+    blocks
+}
+
+/// Blocks for the whole document, merging paragraphs that continue across a page break.
+pub fn document_blocks(pages: &[String], outline: &[OutlineEntry], language: Option<Language>) -> Vec<(usize, Block)> {
+    let mut all: Vec<(usize, Block)> = Vec::new();
+    for (index, page) in pages.iter().enumerate() {
+        for (i, block) in blocks(page, index, outline, language).into_iter().enumerate() {
+            if i == 0
+                && let (Block::Paragraph(next), Some((_, Block::Paragraph(prev)))) = (&block, all.last_mut())
+                && !prev.ends_with(['.', '!', '?', ':'])
+                && next.starts_with(|c: char| c.is_lowercase())
+            {
+                join_line(prev, next, language);
+                continue;
+            }
+            all.push((index, block));
+        }
+    }
+    all
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflow_joins_wrapped_lines_and_keeps_paragraphs() {
+        let text = "  The quick brown\nfox jumps over a hy-\nphenated dog.\n\n\nNew para-\nGraph here.\n";
+        assert_eq!(reflow(text, None), "The quick brown fox jumps over a hyphenated dog.\n\nNew para- Graph here.");
+        assert_eq!(reflow("\n\n", None), "");
+    }
+}
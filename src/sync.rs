@@ -0,0 +1,234 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the shared progress file inside the sync directory.
+const FILE_NAME: &str = "pdf_reader-progress.json";
+
+/// Bumped on incompatible changes; readers ignore files from a newer version.
+const VERSION: u32 = 1;
+
+/// Reading positions shared between machines, keyed by PDF file name so the
+/// same book matches wherever it lives on each machine.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "documents": {
+///     "paper.pdf": { "page": 12, "line": 3, "pages": 40, "updated": 1760000000, "device": "laptop" }
+///   }
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Progress {
+    pub version: u32,
+    pub documents: BTreeMap<String, Entry>,
+}
+
+/// Where one document was left.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Entry {
+    /// 0-based page and line within it
+    pub page: usize,
+    pub line: usize,
+    /// Page count, to tell a different edition of the same file name
+    pub pages: usize,
+    /// Seconds since the Unix epoch
+    pub updated: u64,
+    /// Machine that wrote the entry
+    pub device: String,
+}
+
+impl Progress {
+    /// Read the progress file and any conflict copies a sync tool left next
+    /// to it, keeping the newest entry per document.
+    pub fn load(dir: &Path) -> Self {
+        let mut progress = Self::default();
+        for path in progress_files(dir) {
+            if let Ok(other) = read(&path) {
+                progress.merge(other);
+            }
+        }
+        progress
+    }
+
+    /// Take entries from `other` that are newer than ours.
+    fn merge(&mut self, other: Progress) {
+        for (name, entry) in other.documents {
+            match self.documents.get(&name) {
+                Some(ours) if ours.updated >= entry.updated => {}
+                _ => {
+                    self.documents.insert(name, entry);
+                }
+            }
+        }
+    }
+}
+
+/// Record a position, merging with whatever other machines wrote since we
+/// last read. The file is replaced atomically, so a sync tool never sees it
+/// half written; conflict copies are folded in and removed.
+///
+/// A progress file that cannot be read (half synced, or from a newer
+/// version) is left alone rather than replaced by one without its
+/// positions, and conflict copies are only removed once merged.
+pub fn save(dir: &Path, name: &str, mut entry: Entry) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    let path = dir.join(FILE_NAME);
+    let mut progress = Progress::default();
+    let mut merged = Vec::new();
+    for file in progress_files(dir) {
+        match read(&file) {
+            Ok(other) => {
+                progress.merge(other);
+                merged.push(file);
+            }
+            Err(e) if file == path => return Err(e),
+            Err(_) => {}
+        }
+    }
+    progress.version = VERSION;
+    // This is the latest thing the reader did, even if another machine's clock runs ahead
+    if let Some(theirs) = progress.documents.get(name) {
+        entry.updated = entry.updated.max(theirs.updated + 1);
+    }
+    progress.merge(Progress {
+        version: VERSION,
+        documents: BTreeMap::from([(name.to_string(), entry)]),
+    });
+
+    // Dot-prefixed so sync tools skip the temporary file
+    let temp = dir.join(format!(".{}.{}.tmp", FILE_NAME, std::process::id()));
+    fs::write(&temp, serde_json::to_string_pretty(&progress)?)
+        .with_context(|| format!("Could not write {}", temp.display()))?;
+    fs::rename(&temp, &path).with_context(|| format!("Could not replace {}", path.display()))?;
+    for conflict in merged.into_iter().filter(|file| *file != path) {
+        let _ = fs::remove_file(conflict);
+    }
+    Ok(())
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// This machine's name, for telling where a position came from.
+pub fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// "5 min ago"-style age of a timestamp.
+pub fn age(updated: u64) -> String {
+    let seconds = now().saturating_sub(updated);
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} min ago", seconds / 60),
+        3600..86400 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+/// The progress file and conflict copies such as Syncthing's
+/// `pdf_reader-progress.sync-conflict-20240101-120000-ABCDEFG.json`.
+fn progress_files(dir: &Path) -> Vec<PathBuf> {
+    let stem = FILE_NAME.trim_end_matches(".json");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(stem) && name.ends_with(".json"))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// A progress file this version can merge: it parses and is not from a newer version.
+fn read(path: &Path) -> Result<Progress> {
+    let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let progress: Progress = serde_json::from_str(&text).with_context(|| format!("Could not parse {}", path.display()))?;
+    if progress.version > VERSION {
+        bail!("{} is from a newer version (format {})", path.display(), progress.version);
+    }
+    Ok(progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(page: usize, updated: u64) -> Entry {
+        Entry { page, updated, ..Entry::default() }
+    }
+
+    /// An empty sync folder of its own for each test.
+    fn sync_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pdf_reader-sync-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn merge_keeps_the_newest_entry() {
+        let progress = |a, b| Progress {
+            version: VERSION,
+            documents: BTreeMap::from([("a.pdf".to_string(), a), ("b.pdf".to_string(), b)]),
+        };
+        let mut ours = progress(entry(1, 10), entry(2, 30));
+        ours.merge(progress(entry(5, 20), entry(6, 20)));
+        assert_eq!(ours.documents["a.pdf"].page, 5);
+        assert_eq!(ours.documents["b.pdf"].page, 2);
+    }
+
+    #[test]
+    fn save_folds_in_and_removes_conflict_copies() {
+        let dir = sync_dir("conflict");
+        let conflict = dir.join("pdf_reader-progress.sync-conflict-20240101-120000-ABC.json");
+        fs::write(&conflict, r#"{"version": 1, "documents": {"other.pdf": {"page": 7, "updated": 5}}}"#).unwrap();
+        save(&dir, "book.pdf", entry(3, 10)).unwrap();
+        let progress = Progress::load(&dir);
+        assert_eq!(progress.documents["other.pdf"].page, 7);
+        assert_eq!(progress.documents["book.pdf"].page, 3);
+        assert!(!conflict.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn save_leaves_an_unreadable_progress_file_alone() {
+        let dir = sync_dir("unreadable");
+        let path = dir.join(FILE_NAME);
+        for text in [r#"{"version": 1, "documents": {"other.pdf": {"pa"#, r#"{"version": 2, "documents": {}}"#] {
+            fs::write(&path, text).unwrap();
+            assert!(save(&dir, "book.pdf", entry(3, 10)).is_err());
+            assert_eq!(fs::read_to_string(&path).unwrap(), text);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn save_keeps_conflict_copies_it_could_not_merge() {
+        let dir = sync_dir("newer");
+        let conflict = dir.join("pdf_reader-progress.sync-conflict-20240101-120000-ABC.json");
+        fs::write(&conflict, r#"{"version": 2, "documents": {}}"#).unwrap();
+        save(&dir, "book.pdf", entry(3, 10)).unwrap();
+        assert!(conflict.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use crate::external;
+
+/// The SyncTeX file LaTeX wrote next to `pdf`, if any.
+pub fn synctex_file(pdf: &Path) -> Option<PathBuf> {
+    ["synctex.gz", "synctex"]
+        .iter()
+        .map(|ext| pdf.with_extension(ext))
+        .find(|path| path.exists())
+}
+
+/// Map a point on a page (1-based page, PDF points from the top-left corner)
+/// back to a source file and line using the `synctex` command-line tool.
+pub fn source_location(pdf: &Path, page: usize, x: f32, y: f32) -> Result<(PathBuf, usize)> {
+    let spec = format!("{}:{:.0}:{:.0}:{}", page, x, y, pdf.display());
+    let output = external::run("synctex edit -o {spec}", &[("spec", &spec)], None)?;
+
+    let field = |name: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(str::trim)
+    };
+    let input = field("Input:").ok_or_else(|| anyhow!("SyncTeX found no source for page {}", page))?;
+    let line = field("Line:").and_then(|line| line.parse().ok()).unwrap_or(1);
+
+    // Paths may be relative to the directory LaTeX ran in, which is usually the PDF's
+    let mut path = PathBuf::from(input);
+    if path.is_relative()
+        && let Some(dir) = pdf.parent()
+    {
+        path = dir.join(path);
+    }
+    Ok((path, line))
+}
+
+/// Map a source line to a 1-based page and the vertical position on it
+/// (PDF points from the top), the reverse of [`source_location`].
+pub fn pdf_location(pdf: &Path, source: &Path, line: usize) -> Result<(usize, f32)> {
+    let input = format!("{}:0:{}", line, source.display());
+    let output = external::run(
+        "synctex view -i {input} -o {pdf}",
+        &[("input", &input), ("pdf", &pdf.display().to_string())],
+        None,
+    )?;
+
+    let field = |name: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(str::trim)
+    };
+    let page = field("Page:")
+        .and_then(|page| page.parse().ok())
+        .ok_or_else(|| anyhow!("SyncTeX found no page for {}:{}", source.display(), line))?;
+    let y = field("y:").and_then(|y| y.parse().ok()).unwrap_or(0.0);
+    Ok((page, y))
+}
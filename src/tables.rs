@@ -0,0 +1,134 @@
+use crate::pdf::COLUMN_GAP;
+
+/// Goes between the cells of an aligned table row. It opens with a
+/// `COLUMN_GAP`, which formatting turns into a plain space everywhere else,
+/// so a page that prints " │ " itself is not taken for a table.
+pub const SEPARATOR: &str = "\u{2007}│ ";
+
+/// Fewer consecutive rows are more likely a coincidence of spacing than a table.
+const MIN_ROWS: usize = 3;
+
+/// Longer cells are running text, e.g. the two columns of a paper set side by side.
+const MAX_CELL: usize = 40;
+
+/// Whether `line` is a row of a table `align` laid out.
+pub fn is_row(line: &str) -> bool {
+    line.contains(SEPARATOR)
+}
+
+/// Lay out each run of lines that split into the same number of columns at
+/// wide gaps as a table, with aligned columns and numbers right-aligned.
+/// Other lines are returned as they are.
+pub fn align(lines: &[&str]) -> Vec<String> {
+    let mut aligned = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some(first) = cells(lines[i]) {
+            let mut rows = vec![first];
+            let mut next = i + 1;
+            while next < lines.len()
+                && let Some(row) = cells(lines[next])
+                && row.len() == rows[0].len()
+            {
+                rows.push(row);
+                next += 1;
+            }
+            if rows.len() >= MIN_ROWS {
+                aligned.extend(layout(&rows));
+                i = next;
+                continue;
+            }
+        }
+        aligned.push(lines[i].to_string());
+        i += 1;
+    }
+    aligned
+}
+
+/// The cells of a line with at least one wide gap, unless one is too long to be a cell.
+fn cells(line: &str) -> Option<Vec<&str>> {
+    let cells: Vec<&str> = line.split(COLUMN_GAP).map(str::trim).filter(|cell| !cell.is_empty()).collect();
+    (cells.len() >= 2 && cells.iter().all(|cell| cell.chars().count() <= MAX_CELL)).then_some(cells)
+}
+
+fn layout(rows: &[Vec<&str>]) -> Vec<String> {
+    let columns = rows[0].len();
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or(0))
+        .collect();
+    // The first row may be a header of words over a column of numbers
+    let numeric: Vec<bool> = (0..columns).map(|column| rows[1..].iter().all(|row| is_number(row[column]))).collect();
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(column, cell)| {
+                    if numeric[column] {
+                        format!("{:>width$}", cell, width = widths[column])
+                    } else {
+                        format!("{:<width$}", cell, width = widths[column])
+                    }
+                })
+                .collect();
+            cells.join(SEPARATOR).trim_end().to_string()
+        })
+        .collect()
+}
+
+/// "12", "-3.5", "1,024", "42%", "$9.99"
+fn is_number(cell: &str) -> bool {
+    let digits = cell.trim_matches(|c: char| "%$€£+-−±()".contains(c)).replace(',', "");
+    !digits.is_empty() && digits.parse::<f64>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FormatConfig;
+    use crate::format;
+
+    #[test]
+    fn only_rows_laid_out_here_are_table_rows() {
+        let text = "Name\u{2007}\u{2007}\u{2007}Age\nAda\u{2007}\u{2007}36\nAlan\u{2007}\u{2007}\u{2007}41\nmenu │ file │ edit";
+        let lines: Vec<String> = format::page(text, FormatConfig::default()).lines().map(str::to_string).collect();
+        let rows: Vec<bool> = lines.iter().map(|line| is_row(line)).collect();
+        assert_eq!(rows, vec![true, true, true, false]);
+        assert_eq!(lines[3], "menu │ file │ edit");
+    }
+
+    #[test]
+    fn columns_align_and_numbers_go_right() {
+        let gap = "\u{2007}\u{2007}";
+        let lines = [format!("Item{gap}Price"), format!("Tea{gap}3.50"), format!("Coffee beans{gap}12"), "after".to_string()];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let bar = SEPARATOR;
+        assert_eq!(align(&lines), vec![
+            format!("Item        {bar}Price"),
+            format!("Tea         {bar} 3.50"),
+            format!("Coffee beans{bar}   12"),
+            "after".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn short_runs_and_running_text_stay_as_they_are() {
+        let gap = "\u{2007}\u{2007}";
+        let two = [format!("a{gap}b"), format!("c{gap}d")];
+        let two: Vec<&str> = two.iter().map(String::as_str).collect();
+        assert_eq!(align(&two), two);
+        let long = "a column of running text set beside another";
+        let columns = [format!("{long}{gap}x"), format!("{long}{gap}y"), format!("{long}{gap}z")];
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        assert_eq!(align(&columns), columns);
+    }
+
+    #[test]
+    fn rows_must_have_the_same_number_of_cells() {
+        let gap = "\u{2007}\u{2007}";
+        let lines = [format!("a{gap}b"), format!("c{gap}d{gap}e"), format!("f{gap}g")];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(align(&lines), lines);
+        assert!(is_number("−1,024.5%") && is_number("$9.99") && !is_number("n/a") && !is_number("-"));
+    }
+}
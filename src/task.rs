@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+/// Work running on a background thread, polled from the event loop.
+pub struct Task<T> {
+    rx: Receiver<Result<T>>,
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn spawn(work: impl FnOnce() -> Result<T> + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(work());
+        });
+        Self { rx }
+    }
+
+    /// The task's result, once it has finished.
+    pub fn poll(&self) -> Option<Result<T>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow::anyhow!("Background task panicked"))),
+        }
+    }
+}
+
+/// Work on a background thread that hands over results as it goes.
+pub struct Stream<T> {
+    rx: Receiver<T>,
+}
+
+impl<T: Send + 'static> Stream<T> {
+    /// `work` sends each item as it is ready; a failed send means the stream
+    /// was dropped and the work can stop.
+    pub fn spawn(work: impl FnOnce(Sender<T>) + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || work(tx));
+        Self { rx }
+    }
+
+    /// Items sent since the last poll, and whether the work has finished.
+    pub fn poll(&self) -> (Vec<T>, bool) {
+        let mut items = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok(item) => items.push(item),
+                Err(TryRecvError::Empty) => return (items, false),
+                Err(TryRecvError::Disconnected) => return (items, true),
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use ratatui::style::Color;
+use serde::Deserialize;
+use std::str::FromStr;
+
+use crate::config::ThemeConfig;
+use crate::display;
+
+/// Contrast WCAG asks of body text, as a luminance ratio.
+pub const MIN_CONTRAST: f32 = 4.5;
+
+/// The themes to start from before `[theme]` overrides.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    /// The terminal's background with the usual ANSI colors
+    #[default]
+    Default,
+    /// White and bright colors on black, every pair well past WCAG AA
+    HighContrast,
+}
+
+/// Colors of the viewer by what they are for.
+#[derive(Clone)]
+pub struct Theme {
+    /// The page text
+    pub text: Color,
+    /// Behind everything; `reset` leaves the terminal's own
+    pub background: Color,
+    /// The header line and the frames of previews
+    pub header: Color,
+    /// The header while typing a search, page or command
+    pub prompt: Color,
+    pub footer: Color,
+    pub status: Color,
+    /// Line numbers and continuation marks
+    pub gutter: Color,
+    /// Footnote markers
+    pub link: Color,
+    /// Misspelled words
+    pub error: Color,
+    /// Text on the match, find and find_current backgrounds
+    pub highlight: Color,
+    /// Behind search matches
+    pub r#match: Color,
+    /// Behind matches of find on page
+    pub find: Color,
+    /// Behind the current find match
+    pub find_current: Color,
+    /// Behind selected text
+    pub selection: Color,
+    /// Behind a line just jumped to
+    pub flash: Color,
+}
+
+/// Foreground and background roles that have to stay readable together.
+const PAIRS: &[(&str, &str)] = &[
+    ("text", "background"),
+    ("header", "background"),
+    ("prompt", "background"),
+    ("footer", "background"),
+    ("status", "background"),
+    ("gutter", "background"),
+    ("link", "background"),
+    ("error", "background"),
+    ("highlight", "match"),
+    ("highlight", "find"),
+    ("highlight", "find_current"),
+    ("text", "selection"),
+    ("text", "flash"),
+];
+
+impl Theme {
+    fn named(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Default => Theme {
+                text: Color::White,
+                background: Color::Reset,
+                header: Color::Cyan,
+                prompt: Color::Yellow,
+                footer: Color::Yellow,
+                status: Color::Green,
+                gutter: Color::DarkGray,
+                link: Color::Cyan,
+                error: Color::Red,
+                highlight: Color::Black,
+                r#match: Color::Yellow,
+                find: Color::LightMagenta,
+                find_current: Color::Magenta,
+                selection: Color::Blue,
+                flash: Color::DarkGray,
+            },
+            ThemeName::HighContrast => Theme {
+                text: Color::Rgb(255, 255, 255),
+                background: Color::Rgb(0, 0, 0),
+                header: Color::Rgb(0, 255, 255),
+                prompt: Color::Rgb(255, 255, 0),
+                footer: Color::Rgb(255, 255, 255),
+                status: Color::Rgb(0, 255, 0),
+                gutter: Color::Rgb(200, 200, 200),
+                link: Color::Rgb(0, 255, 255),
+                error: Color::Rgb(255, 120, 120),
+                highlight: Color::Rgb(0, 0, 0),
+                r#match: Color::Rgb(255, 255, 0),
+                find: Color::Rgb(255, 160, 255),
+                find_current: Color::Rgb(0, 255, 255),
+                selection: Color::Rgb(0, 0, 170),
+                flash: Color::Rgb(100, 100, 0),
+            },
+        }
+    }
+
+    fn role(&mut self, role: &str) -> Option<&mut Color> {
+        Some(match role {
+            "text" => &mut self.text,
+            "background" => &mut self.background,
+            "header" => &mut self.header,
+            "prompt" => &mut self.prompt,
+            "footer" => &mut self.footer,
+            "status" => &mut self.status,
+            "gutter" => &mut self.gutter,
+            "link" => &mut self.link,
+            "error" => &mut self.error,
+            "highlight" => &mut self.highlight,
+            "match" => &mut self.r#match,
+            "find" => &mut self.find,
+            "find_current" => &mut self.find_current,
+            "selection" => &mut self.selection,
+            "flash" => &mut self.flash,
+            _ => return None,
+        })
+    }
+
+    /// The theme `config` describes, with a warning for each color that could
+    /// not be used and each pair of colors set there that is hard to read.
+    /// Pairs the named theme sets on its own are not checked.
+    pub fn load(config: &ThemeConfig) -> (Theme, Vec<String>) {
+        let mut theme = Theme::named(config.name);
+        let mut warnings = Vec::new();
+        for (role, value) in &config.colors {
+            let Ok(color) = Color::from_str(value) else {
+                warnings.push(format!("theme: {} = \"{}\" is not a color", role, value));
+                continue;
+            };
+            match theme.role(role) {
+                Some(slot) => *slot = color,
+                None => warnings.push(format!("theme: no color called {}", role)),
+            }
+        }
+        for &(fg, bg) in PAIRS {
+            if !config.colors.contains_key(fg) && !config.colors.contains_key(bg) {
+                continue;
+            }
+            let (Some(fg_color), Some(bg_color)) = (theme.role(fg).copied(), theme.role(bg).copied()) else {
+                continue;
+            };
+            let ratio = contrast(fg_color, bg_color);
+            if ratio < MIN_CONTRAST {
+                warnings.push(format!("theme: {} on {} has contrast {:.1}:1, under {}:1", fg, bg, ratio, MIN_CONTRAST));
+            }
+        }
+        (theme, warnings)
+    }
+}
+
+/// WCAG contrast ratio of two colors, from 1 (the same) to 21 (black and
+/// white). The terminal's own colors are taken to be light gray on black.
+pub fn contrast(fg: Color, bg: Color) -> f32 {
+    let fg = luminance(display::rgb(fg).unwrap_or((229, 229, 229)));
+    let bg = luminance(display::rgb(bg).unwrap_or((0, 0, 0)));
+    (fg.max(bg) + 0.05) / (fg.min(bg) + 0.05)
+}
+
+/// Relative luminance of an sRGB color.
+fn luminance((r, g, b): (u8, u8, u8)) -> f32 {
+    let linear = |channel: u8| {
+        let value = channel as f32 / 255.0;
+        if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
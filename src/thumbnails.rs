@@ -0,0 +1,222 @@
+use anyhow::{bail, Context, Result};
+use ratatui::layout::Rect;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::{external, images, kitty, paths, state};
+
+/// How a page is meant to be seen, to stand up thumbnails from commands
+/// that ignore /Rotate.
+#[derive(Clone, Copy)]
+pub struct PageShape {
+    /// Width and height of the MediaBox in points
+    pub size: Option<(f32, f32)>,
+    /// The page's /Rotate in degrees clockwise
+    pub rotate: u16,
+}
+
+/// Page thumbnails rendered on a background thread and cached on disk.
+pub struct Thumbnails {
+    rx: Receiver<(usize, Result<PathBuf>)>,
+    /// Where rendered images are cached
+    dir: PathBuf,
+    /// Rendered images by 0-based page
+    images: HashMap<usize, PathBuf>,
+    /// Images as shown, after any turns, with their size in pixels
+    shown: HashMap<usize, (PathBuf, (u32, u32))>,
+    /// Quarter turns clockwise given to pages with `r`, on top of /Rotate
+    pub turns: HashMap<usize, u16>,
+    /// Where each page's image was last placed on screen
+    placed: HashMap<usize, Rect>,
+    pub failed: Option<String>,
+}
+
+impl Thumbnails {
+    /// Start rendering every page of `pdf` with the configured command,
+    /// reusing thumbnails cached by an earlier session. Pages are turned as
+    /// `shapes` say, and then by `turns`.
+    pub fn start(command: &str, pdf: &Path, shapes: Vec<PageShape>, turns: HashMap<usize, u16>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let command = command.to_string();
+        let pdf = pdf.to_path_buf();
+        let dir = paths::cache_dir().unwrap_or_else(env::temp_dir).join("thumbnails").join(state::file_key(&pdf));
+        let render_dir = dir.clone();
+        thread::spawn(move || {
+            for (page, shape) in shapes.into_iter().enumerate() {
+                let result = render(&command, &pdf, &render_dir, page).and_then(|png| upright(png, shape));
+                let failed = result.is_err();
+                // Stop once the viewer has lost interest or the command does not work
+                if tx.send((page, result)).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Self {
+            rx,
+            dir,
+            images: HashMap::new(),
+            shown: HashMap::new(),
+            turns,
+            placed: HashMap::new(),
+            failed: None,
+        }
+    }
+
+    /// Collect thumbnails finished since the last call.
+    pub fn poll(&mut self) {
+        while let Ok((page, result)) = self.rx.try_recv() {
+            match result {
+                Ok(path) => {
+                    self.images.insert(page, path);
+                }
+                Err(e) => self.failed = Some(e.to_string()),
+            }
+        }
+    }
+
+    pub fn rendered(&self, page: usize) -> bool {
+        self.images.contains_key(&page)
+    }
+
+    /// Turn `page` a quarter clockwise, or counterclockwise, and draw it again.
+    pub fn rotate(&mut self, page: usize, clockwise: bool) -> io::Result<()> {
+        let turns = self.turns.entry(page).or_default();
+        *turns = (*turns + if clockwise { 1 } else { 3 }) % 4;
+        self.shown.remove(&page);
+        if self.placed.remove(&page).is_some() {
+            kitty::delete(page as u32 + 1)?;
+        }
+        Ok(())
+    }
+
+    /// The image of `page` as shown and its size, turning the rendered one
+    /// the first time it is asked for.
+    fn shown(&mut self, page: usize) -> Result<Option<(PathBuf, (u32, u32))>> {
+        if let Some(shown) = self.shown.get(&page) {
+            return Ok(Some(shown.clone()));
+        }
+        let Some(path) = self.images.get(&page) else {
+            return Ok(None);
+        };
+        let shown = match self.turns.get(&page).copied().unwrap_or(0) {
+            0 => (path.clone(), images::png_size(path)?),
+            turns => {
+                let turned = self.dir.join(format!("page-{}-turned.png", page + 1));
+                let size = images::rotate_png(path, &turned, turns)?;
+                (turned, size)
+            }
+        };
+        self.shown.insert(page, shown.clone());
+        Ok(Some(shown))
+    }
+
+    /// Draw the thumbnails for `cells` over the text grid, skipping ones already in place.
+    pub fn place(&mut self, cells: &[(usize, Rect)]) -> io::Result<()> {
+        let visible: HashMap<usize, Rect> = cells.iter().copied().collect();
+        // Placements survive redraws, so drop those that scrolled away or moved
+        let stale: Vec<usize> = self
+            .placed
+            .iter()
+            .filter(|&(page, area)| visible.get(page) != Some(area))
+            .map(|(&page, _)| page)
+            .collect();
+        for page in stale {
+            kitty::delete(page as u32 + 1)?;
+            self.placed.remove(&page);
+        }
+
+        for &(page, area) in cells {
+            if self.placed.contains_key(&page) || area.width == 0 || area.height == 0 {
+                continue;
+            }
+            let (path, size) = match self.shown(page) {
+                Ok(Some(shown)) => shown,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.failed = Some(e.to_string());
+                    continue;
+                }
+            };
+            kitty::show(page as u32 + 1, &path, fit(area, size))?;
+            self.placed.insert(page, area);
+        }
+        Ok(())
+    }
+
+    /// Remove every image from the screen.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.placed.clear();
+        kitty::delete_all()
+    }
+}
+
+/// Render one page to the cache unless it is already there.
+fn render(command: &str, pdf: &Path, dir: &Path, page: usize) -> Result<PathBuf> {
+    let output = dir.join(format!("page-{}", page + 1));
+    let png = output.with_extension("png");
+    let fresh = match (fs::metadata(&png), fs::metadata(pdf)) {
+        (Ok(thumb), Ok(doc)) => thumb.modified()? >= doc.modified()?,
+        _ => false,
+    };
+    if fresh {
+        return Ok(png);
+    }
+
+    fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    let page_number = (page + 1).to_string();
+    external::run(
+        command,
+        &[
+            ("page", &page_number),
+            ("file", &pdf.display().to_string()),
+            ("output", &output.display().to_string()),
+        ],
+        None,
+    )?;
+    if !png.exists() {
+        bail!("'{}' did not write {}", command, png.display());
+    }
+    Ok(png)
+}
+
+/// Turn a rendered page upright when the command left out its /Rotate: a
+/// page turned a quarter is wider than tall if it was printed taller than
+/// wide. Half turns cannot be told apart and are left to the command.
+fn upright(png: PathBuf, shape: PageShape) -> Result<PathBuf> {
+    let Some((width, height)) = shape.size else {
+        return Ok(png);
+    };
+    if shape.rotate.is_multiple_of(180) || width == height {
+        return Ok(png);
+    }
+    let (png_width, png_height) = images::png_size(&png)?;
+    if (png_width > png_height) == (width > height) {
+        images::rotate_png(&png, &png, shape.rotate / 90)?;
+    }
+    Ok(png)
+}
+
+/// The largest part of `area`, centred, that shows an image of `size` pixels
+/// without stretching it; terminal cells are about twice as tall as wide.
+fn fit(area: Rect, (width, height): (u32, u32)) -> Rect {
+    if width == 0 || height == 0 {
+        return area;
+    }
+    let aspect = width as f32 / height as f32 * 2.0;
+    let (columns, rows) = if (area.width as f32) < area.height as f32 * aspect {
+        (area.width, ((area.width as f32 / aspect).round() as u16).clamp(1, area.height))
+    } else {
+        (((area.height as f32 * aspect).round() as u16).clamp(1, area.width), area.height)
+    };
+    Rect {
+        x: area.x + (area.width - columns) / 2,
+        y: area.y + (area.height - rows) / 2,
+        width: columns,
+        height: rows,
+    }
+}
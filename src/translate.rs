@@ -0,0 +1,40 @@
+use anyhow::{anyhow, Result};
+use serde_json::json;
+
+use crate::config::TranslationConfig;
+use crate::{external, sandbox};
+
+/// Translate `text` using the configured HTTP endpoint, or the shell command
+/// when no endpoint is set.
+pub fn translate(config: &TranslationConfig, text: &str) -> Result<String> {
+    if config.endpoint.is_empty() {
+        return external::run(
+            &config.command,
+            &[("text", text), ("source", &config.source), ("target", &config.target)],
+            None,
+        );
+    }
+
+    sandbox::check("Network access")?;
+    // LibreTranslate-compatible request body
+    let source = if config.source.is_empty() { "auto" } else { &config.source };
+    let mut body = json!({
+        "q": text,
+        "source": source,
+        "target": config.target,
+        "format": "text",
+    });
+    if !config.api_key.is_empty() {
+        body["api_key"] = json!(config.api_key);
+    }
+
+    let response: serde_json::Value = ureq::post(&config.endpoint)
+        .send_json(body)
+        .map_err(|e| anyhow!("Translation request failed: {}", e))?
+        .into_json()?;
+
+    response["translatedText"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Unexpected response from {}", config.endpoint))
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use std::ops::Range;
+use std::process::Child;
+
+use crate::external;
+
+/// Reads a page aloud one paragraph at a time through an external TTS command.
+pub struct Speech {
+    command: String,
+    page: usize,
+    /// Line ranges of the page's paragraphs
+    paragraphs: Vec<Range<usize>>,
+    current: usize,
+    child: Option<Child>,
+    pub paused: bool,
+}
+
+impl Speech {
+    pub fn start(command: &str, page: usize, lines: &[&str]) -> Result<Self> {
+        let mut speech = Self {
+            command: command.to_string(),
+            page,
+            paragraphs: paragraphs(lines),
+            current: 0,
+            child: None,
+            paused: false,
+        };
+        speech.speak(lines)?;
+        Ok(speech)
+    }
+
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    /// Lines of the paragraph being spoken.
+    pub fn current_lines(&self) -> Option<Range<usize>> {
+        self.paragraphs.get(self.current).cloned()
+    }
+
+    fn speak(&mut self, lines: &[&str]) -> Result<()> {
+        self.stop_child();
+        if let Some(range) = self.current_lines() {
+            let text = lines[range].join(" ");
+            self.child = Some(external::spawn(&self.command, &[("text", &text)])?);
+        }
+        Ok(())
+    }
+
+    fn stop_child(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    pub fn toggle_pause(&mut self, lines: &[&str]) -> Result<()> {
+        self.paused = !self.paused;
+        if self.paused {
+            self.stop_child();
+            Ok(())
+        } else {
+            // Resuming restarts the paragraph that was interrupted
+            self.speak(lines)
+        }
+    }
+
+    pub fn next_paragraph(&mut self, lines: &[&str]) -> Result<()> {
+        if self.current + 1 < self.paragraphs.len() {
+            self.current += 1;
+            if !self.paused {
+                self.speak(lines)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn prev_paragraph(&mut self, lines: &[&str]) -> Result<()> {
+        self.current = self.current.saturating_sub(1);
+        if !self.paused {
+            self.speak(lines)?;
+        }
+        Ok(())
+    }
+
+    /// Advance to the next paragraph once the current one has been spoken.
+    /// Returns false when the end of the page has been reached.
+    pub fn poll(&mut self, lines: &[&str]) -> Result<bool> {
+        if self.paused {
+            return Ok(true);
+        }
+        let finished = match self.child.as_mut() {
+            Some(child) => child.try_wait()?.is_some(),
+            None => true,
+        };
+        if !finished {
+            return Ok(true);
+        }
+        self.child = None;
+        if self.current + 1 >= self.paragraphs.len() {
+            return Ok(false);
+        }
+        self.current += 1;
+        self.speak(lines)?;
+        Ok(true)
+    }
+}
+
+impl Drop for Speech {
+    fn drop(&mut self) {
+        self.stop_child();
+    }
+}
+
+/// Group lines into paragraphs. Blank lines are stripped during extraction, so
+/// a paragraph ends at a line closing a sentence or at an explicit blank line.
+fn paragraphs(lines: &[&str]) -> Vec<Range<usize>> {
+    let mut paragraphs = Vec::new();
+    let mut start = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            if i > start {
+                paragraphs.push(start..i);
+            }
+            start = i + 1;
+        } else if line.ends_with(['.', '!', '?', ':']) {
+            paragraphs.push(start..i + 1);
+            start = i + 1;
+        }
+    }
+    if start < lines.len() {
+        paragraphs.push(start..lines.len());
+    }
+    paragraphs
+}
@@ -0,0 +1,217 @@
+use ratatui::text::{Line, Span};
+use std::ops::Range;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Drawn at the start of rows continuing a wrapped line.
+pub const CONTINUATION: &str = "↪";
+
+/// Closing punctuation and small kana, which East Asian typesetting keeps
+/// off the start of a row.
+const NO_BREAK_BEFORE: &str = "、。，．・：；？！ー）」』】〕〉》〙〗｝］〜～ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ々〻,.:;?!)]}%";
+
+/// Opening brackets, which stay with what follows them.
+const NO_BREAK_AFTER: &str = "（「『【〔〈《〘〖｛［([{";
+
+/// Bullets that start list items, for hanging indents.
+const BULLETS: &[char] = &['-', '*', '•', '◦', '▪', '–', '·'];
+
+/// Wide characters (CJK ideographs, kana, Hangul, full-width forms) are
+/// written without spaces, so rows may break between any two of them.
+fn is_wide(c: char) -> bool {
+    c.width() == Some(2)
+}
+
+/// Whether a row may start at `next` after `prev`: after spaces, and
+/// between wide characters where the punctuation rules allow it.
+fn can_break(prev: char, next: char) -> bool {
+    if next.is_whitespace() {
+        return false;
+    }
+    if prev.is_whitespace() {
+        return true;
+    }
+    (is_wide(prev) || is_wide(next)) && !NO_BREAK_BEFORE.contains(next) && !NO_BREAK_AFTER.contains(prev)
+}
+
+/// Columns that continuation rows of `text` are indented by so they line up
+/// under its text: its leading spaces, and its bullet or number for list
+/// items. None where that would leave less than half of `width`.
+pub fn hanging_indent(text: &str, width: usize) -> usize {
+    let body = text.trim_start();
+    let mut indent = text[..text.len() - body.len()].width();
+    let digits = body.len() - body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker = match body.chars().next() {
+        Some(c) if BULLETS.contains(&c) => c.len_utf8(),
+        _ if (1..=3).contains(&digits) && body[digits..].starts_with(['.', ')']) => digits + 1,
+        _ => 0,
+    };
+    if marker > 0 && body[marker..].starts_with(' ') {
+        indent += body[..marker].width() + 1;
+    }
+    if indent * 2 > width { 0 } else { indent }
+}
+
+/// Byte ranges of `text` on each row when wrapped: the first row at most
+/// `first` columns wide, the rest `rest` less their hanging indent. Rows
+/// break at the last opportunity that fits and mid-word only for words wider
+/// than a row; a space the break falls on is left out. There is always a row,
+/// if only an empty one.
+pub fn rows(text: &str, first: usize, rest: usize) -> Vec<Range<usize>> {
+    let first = first.max(1);
+    let rest = rest.saturating_sub(hanging_indent(text, rest)).max(1);
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut width = 0;
+    // Where the row could end and the next start, and the width before it
+    let mut last_break: Option<(usize, usize)> = None;
+    let mut prev = None;
+    for (i, c) in text.char_indices() {
+        let char_width = c.width().unwrap_or(0);
+        if let Some(prev) = prev
+            && i > start
+            && can_break(prev, c)
+        {
+            last_break = Some((i, width));
+        }
+        prev = Some(c);
+        let limit = if rows.is_empty() { first } else { rest };
+        if width + char_width > limit && i > start {
+            if c == ' ' {
+                rows.push(start..i);
+                start = i + 1;
+                width = 0;
+                last_break = None;
+                continue;
+            }
+            match last_break.take() {
+                Some((at, before)) if at > start => {
+                    rows.push(start..at);
+                    start = at;
+                    width -= before;
+                }
+                _ => {
+                    rows.push(start..i);
+                    start = i;
+                    width = 0;
+                }
+            }
+            // What moved down may itself be too wide for a continuation row
+            let limit = rest;
+            while width + char_width > limit && i > start {
+                let mut cut = start;
+                let mut cut_width = 0;
+                for (j, d) in text[start..i].char_indices() {
+                    let w = d.width().unwrap_or(0);
+                    if cut_width + w > limit && j > 0 {
+                        break;
+                    }
+                    cut = start + j + d.len_utf8();
+                    cut_width += w;
+                }
+                rows.push(start..cut);
+                start = cut;
+                width -= cut_width;
+            }
+        }
+        width += char_width;
+    }
+    rows.push(start..text.len());
+    rows
+}
+
+/// Cut a styled line into rows as `rows` does, with continuation rows
+/// starting with their hanging indent.
+pub fn wrap_line(line: &Line, first: usize, rest: usize) -> Vec<Line<'static>> {
+    let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+    let indent = hanging_indent(&text, rest);
+    rows(&text, first, rest)
+        .into_iter()
+        .enumerate()
+        .map(|(row, range)| {
+            let mut spans = Vec::new();
+            if row > 0 && indent > 0 {
+                spans.push(Span::raw(" ".repeat(indent)));
+            }
+            let mut offset = 0;
+            for span in &line.spans {
+                let span_range = offset..offset + span.content.len();
+                offset = span_range.end;
+                let (from, to) = (range.start.max(span_range.start), range.end.min(span_range.end));
+                if from < to {
+                    spans.push(Span::styled(text[from..to].to_string(), span.style));
+                }
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// The character of row `range` of `text` under `column`, counting from the
+/// row's first column after any indent; the row's last character when the
+/// column is past its end.
+pub fn char_at_column(text: &str, range: Range<usize>, column: usize) -> usize {
+    let before = text[..range.start].chars().count();
+    let mut width = 0;
+    for (i, c) in text[range.clone()].chars().enumerate() {
+        width += c.width().unwrap_or(0);
+        if width > column {
+            return before + i;
+        }
+    }
+    before + text[range].chars().count().saturating_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::{Style, Stylize};
+
+    fn split(text: &str, first: usize, rest: usize) -> Vec<&str> {
+        rows(text, first, rest).into_iter().map(|range| &text[range]).collect()
+    }
+
+    #[test]
+    fn rows_break_after_spaces() {
+        assert_eq!(split("the quick brown fox", 10, 10), vec!["the quick ", "brown fox"]);
+        // A space right at the edge is left out rather than starting a row
+        assert_eq!(split("the quick brown fox", 9, 9), vec!["the quick", "brown fox"]);
+    }
+
+    #[test]
+    fn a_word_wider_than_the_row_is_cut() {
+        assert_eq!(split("a supercalifragilistic word", 8, 8), vec!["a ", "supercal", "ifragili", "stic ", "word"]);
+    }
+
+    #[test]
+    fn cjk_punctuation_stays_off_the_start_of_a_row() {
+        // "。" would start the second row; the character before it comes along
+        assert_eq!(split("日本語です。", 10, 10), vec!["日本語で", "す。"]);
+        assert_eq!(split("「日本」語", 6, 6), vec!["「日", "本」語"]);
+    }
+
+    #[test]
+    fn bullets_and_numbers_hang() {
+        assert_eq!(hanging_indent("- item text", 40), 2);
+        assert_eq!(hanging_indent("  12. item", 40), 6);
+        assert_eq!(hanging_indent("-not a bullet", 40), 0);
+        assert_eq!(hanging_indent("1999. A year", 40), 0);
+        // Too deep to leave half the row
+        assert_eq!(hanging_indent("          - x", 20), 0);
+        assert_eq!(split("- one two three four", 10, 10), vec!["- one two ", "three ", "four"]);
+    }
+
+    #[test]
+    fn an_empty_line_is_one_empty_row() {
+        assert_eq!(rows("", 10, 10), vec![0..0]);
+        assert_eq!(wrap_line(&Line::from(""), 10, 10).len(), 1);
+    }
+
+    #[test]
+    fn wrapped_lines_keep_styles_and_indent_continuations() {
+        let line = Line::from(vec![Span::raw("- one "), Span::raw("two three").bold()]);
+        let wrapped = wrap_line(&line, 9, 9);
+        let text = |line: &Line| line.spans.iter().map(|span| span.content.as_ref()).collect::<String>();
+        assert_eq!(wrapped.iter().map(text).collect::<Vec<_>>(), vec!["- one two", "  three"]);
+        assert_eq!(wrapped[1].spans[1].style, Style::new().bold());
+    }
+}